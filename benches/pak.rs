@@ -0,0 +1,61 @@
+use std::hint::black_box;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use pak_db::{bench::{PakBenchDataset, PakBenchItem}, index::PakIndexIdentifier, Pak};
+
+//==============================================================================================
+//        Benchmarks
+//==============================================================================================
+
+fn dataset() -> PakBenchDataset {
+    PakBenchDataset::new(10_000).with_indices(4).with_cardinality(1_000).with_seed(42)
+}
+
+fn build(c : &mut Criterion) {
+    let dataset = dataset();
+    c.bench_function("build 10k items", |b| {
+        b.iter_batched(|| dataset.builder().unwrap(), |builder| builder.build_in_memory().unwrap(), BatchSize::LargeInput)
+    });
+}
+
+fn build_duplicates(c : &mut Criterion) {
+    let dataset = dataset().with_cardinality(10);
+    c.bench_function("build 10k items with duplicate keys", |b| {
+        b.iter_batched(|| dataset.builder().unwrap(), |builder| builder.build_in_memory().unwrap(), BatchSize::LargeInput)
+    });
+}
+
+fn point_query(c : &mut Criterion) {
+    let pak = dataset().build_in_memory().unwrap();
+    c.bench_function("point query", |b| {
+        b.iter(|| pak.query::<(PakBenchItem,)>("field_0".equals(black_box(500u64))).unwrap())
+    });
+}
+
+fn range_query(c : &mut Criterion) {
+    let pak = dataset().build_in_memory().unwrap();
+    c.bench_function("range query", |b| {
+        b.iter(|| pak.query::<(PakBenchItem,)>("field_1".greater_than_or_equal(black_box(400u64)) & "field_1".less_than(black_box(450u64))).unwrap())
+    });
+}
+
+fn cold_vs_warm(c : &mut Criterion) {
+    let path = std::env::temp_dir().join(format!("pak-bench-{}.pak", std::process::id()));
+    let warm = dataset().build_file(&path).unwrap();
+    
+    let mut group = c.benchmark_group("file reads");
+    group.bench_function("cold", |b| {
+        b.iter(|| {
+            let pak = Pak::new_from_file(&path).unwrap();
+            pak.query::<(PakBenchItem,)>("field_2".equals(black_box(7u64))).unwrap()
+        })
+    });
+    group.bench_function("warm", |b| {
+        b.iter(|| warm.query::<(PakBenchItem,)>("field_2".equals(black_box(7u64))).unwrap())
+    });
+    group.finish();
+    
+    let _ = std::fs::remove_file(&path);
+}
+
+criterion_group!(benches, build, build_duplicates, point_query, range_query, cold_vs_warm);
+criterion_main!(benches);
@@ -0,0 +1,164 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, LitInt, LitStr, Path};
+
+//==============================================================================================
+//        PakItem
+//==============================================================================================
+
+struct ItemOptions {
+    version : Option<u32>,
+    migrate_from : Option<Path>,
+}
+
+fn parse_item_options(input : &DeriveInput) -> syn::Result<ItemOptions> {
+    let mut options = ItemOptions { version : None, migrate_from : None };
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("pak")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("version") {
+                options.version = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+                Ok(())
+            } else if meta.path.is_ident("migrate_from") {
+                options.migrate_from = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `version` or `migrate_from`"))
+            }
+        })?;
+    }
+    if options.migrate_from.is_some() && options.version.is_none() {
+        return Err(syn::Error::new(input.ident.span(), "`migrate_from` needs a `version` to migrate to"))
+    }
+    Ok(options)
+}
+
+/// A field with a `#[pak(...)]` attribute.
+struct ItemField {
+    ident : syn::Ident,
+    vis : syn::Visibility,
+    ty : syn::Type,
+    /// The key the field is indexed under, if it is indexed.
+    index : Option<String>,
+    /// The type of the item the field points to, if it was marked with `ref`.
+    reference : Option<Path>,
+}
+
+/// Finds the fields marked with `#[pak(index)]`, `#[pak(index = "key")]` or `#[pak(ref = "Type")]`. Reference fields are indexed under their
+/// own name unless they are also given an index key.
+fn parse_fields(input : &DeriveInput) -> syn::Result<Vec<ItemField>> {
+    let Data::Struct(data) = &input.data else { return Err(syn::Error::new(input.ident.span(), "PakItem can only be derived for structs")) };
+    let Fields::Named(fields) = &data.fields else { return Ok(Vec::new()) };
+    
+    let mut items = Vec::new();
+    for field in &fields.named {
+        let ident = field.ident.clone().unwrap();
+        let mut item = ItemField { ident : ident.clone(), vis : field.vis.clone(), ty : field.ty.clone(), index : None, reference : None };
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("pak")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("index") {
+                    item.index = Some(match meta.value() {
+                        Ok(value) => value.parse::<LitStr>()?.value(),
+                        Err(_) => ident.to_string(),
+                    });
+                    Ok(())
+                } else if meta.path.is_ident("ref") {
+                    item.reference = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `index` or `ref`"))
+                }
+            })?;
+        }
+        if item.reference.is_some() && item.index.is_none() { item.index = Some(ident.to_string()) }
+        if item.index.is_some() || item.reference.is_some() { items.push(item) }
+    }
+    Ok(items)
+}
+
+/// Returns true if the type is a `PakRef<T>`, which lets the derive check the referenced type at compile time.
+fn is_pak_ref(ty : &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(path) => path.path.segments.last().is_some_and(|segment| segment.ident == "PakRef"),
+        _ => false,
+    }
+}
+
+pub(crate) fn expand(input : DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let options = parse_item_options(&input)?;
+    
+    let fields = parse_fields(&input)?;
+    
+    let indices = fields.iter().filter_map(|field| {
+        let (ident, key) = (&field.ident, field.index.as_ref()?);
+        Some(match field.reference {
+            Some(_) => quote! { ::pak_db::index::PakIndex::new(#key, &self.#ident), ::pak_db::index::PakIndex::reference(#key) },
+            None => quote! { ::pak_db::index::PakIndex::new(#key, ::std::clone::Clone::clone(&self.#ident)) },
+        })
+    });
+    let keys = fields.iter().filter_map(|field| field.index.as_ref());
+    let searchable = quote! {
+        impl #impl_generics ::pak_db::item::PakItemSearchable for #name #ty_generics #where_clause {
+            fn get_indices(&self) -> ::std::vec::Vec<::pak_db::index::PakIndex> {
+                vec![#(#indices),*]
+            }
+            
+            fn index_keys() -> &'static [&'static str] {
+                &[#(#keys),*]
+            }
+        }
+    };
+    
+    let accessors = fields.iter().filter_map(|field| {
+        let (ident, vis, target) = (&field.ident, &field.vis, field.reference.as_ref()?);
+        let doc = format!(" Loads the `{}` that `{ident}` points to.", quote!(#target).to_string().replace(' ', ""));
+        let body = if is_pak_ref(&field.ty) {
+            quote! {
+                let reference : &::pak_db::pointer::PakRef<#target> = &self.#ident;
+                reference.get(pak)
+            }
+        } else {
+            quote! { pak.get::<#target>(::std::convert::AsRef::<::pak_db::pointer::PakPointer>::as_ref(&self.#ident)) }
+        };
+        Some(quote! {
+            #[doc = #doc]
+            #vis fn #ident(&self, pak : &::pak_db::Pak) -> ::pak_db::error::PakResult<#target> {
+                #body
+            }
+        })
+    }).collect::<Vec<_>>();
+    let accessors = if accessors.is_empty() { quote! {} } else {
+        quote! {
+            impl #impl_generics #name #ty_generics #where_clause {
+                #(#accessors)*
+            }
+        }
+    };
+    
+    let versioned = match (options.version, &options.migrate_from) {
+        (Some(version), Some(previous)) => quote! {
+            impl #impl_generics ::pak_db::envelope::PakVersioned for #name #ty_generics #where_clause {
+                const VERSION : u32 = #version;
+                
+                fn decode_version(version : u32, payload : &[u8]) -> ::pak_db::error::PakResult<Self> {
+                    if version == #version { return <Self as ::pak_db::item::PakItemDeserialize>::from_bytes(payload) }
+                    let previous = <#previous as ::pak_db::envelope::PakVersioned>::decode_version(version, payload)?;
+                    Ok(::std::convert::From::from(previous))
+                }
+            }
+        },
+        (Some(version), None) => quote! {
+            impl #impl_generics ::pak_db::envelope::PakVersioned for #name #ty_generics #where_clause {
+                const VERSION : u32 = #version;
+            }
+        },
+        _ => quote! {},
+    };
+    
+    Ok(quote! {
+        #searchable
+        #accessors
+        #versioned
+    })
+}
@@ -0,0 +1,38 @@
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+mod item;
+mod schema;
+
+//==============================================================================================
+//        PakSchema
+//==============================================================================================
+
+/// Derives `PakSchema` for a marker struct. The `#[pak_schema(Person(first_name, age), Pet(name))]` attribute lists every item type along
+/// with the index keys it is paked with.
+#[proc_macro_derive(PakSchema, attributes(pak_schema))]
+pub fn derive_pak_schema(input : TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match schema::expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+//==============================================================================================
+//        PakItem
+//==============================================================================================
+
+/// Derives `PakItemSearchable` for a struct. Fields marked with `#[pak(index)]` are indexed under their own name, and `#[pak(index = "key")]`
+/// picks a different key. Pointer and `PakRef` fields marked with `#[pak(ref = "Person")]` get an accessor with the field's name that loads the
+/// referenced `Person`, and are indexed by the offset of the item they point to, so items can be looked up by what they reference. Repacks that
+/// move the items rewrite them. Adding `#[pak(version = 3, migrate_from = "v2::Person")]` to the struct also derives `PakVersioned`, decoding older
+/// payloads through the previous version and converting them with `From`.
+#[proc_macro_derive(PakItem, attributes(pak))]
+pub fn derive_pak_item(input : TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match item::expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
@@ -0,0 +1,53 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parenthesized, parse::ParseStream, punctuated::Punctuated, DeriveInput, Ident, Path, Token};
+
+//==============================================================================================
+//        PakSchema
+//==============================================================================================
+
+struct SchemaItem {
+    ty : Path,
+    fields : Vec<Ident>,
+}
+
+fn parse_item(input : ParseStream) -> syn::Result<SchemaItem> {
+    let ty : Path = input.parse()?;
+    let mut fields = Vec::new();
+    if input.peek(syn::token::Paren) {
+        let content;
+        parenthesized!(content in input);
+        fields = Punctuated::<Ident, Token![,]>::parse_terminated(&content)?.into_iter().collect();
+    }
+    Ok(SchemaItem { ty, fields })
+}
+
+pub(crate) fn expand(input : DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    
+    let mut items = Vec::new();
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("pak_schema")) {
+        items.extend(attr.parse_args_with(|input : ParseStream| Punctuated::<SchemaItem, Token![,]>::parse_terminated_with(input, parse_item))?);
+    }
+    if items.is_empty() {
+        return Err(syn::Error::new(name.span(), "a schema needs a #[pak_schema(Type(fields...), ...)] attribute listing at least one item type"))
+    }
+    
+    // The types are named the way they are written in the attribute rather than by `type_name`, whose output isn't stable across compilers.
+    let items = items.iter().map(|SchemaItem { ty, fields }| {
+        let ty = quote!(#ty).to_string().replace(' ', "");
+        let fields = fields.iter().map(|field| field.to_string());
+        quote! {
+            ::pak_db::schema::PakSchemaItem::new(#ty, &[#(#fields),*])
+        }
+    });
+    
+    Ok(quote! {
+        impl #impl_generics ::pak_db::schema::PakSchema for #name #ty_generics #where_clause {
+            fn descriptor() -> ::pak_db::schema::PakSchemaDescriptor {
+                ::pak_db::schema::PakSchemaDescriptor::new(vec![#(#items),*])
+            }
+        }
+    })
+}
@@ -0,0 +1,55 @@
+use std::{collections::HashSet, fs, path::Path};
+use serde::{Deserialize, Serialize};
+use crate::error::PakResult;
+
+//==============================================================================================
+//        PakAccessRecorder
+//==============================================================================================
+
+/// Remembers the order chunks were first read in, while a pak is recording with [Pak::record_access](crate::Pak::record_access).
+#[derive(Default)]
+pub(crate) struct PakAccessRecorder {
+    seen : HashSet<u64>,
+    order : Vec<u64>,
+}
+
+impl PakAccessRecorder {
+    pub(crate) fn record(&mut self, offset : u64) {
+        if self.seen.insert(offset) { self.order.push(offset) }
+    }
+    
+    /// The offsets of every chunk that was read, in the order they were first read.
+    pub(crate) fn order(&self) -> &[u64] {
+        &self.order
+    }
+}
+
+//==============================================================================================
+//        PakPreloadList
+//==============================================================================================
+
+/// The items a profiling run read, by ordinal, in the order it first read them. It comes from [Pak::preload_list](crate::Pak::preload_list),
+/// and can be used to read the items ahead of time with [Pak::preload](crate::Pak::preload), or to lay them out in that order with
+/// [Pak::repack_ordered](crate::Pak::repack_ordered) so items that are used together are next to each other on disk. Ordinals don't change
+/// when a pak is reordered, so a list stays valid for the reordered pak.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PakPreloadList {
+    pub ordinals : Vec<u32>,
+}
+
+impl PakPreloadList {
+    pub fn new(ordinals : Vec<u32>) -> Self {
+        Self { ordinals }
+    }
+    
+    /// Writes the list to a file.
+    pub fn save(&self, path : impl AsRef<Path>) -> PakResult<()> {
+        fs::write(path, bincode::serialize(self)?)?;
+        Ok(())
+    }
+    
+    /// Reads a list written with [save](crate::access::PakPreloadList::save).
+    pub fn load(path : impl AsRef<Path>) -> PakResult<Self> {
+        Ok(bincode::deserialize(&fs::read(path)?)?)
+    }
+}
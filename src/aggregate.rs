@@ -0,0 +1,80 @@
+use crate::{btree::PakPostings, value::PakValue};
+
+//==============================================================================================
+//        Aggregate
+//==============================================================================================
+
+/// An aggregation that can be computed for each distinct value of an index. Aggregates only look at the index structures, so no items are deserialized while computing them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    /// The number of items that have been indexed with the value.
+    Count,
+}
+
+impl Aggregate {
+    pub(crate) fn apply(&self, postings : &PakPostings) -> u64 {
+        match self {
+            Aggregate::Count => postings.len(),
+        }
+    }
+}
+
+//==============================================================================================
+//        PakHistogram
+//==============================================================================================
+
+/// An equi-depth histogram over the numeric values of an index. Each bucket holds roughly the same number of items, and a single value is never split across two buckets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PakHistogram {
+    pub buckets : Vec<PakHistogramBucket>,
+}
+
+/// One bucket of a [PakHistogram](crate::aggregate::PakHistogram). `low` and `high` are the smallest and largest values inside of the bucket.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PakHistogramBucket {
+    pub low : PakValue,
+    pub high : PakValue,
+    pub count : u64,
+}
+
+impl PakHistogram {
+    pub(crate) fn equi_depth(groups : &[(PakValue, u64)], bucket_count : usize) -> Self {
+        let total = groups.iter().map(|(_, count)| count).sum::<u64>();
+        let depth = total as f64 / bucket_count.max(1) as f64;
+        
+        let mut buckets = Vec::new();
+        let mut current : Option<PakHistogramBucket> = None;
+        let mut seen = 0u64;
+        for (value, count) in groups {
+            let bucket = current.get_or_insert_with(|| PakHistogramBucket { low: value.clone(), high: value.clone(), count: 0 });
+            bucket.high = value.clone();
+            bucket.count += count;
+            seen += count;
+            if seen as f64 >= depth * (buckets.len() + 1) as f64 && buckets.len() + 1 < bucket_count {
+                buckets.push(current.take().unwrap());
+            }
+        }
+        buckets.extend(current);
+        
+        Self { buckets }
+    }
+    
+    /// The total number of items counted by the histogram.
+    pub fn total(&self) -> u64 {
+        self.buckets.iter().map(|bucket| bucket.count).sum()
+    }
+}
+
+/// Finds the value at the given percentile using the nearest-rank method. `groups` must be sorted by value.
+pub(crate) fn percentile(groups : &[(PakValue, u64)], percentile : f64) -> Option<PakValue> {
+    let total = groups.iter().map(|(_, count)| count).sum::<u64>();
+    if total == 0 { return None }
+    
+    let rank = ((percentile.clamp(0.0, 1.0) * total as f64).ceil() as u64).max(1);
+    let mut seen = 0u64;
+    for (value, count) in groups {
+        seen += count;
+        if seen >= rank { return Some(value.clone()) }
+    }
+    None
+}
@@ -0,0 +1,141 @@
+use std::io::Read;
+use crate::{error::PakResult, index::PakIndex, path::{PakBlob, PAK_MTIME_KEY, PAK_SIZE_KEY}, PakBuilder};
+#[cfg(feature = "zip")]
+use std::{collections::HashMap, io::{Seek, Write}};
+#[cfg(feature = "zip")]
+use crate::{pointer::PakPointer, value::PakValue, Pak};
+
+//==============================================================================================
+//        Import
+//==============================================================================================
+
+impl PakBuilder {
+    /// Adds every file in a zip archive as a [PakBlob](crate::path::PakBlob) at its path in the archive, indexed by its size under
+    /// [PAK_SIZE_KEY](crate::path::PAK_SIZE_KEY) and its modification time under [PAK_MTIME_KEY](crate::path::PAK_MTIME_KEY). Directories
+    /// are left out, since they exist through the paths of their files. Returns the number of files that were added.
+    #[cfg(feature = "zip")]
+    pub fn import_zip<R>(&mut self, reader : R) -> PakResult<usize> where R : Read + std::io::Seek {
+        let mut archive = zip::ZipArchive::new(reader)?;
+        let mut imported = 0;
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index)?;
+            if file.is_dir() { continue }
+            let mut bytes = Vec::with_capacity(crate::pointer::to_usize(file.size())?);
+            file.read_to_end(&mut bytes)?;
+            let mtime = file.last_modified().map(zip_time);
+            self.pak_file(file.name(), bytes, mtime)?;
+            imported += 1;
+        }
+        Ok(imported)
+    }
+    
+    /// Adds every regular file in a tar archive the same way [import_zip](crate::PakBuilder::import_zip) does. Returns the number of files
+    /// that were added.
+    #[cfg(feature = "tar")]
+    pub fn import_tar<R>(&mut self, reader : R) -> PakResult<usize> where R : Read {
+        let mut archive = tar::Archive::new(reader);
+        let mut imported = 0;
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() { continue }
+            let path = entry.path()?.to_string_lossy().into_owned();
+            let mtime = entry.header().mtime().ok();
+            let mut bytes = Vec::with_capacity(crate::pointer::to_usize(entry.size())?);
+            entry.read_to_end(&mut bytes)?;
+            self.pak_file(&path, bytes, mtime)?;
+            imported += 1;
+        }
+        Ok(imported)
+    }
+    
+    fn pak_file(&mut self, path : &str, bytes : Vec<u8>, mtime : Option<u64>) -> PakResult<()> {
+        let mut indices = vec![self.claim_path(path)?, PakIndex::new(PAK_SIZE_KEY, bytes.len() as u64)];
+        indices.extend(mtime.map(|mtime| PakIndex::new(PAK_MTIME_KEY, mtime)));
+        self.pak_internal::<PakBlob>(bytes, indices)?;
+        Ok(())
+    }
+}
+
+//==============================================================================================
+//        Export
+//==============================================================================================
+
+#[cfg(feature = "zip")]
+impl Pak {
+    /// Writes every item that passes the filter into a zip archive, so the items can be handed to tools that don't read paks. Items are
+    /// stored at their path, or as `<ordinal>.bin` if they don't have one, and keep their modification time if they were imported with one.
+    /// A [PakBlob](crate::path::PakBlob) is written as the file it holds, and any other item as its encoded bytes. Returns the number of
+    /// items that were written.
+    pub fn export_zip<W, F>(&self, writer : W, mut filter : F) -> PakResult<usize> where W : Write + Seek, F : FnMut(&PakPointer) -> bool {
+        let paths = self.paths()?.into_iter().map(|(path, pointer)| (pointer.offset(), path)).collect::<HashMap<_, _>>();
+        let mut mtimes = HashMap::new();
+        if self.fetch_indices()?.contains_key(PAK_MTIME_KEY) {
+            self.get_tree(PAK_MTIME_KEY)?.walk(|value, postings| {
+                let PakValue::Uint(mtime) = value else { return Ok(true) };
+                for pointer in postings.pointers()? {
+                    mtimes.insert(pointer?.offset(), *mtime);
+                }
+                Ok(true)
+            })?;
+        }
+        
+        let mut archive = zip::ZipWriter::new(writer);
+        let mut written = 0;
+        for (ordinal, pointer) in self.ordinals()?.iter().enumerate() {
+            let pointer = pointer.clone().into_pointer();
+            if !filter(&pointer) { continue }
+            let name = paths.get(&pointer.offset()).cloned().unwrap_or_else(|| format!("{ordinal}.bin"));
+            let mut options = zip::write::SimpleFileOptions::default();
+            if let Some(time) = mtimes.get(&pointer.offset()).and_then(|mtime| zip_datetime(*mtime)) {
+                options = options.last_modified_time(time);
+            }
+            archive.start_file(name, options)?;
+            archive.write_all(&self.read_bytes(&pointer)?)?;
+            written += 1;
+        }
+        archive.finish()?;
+        Ok(written)
+    }
+}
+
+/// Zip archives store local times with no time zone, which are read as UTC.
+#[cfg(feature = "zip")]
+fn zip_time(time : zip::DateTime) -> u64 {
+    let days = days_from_civil(time.year() as i64, time.month() as i64, time.day() as i64);
+    (days * 86_400 + time.hour() as i64 * 3_600 + time.minute() as i64 * 60 + time.second() as i64).max(0) as u64
+}
+
+/// The zip time of a unix time, if it falls in the years zip archives can hold.
+#[cfg(feature = "zip")]
+fn zip_datetime(time : u64) -> Option<zip::DateTime> {
+    let (days, seconds) = ((time / 86_400) as i64, time % 86_400);
+    let (year, month, day) = civil_from_days(days);
+    let year = u16::try_from(year).ok()?;
+    zip::DateTime::from_date_and_time(year, month as u8, day as u8, (seconds / 3_600) as u8, (seconds / 60 % 60) as u8, (seconds % 60) as u8).ok()
+}
+
+/// The date that is the number of days after 1970-01-01, the reverse of [days_from_civil].
+#[cfg(feature = "zip")]
+fn civil_from_days(days : i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+/// The number of days from 1970-01-01 to the date, in the proleptic Gregorian calendar.
+#[cfg(feature = "zip")]
+fn days_from_civil(year : i64, month : i64, day : i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
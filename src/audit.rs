@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use crate::{error::{PakError, PakResult}, index::PakIndex, item::PakItemSearchable, path::PAK_SIDECAR_KEY, pointer::PakPointer, PakBuilder};
+
+/// What a builder does when an item of an audited type is paked without an entry for one of its index keys, like an item that was
+/// paked with [pak_no_search](crate::PakBuilder::pak_no_search) by mistake. See [with_index_audit](crate::PakBuilder::with_index_audit).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PakIndexAudit {
+    /// The item is paked, and the gap is recorded in [index_gaps](crate::PakBuilder::index_gaps).
+    Warn,
+    /// The item is refused with [PakError::MissingIndices](crate::error::PakError::MissingIndices).
+    Deny,
+}
+
+/// An item that was paked without entries for some of the index keys its type declares.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PakIndexGap {
+    pub type_name : String,
+    pub pointer : PakPointer,
+    pub missing : Vec<String>,
+}
+
+/// The index keys every audited type is expected to be paked with, and the gaps that were found so far.
+#[derive(Debug)]
+pub(crate) struct PakIndexAuditor {
+    mode : PakIndexAudit,
+    types : HashMap<String, Vec<String>>,
+    gaps : Vec<PakIndexGap>,
+}
+
+impl PakBuilder {
+    /// Checks every item of an audited type for entries under each of the index keys its type declares, catching items that silently
+    /// won't show up in searches. Types are audited with [audit](crate::PakBuilder::audit), and every type in the builder's
+    /// [schema](crate::PakBuilder::with_schema) is audited with the fields it lists.
+    pub fn with_index_audit(mut self, mode : PakIndexAudit) -> Self {
+        match &mut self.audit {
+            Some(auditor) => auditor.mode = mode,
+            None => self.audit = Some(PakIndexAuditor { mode, types : HashMap::new(), gaps : Vec::new() }),
+        }
+        self
+    }
+
+    /// Audits items of type `T` against the index keys it declares, which the [PakItem](crate::item::PakItem) derive lists from its
+    /// `#[pak(index)]` fields. This turns the audit on in [Warn](PakIndexAudit::Warn) mode, unless
+    /// [with_index_audit](crate::PakBuilder::with_index_audit) picked a mode already.
+    pub fn audit<T : PakItemSearchable>(mut self) -> Self {
+        let keys = T::index_keys().iter().map(|key| key.to_string()).collect();
+        let auditor = self.audit.get_or_insert_with(|| PakIndexAuditor { mode : PakIndexAudit::Warn, types : HashMap::new(), gaps : Vec::new() });
+        auditor.types.insert(std::any::type_name::<T>().to_string(), keys);
+        self
+    }
+
+    /// The items that were paked with missing index entries while the audit was in [Warn](PakIndexAudit::Warn) mode.
+    pub fn index_gaps(&self) -> &[PakIndexGap] {
+        self.audit.as_ref().map(|auditor| auditor.gaps.as_slice()).unwrap_or_default()
+    }
+
+    pub(crate) fn audit_item(&mut self, pointer : &PakPointer, indices : &[PakIndex]) -> PakResult<()> {
+        let Some(auditor) = &mut self.audit else { return Ok(()) };
+        // Sidecars are paked without indices on purpose, they are only found through their blob.
+        if indices.iter().any(|index| index.key == PAK_SIDECAR_KEY) { return Ok(()) }
+        let schema = self.schema.as_ref().and_then(|schema| schema.items.iter().find(|item| item.is_type(pointer.type_name())));
+        let Some(keys) = auditor.types.get(pointer.type_name()).or(schema.map(|item| &item.fields)) else { return Ok(()) };
+        let missing = keys.iter().filter(|key| !indices.iter().any(|index| &index.key == *key)).cloned().collect::<Vec<_>>();
+        if missing.is_empty() { return Ok(()) }
+        match auditor.mode {
+            PakIndexAudit::Warn => auditor.gaps.push(PakIndexGap { type_name : pointer.type_name().to_string(), pointer : pointer.clone(), missing }),
+            PakIndexAudit::Deny => return Err(PakError::MissingIndices(pointer.type_name().to_string(), missing.join(", "))),
+        }
+        Ok(())
+    }
+}
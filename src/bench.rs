@@ -0,0 +1,91 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::{error::PakResult, index::PakIndex, item::PakItemSearchable, Pak, PakBuilder};
+
+//==============================================================================================
+//        PakBenchDataset
+//==============================================================================================
+
+/// Generates reproducible datasets for benchmarking. Each item has a number of indexed fields, and every field value is drawn from
+/// `0..cardinality` with a seeded generator, so the same settings always produce the same pak.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PakBenchDataset {
+    pub items : usize,
+    pub indices : usize,
+    pub cardinality : u64,
+    pub seed : u64,
+}
+
+impl PakBenchDataset {
+    /// Creates a dataset with the number of items, a single index and a cardinality equal to the number of items.
+    pub fn new(items : usize) -> Self {
+        Self { items, indices : 1, cardinality : items.max(1) as u64, seed : 0 }
+    }
+    
+    /// Sets the number of indexed fields on each item. They are named `field_0`, `field_1` and so on.
+    pub fn with_indices(mut self, indices : usize) -> Self {
+        self.indices = indices;
+        self
+    }
+    
+    /// Sets how many distinct values each field can have.
+    pub fn with_cardinality(mut self, cardinality : u64) -> Self {
+        self.cardinality = cardinality.max(1);
+        self
+    }
+    
+    /// Sets the seed of the value generator.
+    pub fn with_seed(mut self, seed : u64) -> Self {
+        self.seed = seed;
+        self
+    }
+    
+    /// Generates the items of the dataset.
+    pub fn items(&self) -> Vec<PakBenchItem> {
+        let mut state = self.seed;
+        (0..self.items as u64).map(|id| {
+            let fields = (0..self.indices).map(|_| split_mix(&mut state) % self.cardinality).collect();
+            PakBenchItem { id, fields }
+        }).collect()
+    }
+    
+    /// Paks every item of the dataset into a new builder.
+    pub fn builder(&self) -> PakResult<PakBuilder> {
+        let mut builder = PakBuilder::new();
+        for item in self.items() {
+            builder.pak(item)?;
+        }
+        Ok(builder)
+    }
+    
+    /// Builds the dataset into a pak that lives in memory.
+    pub fn build_in_memory(&self) -> PakResult<Pak> {
+        self.builder()?.build_in_memory()
+    }
+    
+    /// Builds the dataset into a pak file at the path.
+    pub fn build_file(&self, path : impl AsRef<Path>) -> PakResult<Pak> {
+        self.builder()?.build_file(path)
+    }
+}
+
+/// An item generated by a [PakBenchDataset](crate::bench::PakBenchDataset).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PakBenchItem {
+    pub id : u64,
+    pub fields : Vec<u64>,
+}
+
+impl PakItemSearchable for PakBenchItem {
+    fn get_indices(&self) -> Vec<PakIndex> {
+        self.fields.iter().enumerate().map(|(field, value)| PakIndex::new(format!("field_{field}"), *value)).collect()
+    }
+}
+
+fn split_mix(state : &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
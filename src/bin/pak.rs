@@ -0,0 +1,47 @@
+use std::process::ExitCode;
+use pak_db::{convert::{self, PakUpgradeOptions}, format::PakFormat};
+
+const USAGE : &str = "usage: pak upgrade <old> <new> [--format 1|2] [--no-verify]";
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let result = match args.first().map(String::as_str) {
+        Some("upgrade") => upgrade(&args[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(message) => {
+            println!("{message}");
+            ExitCode::SUCCESS
+        },
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        },
+    }
+}
+
+//==============================================================================================
+//        Upgrade
+//==============================================================================================
+
+fn upgrade(args : &[String]) -> Result<String, String> {
+    let mut paths = Vec::new();
+    let mut options = PakUpgradeOptions::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => options = options.with_format(match args.next().map(String::as_str) {
+                Some("1") => PakFormat::V1,
+                Some("2") => PakFormat::V2,
+                _ => return Err(USAGE.to_string()),
+            }),
+            "--no-verify" => options = options.with_verify(false),
+            _ => paths.push(arg),
+        }
+    }
+    let [old, new] = paths.as_slice() else { return Err(USAGE.to_string()) };
+    
+    let from = convert::upgrade(old, new, options).map_err(|error| format!("failed to upgrade {old}: {error}"))?;
+    Ok(format!("upgraded {old} from v{} to v{} at {new}", from.version(), options.format.version()))
+}
@@ -0,0 +1,104 @@
+use std::{collections::{BTreeMap, HashSet}, ops::Bound};
+use roaring::RoaringBitmap;
+use serde::{Deserialize, Serialize};
+use crate::{error::PakResult, pointer::{PakTypedPointer, PakUntypedPointer}, value::PakValue, Pak, PakBuilder};
+
+//==============================================================================================
+//        PakBitmapIndex
+//==============================================================================================
+
+/// The serialized form of a bitmap index. Each distinct value maps to a roaring bitmap of item ordinals, which are resolved back into
+/// pointers through the pak's ordinal table. Since every bitmap index is built alongside a tree, paks with bitmap indices can still be read without
+/// the `roaring` feature, queries just fall back to the tree.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PakBitmapIndex {
+    values : Vec<(PakValue, Vec<u8>)>,
+}
+
+impl PakBitmapIndex {
+    pub(crate) fn read(pak : &Pak, pointer : &PakUntypedPointer) -> PakResult<Self> {
+        pak.read_err(&pointer.as_pointer())
+    }
+
+    /// ORs together the bitmaps of every value inside of the range.
+    pub(crate) fn range(&self, range : (Bound<&PakValue>, Bound<&PakValue>)) -> PakResult<PakBitmapSet> {
+        let mut bitmap = RoaringBitmap::new();
+        for (value, bytes) in &self.values {
+            if crate::btree::range_contains(&range, value) {
+                bitmap |= RoaringBitmap::deserialize_from(bytes.as_slice())?;
+            }
+        }
+        Ok(PakBitmapSet { bitmap })
+    }
+}
+
+//==============================================================================================
+//        PakBitmapBuilder
+//==============================================================================================
+
+#[derive(Default)]
+pub(crate) struct PakBitmapBuilder {
+    values : BTreeMap<PakValue, RoaringBitmap>,
+}
+
+impl PakBitmapBuilder {
+    pub(crate) fn insert(&mut self, value : PakValue, ordinal : u32) {
+        self.values.entry(value).or_default().insert(ordinal);
+    }
+
+    pub(crate) fn into_pak(self, pak : &mut PakBuilder) -> PakResult<PakUntypedPointer> {
+        let mut values = Vec::with_capacity(self.values.len());
+        for (value, bitmap) in self.values {
+            let mut bytes = Vec::with_capacity(bitmap.serialized_size());
+            bitmap.serialize_into(&mut bytes)?;
+            values.push((value, bytes));
+        }
+        let index = PakBitmapIndex { values };
+        Ok(pak.pak_no_search(index)?.as_untyped())
+    }
+}
+
+//==============================================================================================
+//        PakBitmapSet
+//==============================================================================================
+
+/// The result of a query that was answered entirely from bitmap indices. Sets can be combined with fast bitwise operations before they
+/// are turned into pointers.
+pub struct PakBitmapSet {
+    bitmap : RoaringBitmap,
+}
+
+impl PakBitmapSet {
+    /// Combines two sets, keeping items that are in either.
+    pub fn union(mut self, other : PakBitmapSet) -> Self {
+        self.bitmap |= other.bitmap;
+        self
+    }
+
+    /// Combines two sets, keeping only items that are in both.
+    pub fn intersection(mut self, other : PakBitmapSet) -> Self {
+        self.bitmap &= other.bitmap;
+        self
+    }
+
+    /// The number of items in the set.
+    pub fn len(&self) -> u64 {
+        self.bitmap.len()
+    }
+
+    /// Returns true if no items are in the set.
+    pub fn is_empty(&self) -> bool {
+        self.bitmap.is_empty()
+    }
+
+    /// The ordinals of the items in the set, in ascending order.
+    pub fn ordinals(&self) -> impl Iterator<Item = u32> + '_ {
+        self.bitmap.iter()
+    }
+
+    /// Resolves the set into the pointers of its items using the pak's ordinal table.
+    pub fn into_pointers(self, pak : &Pak) -> PakResult<HashSet<PakTypedPointer>> {
+        let ordinals = pak.ordinals()?;
+        Ok(self.bitmap.iter().filter_map(|ordinal| ordinals.get(ordinal as usize).cloned()).collect())
+    }
+}
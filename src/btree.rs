@@ -1,7 +1,8 @@
-use std::{cmp::Ordering, collections::{HashMap, HashSet, VecDeque}, fmt::Debug};
+use std::{cmp::Ordering, collections::{BTreeMap, HashMap, HashSet, VecDeque}, fmt::Debug, ops::Bound, path::PathBuf};
 use serde::{Deserialize, Serialize};
+use smallvec::{smallvec, SmallVec};
 
-use crate::{error::PakResult, pointer::{PakPointer, PakTypedPointer, PakUntypedPointer}};
+use crate::{error::{PakError, PakResult}, hash::PakHashMap, inline::PakPageInlined, legacy, pointer::{PakPointer, PakTypedPointer, PakUntypedPointer}};
 
 use super::{value::PakValue, Pak, PakBuilder};
 
@@ -18,8 +19,14 @@ pub struct PakTree<'p> {
 impl <'p> PakTree<'p> {
     pub fn new(pak: &'p Pak, key : &str) -> PakResult<PakTree<'p>> {
         let indices = pak.fetch_indices()?;
-        let pointer = indices.get(key).unwrap();
-        let meta : PakTreeMeta = pak.read_err(&pointer.as_pointer())?;
+        let Some(pointer) = indices.get(key) else { return Err(PakError::UnknownIndex(key.to_string())) };
+        if pak.meta.revision == 0 {
+            let pages = legacy::tree_meta(pak, *pointer)?.pages.into_iter().collect();
+            return Ok(PakTree { pak, meta : PakTreeMeta { pages, bitmap : None, custom : None } })
+        }
+        let pointer = pointer.as_pointer();
+        if !pointer.type_is_match::<PakTreeMeta>() { return Err(PakError::type_mismatch::<PakTreeMeta>(&pointer, Some(key))) }
+        let meta : PakTreeMeta = pak.meta.header_encoding.deserialize(&pak.read_bytes(&pointer)?)?;
         
         Ok(PakTree {
             pak,
@@ -27,123 +34,417 @@ impl <'p> PakTree<'p> {
         })
     }
     
+    pub(crate) fn pak(&self) -> &'p Pak {
+        self.pak
+    }
+    
+    /// Reads the page at the pointer, and keeps the items inlined after it. The pages of paks written by pak-db 0.1 point straight at
+    /// their items, which are turned into ordinals.
+    fn page(&self, pointer : PakUntypedPointer) -> PakResult<PakTreePage> {
+        if self.pak.meta.revision > 0 {
+            let bytes = self.pak.read_bytes(&pointer.as_pointer())?;
+            let mut rest = bytes.as_slice();
+            let page = bincode::deserialize_from(&mut rest)?;
+            // The inlined items come after the page, where readers that don't know about them never look.
+            if !rest.is_empty() { self.pak.load_inlined(bincode::deserialize::<PakPageInlined>(rest)?)? }
+            return Ok(page)
+        }
+        let page = legacy::tree_page(self.pak, pointer)?;
+        let table = self.pak.ordinals()?;
+        let values = page.values.into_iter().map(|entry| PakTreePageEntry {
+            key : entry.key,
+            values : entry.values.iter().filter_map(|item| ordinal_of(table, item.offset())).collect(),
+            overflow : None,
+            previous : entry.previous,
+        }).collect();
+        Ok(PakTreePage { values, next : page.next })
+    }
+    
+    /// The name of the custom [PakIndexKind](crate::kind::PakIndexKind) built alongside this tree and the pointer to its structure, if there is one.
+    pub fn custom(&self) -> Option<(&str, PakUntypedPointer)> {
+        self.meta.custom.as_ref().map(|(name, pointer)| (name.as_str(), *pointer))
+    }
+    
+    /// Every chunk the tree is made of other than its meta, which are the pages, the overflow chunks, and the bitmap and custom structures.
+    #[cfg(feature = "encryption")]
+    pub(crate) fn chunks(&self) -> PakResult<Vec<PakUntypedPointer>> {
+        let mut chunks = Vec::new();
+        for pointer in self.meta.pages.values() {
+            chunks.push(*pointer);
+            let page = self.page(*pointer)?;
+            chunks.extend(page.values.into_iter().filter_map(|entry| entry.overflow).flat_map(|overflow| overflow.chunks));
+        }
+        chunks.extend(self.meta.bitmap);
+        chunks.extend(self.meta.custom.as_ref().map(|(_, pointer)| *pointer));
+        Ok(chunks)
+    }
+    
+    /// The most ordinals the tree keeps in an overflow chunk, or `None` if none of its posting lists were long enough to overflow.
+    pub(crate) fn overflow_limit(&self) -> PakResult<Option<usize>> {
+        for pointer in self.meta.pages.values() {
+            // Posting lists are split from the front, so the first chunk of every overflowed list is full.
+            let Some(overflow) = self.page(*pointer)?.values.into_iter().find_map(|entry| entry.overflow) else { continue };
+            return Ok(Some(self.pak.read_err::<Vec<u32>>(&overflow.chunks[0].as_pointer())?.len()))
+        }
+        Ok(None)
+    }
+    
+    /// The pointer to the bitmap index that was built alongside this tree, if there is one.
+    #[cfg(feature = "roaring")]
+    pub fn bitmap(&self) -> Option<PakUntypedPointer> {
+        self.meta.bitmap
+    }
+    
     pub fn get(&self, value : &PakValue) -> PakResult<HashSet<PakTypedPointer>> {
-        let pointer = self.meta.pages.get(&0).unwrap();
+        self.collect((Bound::Included(value), Bound::Included(value)))
+    }
+    
+    pub fn get_less(&self, value : &PakValue) -> PakResult<HashSet<PakTypedPointer>> {
+        self.collect((Bound::Unbounded, Bound::Excluded(value)))
+    }
+    
+    pub fn get_less_eq(&self, value : &PakValue) -> PakResult<HashSet<PakTypedPointer>> {
+        self.collect((Bound::Unbounded, Bound::Included(value)))
+    }
+    
+    pub fn get_greater(&self, value : &PakValue) -> PakResult<HashSet<PakTypedPointer>> {
+        self.collect((Bound::Excluded(value), Bound::Unbounded))
+    }
+    
+    pub fn get_greater_eq(&self, value : &PakValue) -> PakResult<HashSet<PakTypedPointer>> {
+        self.collect((Bound::Included(value), Bound::Unbounded))
+    }
+    
+    fn collect(&self, range : (Bound<&PakValue>, Bound<&PakValue>)) -> PakResult<HashSet<PakTypedPointer>> {
         let mut set = HashSet::new();
-        self.get_r(value, *pointer, &mut set)?;
+        self.range(range, |_, postings| {
+            for pointer in postings.pointers()? {
+                set.insert(pointer?.clone());
+            }
+            Ok(true)
+        })?;
         Ok(set)
     }
+
+    /// The number of items under a key inside of the range, worked out from the lengths of the posting lists without reading any of them.
+    pub(crate) fn count(&self, range : (Bound<&PakValue>, Bound<&PakValue>)) -> PakResult<u64> {
+        let mut count = 0;
+        self.range(range, |_, postings| {
+            count += postings.len();
+            Ok(true)
+        })?;
+        Ok(count)
+    }
     
-    fn get_r(&self, value : &PakValue, current_page : PakUntypedPointer, set : &mut HashSet<PakTypedPointer>) -> PakResult<()> {
-        let page : PakTreePage = self.pak.read_err(&current_page.as_pointer())?;
-        
+    /// Keeps the candidates that are under a key inside of the range. Long posting lists are probed for each candidate instead of being read
+    /// whole, and the walk stops as soon as every candidate has been found.
+    pub(crate) fn probe(&self, range : (Bound<&PakValue>, Bound<&PakValue>), candidates : &HashSet<PakTypedPointer>) -> PakResult<HashSet<PakTypedPointer>> {
+        let table = self.pak.ordinals()?;
+        let mut remaining = candidates.iter().filter_map(|pointer| Some((ordinal_of(table, pointer.offset())?, pointer))).collect::<HashMap<_, _>>();
+        let mut found = HashSet::new();
+        if remaining.is_empty() { return Ok(found) }
+        self.range(range, |_, postings| {
+            let mut ordinals = remaining.keys().copied().collect::<Vec<_>>();
+            ordinals.sort_unstable();
+            for ordinal in postings.intersect_ordinals(&ordinals)? {
+                if let Some(pointer) = remaining.remove(&ordinal) { found.insert(pointer.clone()); }
+            }
+            Ok(!remaining.is_empty())
+        })?;
+        Ok(found)
+    }
+
+    /// Iterates over the keys of the tree in order. Pages are read as the iterator reaches them, so stopping early never reads the rest of the tree.
+    pub fn keys(self) -> PakDistinctIter<'p> {
+        let stack = self.meta.pages.get(&0).map(|pointer| vec![PakDistinctFrame::Page(*pointer)]).unwrap_or_default();
+        PakDistinctIter { tree : self, stack }
+    }
+    
+    /// Visits every entry in the tree in key order. The visitor returns false to stop the walk early.
+    pub fn walk<F>(&self, visitor : F) -> PakResult<()> where F : FnMut(&PakValue, &PakPostings) -> PakResult<bool> {
+        self.range((Bound::Unbounded, Bound::Unbounded), visitor)
+    }
+    
+    /// Visits every entry with a key inside of the range in key order, skipping pages that can't hold any matching keys. The visitor returns false to stop the walk early.
+    pub fn range<F>(&self, range : (Bound<&PakValue>, Bound<&PakValue>), mut visitor : F) -> PakResult<()> where F : FnMut(&PakValue, &PakPostings) -> PakResult<bool> {
+        let pointer = self.meta.pages.get(&0).unwrap();
+        self.range_r(*pointer, &range, &mut visitor)?;
+        Ok(())
+    }
+
+    fn range_r<F>(&self, current_page : PakUntypedPointer, range : &(Bound<&PakValue>, Bound<&PakValue>), visitor : &mut F) -> PakResult<bool> where F : FnMut(&PakValue, &PakPostings) -> PakResult<bool> {
+        let page = self.page(current_page)?;
+
         for entry in page.values {
-            if &entry.key < value {
-                continue;
-            } else if &entry.key > value {
-                if let Some(index) = entry.previous {
-                    let pointer = self.meta.pages.get(&index).unwrap();
-                    self.get_r(value, *pointer, set)?;
-                    return Ok(());
-                }
-            } else {
-                entry.values.clone().into_iter().for_each(|value| {set.insert(value);});
-                return Ok(());
+            // Everything under `previous` is smaller than this entry, so it can only be skipped when this entry is at or below the lower bound.
+            let below_lower = match range.0 {
+                Bound::Included(lower) | Bound::Excluded(lower) => &entry.key <= lower,
+                Bound::Unbounded => false,
+            };
+            if let (Some(index), false) = (entry.previous, below_lower) {
+                let pointer = self.meta.pages.get(&index).unwrap();
+                if !self.range_r(*pointer, range, visitor)? { return Ok(false) }
+            }
+            
+            // Everything after this entry is larger, so the walk is over once the upper bound is passed.
+            let above_upper = match range.1 {
+                Bound::Included(upper) => &entry.key > upper,
+                Bound::Excluded(upper) => &entry.key >= upper,
+                Bound::Unbounded => false,
+            };
+            if above_upper { return Ok(false) }
+            
+            if range_contains(range, &entry.key) {
+                let postings = PakPostings { pak: self.pak, inline: &entry.values, overflow: entry.overflow.as_ref() };
+                if !visitor(&entry.key, &postings)? { return Ok(false) }
             }
         }
-        
+
         if let Some(index) = page.next {
             let pointer = self.meta.pages.get(&index).unwrap();
-            self.get_r(value, *pointer, set)?;
+            return self.range_r(*pointer, range, visitor);
+        }
+
+        Ok(true)
+    }
+}
+
+/// The ordinal of the item at the pointer. Items are laid out in the order of their ordinals, so the table is searched by offset first.
+pub(crate) fn ordinal_of(table : &[PakTypedPointer], offset : u64) -> Option<u32> {
+    let ordinal = match table.binary_search_by_key(&offset, |entry| entry.offset()) {
+        Ok(ordinal) => ordinal,
+        Err(_) => table.iter().position(|entry| entry.offset() == offset)?,
+    };
+    u32::try_from(ordinal).ok()
+}
+
+pub(crate) fn range_contains(range : &(Bound<&PakValue>, Bound<&PakValue>), key : &PakValue) -> bool {
+    let lower = match range.0 {
+        Bound::Included(lower) => key >= lower,
+        Bound::Excluded(lower) => key > lower,
+        Bound::Unbounded => true,
+    };
+    let upper = match range.1 {
+        Bound::Included(upper) => key <= upper,
+        Bound::Excluded(upper) => key < upper,
+        Bound::Unbounded => true,
+    };
+    lower && upper
+}
+
+//==============================================================================================
+//        PakPostings
+//==============================================================================================
+
+/// The items stored under a single value of an index. Pages only store the ordinals of the items, which are resolved into pointers through
+/// the pak's ordinal table. Small posting lists live inline in the tree page, while large ones are split into overflow chunks that are only
+/// read from the pak when they are asked for.
+pub struct PakPostings<'t> {
+    pak : &'t Pak,
+    inline : &'t [u32],
+    overflow : Option<&'t PakTreeOverflow>,
+}
+
+impl<'t> PakPostings<'t> {
+    /// The number of pointers in the posting list. This never reads any overflow chunks.
+    pub fn len(&self) -> u64 {
+        match self.overflow {
+            Some(overflow) => overflow.len,
+            None => self.inline.len() as u64,
         }
-        
-        Ok(())
     }
     
-    pub fn get_less(&self, value : &PakValue) -> PakResult<HashSet<PakTypedPointer>> {
-        let pointer = self.meta.pages.get(&0).unwrap();
-        let mut results = HashSet::new();
-        self.get_less_r(value, *pointer, &mut results, false)?;
-        Ok(results)
+    /// Returns true if the posting list has no pointers.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
     
-    pub fn get_less_eq(&self, value : &PakValue) -> PakResult<HashSet<PakTypedPointer>> {
-        let pointer = self.meta.pages.get(&0).unwrap();
-        let mut results = HashSet::new();
-        self.get_less_r(value, *pointer, &mut results, true)?;
-        Ok(results)
+    /// Iterates over the pointers of the posting list. The pointers are borrowed from the pak's ordinal table, so nothing is allocated per pointer,
+    /// and overflow chunks are only read once the iterator reaches them.
+    pub fn pointers(&self) -> PakResult<PakPostingsIter<'t>> {
+        Ok(PakPostingsIter {
+            pak : self.pak,
+            table : self.pak.ordinals()?,
+            inline : self.inline.iter(),
+            chunk : Vec::new().into_iter(),
+            overflow : self.overflow.map(|overflow| overflow.chunks.iter()).unwrap_or_default(),
+        })
     }
     
-    fn get_less_r(&self, value : &PakValue, current_page : PakUntypedPointer, set : &mut HashSet<PakTypedPointer>, match_eq : bool) -> PakResult<()> {
-        let page : PakTreePage = self.pak.read_err(&current_page.as_pointer())?;
-        
-        for entry in page.values {
-            if &entry.key > value {
-                continue;
-            } else if &entry.key < value {
-                entry.values.clone().into_iter().for_each(|value| {set.insert(value);});
-                if let Some(index) = entry.previous {
-                    let pointer = self.meta.pages.get(&index).unwrap();
-                    self.get_less_r(value, *pointer, set, match_eq)?;
-                }
-                continue;
-            } else {
-                if match_eq {
-                    entry.values.clone().into_iter().for_each(|value| {set.insert(value);});
-                }
-                continue;
+    /// Streams the posting list one chunk at a time. Overflow chunks are read one by one, and the visitor returns false to stop reading.
+    pub fn for_each_chunk<F>(&self, mut visitor : F) -> PakResult<()> where F : FnMut(&[PakTypedPointer]) -> bool {
+        let table = self.pak.ordinals()?;
+        self.for_each_ordinal_chunk(|chunk| {
+            let pointers = chunk.iter().filter_map(|ordinal| table.get(*ordinal as usize).cloned()).collect::<Vec<_>>();
+            visitor(&pointers)
+        })
+    }
+    
+    /// Streams the ordinals of the posting list one chunk at a time, without resolving them into pointers.
+    pub fn for_each_ordinal_chunk<F>(&self, mut visitor : F) -> PakResult<()> where F : FnMut(&[u32]) -> bool {
+        if !visitor(self.inline) { return Ok(()) }
+        if let Some(overflow) = self.overflow {
+            for chunk in &overflow.chunks {
+                let chunk : Vec<u32> = self.pak.read_err(&chunk.as_pointer())?;
+                if !visitor(&chunk) { break }
             }
         }
-        
-        if let Some(index) = page.next {
-            let pointer = self.meta.pages.get(&index).unwrap();
-            return self.get_less_r(value, *pointer, set, match_eq);
-        }
-        
         Ok(())
     }
     
-    pub fn get_greater(&self, value : &PakValue) -> PakResult<HashSet<PakTypedPointer>> {
-        let pointer = self.meta.pages.get(&0).unwrap();
-        let mut results = HashSet::new();
-        self.get_greater_r(value, *pointer, &mut results, false)?;
-        Ok(results)
+    /// Returns true if the item with the [ordinal](crate::Pak::pointer_of) is in the posting list. The skip entries of an overflowing list
+    /// point straight at the one chunk that could hold the ordinal, so at most one chunk is read.
+    pub fn contains_ordinal(&self, ordinal : u32) -> PakResult<bool> {
+        Ok(!self.intersect_ordinals(&[ordinal])?.is_empty())
     }
     
-    pub fn get_greater_eq(&self, value : &PakValue) -> PakResult<HashSet<PakTypedPointer>> {
-        let pointer = self.meta.pages.get(&0).unwrap();
-        let mut results = HashSet::new();
-        self.get_greater_r(value, *pointer, &mut results, true)?;
-        Ok(results)
+    /// Returns the ordinals that are in the posting list, out of a list of ordinals sorted in ascending order. The skip entries let the
+    /// search leapfrog over every overflow chunk that can't hold any of them, so intersecting a short list with a long one only reads
+    /// the chunks the short list lands in.
+    pub fn intersect_ordinals(&self, ordinals : &[u32]) -> PakResult<Vec<u32>> {
+        let mut found = self.inline.iter().copied().filter(|ordinal| ordinals.binary_search(ordinal).is_ok()).collect::<Vec<_>>();
+        let Some(overflow) = self.overflow else { return Ok(found) };
+        let mut rest = ordinals;
+        for (i, chunk) in overflow.chunks.iter().enumerate() {
+            // The chunks are in order, so ordinals below the first one of this chunk aren't anywhere in the list.
+            rest = &rest[rest.partition_point(|ordinal| *ordinal < overflow.skips[i])..];
+            if rest.is_empty() { break }
+            let inside = match overflow.skips.get(i + 1) {
+                Some(next) => rest.partition_point(|ordinal| ordinal < next),
+                None => rest.len(),
+            };
+            if inside == 0 { continue }
+            let chunk : Vec<u32> = self.pak.read_err(&chunk.as_pointer())?;
+            found.extend(rest[..inside].iter().copied().filter(|ordinal| chunk.binary_search(ordinal).is_ok()));
+            rest = &rest[inside..];
+        }
+        Ok(found)
     }
     
-    fn get_greater_r(&self, value : &PakValue, current_page : PakUntypedPointer, set : &mut HashSet<PakTypedPointer>, match_eq : bool) -> PakResult<()> {
-        let page : PakTreePage = self.pak.read_err(&current_page.as_pointer())?;
-        
-        for entry in page.values {
-            if &entry.key < value {
-                continue;
-            } else if &entry.key > value {
-                entry.values.clone().into_iter().for_each(|value| {set.insert(value);});
-                if let Some(index) = entry.previous {
-                    let pointer = self.meta.pages.get(&index).unwrap();
-                    self.get_less_r(value, *pointer, set, match_eq)?;
-                }
-                continue;
-            } else {
-                if match_eq {
-                    entry.values.clone().into_iter().for_each(|value| {set.insert(value);});
-                }
-                continue;
-            }
+    /// Loads the entire posting list into memory.
+    pub fn load(&self) -> PakResult<Vec<PakTypedPointer>> {
+        self.pointers()?.map(|pointer| pointer.cloned()).collect()
+    }
+}
+
+/// An iterator over the pointers of a [PakPostings](crate::index::PakPostings). Reading an overflow chunk can fail, so every item is a result.
+pub struct PakPostingsIter<'t> {
+    pak : &'t Pak,
+    table : &'t [PakTypedPointer],
+    inline : std::slice::Iter<'t, u32>,
+    chunk : std::vec::IntoIter<u32>,
+    overflow : std::slice::Iter<'t, PakUntypedPointer>,
+}
+
+impl<'t> Iterator for PakPostingsIter<'t> {
+    type Item = PakResult<&'t PakTypedPointer>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let ordinal = match self.inline.next().copied().or_else(|| self.chunk.next()) {
+                Some(ordinal) => ordinal,
+                None => {
+                    let chunk = self.overflow.next()?;
+                    match self.pak.read_err::<Vec<u32>>(&chunk.as_pointer()) {
+                        Ok(chunk) => self.chunk = chunk.into_iter(),
+                        Err(error) => return Some(Err(error)),
+                    }
+                    continue;
+                },
+            };
+            if let Some(pointer) = self.table.get(ordinal as usize) { return Some(Ok(pointer)) }
         }
-        
-        if let Some(index) = page.next {
-            let pointer = self.meta.pages.get(&index).unwrap();
-            return self.get_greater_r(value, *pointer, set, match_eq);
+    }
+}
+
+/// An iterator over the distinct keys of an index in order, from [Pak::distinct](crate::Pak::distinct). Reading a page can fail, so every item is a result.
+pub struct PakDistinctIter<'p> {
+    tree : PakTree<'p>,
+    stack : Vec<PakDistinctFrame>,
+}
+
+enum PakDistinctFrame {
+    /// A page that hasn't been read yet.
+    Page(PakUntypedPointer),
+    /// The entries of a page that are left, and the page after its last entry.
+    Entries(VecDeque<PakTreePageEntry>, Option<usize>),
+    Key(PakValue),
+}
+
+impl PakDistinctIter<'_> {
+    fn page(&self, index : usize) -> PakDistinctFrame {
+        PakDistinctFrame::Page(*self.tree.meta.pages.get(&index).unwrap())
+    }
+}
+
+impl Iterator for PakDistinctIter<'_> {
+    type Item = PakResult<PakValue>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.pop()? {
+                PakDistinctFrame::Page(pointer) => match self.tree.page(pointer) {
+                    Ok(page) => self.stack.push(PakDistinctFrame::Entries(page.values, page.next)),
+                    Err(error) => {
+                        self.stack.clear();
+                        return Some(Err(error))
+                    },
+                },
+                PakDistinctFrame::Entries(mut entries, next) => match entries.pop_front() {
+                    // Everything under `previous` comes before the entry, so it goes on top of the stack.
+                    Some(entry) => {
+                        self.stack.push(PakDistinctFrame::Entries(entries, next));
+                        self.stack.push(PakDistinctFrame::Key(entry.key));
+                        if let Some(previous) = entry.previous { self.stack.push(self.page(previous)) }
+                    },
+                    None => if let Some(next) = next { self.stack.push(self.page(next)) },
+                },
+                PakDistinctFrame::Key(key) => return Some(Ok(key)),
+            }
         }
-        
-        Ok(())
+    }
+}
+
+/// Where the ordinals of a large posting list were moved to when the tree was paked. Its fields are part of the
+/// [meta revision](crate::meta::PAK_META_REVISION).
+#[derive(Debug, Deserialize, Serialize)]
+struct PakTreeOverflow {
+    chunks : Vec<PakUntypedPointer>,
+    /// The first ordinal of every chunk, which are skip entries for jumping straight to the chunk that could hold an ordinal.
+    skips : Vec<u32>,
+    len : u64,
+}
+
+/// How the builder stores many items that share the same value in an index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PakDuplicateKeys {
+    /// Every item is stored inline in the tree page, no matter how many there are.
+    Inline,
+    /// Posting lists longer than the limit are split into chunks of at most `limit` items that are stored outside of the tree pages.
+    Overflow(usize),
+}
+
+impl Default for PakDuplicateKeys {
+    fn default() -> Self {
+        PakDuplicateKeys::Overflow(128)
+    }
+}
+
+/// How the builder sorts the entries of every index before its tree is paked.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum PakIndexBuild {
+    /// Every tree is built in memory. This is the fastest way for indices that fit in memory.
+    #[default]
+    InMemory,
+    /// Entries are sorted in runs of `run_entries` that are spilled to files in `directory`, and the runs are merged into pages that are
+    /// written as soon as they fill up. Only one run per index is held in memory, which makes this meant for paks with huge indices.
+    External { run_entries : usize, directory : PathBuf },
+}
+
+impl PakIndexBuild {
+    /// An external build that spills its runs to the system's temporary directory.
+    pub fn external(run_entries : usize) -> Self {
+        PakIndexBuild::External { run_entries, directory : std::env::temp_dir() }
     }
 }
 
@@ -153,7 +454,9 @@ impl <'p> PakTree<'p> {
 
 #[derive(Deserialize, Serialize)]
 pub struct PakTreeMeta {
-    pages: HashMap<usize, PakUntypedPointer>,
+    pages: PakHashMap<usize, PakUntypedPointer>,
+    bitmap: Option<PakUntypedPointer>,
+    custom: Option<(String, PakUntypedPointer)>,
 }
 
 //==============================================================================================
@@ -184,18 +487,115 @@ impl PakTreeBuilder {
         }
     }
     
-    pub fn into_pak(self, pak : &mut PakBuilder) -> PakResult<PakPointer> {
+    pub fn into_pak(self, pak : &mut PakBuilder, duplicate_keys : PakDuplicateKeys, bitmap : Option<PakUntypedPointer>, custom : Option<(String, PakUntypedPointer)>) -> PakResult<PakPointer> {
         
-        let mut page_map = HashMap::<usize, PakUntypedPointer>::new();
+        let mut page_map = PakHashMap::<usize, PakUntypedPointer>::default();
         for (index, page) in self.pages.into_iter().enumerate() {
-            let pointer = pak.pak_no_search(page)?;
-            page_map.insert(index as usize, pointer.as_untyped());
+            page_map.insert(index, pak_page(pak, page, duplicate_keys)?);
         }
-        
-        pak.pak_no_search(PakTreeMeta{ pages : page_map})
+        pak_tree_meta(pak, page_map, bitmap, custom)
     } 
 }
 
+fn pak_page(pak : &mut PakBuilder, mut page : PakTreePage, duplicate_keys : PakDuplicateKeys) -> PakResult<PakUntypedPointer> {
+    if let PakDuplicateKeys::Overflow(limit) = duplicate_keys {
+        for entry in page.values.iter_mut().filter(|entry| entry.values.len() > limit) {
+            // The skip entries only work if the ordinals are in order, so that every chunk covers the range up to the next one.
+            let mut values = std::mem::take(&mut entry.values);
+            values.sort_unstable();
+            let mut chunks = Vec::new();
+            let mut skips = Vec::new();
+            for chunk in values.chunks(limit.max(1)) {
+                chunks.push(pak.pak_no_search(chunk.to_vec())?.as_untyped());
+                skips.push(chunk[0]);
+            }
+            entry.overflow = Some(PakTreeOverflow { chunks, skips, len: values.len() as u64 });
+        }
+    }
+    let mut bytes = bincode::serialize(&page)?;
+    let inlined = page.values.iter().flat_map(|entry| &entry.values).filter_map(|ordinal| Some((*ordinal, pak.inlined.get(ordinal)?))).collect::<BTreeMap<_, _>>();
+    if !inlined.is_empty() { bytes.extend(bincode::serialize(&inlined.into_iter().collect::<Vec<_>>())?) }
+    Ok(pak.pak_internal::<PakTreePage>(bytes, vec![])?.as_untyped())
+}
+
+fn pak_tree_meta(pak : &mut PakBuilder, pages : PakHashMap<usize, PakUntypedPointer>, bitmap : Option<PakUntypedPointer>, custom : Option<(String, PakUntypedPointer)>) -> PakResult<PakPointer> {
+    let meta = pak.header_encoding.serialize(&PakTreeMeta{ pages, bitmap, custom })?;
+    pak.pak_internal::<PakTreeMeta>(meta, vec![])
+}
+
+//==============================================================================================
+//        PakTreeBulkLoader
+//==============================================================================================
+
+/// Builds a tree from entries that arrive in ascending order, like the output of a [PakRunSorter](crate::sort::PakRunSorter). There is one open
+/// page per level of the tree, and a page is paked as soon as it is full, so only a single path from the root to a leaf is held in memory.
+/// When a full page gets another entry, that entry moves up a level with the full page as its `previous` child.
+pub(crate) struct PakTreeBulkLoader {
+    levels : Vec<VecDeque<PakTreePageEntry>>,
+    current : Option<PakTreePageEntry>,
+    page_map : PakHashMap<usize, PakUntypedPointer>,
+    max_size : usize,
+    duplicate_keys : PakDuplicateKeys,
+}
+
+impl PakTreeBulkLoader {
+    pub(crate) fn new(power_of_two : u32, duplicate_keys : PakDuplicateKeys) -> Self {
+        Self { levels : Vec::new(), current : None, page_map : PakHashMap::default(), max_size : 2usize.pow(power_of_two), duplicate_keys }
+    }
+    
+    /// Adds the ordinal under the key. Keys must never be smaller than the key before them.
+    pub(crate) fn push(&mut self, pak : &mut PakBuilder, key : PakValue, ordinal : u32) -> PakResult<()> {
+        match &mut self.current {
+            Some(entry) if entry.key == key => entry.values.push(ordinal),
+            _ => if let Some(entry) = self.current.replace(PakTreePageEntry::new(key, ordinal)) {
+                self.push_entry(pak, 0, entry)?;
+            },
+        }
+        Ok(())
+    }
+    
+    fn push_entry(&mut self, pak : &mut PakBuilder, level : usize, mut entry : PakTreePageEntry) -> PakResult<()> {
+        if self.levels.len() == level { self.levels.push(VecDeque::new()) }
+        if self.levels[level].len() < self.max_size {
+            self.levels[level].push_back(entry);
+            return Ok(())
+        }
+        // Everything between the last entry of the full page and this one is under this entry's `previous`, which becomes the page's `next`.
+        let values = std::mem::take(&mut self.levels[level]);
+        let index = self.write_page(pak, PakTreePage { values, next : entry.previous })?;
+        entry.previous = Some(index);
+        self.push_entry(pak, level + 1, entry)
+    }
+    
+    fn write_page(&mut self, pak : &mut PakBuilder, page : PakTreePage) -> PakResult<usize> {
+        // The root is always page 0, and it is only written once everything else is.
+        let index = self.page_map.len() + 1;
+        let pointer = pak_page(pak, page, self.duplicate_keys)?;
+        self.page_map.insert(index, pointer);
+        Ok(index)
+    }
+    
+    pub(crate) fn into_pak(mut self, pak : &mut PakBuilder, bitmap : Option<PakUntypedPointer>, custom : Option<(String, PakUntypedPointer)>) -> PakResult<PakPointer> {
+        if let Some(entry) = self.current.take() { self.push_entry(pak, 0, entry)? }
+        
+        let levels = std::mem::take(&mut self.levels);
+        let top = levels.len().saturating_sub(1);
+        let mut root = PakTreePage::new();
+        let mut child = None;
+        for (level, values) in levels.into_iter().enumerate() {
+            let page = PakTreePage { values, next : child };
+            if level == top {
+                root = page;
+            } else {
+                child = Some(self.write_page(pak, page)?);
+            }
+        }
+        let root = pak_page(pak, root, self.duplicate_keys)?;
+        self.page_map.insert(0, root);
+        pak_tree_meta(pak, self.page_map, bitmap, custom)
+    }
+}
+
 //==============================================================================================
 //        PakTreeAccess
 //==============================================================================================
@@ -257,25 +657,19 @@ impl PakTreeBuilderAccess<'_> {
         index
     }
     
-    pub fn insert<K>(&mut self, key: K, value: PakTypedPointer) -> &mut Self where K: Into<PakValue> {
-        self.insert_entry(PakTreePageEntry::new(key.into(), value));
+    pub fn insert<K>(&mut self, key: K, ordinal: u32) -> &mut Self where K: Into<PakValue> {
+        self.insert_entry(PakTreePageEntry::new(key.into(), ordinal));
         self
     }
     
     fn split(&mut self) {
-        let mut leading_entries = VecDeque::new();
-        let mut trailing_entries = VecDeque::new();
-        
         let half_max = self.table.max_size / 2;
         
-        for _ in 0..half_max {
-            let current = self.current_mut();
-            leading_entries.push_back(current.values.pop_front().unwrap());
-            trailing_entries.push_front(current.values.pop_back().unwrap());
-        }
-        
-        let mut middle_entry = self.current_mut().values.pop_front().unwrap();
-        self.current_mut().values = trailing_entries;
+        // The entries are moved between pages in bulk, so a split never clones or reallocates the posting lists.
+        let current = self.current_mut();
+        let trailing_entries = current.values.split_off(current.values.len() - half_max);
+        let mut leading_entries = std::mem::replace(&mut current.values, trailing_entries);
+        let mut middle_entry = leading_entries.pop_back().unwrap();
         let leading_index = self.new_page(leading_entries);
         middle_entry.previous = Some(leading_index);
         
@@ -316,28 +710,23 @@ impl PakTreePage {
         }
     }
     
-    fn push(&mut self, mut e : PakTreePageEntry) -> PakTreeStatus {
-        for (index, entry) in self.values.iter_mut().enumerate() {
-            match entry.key.cmp(&e.key) {
-                Ordering::Less => continue,
-                Ordering::Greater => match entry.previous {
-                        Some(next) => return PakTreeStatus::Next(next, e),
-                        None => {
-                            self.values.insert(index, e);
-                            return PakTreeStatus::Ok(index);
-                        },
-                    },
-                Ordering::Equal => {
-                    entry.values.append(&mut e.values);
-                    return PakTreeStatus::Ok(index);
-                },
-            }
-        }
-        match self.next {
-            Some(index) => PakTreeStatus::Next(index, e),
+    fn push(&mut self, e : PakTreePageEntry) -> PakTreeStatus {
+        let index = match self.values.binary_search_by(|entry| entry.key.cmp(&e.key)) {
+            Ok(index) => {
+                self.values[index].values.extend(e.values);
+                return PakTreeStatus::Ok(index);
+            },
+            Err(index) => index,
+        };
+        let child = match self.values.get(index) {
+            Some(entry) => entry.previous,
+            None => self.next,
+        };
+        match child {
+            Some(child) => PakTreeStatus::Next(child, e),
             None => {
-                self.values.push_back(e);
-                return PakTreeStatus::Ok(self.values.len() - 1);
+                self.values.insert(index, e);
+                PakTreeStatus::Ok(index)
             },
         }
     }
@@ -352,10 +741,14 @@ enum PakTreeStatus {
 //        PakTreePageEntry
 //==============================================================================================
 
+/// Most posting lists only hold a handful of ordinals, so they are stored inline in the entry until they grow past this.
+type PakTreeOrdinals = SmallVec<[u32; 4]>;
+
 #[derive(Serialize, Deserialize)]
 pub struct PakTreePageEntry {
     key: PakValue,
-    values: Vec<PakTypedPointer>,
+    values: PakTreeOrdinals,
+    overflow: Option<PakTreeOverflow>,
     previous: Option<usize>,
 }
 
@@ -371,10 +764,11 @@ impl Debug for PakTreePageEntry {
 }
 
 impl PakTreePageEntry {
-    pub fn new(key: PakValue, value: PakTypedPointer) -> Self {
+    pub fn new(key: PakValue, ordinal: u32) -> Self {
         PakTreePageEntry {
             key,
-            values : vec![value],
+            values : smallvec![ordinal],
+            overflow: None,
             previous: None,
         }
     }
@@ -390,7 +784,7 @@ impl Eq for PakTreePageEntry {}
 
 impl PartialOrd for PakTreePageEntry {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.key.partial_cmp(&other.key)
+        Some(self.cmp(other))
     }
 }
 
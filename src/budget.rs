@@ -0,0 +1,77 @@
+use crate::error::{PakError, PakResult};
+
+//==============================================================================================
+//        PakBudget
+//==============================================================================================
+
+/// Limits on how much a query may read, for [Pak::query_with_budget](crate::Pak::query_with_budget) and [Pak::with_budget](crate::Pak::with_budget).
+/// Every chunk a query reads counts as a read, including the pages of the index trees and reads served from the cache, while only the
+/// bytes that go to the source count as bytes read. A read that would go over either limit fails with
+/// [PakError::BudgetExceeded](crate::error::PakError::BudgetExceeded) before it touches the source.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PakBudget {
+    pub max_bytes : Option<u64>,
+    pub max_reads : Option<u64>,
+}
+
+impl PakBudget {
+    /// A budget with no limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn with_max_bytes(mut self, max_bytes : u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+    
+    pub fn with_max_reads(mut self, max_reads : u64) -> Self {
+        self.max_reads = Some(max_reads);
+        self
+    }
+}
+
+/// Keeps track of what a budgeted query has read so far. A meter started inside another one charges the outer meter with everything it
+/// counts, so an inner budget can only ever tighten the outer one.
+pub(crate) struct PakBudgetMeter {
+    budget : PakBudget,
+    bytes : u64,
+    reads : u64,
+    outer : Option<Box<PakBudgetMeter>>,
+}
+
+impl PakBudgetMeter {
+    pub(crate) fn new(budget : PakBudget, outer : Option<PakBudgetMeter>) -> Self {
+        Self { budget, bytes : 0, reads : 0, outer : outer.map(Box::new) }
+    }
+    
+    pub(crate) fn charge_read(&mut self) -> PakResult<()> {
+        self.charge(1, 0)
+    }
+    
+    pub(crate) fn charge_bytes(&mut self, bytes : u64) -> PakResult<()> {
+        self.charge(0, bytes)
+    }
+    
+    fn charge(&mut self, reads : u64, bytes : u64) -> PakResult<()> {
+        self.reads += reads;
+        self.bytes += bytes;
+        if let Some(outer) = self.outer.as_mut() { outer.charge(reads, bytes)? }
+        self.exceeded().map_or(Ok(()), Err)
+    }
+    
+    /// The error for the first limit that was gone over, if any was, including the limits of the outer meters. Some reads swallow their
+    /// errors, like the ones that deserialize the results of a query, so the budget is checked again once the query is done.
+    pub(crate) fn exceeded(&self) -> Option<PakError> {
+        match (self.budget.max_reads, self.budget.max_bytes) {
+            (Some(max_reads), _) if self.reads > max_reads => Some(PakError::BudgetExceeded("reads".to_string(), max_reads)),
+            (_, Some(max_bytes)) if self.bytes > max_bytes => Some(PakError::BudgetExceeded("bytes".to_string(), max_bytes)),
+            _ => self.outer.as_ref().and_then(|outer| outer.exceeded()),
+        }
+    }
+    
+    /// Ends the meter, handing back the outer meter it was charging.
+    pub(crate) fn into_outer(self) -> Option<PakBudgetMeter> {
+        self.outer.map(|outer| *outer)
+    }
+}
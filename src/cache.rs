@@ -0,0 +1,129 @@
+use std::{collections::BTreeMap, sync::{Condvar, Mutex, PoisonError}};
+use serde::{Deserialize, Serialize};
+use crate::hash::PakHashMap;
+
+//==============================================================================================
+//        PakChunkCache
+//==============================================================================================
+
+/// Keeps the bytes of the most recently read chunks, up to a number of bytes. Chunks are keyed by their offset and size, and the least
+/// recently used ones are evicted first. Turned on with [Pak::with_cache](crate::Pak::with_cache).
+#[derive(Default)]
+pub(crate) struct PakChunkCache {
+    capacity : u64,
+    used : u64,
+    tick : u64,
+    chunks : PakHashMap<(u64, u64), (u64, Vec<u8>)>,
+    recency : BTreeMap<u64, (u64, u64)>,
+}
+
+impl PakChunkCache {
+    pub(crate) fn new(capacity : u64) -> Self {
+        Self { capacity, ..Self::default() }
+    }
+
+    pub(crate) fn get(&mut self, offset : u64, size : u64) -> Option<Vec<u8>> {
+        self.tick += 1;
+        let (tick, bytes) = self.chunks.get_mut(&(offset, size))?;
+        self.recency.remove(tick);
+        self.recency.insert(self.tick, (offset, size));
+        *tick = self.tick;
+        Some(bytes.clone())
+    }
+
+    pub(crate) fn insert(&mut self, offset : u64, size : u64, bytes : &[u8]) {
+        let len = bytes.len() as u64;
+        if len > self.capacity || self.chunks.contains_key(&(offset, size)) { return }
+        while self.used + len > self.capacity {
+            let Some((_, key)) = self.recency.pop_first() else { break };
+            if let Some((_, evicted)) = self.chunks.remove(&key) { self.used -= evicted.len() as u64 }
+        }
+        self.tick += 1;
+        self.used += len;
+        self.chunks.insert((offset, size), (self.tick, bytes.to_vec()));
+        self.recency.insert(self.tick, (offset, size));
+    }
+
+    /// Inserts the chunk only if it fits in the room that is left, so it never evicts anything.
+    pub(crate) fn insert_spare(&mut self, offset : u64, size : u64, bytes : &[u8]) {
+        if self.used + bytes.len() as u64 <= self.capacity { self.insert(offset, size, bytes) }
+    }
+    
+    /// The chunks in the cache, from the most recently used to the least.
+    pub(crate) fn hot(&self) -> Vec<(u64, u64)> {
+        self.recency.values().rev().copied().collect()
+    }
+}
+
+//==============================================================================================
+//        PakPriority
+//==============================================================================================
+
+/// How urgent the reads made through a pak are, set with [Pak::with_priority](crate::Pak::with_priority). Background reads, like the ones
+/// [Pak::preload](crate::Pak::preload) makes, never push the chunks that foreground reads brought into the cache back out, and aren't
+/// recorded by [Pak::record_access](crate::Pak::record_access). They also wait for the foreground reads of the pak to finish before they
+/// start, including the ones made on the threads of [parallel queries](crate::Pak::with_parallel_threads) and
+/// [extract streams](crate::Pak::extract_stream), which read at the priority they were started with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PakPriority {
+    /// Reads the game is waiting on.
+    #[default]
+    Foreground,
+    /// Prefetches and other reads that nothing is waiting on yet.
+    Background,
+}
+
+//==============================================================================================
+//        PakReadScheduler
+//==============================================================================================
+
+/// Orders the reads of a pak and of every copy of it that other threads read through. Foreground reads never wait, and a background read
+/// doesn't start while a foreground read is being made, so a prefetch that is queued behind gameplay loads can't hold them up. A background
+/// read that has already started isn't interrupted, which is why streams and parallel queries read a chunk or a branch at a time.
+#[derive(Default)]
+pub(crate) struct PakReadScheduler {
+    foreground : Mutex<usize>,
+    idle : Condvar,
+}
+
+impl PakReadScheduler {
+    /// Waits until a read at the priority may start. The read is made while the returned turn is held.
+    pub(crate) fn turn(&self, priority : PakPriority) -> PakReadTurn<'_> {
+        let mut foreground = self.foreground.lock().unwrap_or_else(PoisonError::into_inner);
+        match priority {
+            PakPriority::Foreground => *foreground += 1,
+            PakPriority::Background => while *foreground > 0 {
+                foreground = self.idle.wait(foreground).unwrap_or_else(PoisonError::into_inner);
+            },
+        }
+        PakReadTurn { scheduler : self, priority }
+    }
+}
+
+pub(crate) struct PakReadTurn<'s> {
+    scheduler : &'s PakReadScheduler,
+    priority : PakPriority,
+}
+
+impl Drop for PakReadTurn<'_> {
+    fn drop(&mut self) {
+        if self.priority == PakPriority::Background { return }
+        let mut foreground = self.scheduler.foreground.lock().unwrap_or_else(PoisonError::into_inner);
+        *foreground -= 1;
+        if *foreground == 0 { self.scheduler.idle.notify_all() }
+    }
+}
+
+//==============================================================================================
+//        PakCacheState
+//==============================================================================================
+
+/// The chunks that were in a pak's cache, saved with [Pak::save_cache_state](crate::Pak::save_cache_state). The state remembers which pak
+/// it came from, so a state left over from another build of the pak is ignored instead of prefetching the wrong bytes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PakCacheState {
+    pub generation : u64,
+    pub size : u64,
+    /// The offset and size of every chunk, from the most recently used to the least.
+    pub chunks : Vec<(u64, u64)>,
+}
@@ -0,0 +1,54 @@
+use sha2::{Digest, Sha256};
+use crate::{error::PakResult, hash::PakHashMap, pointer::{PakPointer, PakTypedPointer}, value::PakValue, Pak};
+
+/// The reserved index key that the content hash of every item is stored under in a pak built with
+/// [with_content_addressing](crate::PakBuilder::with_content_addressing), as lowercase hex.
+pub const PAK_HASH_KEY : &str = "__pak_hash";
+
+/// The SHA-256 hash of an item's bytes, as they are before any encryption.
+pub type PakContentHash = [u8; 32];
+
+/// The chunk each distinct item of a content addressed builder was paked as, by type name, version and hash.
+pub(crate) type PakContentChunks = PakHashMap<(String, Option<u32>, PakContentHash), usize>;
+
+/// Hashes the bytes of an item the way a content addressed pak does, so the hash of an item can be worked out without the pak.
+pub fn content_hash(bytes : &[u8]) -> PakContentHash {
+    Sha256::digest(bytes).into()
+}
+
+pub(crate) fn to_hex(hash : &PakContentHash) -> String {
+    hash.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex : &str) -> Option<PakContentHash> {
+    let mut hash = [0u8; 32];
+    if hex.len() != 64 { return None }
+    for (byte, pair) in hash.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(hash)
+}
+
+impl Pak {
+    /// Finds the item with the content hash, in a pak built with [with_content_addressing](crate::PakBuilder::with_content_addressing).
+    pub fn by_hash(&self, hash : &PakContentHash) -> PakResult<Option<PakPointer>> {
+        if !self.fetch_indices()?.contains_key(PAK_HASH_KEY) { return Ok(None) }
+        let pointers = self.get_tree(PAK_HASH_KEY)?.get(&to_hex(hash).into())?;
+        Ok(pointers.into_iter().next().map(PakTypedPointer::into_pointer))
+    }
+
+    /// The content hash of every item, in hash order. Comparing the hashes of two builds of a pak shows which items were added, removed
+    /// or changed between them without reading any items.
+    pub fn content_hashes(&self) -> PakResult<Vec<(PakContentHash, PakPointer)>> {
+        let mut hashes = Vec::new();
+        if !self.fetch_indices()?.contains_key(PAK_HASH_KEY) { return Ok(hashes) }
+        self.get_tree(PAK_HASH_KEY)?.walk(|value, postings| {
+            let Some(hash) = (match value { PakValue::String(hex) => from_hex(hex), _ => None }) else { return Ok(true) };
+            for pointer in postings.pointers()? {
+                hashes.push((hash, pointer?.clone().into_pointer()));
+            }
+            Ok(true)
+        })?;
+        Ok(hashes)
+    }
+}
@@ -0,0 +1,105 @@
+use std::{collections::BTreeSet, path::Path};
+use crate::{error::{PakError, PakResult}, format::PakFormat, legacy, pointer::to_usize, value::PakValue, write_atomic, Pak};
+
+//==============================================================================================
+//        PakUpgradeOptions
+//==============================================================================================
+
+/// How [upgrade](crate::convert::upgrade) rewrites a pak.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PakUpgradeOptions {
+    /// The format to rewrite the pak in, which defaults to the newest one.
+    pub format : PakFormat,
+    /// Reopens the rewritten pak and checks that its vault and index map match the original. This is on by default.
+    pub verify : bool,
+}
+
+impl Default for PakUpgradeOptions {
+    fn default() -> Self {
+        Self { format : PakFormat::V2, verify : true }
+    }
+}
+
+impl PakUpgradeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn with_format(mut self, format : PakFormat) -> Self {
+        self.format = format;
+        self
+    }
+    
+    pub fn with_verify(mut self, verify : bool) -> Self {
+        self.verify = verify;
+        self
+    }
+}
+
+//==============================================================================================
+//        Upgrade
+//==============================================================================================
+
+/// Rewrites the pak at `old_path` to `new_path` in the format of the options, returning the format it was in before. Every part of a pak
+/// other than its layout is shared by all the formats, so the vault, the index map and the meta are copied byte for byte and every pointer
+/// into the pak stays valid. Encrypted paks can be upgraded without their key. Paks written by pak-db 0.1 are rebuilt at the current meta
+/// revision instead, which keeps the items where they are and builds the index trees again. The new pak is written atomically, so the two
+/// paths may be the same.
+pub fn upgrade(old_path : impl AsRef<Path>, new_path : impl AsRef<Path>, options : PakUpgradeOptions) -> PakResult<PakFormat> {
+    let old = Pak::new_from_file(old_path)?;
+    if old.meta.revision == 0 { return upgrade_legacy(old, new_path.as_ref(), options) }
+    let vault = old.read_vault()?;
+    let indices = old.fetch_indices()?;
+    let (out, _) = options.format.driver().write(&old.meta, indices, &vault)?;
+    write_atomic(new_path.as_ref(), &out)?;
+    
+    if options.verify {
+        let new = Pak::new_from_file(new_path)?;
+        if new.format() != options.format { return Err(mismatch("format")) }
+        if new.fetch_indices()? != indices { return Err(mismatch("index map")) }
+        if new.read_vault()? != vault { return Err(mismatch("vault")) }
+    }
+    Ok(old.format())
+}
+
+fn upgrade_legacy(old : Pak, new_path : &Path, options : PakUpgradeOptions) -> PakResult<PakFormat> {
+    let laid_out = legacy::rebuild(&old)?.lay_out()?;
+    let (out, _) = options.format.driver().write(&laid_out.meta, &laid_out.indices, &laid_out.vault)?;
+    write_atomic(new_path, &out)?;
+    
+    if options.verify {
+        let new = Pak::new_from_file(new_path)?;
+        if new.format() != options.format { return Err(mismatch("format")) }
+        let old_vault = old.read_vault()?;
+        let new_vault = new.read_vault()?;
+        let items = to_usize(legacy::items_end(&old)?)?;
+        if old_vault.get(..items) != new_vault.get(..items) { return Err(mismatch("vault")) }
+        let old_keys = old.fetch_indices()?.keys().collect::<BTreeSet<_>>();
+        if new.fetch_indices()?.keys().collect::<BTreeSet<_>>() != old_keys { return Err(mismatch("index map")) }
+        for key in old_keys {
+            if tree_entries(&old, key)? != tree_entries(&new, key)? { return Err(mismatch(key)) }
+        }
+    }
+    Ok(old.format())
+}
+
+/// Every entry of the tree under the key, with the offsets of the items it holds.
+fn tree_entries(pak : &Pak, key : &str) -> PakResult<Vec<(PakValue, Vec<u64>)>> {
+    let ordinals = pak.ordinals()?;
+    let mut entries = Vec::new();
+    pak.get_tree(key)?.walk(|value, postings| {
+        let mut offsets = Vec::new();
+        postings.for_each_ordinal_chunk(|chunk| {
+            offsets.extend(chunk.iter().map(|ordinal| ordinals[*ordinal as usize].offset()));
+            true
+        })?;
+        offsets.sort_unstable();
+        entries.push((value.clone(), offsets));
+        Ok(true)
+    })?;
+    Ok(entries)
+}
+
+fn mismatch(section : &str) -> PakError {
+    PakError::InvalidHeader(section.to_string(), "the upgraded pak doesn't match the original".to_string())
+}
@@ -0,0 +1,93 @@
+use std::collections::BTreeSet;
+use crate::{error::PakResult, plan::PakPlan, query::{PakQuery, PakQueryNode}, value::PakValueKind, Pak};
+
+//==============================================================================================
+//        PakCoverageReport
+//==============================================================================================
+
+/// How well the indices of a pak cover a set of queries, from [Pak::index_coverage](crate::Pak::index_coverage) or
+/// [PakPlan::index_coverage](crate::plan::PakPlan::index_coverage). Queries on keys that no index provides quietly match nothing, so
+/// running the queries a game makes against a report before shipping catches the indices that were forgotten.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PakCoverageReport {
+    /// Every query that was checked, in the order they were given.
+    pub queries : Vec<PakQueryCoverage>,
+}
+
+/// The coverage of a single query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PakQueryCoverage {
+    pub query : PakQueryNode,
+    /// The keys the query compares against that the pak has no index for.
+    pub missing : Vec<String>,
+    /// The keys the query compares against with a kind of value their index can't be queried with, along with the kind of the index and
+    /// the kind of the value. Running the query fails with [PakError::ValueKindMismatch](crate::error::PakError::ValueKindMismatch).
+    pub mismatched : Vec<(String, PakValueKind, PakValueKind)>,
+}
+
+impl PakQueryCoverage {
+    pub fn is_covered(&self) -> bool {
+        self.missing.is_empty() && self.mismatched.is_empty()
+    }
+}
+
+impl PakCoverageReport {
+    /// Checks every comparison in the queries against the kinds of the indices that exist, where `index_kind` returns `None` for keys
+    /// that have no index.
+    fn new<F>(queries : impl IntoIterator<Item = impl Into<PakQueryNode>>, index_kind : F) -> Self where F : Fn(&str) -> Option<PakValueKind> {
+        let queries = queries.into_iter().map(|query| {
+            let query = query.into();
+            let mut coverage = PakQueryCoverage { query : query.clone(), missing : Vec::new(), mismatched : Vec::new() };
+            for compare in comparisons(&query) {
+                let key = compare.key();
+                match index_kind(key) {
+                    None if !coverage.missing.iter().any(|missing| missing == key) => coverage.missing.push(key.to_string()),
+                    Some(kind) if !kind.accepts(compare.value().kind()) => coverage.mismatched.push((key.to_string(), kind, compare.value().kind())),
+                    _ => {},
+                }
+            }
+            coverage
+        }).collect();
+        Self { queries }
+    }
+
+    /// Returns true if every query only uses keys that are indexed, with kinds of values their indices accept.
+    pub fn is_covered(&self) -> bool {
+        self.queries.iter().all(PakQueryCoverage::is_covered)
+    }
+
+    /// The queries that use a key that isn't indexed or compare against the wrong kind of value.
+    pub fn uncovered(&self) -> impl Iterator<Item = &PakQueryCoverage> {
+        self.queries.iter().filter(|query| !query.is_covered())
+    }
+
+    /// Every key that at least one of the queries needs an index for, in order.
+    pub fn missing_keys(&self) -> BTreeSet<&str> {
+        self.queries.iter().flat_map(|query| query.missing.iter().map(String::as_str)).collect()
+    }
+}
+
+/// Every comparison in the tree, in the order they appear in it.
+fn comparisons(node : &PakQueryNode) -> Vec<&PakQuery> {
+    match node {
+        PakQueryNode::Compare(query) => vec![query],
+        PakQueryNode::All(nodes) | PakQueryNode::Any(nodes) => nodes.iter().flat_map(comparisons).collect(),
+    }
+}
+
+impl Pak {
+    /// Checks which of the queries compare against keys the pak has no index for, or against values of the wrong kind. Queries can be
+    /// anything with a [PakQueryNode] tree, like the output of [parse](crate::query::parse) or a [Query](crate::query::Query).
+    pub fn index_coverage(&self, queries : impl IntoIterator<Item = impl Into<PakQueryNode>>) -> PakResult<PakCoverageReport> {
+        let indices = self.fetch_indices()?;
+        Ok(PakCoverageReport::new(queries, |key| indices.contains_key(key).then(|| self.index_kind(key).unwrap_or(PakValueKind::Void))))
+    }
+}
+
+impl PakPlan {
+    /// Checks the queries against the indices of the planned pak like [Pak::index_coverage](crate::Pak::index_coverage) does, so gaps
+    /// can be caught before the pak is built.
+    pub fn index_coverage(&self, queries : impl IntoIterator<Item = impl Into<PakQueryNode>>) -> PakCoverageReport {
+        PakCoverageReport::new(queries, |key| self.indices.get(key).map(|index| index.kind))
+    }
+}
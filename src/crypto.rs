@@ -0,0 +1,157 @@
+use std::fmt::Debug;
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{aead::{Aead, KeyInit}, XChaCha20Poly1305, XNonce};
+use sha2::{Digest, Sha256};
+use crate::{error::{PakError, PakResult}, meta::{PakCipherAlgorithm, PakEncryption, PakKdf}};
+
+/// The number of bytes the authentication tag adds to every encrypted chunk.
+pub const PAK_TAG_SIZE : u64 = 16;
+
+//==============================================================================================
+//        PakKey
+//==============================================================================================
+
+/// A 256 bit key for an encrypted pak. Build a pak with it using [PakBuilder::with_encryption](crate::PakBuilder::with_encryption), and
+/// read it back with [Pak::open_with_key](crate::Pak::open_with_key).
+#[derive(Clone, PartialEq, Eq)]
+pub struct PakKey([u8; 32]);
+
+impl PakKey {
+    pub fn new(bytes : [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Derives a key from the password with Argon2id, using the salt and costs of the [PakKdf](crate::meta::PakKdf).
+    pub fn derive(password : &str, kdf : &PakKdf) -> PakResult<Self> {
+        let invalid = |error : argon2::Error| PakError::InvalidHeader("key derivation".to_string(), error.to_string());
+        let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32)).map_err(invalid)?;
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params).hash_password_into(password.as_bytes(), &kdf.salt, &mut key).map_err(invalid)?;
+        Ok(Self(key))
+    }
+}
+
+impl From<[u8; 32]> for PakKey {
+    fn from(bytes : [u8; 32]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl Debug for PakKey {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PakKey(..)")
+    }
+}
+
+impl PakKdf {
+    /// Argon2id with a fresh random salt and the costs recommended for interactive logins, 19 MiB of memory and 2 iterations.
+    pub fn new() -> PakResult<Self> {
+        Self::with_cost(19 * 1024, 2, 1)
+    }
+
+    /// Argon2id with a fresh random salt and the given costs. Higher costs make passwords harder to guess, and slower to open the pak with.
+    pub fn with_cost(memory_kib : u32, iterations : u32, parallelism : u32) -> PakResult<Self> {
+        Ok(Self { salt : random_salt()?, memory_kib, iterations, parallelism })
+    }
+}
+
+pub(crate) fn random_salt() -> PakResult<[u8; 16]> {
+    let mut salt = [0u8; 16];
+    getrandom::getrandom(&mut salt).map_err(std::io::Error::other)?;
+    Ok(salt)
+}
+
+//==============================================================================================
+//        PakCipher
+//==============================================================================================
+
+/// Seals and opens the chunks of a pak's vault. With XChaCha20-Poly1305, the nonce of a chunk is the pak's salt followed by the chunk's
+/// offset, so every chunk of every pak gets its own nonce without storing any. AES-256-GCM nonces only have room for the offset, so the salt
+/// goes into a subkey for the pak instead.
+#[derive(Clone)]
+pub(crate) struct PakCipher {
+    aead : PakAead,
+    key : PakKey,
+    salt : [u8; 16],
+}
+
+#[derive(Clone)]
+enum PakAead {
+    XChaCha20Poly1305(XChaCha20Poly1305),
+    Aes256Gcm(Box<Aes256Gcm>),
+}
+
+impl PakCipher {
+    /// The offset the key check is sealed at. No chunk ever starts there.
+    const CHECK_OFFSET : u64 = u64::MAX;
+
+    pub(crate) fn new(key : &PakKey, salt : [u8; 16], algorithm : PakCipherAlgorithm) -> Self {
+        let aead = match algorithm {
+            PakCipherAlgorithm::XChaCha20Poly1305 => PakAead::XChaCha20Poly1305(XChaCha20Poly1305::new((&key.0).into())),
+            PakCipherAlgorithm::Aes256Gcm => {
+                let subkey = Sha256::new().chain_update(b"pak-db aes-256-gcm").chain_update(key.0).chain_update(salt).finalize();
+                PakAead::Aes256Gcm(Box::new(Aes256Gcm::new(&subkey)))
+            },
+        };
+        Self { aead, key : key.clone(), salt }
+    }
+
+    /// The same key and salt, sealing with another algorithm.
+    pub(crate) fn with_algorithm(&self, algorithm : PakCipherAlgorithm) -> Self {
+        Self::new(&self.key, self.salt, algorithm)
+    }
+
+    /// The same key and algorithm with a new random salt, for sealing chunks again at offsets the old salt already sealed other chunks at.
+    pub(crate) fn with_fresh_salt(&self) -> PakResult<Self> {
+        Ok(Self::new(&self.key, random_salt()?, self.algorithm()))
+    }
+
+    pub(crate) fn algorithm(&self) -> PakCipherAlgorithm {
+        match self.aead {
+            PakAead::XChaCha20Poly1305(_) => PakCipherAlgorithm::XChaCha20Poly1305,
+            PakAead::Aes256Gcm(_) => PakCipherAlgorithm::Aes256Gcm,
+        }
+    }
+
+    fn xnonce(&self, offset : u64) -> XNonce {
+        let mut nonce = [0u8; 24];
+        nonce[..16].copy_from_slice(&self.salt);
+        nonce[16..].copy_from_slice(&offset.to_le_bytes());
+        nonce.into()
+    }
+
+    fn nonce(offset : u64) -> Nonce<aes_gcm::aead::consts::U12> {
+        let mut nonce = [0u8; 12];
+        nonce[4..].copy_from_slice(&offset.to_le_bytes());
+        nonce.into()
+    }
+
+    pub(crate) fn seal(&self, offset : u64, bytes : &[u8]) -> PakResult<Vec<u8>> {
+        let sealed = match &self.aead {
+            PakAead::XChaCha20Poly1305(cipher) => cipher.encrypt(&self.xnonce(offset), bytes),
+            PakAead::Aes256Gcm(cipher) => cipher.encrypt(&Self::nonce(offset), bytes),
+        };
+        sealed.map_err(|_| PakError::DecryptionFailed(offset))
+    }
+
+    pub(crate) fn open(&self, offset : u64, bytes : &[u8]) -> PakResult<Vec<u8>> {
+        let opened = match &self.aead {
+            PakAead::XChaCha20Poly1305(cipher) => cipher.decrypt(&self.xnonce(offset), bytes),
+            PakAead::Aes256Gcm(cipher) => cipher.decrypt(&Self::nonce(offset), bytes),
+        };
+        opened.map_err(|_| PakError::DecryptionFailed(offset))
+    }
+
+    /// The header entry that lets readers check their key before reading anything.
+    pub(crate) fn encryption(&self, kdf : Option<PakKdf>) -> PakResult<PakEncryption> {
+        Ok(PakEncryption { salt : self.salt, check : self.seal(Self::CHECK_OFFSET, &[])?, kdf, algorithm : self.algorithm() })
+    }
+
+    /// Creates the cipher for an encrypted pak, failing with [PakError::WrongKey](crate::error::PakError::WrongKey) if the key isn't the one it was encrypted with.
+    pub(crate) fn unlock(key : &PakKey, encryption : &PakEncryption) -> PakResult<Self> {
+        let cipher = Self::new(key, encryption.salt, encryption.algorithm);
+        cipher.open(Self::CHECK_OFFSET, &encryption.check).map_err(|_| PakError::WrongKey)?;
+        Ok(cipher)
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use crate::{error::PakResult, meta::PakManifestEntry, pointer::PakPointer, query::PakQueryExpression, Pak};
+
+//==============================================================================================
+//        PakDiffReport
+//==============================================================================================
+
+/// The differences [compare_queries](crate::diff::compare_queries) found between two paks. Only the queries that disagree are listed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PakDiffReport {
+    pub queries : usize,
+    pub differences : Vec<PakQueryDiff>,
+}
+
+impl PakDiffReport {
+    /// Returns true if every query found the same items in both paks.
+    pub fn is_identical(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+/// The results of a single query that only one of the paks returned. `query` is the position of the query in the list that was compared,
+/// and the pointers point into the pak they were found in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PakQueryDiff {
+    pub query : usize,
+    pub only_in_a : Vec<PakPointer>,
+    pub only_in_b : Vec<PakPointer>,
+}
+
+//==============================================================================================
+//        Compare
+//==============================================================================================
+
+/// Runs every query against both paks and reports the ones whose results differ. Results are compared by the type and the bytes of the
+/// items they point to, not by where the items are, so a pak that was rebuilt or migrated to another format compares as identical as long
+/// as its queries return the same items. An item that is returned more times by one pak than the other is reported as many times as it is
+/// missing.
+pub fn compare_queries(pak_a : &Pak, pak_b : &Pak, queries : &[&dyn PakQueryExpression]) -> PakResult<PakDiffReport> {
+    let mut differences = Vec::new();
+    for (query, expression) in queries.iter().enumerate() {
+        let mut results_a = contents(pak_a, *expression)?;
+        let mut results_b = contents(pak_b, *expression)?;
+        let mut only_in_a = Vec::new();
+        let mut only_in_b = Vec::new();
+        for (content, mut pointers_a) in results_a.drain() {
+            let mut pointers_b = results_b.remove(&content).unwrap_or_default();
+            let common = pointers_a.len().min(pointers_b.len());
+            only_in_a.extend(pointers_a.drain(common..));
+            only_in_b.extend(pointers_b.drain(common..));
+        }
+        only_in_b.extend(results_b.into_values().flatten());
+        if only_in_a.is_empty() && only_in_b.is_empty() { continue }
+        
+        only_in_a.sort_by_key(|pointer| pointer.offset());
+        only_in_b.sort_by_key(|pointer| pointer.offset());
+        differences.push(PakQueryDiff { query, only_in_a, only_in_b });
+    }
+    Ok(PakDiffReport { queries : queries.len(), differences })
+}
+
+/// The type name and hash of an item's bytes.
+type PakContent = (String, [u8; 32]);
+
+/// The results of the query, grouped by the type and hash of the items they point to.
+fn contents(pak : &Pak, expression : &dyn PakQueryExpression) -> PakResult<HashMap<PakContent, Vec<PakPointer>>> {
+    let mut contents : HashMap<_, Vec<PakPointer>> = HashMap::new();
+    for pointer in expression.execute(pak)? {
+        let pointer = pointer.into_pointer();
+        let entry = PakManifestEntry::new(pointer.type_name(), &pak.read_bytes(&pointer)?);
+        contents.entry((entry.type_name, entry.hash)).or_default().push(pointer);
+    }
+    Ok(contents)
+}
@@ -0,0 +1,126 @@
+use std::{fs::File, io, path::Path};
+use crate::{error::{PakError, PakResult}, pointer::{to_usize, PakPointer}, PakSource};
+
+/// The alignment [PakDirectSource](crate::direct::PakDirectSource) uses unless told otherwise, which is the page size on most platforms
+/// and a multiple of the sector size of most drives.
+pub const PAK_DIRECT_ALIGNMENT : u64 = 4096;
+
+//==============================================================================================
+//        PakDirectSource
+//==============================================================================================
+
+/// A file source that bypasses the operating system's page cache, for platforms where the cache has to be managed by the game instead. The
+/// file is opened with `O_DIRECT` on Linux, `FILE_FLAG_NO_BUFFERING` on Windows and `F_NOCACHE` on macOS, and every read is widened to
+/// whole aligned blocks and read into an aligned buffer, as unbuffered IO requires. Reads are positional, so they never move a shared
+/// file cursor. File systems that can't do unbuffered IO fall back to a normal file, which [is_direct](PakDirectSource::is_direct) reports.
+/// Paks built with the same [alignment](crate::PakBuilder::with_alignment) have every item start on a block, so reads don't also pull in
+/// the end of the item before it.
+pub struct PakDirectSource {
+    file : File,
+    alignment : u64,
+    direct : bool,
+}
+
+impl PakDirectSource {
+    pub fn open(path : impl AsRef<Path>) -> PakResult<Self> {
+        Self::open_with_alignment(path, PAK_DIRECT_ALIGNMENT)
+    }
+    
+    /// Opens the file with reads aligned to the given number of bytes, which has to be a power of two.
+    pub fn open_with_alignment(path : impl AsRef<Path>, alignment : u64) -> PakResult<Self> {
+        if !alignment.is_power_of_two() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("the alignment {alignment} isn't a power of two")).into())
+        }
+        let path = path.as_ref();
+        let (file, direct) = match open_unbuffered(path) {
+            Ok(file) => (file, true),
+            Err(error) if error.kind() == io::ErrorKind::InvalidInput => (File::open(path)?, false),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(Self { file, alignment, direct })
+    }
+    
+    /// Returns true if the file was opened for unbuffered IO, and false if the platform or the file system couldn't do it.
+    pub fn is_direct(&self) -> bool {
+        self.direct
+    }
+    
+    pub fn alignment(&self) -> u64 {
+        self.alignment
+    }
+}
+
+impl PakSource for PakDirectSource {
+    fn read(&mut self, pointer : &PakPointer, offset : u64) -> PakResult<Vec<u8>> {
+        let start = pointer.offset().checked_add(offset).ok_or(PakError::OffsetOverflow(offset))?;
+        let end = start.checked_add(pointer.size()).ok_or(PakError::OffsetOverflow(start))?;
+        let aligned_start = start & !(self.alignment - 1);
+        let aligned_len = to_usize((end - aligned_start).next_multiple_of(self.alignment))?;
+        let alignment = to_usize(self.alignment)?;
+        
+        // The buffer is over-allocated so an aligned window can be cut out of it.
+        let mut buffer = vec![0u8; aligned_len + alignment];
+        let skip = buffer.as_ptr().align_offset(alignment);
+        let window = &mut buffer[skip..skip + aligned_len];
+        let mut filled = 0;
+        while filled < aligned_len {
+            match read_at(&self.file, &mut window[filled..], aligned_start + filled as u64)? {
+                0 => break,
+                read => filled += read,
+            }
+        }
+        let from = to_usize(start - aligned_start)?;
+        let to = from + to_usize(pointer.size())?;
+        if to > filled { return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()) }
+        Ok(window[from..to].to_vec())
+    }
+    
+    fn size(&mut self) -> PakResult<Option<u64>> {
+        Ok(Some(self.file.metadata()?.len()))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn open_unbuffered(path : &Path) -> io::Result<File> {
+    use std::{fs::OpenOptions, os::unix::fs::OpenOptionsExt};
+    OpenOptions::new().read(true).custom_flags(libc::O_DIRECT).open(path)
+}
+
+#[cfg(target_os = "macos")]
+fn open_unbuffered(path : &Path) -> io::Result<File> {
+    use std::{fs::OpenOptions, os::fd::AsRawFd};
+    let file = OpenOptions::new().read(true).open(path)?;
+    // SAFETY: the descriptor belongs to the file, which is open for the whole call.
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_NOCACHE, 1) } == -1 { return Err(io::Error::last_os_error()) }
+    Ok(file)
+}
+
+#[cfg(windows)]
+fn open_unbuffered(path : &Path) -> io::Result<File> {
+    use std::{fs::OpenOptions, os::windows::fs::OpenOptionsExt};
+    const FILE_FLAG_NO_BUFFERING : u32 = 0x2000_0000;
+    OpenOptions::new().read(true).custom_flags(FILE_FLAG_NO_BUFFERING).open(path)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn open_unbuffered(_path : &Path) -> io::Result<File> {
+    Err(io::Error::new(io::ErrorKind::InvalidInput, "unbuffered IO isn't supported on this platform"))
+}
+
+/// Reads from the offset without moving the file's cursor, where the platform can.
+#[cfg(unix)]
+pub(crate) fn read_at(file : &File, buffer : &mut [u8], offset : u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buffer, offset)
+}
+
+#[cfg(windows)]
+pub(crate) fn read_at(file : &File, buffer : &mut [u8], offset : u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buffer, offset)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn read_at(mut file : &File, buffer : &mut [u8], offset : u64) -> io::Result<usize> {
+    use std::io::{Read, Seek, SeekFrom};
+    file.seek(SeekFrom::Start(offset))?;
+    Read::read(&mut file, buffer)
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+use crate::{error::{PakError, PakResult}, item::PakItemDeserialize};
+
+//==============================================================================================
+//        PakEnvelope
+//==============================================================================================
+
+/// A small wrapper around an item's bytes that records which type and which version of that type wrote them. Items are only wrapped when
+/// they are paked with [PakBuilder::pak_versioned](crate::PakBuilder::pak_versioned), and their pointers carry the version so readers know
+/// to unwrap them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PakEnvelope {
+    pub type_tag : String,
+    pub version : u32,
+    pub payload : Vec<u8>,
+}
+
+//==============================================================================================
+//        PakVersioned
+//==============================================================================================
+
+/// An item type whose layout can change over time. The current layout is [VERSION](crate::envelope::PakVersioned::VERSION), and
+/// [decode_version](crate::envelope::PakVersioned::decode_version) is handed the payloads of older versions so they can be migrated.
+/// Items of this type are read with [Pak::get_versioned](crate::Pak::get_versioned).
+pub trait PakVersioned : PakItemDeserialize {
+    const VERSION : u32;
+    
+    /// Decodes a payload that was written by the given version of this type. By default only the current version can be decoded.
+    fn decode_version(version : u32, payload : &[u8]) -> PakResult<Self> {
+        if version == Self::VERSION { return Self::from_bytes(payload) }
+        Err(PakError::UnsupportedItemVersion(std::any::type_name::<Self>().to_string(), version))
+    }
+}
@@ -1,18 +1,191 @@
 use thiserror::Error;
+use crate::pointer::PakPointer;
 
 pub type PakResult<T> = Result<T, PakError>;
 
+/// Everything that can go wrong while building or reading a pak. Use [category](PakError::category) or the `is_` helpers to handle
+/// whole groups of errors at once, since new variants can be added in any release.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum PakError {
-    #[error("Type mismatch error: {0} found, {1} expected")]
-    TypeMismatchError(String, String),
-    
-    #[error("Was unable to update rules item: {0}")]
-    UpdateRuleItemError(String),
-    #[error("Was unable to insert rules item: {0}")]
-    InsertRuleItemError(String),
+    /// An item was read as a type it isn't. The pointer is the one that was read, and the key is the index it came from, if it was an
+    /// index that was being read.
+    #[error("Type mismatch error: {} found at offset {}, {expected} expected{}", .pointer.type_name(), .pointer.offset(), .key.as_ref().map(|key| format!(" for the index {key}")).unwrap_or_default())]
+    TypeMismatchError { pointer : PakPointer, expected : String, key : Option<String> },
+    #[error("The id {0} was given to more than one item")]
+    DuplicateId(String),
+    #[error("Another item was already paked at the path {0}")]
+    DuplicatePath(String),
+    #[error("The key {0} was given more than one text in the language {1}")]
+    DuplicateLocalization(String, String),
+    #[error("The item key id {0} was given more than one key")]
+    DuplicateKeyId(String),
+    #[error("The index key {0} starts with __pak_, which is reserved for the crate")]
+    ReservedKey(String),
+    #[error("Encryption has to be set before anything is paked, but {0} items were paked already")]
+    EncryptionAfterPak(usize),
+    #[error("An item of type {0} was paked without entries for the index keys {1}")]
+    MissingIndices(String, String),
+    #[error("The path {0} is empty or leaves the root of the pak")]
+    InvalidPath(String),
+    #[error("The path {0} is both a file and a directory, so the pak can't be mounted")]
+    PathConflict(String),
+    #[error("The pak has no index with the key {0}")]
+    UnknownIndex(String),
+    #[error("The index {0} holds {1} values, but was queried with a {2} value")]
+    ValueKindMismatch(String, String, String),
+    #[error("The foreign type {0} has no codec, it has to be registered with PakForeign::register first")]
+    UnregisteredForeignType(String),
+    #[error("Version {1} of {0} can't be decoded")]
+    UnsupportedItemVersion(String, u32),
+    #[error("The item is version {1} of {0}, so it has to be read with get_versioned or query_versioned")]
+    VersionedItem(String, u32),
+    #[error("The pak doesn't match the expected schema:\n{0}")]
+    SchemaMismatch(String),
+    #[error("The index {0} can't answer a {2} query with the {1} index kind")]
+    UnsupportedIndexOperation(String, String, String),
+    #[error("The search query is invalid: {0}")]
+    InvalidSearch(String),
+    #[error("The query text is invalid: {0}")]
+    InvalidQuery(String),
+    #[error("The query went over its budget of {1} {0}")]
+    BudgetExceeded(String, u64),
+    #[error("The text analyzer needs the {0} feature")]
+    AnalyzerUnavailable(String),
+    #[error("The pointer is from generation {0} of the pak, but the pak is at generation {1}")]
+    StalePointer(u64, u64),
+    #[error("The pak was rewritten in its source after the snapshot was taken")]
+    SourceChanged,
+    #[error("The pak is encrypted and has to be opened with a key or a password")]
+    MissingKey,
+    #[error("The key or password isn't the one the pak was encrypted with")]
+    WrongKey,
+    #[error("The item is encrypted with the key {0}, which the pak hasn't been given")]
+    KeyRequired(String),
+    #[error("The chunk at {0} couldn't be decrypted")]
+    DecryptionFailed(u64),
+    #[error("The vault doesn't match the hash the pak was built with")]
+    VaultHashMismatch,
+    #[error("The pak was built without a vault hash, so it can't be verified")]
+    MissingVaultHash,
+    #[error("The item at {0} doesn't match its checksum")]
+    ItemChecksumMismatch(u64),
+    #[error("The chunk at {0} couldn't be decompressed")]
+    DecompressionFailed(u64),
+    #[error("Block {0} of the vault failed its checksum {1} times in a row")]
+    ChecksumMismatch(u64, u32),
+    #[error("The remote pak couldn't answer the request: {0}")]
+    RemoteError(String),
+    #[error("The pointer to {1} bytes at {0} reaches past the end of the vault")]
+    PointerOutOfBounds(u64, u64),
+    #[error("The offset or size {0} doesn't fit in the address space of this target")]
+    OffsetOverflow(u64),
+    #[error("The items of the pak are compressed with {0}, which needs the {0} feature")]
+    CompressionUnavailable(String),
+    #[error("The pak is laid out with format version {0}, which this version of the crate can't read")]
+    UnsupportedFormat(u32),
+    #[error("The pak header is written with revision {0}, which this version of the crate can't read")]
+    UnsupportedRevision(u32),
+    #[cfg(feature = "zip")]
+    #[error("The zip archive couldn't be read: {0}")]
+    ZipError(#[from] zip::result::ZipError),
+    #[error("The {0} in the pak header is invalid: {1}")]
+    InvalidHeader(String, String),
+    #[error("{0}")]
+    ValueConversion(#[from] PakValueConversionError),
     #[error("There was an error packing the module: {0}")]
     BincodeError(#[from] Box<bincode::ErrorKind>),
     #[error("There was an error packing the module: {0}")]
     FileError(#[from] std::io::Error),
-}
\ No newline at end of file
+}
+
+impl PakError {
+    pub fn category(&self) -> PakErrorCategory {
+        match self {
+            PakError::TypeMismatchError { .. } | PakError::ValueKindMismatch(..) | PakError::UnsupportedItemVersion(..) | PakError::VersionedItem(..) | PakError::SchemaMismatch(_)
+                | PakError::UnregisteredForeignType(_) | PakError::ValueConversion(_) => PakErrorCategory::Type,
+            PakError::DuplicateId(_) | PakError::DuplicatePath(_) | PakError::DuplicateLocalization(..) | PakError::DuplicateKeyId(_) | PakError::EncryptionAfterPak(_)
+                | PakError::MissingIndices(..) | PakError::ReservedKey(_) => PakErrorCategory::Build,
+            PakError::InvalidPath(_) | PakError::PathConflict(_) | PakError::UnknownIndex(_) | PakError::UnsupportedIndexOperation(..) | PakError::InvalidSearch(_) | PakError::InvalidQuery(_)
+                | PakError::AnalyzerUnavailable(_) | PakError::StalePointer(..) | PakError::SourceChanged | PakError::PointerOutOfBounds(..) => PakErrorCategory::Query,
+            PakError::BudgetExceeded(..) | PakError::OffsetOverflow(_) => PakErrorCategory::Limit,
+            PakError::MissingKey | PakError::WrongKey | PakError::KeyRequired(_) => PakErrorCategory::Key,
+            PakError::DecryptionFailed(_) | PakError::DecompressionFailed(_) | PakError::ChecksumMismatch(..) | PakError::ItemChecksumMismatch(_) | PakError::VaultHashMismatch
+                | PakError::InvalidHeader(..) => PakErrorCategory::Corruption,
+            PakError::BincodeError(error) if matches!(**error, bincode::ErrorKind::Io(_)) => PakErrorCategory::Io,
+            PakError::UnsupportedFormat(_) | PakError::UnsupportedRevision(_) | PakError::CompressionUnavailable(_) | PakError::MissingVaultHash
+                | PakError::BincodeError(_) => PakErrorCategory::Format,
+            #[cfg(feature = "zip")]
+            PakError::ZipError(_) => PakErrorCategory::Format,
+            PakError::RemoteError(_) | PakError::FileError(_) => PakErrorCategory::Io,
+        }
+    }
+
+    pub fn is_format(&self) -> bool {
+        self.category() == PakErrorCategory::Format
+    }
+
+    pub fn is_io(&self) -> bool {
+        self.category() == PakErrorCategory::Io
+    }
+
+    pub fn is_corruption(&self) -> bool {
+        self.category() == PakErrorCategory::Corruption
+    }
+
+    pub fn is_query(&self) -> bool {
+        self.category() == PakErrorCategory::Query
+    }
+
+    pub fn is_type(&self) -> bool {
+        self.category() == PakErrorCategory::Type
+    }
+
+    pub fn is_limit(&self) -> bool {
+        self.category() == PakErrorCategory::Limit
+    }
+
+    pub fn is_build(&self) -> bool {
+        self.category() == PakErrorCategory::Build
+    }
+
+    pub fn is_key(&self) -> bool {
+        self.category() == PakErrorCategory::Key
+    }
+
+    /// The error for reading the item at the pointer as a `T` when it holds something else.
+    pub(crate) fn type_mismatch<T>(pointer : &PakPointer, key : Option<&str>) -> Self {
+        PakError::TypeMismatchError { pointer : pointer.clone(), expected : std::any::type_name::<T>().to_string(), key : key.map(str::to_string) }
+    }
+}
+
+/// The broad kind of a [PakError], for handling errors without matching every variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PakErrorCategory {
+    /// The pak, or an item in it, isn't laid out in a way this version of the crate can read.
+    Format,
+    /// Reading the source failed, or the connection to a remote pak did.
+    Io,
+    /// The bytes of the pak aren't the ones that were written, found by a checksum, a failed decryption or a header that doesn't add up.
+    Corruption,
+    /// The query, search, path or pointer can't be answered by the pak.
+    Query,
+    /// An item or a value was read as a type it doesn't have.
+    Type,
+    /// A budget set on the reads ran out, or the pak is too large for the target to address.
+    Limit,
+    /// The items given to a builder clash with each other.
+    Build,
+    /// The pak or one of its items needs a key that wasn't given, or was given the wrong one.
+    Key,
+}
+
+/// The ways that converting a [PakValue](crate::value::PakValue) into a rust type can fail.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PakValueConversionError {
+    #[error("Can't convert a {0} value into {1}")]
+    KindMismatch(String, String),
+    #[error("The value {0} doesn't fit in {1}")]
+    OutOfRange(String, String),
+}
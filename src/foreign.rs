@@ -0,0 +1,130 @@
+use std::{any::{Any, TypeId}, collections::HashMap, ops::{Deref, DerefMut}, sync::{Arc, OnceLock, RwLock}};
+use crate::{error::{PakError, PakResult}, index::PakIndex, item::{PakItemDeserialize, PakItemSearchable, PakItemSerialize}};
+
+type PakForeignRegistry = RwLock<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>;
+type PakSerializeFn<T> = Box<dyn Fn(&T) -> PakResult<Vec<u8>> + Send + Sync>;
+type PakDeserializeFn<T> = Box<dyn Fn(&[u8]) -> PakResult<T> + Send + Sync>;
+type PakIndicesFn<T> = Box<dyn Fn(&T) -> Vec<PakIndex> + Send + Sync>;
+
+/// The codecs of every foreign type that has been registered, shared by every builder and pak in the process.
+static REGISTRY : OnceLock<PakForeignRegistry> = OnceLock::new();
+
+fn registry() -> &'static PakForeignRegistry {
+    REGISTRY.get_or_init(Default::default)
+}
+
+//==============================================================================================
+//        PakForeignCodec
+//==============================================================================================
+
+/// How a foreign type is turned into bytes, read back, and indexed, given as closures since the type's crate can't be changed to implement
+/// the item traits. Register it with [PakForeign::register](crate::foreign::PakForeign::register).
+pub struct PakForeignCodec<T> {
+    serialize : PakSerializeFn<T>,
+    deserialize : PakDeserializeFn<T>,
+    indices : PakIndicesFn<T>,
+}
+
+impl<T> PakForeignCodec<T> {
+    /// A codec that writes items with `serialize` and reads them back with `deserialize`. Items have no indices until
+    /// [with_indices](crate::foreign::PakForeignCodec::with_indices) gives them some.
+    pub fn new<S, D>(serialize : S, deserialize : D) -> Self where S : Fn(&T) -> PakResult<Vec<u8>> + Send + Sync + 'static, D : Fn(&[u8]) -> PakResult<T> + Send + Sync + 'static {
+        Self { serialize : Box::new(serialize), deserialize : Box::new(deserialize), indices : Box::new(|_| Vec::new()) }
+    }
+
+    /// A codec that converts items to and from a type the crate can already pak, like a tuple or a struct of your own, and writes that.
+    pub fn via<R, I, F>(into : I, from : F) -> Self where R : PakItemSerialize + PakItemDeserialize, I : Fn(&T) -> R + Send + Sync + 'static, F : Fn(R) -> T + Send + Sync + 'static {
+        Self::new(move |item| into(item).into_bytes(), move |bytes| R::from_bytes(bytes).map(&from))
+    }
+
+    /// Sets the indices items are paked with, which can be built with [pak_indices](crate::pak_indices).
+    pub fn with_indices<F>(mut self, indices : F) -> Self where F : Fn(&T) -> Vec<PakIndex> + Send + Sync + 'static {
+        self.indices = Box::new(indices);
+        self
+    }
+}
+
+//==============================================================================================
+//        PakForeign
+//==============================================================================================
+
+/// A wrapper that lets types from other crates, like `glam::Vec3` or `chrono::NaiveDate`, be paked and queried like any other item. The
+/// orphan rule keeps the item traits from being implemented on them directly, so the wrapper looks up a [PakForeignCodec] that was
+/// registered for `T` instead. Paking or reading a wrapper before its type is registered fails with
+/// [PakError::UnregisteredForeignType](crate::error::PakError::UnregisteredForeignType).
+///
+/// ```rust
+/// # use pak_db::{foreign::{PakForeign, PakForeignCodec}, index::PakIndexIdentifier, pak_indices, PakBuilder};
+/// # struct Vec3 { x : f32, y : f32, z : f32 }
+/// # impl Vec3 { fn new(x : f32, y : f32, z : f32) -> Self { Self { x, y, z } } }
+/// # let mut builder = PakBuilder::new();
+/// PakForeign::<Vec3>::register(PakForeignCodec::via(|v : &Vec3| (v.x, v.y, v.z), |(x, y, z)| Vec3::new(x, y, z)).with_indices(|v| pak_indices!(v => { "y" => v.y })));
+/// builder.pak(PakForeign(Vec3::new(1.0, 2.0, 3.0)))?;
+/// # let pak = builder.build_in_memory()?;
+/// let points = pak.query::<(PakForeign<Vec3>,)>("y".greater_than(0.0))?;
+/// # assert_eq!(points.len(), 1);
+/// # Ok::<(), pak_db::error::PakError>(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PakForeign<T>(pub T);
+
+impl<T : 'static> PakForeign<T> {
+    /// Registers the codec for `T` for the whole process, replacing the one it had before. Items are paked under the type name of the
+    /// wrapper, so a pak has to be read with the same codec it was built with.
+    pub fn register(codec : PakForeignCodec<T>) {
+        registry().write().unwrap_or_else(|error| error.into_inner()).insert(TypeId::of::<T>(), Arc::new(codec));
+    }
+
+    /// Returns true if a codec has been registered for `T`.
+    pub fn is_registered() -> bool {
+        registry().read().unwrap_or_else(|error| error.into_inner()).contains_key(&TypeId::of::<T>())
+    }
+
+    fn codec() -> PakResult<Arc<PakForeignCodec<T>>> {
+        let codec = registry().read().unwrap_or_else(|error| error.into_inner()).get(&TypeId::of::<T>()).cloned();
+        codec.and_then(|codec| codec.downcast().ok()).ok_or_else(|| PakError::UnregisteredForeignType(std::any::type_name::<T>().to_string()))
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for PakForeign<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for PakForeign<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> From<T> for PakForeign<T> {
+    fn from(value : T) -> Self {
+        PakForeign(value)
+    }
+}
+
+impl<T : 'static> PakItemSerialize for PakForeign<T> {
+    fn into_bytes(&self) -> PakResult<Vec<u8>> {
+        (Self::codec()?.serialize)(&self.0)
+    }
+}
+
+impl<T : 'static> PakItemDeserialize for PakForeign<T> {
+    fn from_bytes(bytes : &[u8]) -> PakResult<Self> {
+        (Self::codec()?.deserialize)(bytes).map(PakForeign)
+    }
+}
+
+/// Without a codec there are no indices to give, and paking the item fails when it is serialized.
+impl<T : 'static> PakItemSearchable for PakForeign<T> {
+    fn get_indices(&self) -> Vec<PakIndex> {
+        Self::codec().map(|codec| (codec.indices)(&self.0)).unwrap_or_default()
+    }
+}
@@ -0,0 +1,118 @@
+use std::{collections::HashMap, fs::File, io, sync::Arc};
+use crate::{cache::{PakPriority, PakReadScheduler}, error::{PakError, PakResult}, format::PakLayout, hash::PakIndexMap, kind::PakIndexKind, meta::PakMeta, pointer::{PakPointer, PakTypedPointer}, query::PakUnknownKeys, Pak, PakSource};
+
+/// Opens another source onto the same pak, for the branches of a query and the streams that read on other threads.
+pub type PakSourceFactory = dyn Fn() -> PakResult<Box<dyn PakSource>> + Send + Sync;
+
+struct PakForkSource(Box<dyn PakSource>);
+
+impl PakSource for PakForkSource {
+    fn read(&mut self, pointer : &PakPointer, offset : u64) -> PakResult<Vec<u8>> {
+        self.0.read(pointer, offset)
+    }
+
+    fn size(&mut self) -> PakResult<Option<u64>> {
+        self.0.size()
+    }
+}
+
+/// A handle of an opened pak file, read with positional reads so the duplicates of the handle that other threads read through never race
+/// over the cursor they share.
+struct PakSharedFile(File);
+
+impl PakSource for PakSharedFile {
+    fn read(&mut self, pointer : &PakPointer, offset : u64) -> PakResult<Vec<u8>> {
+        let start = pointer.offset().checked_add(offset).ok_or(PakError::OffsetOverflow(offset))?;
+        let mut buffer = vec![0u8; crate::pointer::to_usize(pointer.size())?];
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match crate::direct::read_at(&self.0, &mut buffer[filled..], start + filled as u64)? {
+                0 => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                read => filled += read,
+            }
+        }
+        Ok(buffer)
+    }
+
+    fn size(&mut self) -> PakResult<Option<u64>> {
+        Ok(Some(self.0.metadata()?.len()))
+    }
+}
+
+//==============================================================================================
+//        PakFork
+//==============================================================================================
+
+/// Everything another thread needs to open its own copy of a pak. The copy is handed the index table and ordinals the pak has already
+/// read, so the only reads it makes are the ones its work needs. It reads at the priority the pak had when it was forked, and shares the
+/// pak's [scheduler](crate::cache::PakReadScheduler), so its background reads wait for the foreground reads of the pak and the other copies.
+#[derive(Clone)]
+pub(crate) struct PakFork {
+    factory : Arc<PakSourceFactory>,
+    layout : PakLayout,
+    meta : PakMeta,
+    indices : Option<PakIndexMap>,
+    ordinals : Option<Vec<PakTypedPointer>>,
+    kinds : HashMap<String, Arc<dyn PakIndexKind>>,
+    checksum_retries : u32,
+    unknown_keys : PakUnknownKeys,
+    priority : PakPriority,
+    scheduler : Arc<PakReadScheduler>,
+    #[cfg(feature = "encryption")]
+    cipher : Option<crate::crypto::PakCipher>,
+    #[cfg(feature = "encryption")]
+    item_ciphers : HashMap<String, crate::crypto::PakCipher>,
+}
+
+impl PakFork {
+    /// Opens the copy on the thread that reads through it.
+    pub(crate) fn open(&self) -> PakResult<Pak> {
+        let mut pak = Pak::from_parts(self.layout.clone(), self.meta.clone(), PakForkSource((self.factory)()?));
+        pak.kinds = self.kinds.clone();
+        pak.checksum_retries = self.checksum_retries;
+        pak.unknown_keys = self.unknown_keys;
+        pak.priority.set(self.priority);
+        pak.scheduler = self.scheduler.clone();
+        #[cfg(feature = "encryption")]
+        {
+            pak.cipher = self.cipher.clone();
+            pak.item_ciphers = self.item_ciphers.clone();
+        }
+        if let Some(indices) = &self.indices { let _ = pak.indices.set(indices.clone()); }
+        if let Some(ordinals) = &self.ordinals { let _ = pak.ordinals.set(ordinals.clone()); }
+        Ok(pak)
+    }
+}
+
+impl Pak {
+    /// Opens the file so reads on other threads can go through duplicates of its handle.
+    pub(crate) fn from_shared_file(file : File) -> PakResult<Self> {
+        let shared = file.try_clone()?;
+        let mut pak = Self::new(PakSharedFile(file))?;
+        pak.forks = Some(Arc::new(move || Ok(Box::new(PakSharedFile(shared.try_clone()?)) as Box<dyn PakSource>)));
+        Ok(pak)
+    }
+    
+    /// Returns `None` if the pak has no way to open more sources, or if it is metering or recording its reads, since those only see the
+    /// reads made on this thread.
+    pub(crate) fn fork(&self) -> PakResult<Option<PakFork>> {
+        let Some(factory) = &self.forks else { return Ok(None) };
+        if self.budget.borrow().is_some() || self.recorder.borrow().is_some() { return Ok(None) }
+        Ok(Some(PakFork {
+            factory : factory.clone(),
+            layout : self.layout.clone(),
+            meta : self.meta.clone(),
+            indices : Some(self.fetch_indices()?.clone()),
+            ordinals : self.ordinals.get().cloned(),
+            kinds : self.kinds.clone(),
+            checksum_retries : self.checksum_retries,
+            unknown_keys : self.unknown_keys,
+            priority : self.priority.get(),
+            scheduler : self.scheduler.clone(),
+            #[cfg(feature = "encryption")]
+            cipher : self.cipher.clone(),
+            #[cfg(feature = "encryption")]
+            item_ciphers : self.item_ciphers.clone(),
+        }))
+    }
+}
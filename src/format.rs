@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+use crate::{error::{PakError, PakResult}, hash::PakIndexMap, meta::{PakMeta, PakSizing, PakTrailer}, pointer::PakPointer, PakSource};
+
+/// The first four bytes of every pak from version 2 on, which are followed by the version as a u32. Version 1 paks start with their sizing
+/// instead, and a meta section as large as these bytes would make doesn't fit in any real file, so the two can't be mistaken for each other.
+pub const PAK_HEADER_MAGIC : &[u8; 4] = b"PAKD";
+
+/// The magic bytes at the very end of a version 2 pak, right after its footer.
+pub const PAK_FOOTER_MAGIC : &[u8; 8] = b"PAKEND02";
+
+//==============================================================================================
+//        PakFormat
+//==============================================================================================
+
+/// The on-disk layout of a pak. [Pak::new](crate::Pak::new) reads the version from the start of the source and opens the pak with the
+/// matching driver, so every version can be opened the same way. New paks are written as [V1](crate::format::PakFormat::V1) unless the
+/// builder is told otherwise with [with_format](crate::PakBuilder::with_format).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PakFormat {
+    /// The sizing, meta and index map come first, followed by the vault with a length prefix and a backup copy of the header. This is
+    /// also the layout of paks written by pak-db 0.1, which have no backup copy and are read through the legacy driver, since their
+    /// meta and index trees predate the [meta revision](crate::meta::PAK_META_REVISION). Their items are given ordinals in the order
+    /// they sit in the vault, and items paked without indices have none, though the pointers handed out for them still work.
+    #[default]
+    V1,
+    /// A short header with the version, the raw vault, and the meta and index map in a footer at the end. The header no longer depends on
+    /// anything in the vault, so it can be written once the vault is done.
+    V2,
+}
+
+impl PakFormat {
+    /// The version that is written to the header.
+    pub fn version(self) -> u32 {
+        match self {
+            PakFormat::V1 => 1,
+            PakFormat::V2 => 2,
+        }
+    }
+
+    /// Reads the version of the pak from the start of the source.
+    pub(crate) fn detect(source : &mut dyn PakSource) -> PakResult<Self> {
+        let header = source.read(&PakPointer::new_untyped(0, 8), 0)?;
+        if &header[..4] != PAK_HEADER_MAGIC { return Ok(PakFormat::V1) }
+        match u32::from_le_bytes(header[4..].try_into().unwrap()) {
+            2 => Ok(PakFormat::V2),
+            version => Err(PakError::UnsupportedFormat(version)),
+        }
+    }
+
+    pub(crate) fn driver(self) -> &'static dyn PakFormatDriver {
+        match self {
+            PakFormat::V1 => &PakV1Driver,
+            PakFormat::V2 => &PakV2Driver,
+        }
+    }
+}
+
+/// Where the sections of an opened pak are in its source.
+#[derive(Clone, Debug)]
+pub(crate) struct PakLayout {
+    pub(crate) format : PakFormat,
+    pub(crate) indices : PakPointer,
+    pub(crate) vault_start : u64,
+    pub(crate) vault_len : u64,
+    pub(crate) size : u64,
+}
+
+/// Reads and writes one version of the pak layout. Everything past the layout, like the vault chunks and the index trees, is shared by
+/// every version.
+pub(crate) trait PakFormatDriver {
+    /// Reads the meta of the pak and finds where the index map and the vault are.
+    fn open(&self, source : &mut dyn PakSource) -> PakResult<(PakMeta, PakLayout)>;
+
+    /// Lays out a whole pak file from its meta, its index map and its vault.
+    fn write(&self, meta : &PakMeta, indices : &PakIndexMap, vault : &[u8]) -> PakResult<(Vec<u8>, PakLayout)>;
+    
+    /// Works out the layout [write](PakFormatDriver::write) would produce for a vault of the given length, along with the size of the
+    /// whole file, without laying out the bytes.
+    fn plan(&self, meta : &PakMeta, indices : &PakIndexMap, vault_len : u64) -> PakResult<(PakLayout, u64)>;
+}
+
+//==============================================================================================
+//        V1
+//==============================================================================================
+
+struct PakV1Driver;
+
+impl PakV1Driver {
+    fn layout(sizing : &PakSizing) -> PakLayout {
+        PakLayout {
+            format : PakFormat::V1,
+            indices : PakPointer::new_untyped(24 + sizing.meta_size, sizing.indices_size),
+            // The vault is written with bincode, so its length comes first.
+            vault_start : 24 + sizing.meta_size + sizing.indices_size + 8,
+            vault_len : sizing.vault_size - 8,
+            size : 24 + sizing.meta_size + sizing.indices_size + sizing.vault_size,
+        }
+    }
+}
+
+impl PakFormatDriver for PakV1Driver {
+    fn open(&self, source : &mut dyn PakSource) -> PakResult<(PakMeta, PakLayout)> {
+        let sizing_buffer = source.read(&PakPointer::new_untyped(0, 24), 0)?;
+        let sizing : PakSizing = bincode::deserialize(&sizing_buffer)?;
+        let source_len = match source.size()? {
+            Some(len) => Some(len - PakTrailer::size_in(source, len)?),
+            None => None,
+        };
+        sizing.validate(source_len)?;
+
+        let meta_buffer = source.read(&PakPointer::new_untyped(24, sizing.meta_size), 0)?;
+        let meta = PakMeta::decode(&meta_buffer)?;
+        Ok((meta, Self::layout(&sizing)))
+    }
+
+    fn write(&self, meta : &PakMeta, indices : &PakIndexMap, vault : &[u8]) -> PakResult<(Vec<u8>, PakLayout)> {
+        let mut indices_out = meta.serialize_indices(indices)?;
+        let sizing = PakSizing {
+            meta_size: bincode::serialized_size(meta)?,
+            indices_size: indices_out.len() as u64,
+            vault_size: bincode::serialized_size(vault)?,
+        };
+
+        let mut sizing_out = bincode::serialize(&sizing)?;
+        let mut meta_out = bincode::serialize(meta)?;
+        let mut vault_out = bincode::serialize(vault)?;
+
+        let mut out = Vec::<u8>::new();
+        out.append(&mut sizing_out);
+        out.append(&mut meta_out);
+        out.append(&mut indices_out);
+        out.append(&mut vault_out);
+        PakTrailer::write(&mut out, &sizing, meta, indices)?;
+        Ok((out, Self::layout(&sizing)))
+    }
+    
+    fn plan(&self, meta : &PakMeta, indices : &PakIndexMap, vault_len : u64) -> PakResult<(PakLayout, u64)> {
+        let sizing = PakSizing {
+            meta_size: bincode::serialized_size(meta)?,
+            indices_size: meta.serialize_indices(indices)?.len() as u64,
+            vault_size: vault_len + 8,
+        };
+        let layout = Self::layout(&sizing);
+        let size = layout.size + PakTrailer::size_of(&sizing, meta, indices)?;
+        Ok((layout, size))
+    }
+}
+
+//==============================================================================================
+//        V2
+//==============================================================================================
+
+/// The sizing and the footer magic at the end of a version 2 pak.
+const V2_FOOTER_SIZE : u64 = 32;
+
+/// Where the vault of a version 2 pak starts, right after the magic and the version. It doesn't depend on anything else in the pak, so
+/// a builder can line items up in the file while it is still paking them.
+pub(crate) const V2_VAULT_START : u64 = 8;
+
+struct PakV2Driver;
+
+impl PakV2Driver {
+    fn layout(sizing : &PakSizing) -> PakLayout {
+        PakLayout {
+            format : PakFormat::V2,
+            indices : PakPointer::new_untyped(V2_VAULT_START + sizing.vault_size + sizing.meta_size, sizing.indices_size),
+            vault_start : V2_VAULT_START,
+            vault_len : sizing.vault_size,
+            size : 8 + sizing.vault_size + sizing.meta_size + sizing.indices_size + V2_FOOTER_SIZE,
+        }
+    }
+}
+
+impl PakFormatDriver for PakV2Driver {
+    fn open(&self, source : &mut dyn PakSource) -> PakResult<(PakMeta, PakLayout)> {
+        let invalid = |field : &str, reason : String| PakError::InvalidHeader(field.to_string(), reason);
+        let Some(len) = source.size()? else { return Err(invalid("footer", "the footer can't be found in a source of unknown length".to_string())) };
+        if len < 8 + V2_FOOTER_SIZE { return Err(invalid("footer", format!("a source of {len} bytes is too small to hold one"))) }
+
+        let footer = source.read(&PakPointer::new_untyped(len - V2_FOOTER_SIZE, V2_FOOTER_SIZE), 0)?;
+        if &footer[24..] != PAK_FOOTER_MAGIC { return Err(invalid("footer", "the source doesn't end with the footer magic".to_string())) }
+        let sizing : PakSizing = bincode::deserialize(&footer[..24])?;
+        let total = [sizing.vault_size, sizing.meta_size, sizing.indices_size].into_iter().try_fold(8 + V2_FOOTER_SIZE, u64::checked_add);
+        if total != Some(len) {
+            return Err(invalid("sizing", format!("the sections don't add up to the source, which is {len} bytes")))
+        }
+
+        let meta_buffer = source.read(&PakPointer::new_untyped(8 + sizing.vault_size, sizing.meta_size), 0)?;
+        let meta = PakMeta::decode(&meta_buffer)?;
+        Ok((meta, Self::layout(&sizing)))
+    }
+
+    fn write(&self, meta : &PakMeta, indices : &PakIndexMap, vault : &[u8]) -> PakResult<(Vec<u8>, PakLayout)> {
+        let meta_out = bincode::serialize(meta)?;
+        let indices_out = meta.serialize_indices(indices)?;
+        let sizing = PakSizing { meta_size : meta_out.len() as u64, indices_size : indices_out.len() as u64, vault_size : vault.len() as u64 };
+
+        let mut out = Vec::<u8>::with_capacity(crate::pointer::to_usize(Self::layout(&sizing).size)?);
+        out.extend_from_slice(PAK_HEADER_MAGIC);
+        out.extend_from_slice(&PakFormat::V2.version().to_le_bytes());
+        out.extend_from_slice(vault);
+        out.extend_from_slice(&meta_out);
+        out.extend_from_slice(&indices_out);
+        out.extend_from_slice(&bincode::serialize(&sizing)?);
+        out.extend_from_slice(PAK_FOOTER_MAGIC);
+        Ok((out, Self::layout(&sizing)))
+    }
+    
+    fn plan(&self, meta : &PakMeta, indices : &PakIndexMap, vault_len : u64) -> PakResult<(PakLayout, u64)> {
+        let sizing = PakSizing {
+            meta_size : bincode::serialized_size(meta)?,
+            indices_size : meta.serialize_indices(indices)?.len() as u64,
+            vault_size : vault_len,
+        };
+        let layout = Self::layout(&sizing);
+        let size = layout.size;
+        Ok((layout, size))
+    }
+}
@@ -0,0 +1,222 @@
+use std::{fmt, fs, path::{Path, PathBuf}};
+use serde::{Deserialize, Serialize};
+use crate::{error::{PakError, PakResult}, format::PakFormat, index::PakIndex, item::PakItemSearchable, query::{PakQuery, PakQueryExpression}, testing::{query_ordinals, NaiveStore}, Pak, PakBuilder};
+
+/// The key of the pak that the encrypted golden pak is built with. Golden paks are public test data, so this is no secret.
+#[cfg(feature = "encryption")]
+pub(crate) const PAK_GOLDEN_KEY : [u8; 32] = [7; 32];
+
+//==============================================================================================
+//        PakGoldenItem
+//==============================================================================================
+
+/// The item every golden pak is built from. Its layout and its path, which the v1 golden pak holds as the type name of its items, are part
+/// of the golden data, so neither may change.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PakGoldenItem {
+    pub id : u32,
+    pub name : String,
+    pub kind : String,
+    pub weight : u32,
+}
+
+impl PakItemSearchable for PakGoldenItem {
+    fn get_indices(&self) -> Vec<PakIndex> {
+        crate::pak_indices!(self => { name, kind, weight })
+    }
+}
+
+/// The items of every golden pak, in the order they are paked.
+pub(crate) fn golden_items() -> Vec<PakGoldenItem> {
+    (0..40).map(|id| PakGoldenItem {
+        id,
+        name : format!("Item {id:02}"),
+        kind : ["weapon", "armor", "potion", "scroll"][id as usize % 4].to_string(),
+        weight : id * 3 % 17,
+    }).collect()
+}
+
+//==============================================================================================
+//        PakGolden
+//==============================================================================================
+
+/// A canonical pak whose bytes are checked in, to hold the crate to reading every pak it has written before. Each golden pak is built
+/// once with [write](PakGolden::write) and then only read back with [verify](PakGolden::verify), which runs the same queries against
+/// the checked in bytes that a freshly built pak answers. A change to the format that breaks an old pak fails its golden pak, and
+/// should come with a new format version rather than new golden bytes.
+pub(crate) struct PakGolden {
+    pub(crate) name : &'static str,
+    /// The version of pak-db that wrote the golden pak, if it was an earlier release. Those can't be written again by the current code.
+    written_by : Option<&'static str>,
+    build : fn() -> PakResult<PakBuilder>,
+    open : fn(&Path) -> PakResult<Pak>,
+    check : fn(&Pak) -> Result<(), PakGoldenMismatch>,
+}
+
+impl PakGolden {
+    /// Every golden pak the enabled features can read.
+    pub(crate) fn all() -> Vec<PakGolden> {
+        #[cfg_attr(not(any(feature = "encryption", feature = "zstd")), allow(unused_mut))]
+        let mut goldens = vec![
+            PakGolden {
+                name : "v1",
+                written_by : Some("pak-db 0.1 (e95ef75)"),
+                build : || paked(PakBuilder::new()),
+                open : open_file,
+                check : |_| Ok(()),
+            },
+            PakGolden { name : "v2", written_by : None, build : || paked(PakBuilder::new().with_format(PakFormat::V2)), open : open_file, check : |_| Ok(()) },
+            PakGolden {
+                name : "checked",
+                written_by : None,
+                build : || paked(PakBuilder::new().with_block_checksums(256).with_item_checksums().with_vault_hash().with_manifest().with_inline_items(48)),
+                open : open_file,
+                check : |pak| {
+                    pak.verify()?;
+                    expect(pak.manifest_hashes()?.map(|manifest| manifest.len()) == Some(golden_items().len()), "the manifest lists every item")
+                },
+            },
+            PakGolden {
+                name : "addressed",
+                written_by : None,
+                build : || {
+                    let mut builder = PakBuilder::new();
+                    for item in golden_items() {
+                        let path = format!("items/{}/{:02}", item.kind, item.id);
+                        match item.id % 2 {
+                            0 => builder.pak_with_path(&path, item)?,
+                            _ => builder.pak_with_id(item.id as u64, item)?,
+                        };
+                    }
+                    builder.pak_l10n("menu.start", "en", "Start")?;
+                    builder.pak_l10n("menu.start", "fr", "Commencer")?;
+                    Ok(builder)
+                },
+                open : open_file,
+                check : |pak| {
+                    let item = &golden_items()[6];
+                    expect(pak.by_path::<PakGoldenItem>("items/potion/06")?.as_ref() == Some(item), "items are found by path")?;
+                    expect(pak.by_id::<PakGoldenItem>(7u64)?.as_ref() == Some(&golden_items()[7]), "items are found by id")?;
+                    expect(pak.localize("menu.start", &["fr-CA", "fr"])?.as_deref() == Some("Commencer"), "strings are localized")
+                },
+            },
+        ];
+        #[cfg(feature = "encryption")]
+        goldens.push(PakGolden {
+            name : "encrypted",
+            written_by : None,
+            build : || paked(PakBuilder::new().with_encryption(&crate::crypto::PakKey::new(PAK_GOLDEN_KEY))?),
+            open : |path| Pak::open_with_key(path, &crate::crypto::PakKey::new(PAK_GOLDEN_KEY)),
+            check : |pak| expect(pak.is_encrypted(), "the pak is encrypted"),
+        });
+        #[cfg(feature = "zstd")]
+        goldens.push(PakGolden {
+            name : "zstd",
+            written_by : None,
+            build : || paked(PakBuilder::new().with_compression(crate::meta::PakCompression::Zstd(3)).with_index_compression(crate::meta::PakCompression::Zstd(3))),
+            open : open_file,
+            check : |pak| expect(pak.compression().is_some() && pak.index_compression().is_some(), "the pak is compressed"),
+        });
+        goldens
+    }
+
+    /// Where the golden pak is kept in the directory.
+    pub(crate) fn path(&self, dir : impl AsRef<Path>) -> PathBuf {
+        dir.as_ref().join(format!("{}.pak", self.name))
+    }
+
+    /// Builds the golden pak and writes it to the directory. This is only for adding a golden pak, since rewriting one that exists throws
+    /// away the guarantee it was checked in for, and golden paks written by an earlier release can't be written at all.
+    pub(crate) fn write(&self, dir : impl AsRef<Path>) -> Result<(), PakGoldenMismatch> {
+        if let Some(version) = self.written_by {
+            return Err(PakGoldenMismatch(format!("the golden pak {} was written by {version} and can't be written again", self.name)));
+        }
+        fs::create_dir_all(dir.as_ref()).map_err(PakError::from)?;
+        (self.build)()?.build_file(self.path(dir))?;
+        Ok(())
+    }
+
+    /// Reads the checked in golden pak from the directory with the current code, and fails if it doesn't answer the way it did when it was
+    /// written. A freshly built pak is held to the same checks, so the checks themselves can't drift from what the builder writes.
+    pub(crate) fn verify(&self, dir : impl AsRef<Path>) -> Result<(), PakGoldenMismatch> {
+        let mismatch = |reason : String| PakGoldenMismatch(format!("the golden pak {} no longer reads the way it did when it was written: {reason}", self.name));
+        let golden = (self.open)(&self.path(dir)).map_err(|error| mismatch(format!("it can't be opened: {error}")))?;
+        if self.written_by.is_some() {
+            expect(golden.meta.revision < crate::meta::PAK_META_REVISION, "it has the meta revision of an earlier release").map_err(|PakGoldenMismatch(reason)| mismatch(reason))?;
+        }
+        let fresh = (self.build)()?.build_in_memory()?;
+        #[cfg(feature = "encryption")]
+        let fresh = fresh.with_key(&crate::crypto::PakKey::new(PAK_GOLDEN_KEY))?;
+        for pak in [&golden, &fresh] {
+            check_items(pak).and_then(|_| (self.check)(pak)).map_err(|PakGoldenMismatch(reason)| mismatch(reason))?;
+        }
+        Ok(())
+    }
+}
+
+/// Verifies every golden pak in the directory that the enabled features can read.
+pub(crate) fn verify_goldens(dir : impl AsRef<Path>) -> Result<(), PakGoldenMismatch> {
+    PakGolden::all().iter().try_for_each(|golden| golden.verify(dir.as_ref()))
+}
+
+fn open_file(path : &Path) -> PakResult<Pak> {
+    Pak::new_from_file(path)
+}
+
+fn paked(mut builder : PakBuilder) -> PakResult<PakBuilder> {
+    for item in golden_items() {
+        builder.pak(item)?;
+    }
+    Ok(builder)
+}
+
+fn expect(holds : bool, what : &str) -> Result<(), PakGoldenMismatch> {
+    match holds {
+        true => Ok(()),
+        false => Err(PakGoldenMismatch(format!("expected that {what}"))),
+    }
+}
+
+/// The checks every golden pak has to pass: every item reads back by ordinal, and a set of queries match the same items they match in a
+/// [NaiveStore](crate::testing::NaiveStore).
+fn check_items(pak : &Pak) -> Result<(), PakGoldenMismatch> {
+    let items = golden_items();
+    let mut store = NaiveStore::new();
+    for item in &items {
+        store.push(item);
+    }
+    for (ordinal, item) in items.iter().enumerate() {
+        let Some(pointer) = pak.pointer_of(ordinal as u32)? else { return expect(false, &format!("item {ordinal} has a pointer")) };
+        expect(&pak.get::<PakGoldenItem>(&pointer)? == item, &format!("item {ordinal} reads back"))?;
+    }
+    let queries : Vec<Box<dyn PakQueryExpression>> = vec![
+        Box::new(PakQuery::equals("kind", "weapon")),
+        Box::new(PakQuery::equals("name", "Item 13")),
+        Box::new(PakQuery::greater_than_or_equal("weight", 10u32)),
+        Box::new(PakQuery::less_than("weight", 4u32) | PakQuery::equals("kind", "scroll")),
+        Box::new(PakQuery::equals("kind", "armor") & PakQuery::greater_than("weight", 8u32)),
+    ];
+    for query in queries {
+        expect(query_ordinals(pak, query.as_ref())? == store.query(query.as_ref()), "the queries match the same items")?;
+    }
+    Ok(())
+}
+
+//==============================================================================================
+//        PakGoldenMismatch
+//==============================================================================================
+
+/// Why a golden pak failed its checks, for the test to panic with.
+pub(crate) struct PakGoldenMismatch(String);
+
+impl From<PakError> for PakGoldenMismatch {
+    fn from(error : PakError) -> Self {
+        PakGoldenMismatch(error.to_string())
+    }
+}
+
+impl fmt::Debug for PakGoldenMismatch {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
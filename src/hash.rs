@@ -0,0 +1,49 @@
+use std::collections::{HashMap, HashSet};
+use crate::pointer::PakUntypedPointer;
+
+//==============================================================================================
+//        PakBuildHasher
+//==============================================================================================
+
+/// The hasher behind the maps a pak keeps of its indices, its chunk cache, and the content it has already paked. Without a hashing feature
+/// this is the standard library's SipHash with a random key, which stands up to keys picked to collide but visits a map in a different
+/// order every run. That order reaches the bytes of a built pak through the index map and the order the index structures are paked in,
+/// so two builds of the same items come out different. The `ahash` and `xxhash` features swap in a faster hasher with a fixed key, which
+/// makes builds byte for byte reproducible. If both are turned on, xxhash is used.
+#[cfg(feature = "xxhash")]
+pub type PakBuildHasher = std::hash::BuildHasherDefault<xxhash_rust::xxh3::Xxh3Default>;
+
+#[cfg(all(feature = "ahash", not(feature = "xxhash")))]
+pub type PakBuildHasher = PakFixedAHash;
+
+#[cfg(not(any(feature = "ahash", feature = "xxhash")))]
+pub type PakBuildHasher = std::collections::hash_map::RandomState;
+
+/// True if [PakBuildHasher] hashes the same way every run, so building the same items always gives the same bytes.
+pub const PAK_DETERMINISTIC_HASHER : bool = cfg!(any(feature = "ahash", feature = "xxhash"));
+
+pub type PakHashMap<K, V> = HashMap<K, V, PakBuildHasher>;
+
+pub type PakHashSet<T> = HashSet<T, PakBuildHasher>;
+
+/// The map from index keys to the trees that hold them, as it is stored in the header of a pak.
+pub type PakIndexMap = PakHashMap<String, PakUntypedPointer>;
+
+//==============================================================================================
+//        PakFixedAHash
+//==============================================================================================
+
+/// Builds ahash hashers from fixed seeds. The default ahash hasher takes its keys from the process whenever another crate in the build
+/// turns on its random keys, so the seeds are given here to keep the order of a map the same across runs.
+#[cfg(all(feature = "ahash", not(feature = "xxhash")))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PakFixedAHash;
+
+#[cfg(all(feature = "ahash", not(feature = "xxhash")))]
+impl std::hash::BuildHasher for PakFixedAHash {
+    type Hasher = ahash::AHasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        ahash::RandomState::with_seeds(0x7061_6b2d_6462, 0x6861_7368, 0x6d61_7073, 0x6b65_7973).build_hasher()
+    }
+}
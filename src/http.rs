@@ -0,0 +1,110 @@
+use std::io::Read;
+use crate::{error::{PakError, PakResult}, pointer::{to_usize, PakPointer}, Pak, PakSource};
+
+//==============================================================================================
+//        PakHttpSource
+//==============================================================================================
+
+/// A source that reads a pak hosted behind a URL, like a file on a CDN, with one HTTP `Range` request per read. Opening the pak only
+/// downloads its header, and a query only downloads the tree pages it walks and the items it matches, so a client never needs the whole
+/// file. Every read is a round trip, so pairing the source with [Pak::with_cache](crate::Pak::with_cache) keeps repeated queries from
+/// fetching the same pages again. Servers that ignore the `Range` header and answer with the whole file are rejected rather than
+/// downloaded from. The first `ETag` the server sends is passed back with every range request as `If-Range`, so a file that is replaced
+/// while the pak is open fails the next read instead of mixing bytes of both files.
+pub struct PakHttpSource {
+    agent : ureq::Agent,
+    url : String,
+    size : Option<Option<u64>>,
+    etag : Option<String>,
+}
+
+impl PakHttpSource {
+    pub fn new(url : impl Into<String>) -> Self {
+        Self::with_agent(ureq::Agent::new(), url)
+    }
+
+    /// Makes the requests through the agent, for timeouts, proxies or TLS settings of your own.
+    pub fn with_agent(agent : ureq::Agent, url : impl Into<String>) -> Self {
+        Self { agent, url : url.into(), size : None, etag : None }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl PakHttpSource {
+    /// Remembers the first strong `ETag` the server sends. Weak ones can't be used with `If-Range`, so they are left out.
+    fn remember_etag(&mut self, response : &ureq::Response) {
+        if self.etag.is_some() { return }
+        self.etag = response.header("ETag").filter(|etag| !etag.starts_with("W/")).map(str::to_string);
+    }
+}
+
+impl PakSource for PakHttpSource {
+    fn read(&mut self, pointer : &PakPointer, offset : u64) -> PakResult<Vec<u8>> {
+        let start = pointer.offset().checked_add(offset).ok_or(PakError::OffsetOverflow(offset))?;
+        if pointer.size() == 0 { return Ok(Vec::new()) }
+        let end = start.checked_add(pointer.size() - 1).ok_or(PakError::OffsetOverflow(start))?;
+        let mut request = self.agent.get(&self.url).set("Range", &format!("bytes={start}-{end}"));
+        if let Some(etag) = &self.etag { request = request.set("If-Range", etag) }
+        let response = request.call().map_err(|error| remote_error(&self.url, error))?;
+        if response.status() != 206 {
+            // With If-Range, a server whose file no longer has the ETag answers with the whole new file instead of the range.
+            let reason = match self.etag.is_some() && response.status() == 200 {
+                true => format!("{} changed since the pak was opened", self.url),
+                false => format!("{} answered a range request with {} instead of 206 Partial Content", self.url, response.status()),
+            };
+            return Err(PakError::RemoteError(reason))
+        }
+        let (range, total) = response.header("Content-Range").and_then(content_range).ok_or_else(|| PakError::RemoteError(format!("{} sent a partial response without a valid Content-Range", self.url)))?;
+        if range != (start, end) || total.is_some_and(|total| end >= total) {
+            return Err(PakError::RemoteError(format!("{} sent the bytes {}-{} when {start}-{end} were asked for", self.url, range.0, range.1)))
+        }
+        self.remember_etag(&response);
+        let len = to_usize(pointer.size())?;
+        // The length comes from the pointer, which may be corrupt, so nothing past the end of the file is reserved up front.
+        let known = total.or(self.size.flatten()).map_or(Ok(len), |size| to_usize(size.saturating_sub(start)))?;
+        let mut bytes = Vec::with_capacity(len.min(known));
+        response.into_reader().take(pointer.size()).read_to_end(&mut bytes)?;
+        if bytes.len() != len {
+            return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, format!("{} sent {} of the {len} bytes at {start}", self.url, bytes.len())).into())
+        }
+        Ok(bytes)
+    }
+
+    /// The size is asked for with a `HEAD` request the first time and remembered after that.
+    fn size(&mut self) -> PakResult<Option<u64>> {
+        if let Some(size) = self.size { return Ok(size) }
+        let response = self.agent.head(&self.url).call().map_err(|error| remote_error(&self.url, error))?;
+        let size = response.header("Content-Length").and_then(|len| len.parse().ok());
+        self.remember_etag(&response);
+        self.size = Some(size);
+        Ok(size)
+    }
+}
+
+/// Parses a `Content-Range` header like `bytes 0-499/1234` into the range it holds and the size of the whole file, if the server knows it.
+fn content_range(header : &str) -> Option<((u64, u64), Option<u64>)> {
+    let (range, total) = header.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, end) = range.split_once('-')?;
+    let total = match total {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+    Some(((start.parse().ok()?, end.parse().ok()?), total))
+}
+
+fn remote_error(url : &str, error : ureq::Error) -> PakError {
+    match error {
+        ureq::Error::Status(status, _) => PakError::RemoteError(format!("{url} answered with {status}")),
+        ureq::Error::Transport(transport) => PakError::RemoteError(format!("{url} couldn't be reached: {transport}")),
+    }
+}
+
+impl Pak {
+    /// Loads a Pak from a URL through a [PakHttpSource](crate::http::PakHttpSource). Only the header is downloaded up front.
+    pub fn open_url(url : impl Into<String>) -> PakResult<Self> {
+        Self::new(PakHttpSource::new(url))
+    }
+}
@@ -0,0 +1,73 @@
+use std::fmt::Display;
+use serde::{Deserialize, Serialize};
+use crate::value::PakValue;
+
+/// The reserved index key that stable ids are stored under. Keys starting with [PAK_RESERVED_PREFIX](crate::index::PAK_RESERVED_PREFIX) are reserved for the crate.
+pub const PAK_ID_KEY : &str = "__pak_id";
+
+//==============================================================================================
+//        PakId
+//==============================================================================================
+
+/// A stable id that can be given to an item when it is paked. Unlike a [PakPointer](crate::pointer::PakPointer), an id doesn't change when
+/// the pak is rebuilt, so it is safe to store in save games or reference from other paks. Look items up with [Pak::by_id](crate::Pak::by_id).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum PakId {
+    Number(u64),
+    Uuid(u128),
+}
+
+impl PakId {
+    /// Creates an id from the 128 bits of a UUID.
+    pub fn uuid(uuid : u128) -> Self {
+        PakId::Uuid(uuid)
+    }
+    
+    /// Recovers an id from the value it was indexed with.
+    pub(crate) fn from_value(value : &PakValue) -> Option<Self> {
+        match value {
+            PakValue::Uint(number) => Some(PakId::Number(*number)),
+            PakValue::String(uuid) => u128::from_str_radix(&uuid.replace('-', ""), 16).ok().map(PakId::Uuid),
+            _ => None,
+        }
+    }
+}
+
+impl Display for PakId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PakId::Number(number) => write!(f, "{number}"),
+            PakId::Uuid(uuid) => {
+                let hex = format!("{uuid:032x}");
+                write!(f, "{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+            },
+        }
+    }
+}
+
+impl From<u64> for PakId {
+    fn from(value: u64) -> Self {
+        PakId::Number(value)
+    }
+}
+
+impl From<u32> for PakId {
+    fn from(value: u32) -> Self {
+        PakId::Number(value as u64)
+    }
+}
+
+impl From<[u8; 16]> for PakId {
+    fn from(value: [u8; 16]) -> Self {
+        PakId::Uuid(u128::from_be_bytes(value))
+    }
+}
+
+impl From<PakId> for PakValue {
+    fn from(value: PakId) -> Self {
+        match value {
+            PakId::Number(number) => PakValue::Uint(number),
+            PakId::Uuid(_) => PakValue::String(value.to_string()),
+        }
+    }
+}
@@ -1,9 +1,11 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, ops::{Bound, RangeBounds}};
 use serde::{Deserialize, Serialize};
-use crate::{pointer::PakUntypedPointer, query::PakQuery, value::IntoPakValue};
+use crate::{btree::PakTree, error::PakResult, pointer::{PakPointer, PakTypedPointer, PakUntypedPointer}, query::PakQuery, value::IntoPakValue};
 
 use super::value::PakValue;
 
+pub use crate::btree::{PakDistinctIter, PakDuplicateKeys, PakIndexBuild, PakPostings, PakPostingsIter};
+
 pub type PakIndices = HashMap<PakValue, Vec<PakUntypedPointer>>;
 
 //==============================================================================================
@@ -11,6 +13,7 @@ pub type PakIndices = HashMap<PakValue, Vec<PakUntypedPointer>>;
 //==============================================================================================
 
 #[derive(PartialEq, Debug, Clone, PartialOrd, Deserialize, Serialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct PakIndex {
     pub key : String,
     pub value : PakValue
@@ -23,6 +26,23 @@ impl PakIndex {
             value: value.into_pak_value(),
         }
     }
+    
+    /// Marks the index under the key as holding [pointers to other items](crate::pointer::PakRef), so that repacks which move the items
+    /// rewrite the pointers the item stores along with the index. The [PakItem](crate::item::PakItem) derive adds one for every `ref` field.
+    pub fn reference<I>(key : I) -> Self where I : PakIndexIdentifier {
+        Self::new(crate::pointer::PAK_REF_KEY, key.identifier())
+    }
+}
+
+/// The prefix of the index keys that are reserved for the crate.
+pub const PAK_RESERVED_PREFIX : &str = "__pak_";
+
+/// Returns true if the key is reserved for the crate but isn't one of the keys the crate writes, so no item may be paked with it.
+pub(crate) fn is_reserved_key(key : &str) -> bool {
+    use crate::{content::PAK_HASH_KEY, id::PAK_ID_KEY, l10n::{PAK_L10N_KEY, PAK_LANG_KEY}, path::{PAK_MTIME_KEY, PAK_PATH_KEY, PAK_SIDECAR_KEY, PAK_SIZE_KEY}, pointer::PAK_REF_KEY, validity::{PAK_VALID_FROM_KEY, PAK_VALID_UNTIL_KEY}};
+    
+    const WRITTEN : [&str; 11] = [PAK_REF_KEY, PAK_ID_KEY, PAK_PATH_KEY, PAK_SIZE_KEY, PAK_MTIME_KEY, PAK_SIDECAR_KEY, PAK_L10N_KEY, PAK_LANG_KEY, PAK_HASH_KEY, PAK_VALID_FROM_KEY, PAK_VALID_UNTIL_KEY];
+    key.starts_with(PAK_RESERVED_PREFIX) && !WRITTEN.contains(&key)
 }
 
 //==============================================================================================
@@ -59,8 +79,102 @@ impl PakIndexIdentifier for String {
     }
 }
 
-impl <'id> PakIndexIdentifier for &'id str {
+impl PakIndexIdentifier for &str {
     fn identifier(&self) -> &str {
         self
     }
-}
\ No newline at end of file
+}
+
+//==============================================================================================
+//        PakIndexReader
+//==============================================================================================
+
+/// A read-only view of a single index inside of a [Pak](crate::Pak), created with [Pak::index](crate::Pak::index). This gives ordered access to the
+/// values of an index for when the query api isn't enough.
+///
+/// The reader upholds the following invariants:
+/// - Entries are always visited in ascending order of their value, using the [Ord](std::cmp::Ord) implementation of [PakValue](crate::value::PakValue).
+/// - Every distinct value appears exactly once, with all of the pointers that were indexed under it.
+/// - Only the pages that are needed are read from the pak, so stopping a scan early avoids the rest of the IO.
+pub struct PakIndexReader<'p> {
+    key : String,
+    tree : PakTree<'p>,
+}
+
+impl <'p> PakIndexReader<'p> {
+    pub(crate) fn new(key : &str, tree : PakTree<'p>) -> Self {
+        Self { key: key.to_string(), tree }
+    }
+    
+    /// The key of the index being read.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+    
+    /// Returns the pointers to every item indexed with exactly this value.
+    pub fn get<V>(&self, value : V) -> PakResult<Vec<PakPointer>> where V : IntoPakValue {
+        let value = value.into_pak_value();
+        let mut pointers = Vec::new();
+        self.tree.range((Bound::Included(&value), Bound::Included(&value)), |_, postings| {
+            for pointer in postings.pointers()? {
+                pointers.push(pointer?.clone().into_pointer());
+            }
+            Ok(true)
+        })?;
+        Ok(pointers)
+    }
+    
+    /// Returns true if the item at the pointer is indexed with exactly this value. Only the pages on the way to the value and the overflow
+    /// chunks a binary search lands on are read, so this stays cheap even for values that thousands of items share.
+    pub fn contains<V>(&self, value : V, pointer : &PakPointer) -> PakResult<bool> where V : IntoPakValue {
+        let value = value.into_pak_value();
+        let Some(ordinal) = crate::btree::ordinal_of(self.tree.pak().ordinals()?, pointer.offset()) else { return Ok(false) };
+        let mut found = false;
+        self.tree.range((Bound::Included(&value), Bound::Included(&value)), |_, postings| {
+            found = postings.contains_ordinal(ordinal)?;
+            Ok(false)
+        })?;
+        Ok(found)
+    }
+    
+    /// Returns every entry with a value inside of the range, in order.
+    pub fn range<R>(&self, range : R) -> PakResult<Vec<PakIndexEntry>> where R : RangeBounds<PakValue> {
+        let mut entries = Vec::new();
+        self.scan_range(range, |value, postings| {
+            entries.push(PakIndexEntry::new(value, postings.load()?));
+            Ok(true)
+        })?;
+        Ok(entries)
+    }
+    
+    /// Returns every entry in the index, in order.
+    pub fn entries(&self) -> PakResult<Vec<PakIndexEntry>> {
+        self.range(..)
+    }
+    
+    /// Calls the visitor with every value and its [PakPostings](crate::index::PakPostings) in order. Large posting lists are only read if the visitor asks for them. Return false from the visitor to stop the scan.
+    pub fn scan<F>(&self, visitor : F) -> PakResult<()> where F : FnMut(&PakValue, &PakPostings) -> PakResult<bool> {
+        self.tree.walk(visitor)
+    }
+    
+    /// Calls the visitor with every value inside of the range and its [PakPostings](crate::index::PakPostings) in order. Return false from the visitor to stop the scan.
+    pub fn scan_range<R, F>(&self, range : R, visitor : F) -> PakResult<()> where R : RangeBounds<PakValue>, F : FnMut(&PakValue, &PakPostings) -> PakResult<bool> {
+        self.tree.range((range.start_bound(), range.end_bound()), visitor)
+    }
+}
+
+/// A single value of an index and the items that were indexed with it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PakIndexEntry {
+    pub value : PakValue,
+    pub pointers : Vec<PakPointer>,
+}
+
+impl PakIndexEntry {
+    fn new(value : &PakValue, pointers : Vec<PakTypedPointer>) -> Self {
+        Self {
+            value: value.clone(),
+            pointers: pointers.into_iter().map(PakTypedPointer::into_pointer).collect(),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::{error::PakResult, pointer::PakPointer, Pak};
+
+/// The upper bounds of the size classes in a [size class report](crate::Pak::size_classes). Every class is four times as large as the one
+/// before it, and the last one holds everything larger than 1 MiB.
+pub const PAK_SIZE_CLASSES : [u64; 9] = [16, 64, 256, 1024, 4096, 16 * 1024, 64 * 1024, 256 * 1024, 1024 * 1024];
+
+//==============================================================================================
+//        PakSizeClass
+//==============================================================================================
+
+/// The items of a pak that fall into one size class, from [Pak::size_classes](crate::Pak::size_classes) or
+/// [PakBuilder::size_classes](crate::PakBuilder::size_classes). Paks that are mostly made of items in the smallest classes are the ones
+/// that gain the most from [with_inline_items](crate::PakBuilder::with_inline_items).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PakSizeClass {
+    /// The largest item that falls into the class, in bytes, or `u64::MAX` for the last class.
+    pub max_size : u64,
+    pub items : u64,
+    pub bytes : u64,
+}
+
+pub(crate) fn size_classes(sizes : impl Iterator<Item = u64>) -> Vec<PakSizeClass> {
+    let mut classes = PAK_SIZE_CLASSES.iter().chain([&u64::MAX]).map(|max_size| PakSizeClass { max_size : *max_size, items : 0, bytes : 0 }).collect::<Vec<_>>();
+    for size in sizes {
+        let class = PAK_SIZE_CLASSES.partition_point(|max_size| *max_size < size);
+        classes[class].items += 1;
+        classes[class].bytes += size;
+    }
+    classes
+}
+
+//==============================================================================================
+//        PakInlineTable
+//==============================================================================================
+
+/// The chunk the meta points to when a pak was built with [with_inline_items](crate::PakBuilder::with_inline_items), which records the
+/// largest item that was inlined. The inlined items themselves follow the tree pages whose posting lists hold them. Paks built before that
+/// listed every inlined item here as well, which is never read, since the same items are in the vault.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub(crate) struct PakInlineTable {
+    pub(crate) max_size : u64,
+    pub(crate) items : Vec<(u32, Vec<u8>)>,
+}
+
+/// The items inlined after a tree page, by ordinal, with their encryption and compression taken off.
+pub(crate) type PakPageInlined = Vec<(u32, Vec<u8>)>;
+
+/// The inlined items of an opened pak that came with the pages read so far, by their offset and size in the vault.
+pub(crate) type PakInlined = HashMap<(u64, u64), Vec<u8>>;
+
+impl Pak {
+    /// How many of the pak's items, and how many bytes of them, fall into each of the [size classes](crate::inline::PAK_SIZE_CLASSES).
+    /// Only the ordinal table is read.
+    pub fn size_classes(&self) -> PakResult<Vec<PakSizeClass>> {
+        Ok(size_classes(self.ordinals()?.iter().map(|pointer| pointer.clone().into_pointer().size())))
+    }
+
+    /// The inlined bytes of the item at the pointer, if it was inlined and a tree page that holds it has been read.
+    pub(crate) fn inlined_bytes(&self, pointer : &PakPointer) -> Option<Vec<u8>> {
+        self.inlined.borrow().get(&(pointer.offset(), pointer.size())).cloned()
+    }
+    
+    /// Keeps the items that were inlined after a tree page, so reading them later doesn't go back to the source.
+    pub(crate) fn load_inlined(&self, items : PakPageInlined) -> PakResult<()> {
+        let ordinals = self.ordinals()?;
+        let mut inlined = self.inlined.borrow_mut();
+        for (ordinal, bytes) in items {
+            let Some(pointer) = ordinals.get(ordinal as usize) else { continue };
+            let pointer = pointer.clone().into_pointer();
+            inlined.entry((pointer.offset(), pointer.size())).or_insert(bytes);
+        }
+        Ok(())
+    }
+}
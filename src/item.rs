@@ -3,14 +3,54 @@ use serde::{de::DeserializeOwned, Serialize};
 use crate::{error::PakResult, pointer::PakPointer, Pak};
 use super::index::PakIndex;
 
+#[cfg(feature = "derive")]
+pub use pak_db_derive::PakItem;
+
 //==============================================================================================
 //        PakItem Traits
 //==============================================================================================
 
 pub trait PakItemSearchable {
     fn get_indices(&self) -> Vec<PakIndex>;
+    
+    /// The index keys every item of the type is paked with, which an [index audit](crate::PakBuilder::with_index_audit) checks for. The
+    /// [PakItem](crate::item::PakItem) derive lists its `#[pak(index)]` fields here.
+    fn index_keys() -> &'static [&'static str] where Self : Sized {
+        &[]
+    }
+}
+
+/// Builds the indices of an item for a hand written [get_indices](crate::item::PakItemSearchable::get_indices), for types that can't use
+/// the [PakItem](crate::item::PakItem) derive. Each entry is either a key and the value to index under it, which is converted with
+/// [IntoPakValue](crate::value::IntoPakValue), or the name of a field, which is indexed under its own name with a clone of its value.
+///
+/// ```rust
+/// # use pak_db::{index::PakIndex, item::PakItemSearchable, pak_indices};
+/// # struct Person { first_name : String, age : u32 }
+/// impl PakItemSearchable for Person {
+///     fn get_indices(&self) -> Vec<PakIndex> {
+///         pak_indices!(self => { first_name, "years" => self.age })
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! pak_indices {
+    ($item:expr => { $($entries:tt)* }) => {
+        $crate::pak_indices!(@entries [] $item, $($entries)*)
+    };
+    (@entries [$($indices:expr),*] $item:expr $(,)?) => {{
+        let indices : ::std::vec::Vec<$crate::index::PakIndex> = ::std::vec![$($indices),*];
+        indices
+    }};
+    (@entries [$($indices:expr),*] $item:expr, $field:ident $(, $($rest:tt)*)?) => {
+        $crate::pak_indices!(@entries [$($indices,)* $crate::index::PakIndex::new(::std::stringify!($field), ::std::clone::Clone::clone(&$item.$field))] $item, $($($rest)*)?)
+    };
+    (@entries [$($indices:expr),*] $item:expr, $key:expr => $value:expr $(, $($rest:tt)*)?) => {
+        $crate::pak_indices!(@entries [$($indices,)* $crate::index::PakIndex::new($key, $value)] $item, $($($rest)*)?)
+    };
 }
 
+#[allow(clippy::wrong_self_convention)]
 pub trait PakItemSerialize {
     fn into_bytes(&self) -> PakResult<Vec<u8>>;
 }
@@ -19,7 +59,8 @@ pub trait PakItemDeserialize: Sized {
     fn from_bytes(bytes: &[u8]) -> PakResult<Self>;
     
     fn from_pak(pak : &[u8], pointer : &PakPointer) -> PakResult<Self> { 
-        let data = &pak[pointer.offset() as usize..pointer.offset() as usize + pointer.size() as usize];
+        let range = crate::pointer::byte_range(pointer.offset(), pointer.size())?;
+        let data = pak.get(range).ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof))?;
         let res = Self::from_bytes(data)?;
         Ok(res)
     }
@@ -38,6 +79,36 @@ impl <T> PakItemSerialize for T where T : Serialize {
     }
 }
 
+//==============================================================================================
+//        ErasedPakItem
+//==============================================================================================
+
+/// An object safe version of [PakItemSerialize](crate::item::PakItemSerialize) and [PakItemSearchable](crate::item::PakItemSearchable),
+/// for plugin systems that only discover their item types at runtime. It is implemented for every searchable item, and items are added with
+/// [PakBuilder::pak_dyn](crate::PakBuilder::pak_dyn).
+pub trait ErasedPakItem {
+    /// The type name the item is paked under. Items can only be read back as a type with this name.
+    fn type_name(&self) -> &'static str;
+    
+    fn erased_bytes(&self) -> PakResult<Vec<u8>>;
+    
+    fn erased_indices(&self) -> Vec<PakIndex>;
+}
+
+impl <T> ErasedPakItem for T where T : PakItemSerialize + PakItemSearchable {
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+    
+    fn erased_bytes(&self) -> PakResult<Vec<u8>> {
+        self.into_bytes()
+    }
+    
+    fn erased_indices(&self) -> Vec<PakIndex> {
+        self.get_indices()
+    }
+}
+
 //==============================================================================================
 //        PakItemDeserialzedGroup
 //==============================================================================================
@@ -52,6 +123,7 @@ impl <T> PakItemDeserializeGroup for (T, ) where T : PakItemDeserialize{
     type ReturnType = Vec<T>;
     
     fn deserialize_group(pak : &Pak, pointers : HashSet<PakPointer>) -> PakResult<Self::ReturnType> {
+        let pointers = pak.result_order(pointers);
         let values = pointers.iter().filter_map(|pointer| pak.read::<T>(pointer)).collect::<Vec<_>>();
         Ok(values)
     }
@@ -61,9 +133,10 @@ impl <T1, T2> PakItemDeserializeGroup for (T1, T2) where T1 : PakItemDeserialize
     type ReturnType = (Vec<T1>, Vec<T2>);
 
     fn deserialize_group(pak : &Pak, pointers : HashSet<PakPointer>) -> PakResult<Self::ReturnType> {
+        let pointers = pak.result_order(pointers);
         let t1 = pointers.iter().filter_map(|pointer| pak.read::<T1>(pointer)).collect::<Vec<_>>();
         let t2 = pointers.iter().filter_map(|pointer| pak.read::<T2>(pointer)).collect::<Vec<_>>();
-        return Ok((t1, t2));
+        Ok((t1, t2))
     }
 }
 
@@ -71,10 +144,11 @@ impl <T1, T2, T3> PakItemDeserializeGroup for (T1, T2, T3) where T1 : PakItemDes
     type ReturnType = (Vec<T1>, Vec<T2>, Vec<T3>);
 
     fn deserialize_group(pak : &Pak, pointers : HashSet<PakPointer>) -> PakResult<Self::ReturnType> {
+        let pointers = pak.result_order(pointers);
         let t1 = pointers.iter().filter_map(|pointer| pak.read::<T1>(pointer)).collect::<Vec<_>>();
         let t2 = pointers.iter().filter_map(|pointer| pak.read::<T2>(pointer)).collect::<Vec<_>>();
         let t3 = pointers.iter().filter_map(|pointer| pak.read::<T3>(pointer)).collect::<Vec<_>>();
-        return Ok((t1, t2, t3));
+        Ok((t1, t2, t3))
     }
 }
 
@@ -82,11 +156,12 @@ impl <T1, T2, T3, T4> PakItemDeserializeGroup for (T1, T2, T3, T4) where T1 : Pa
     type ReturnType = (Vec<T1>, Vec<T2>, Vec<T3>, Vec<T4>);
 
     fn deserialize_group(pak : &Pak, pointers : HashSet<PakPointer>) -> PakResult<Self::ReturnType> {
+        let pointers = pak.result_order(pointers);
         let t1 = pointers.iter().filter_map(|pointer| pak.read::<T1>(pointer)).collect::<Vec<_>>();
         let t2 = pointers.iter().filter_map(|pointer| pak.read::<T2>(pointer)).collect::<Vec<_>>();
         let t3 = pointers.iter().filter_map(|pointer| pak.read::<T3>(pointer)).collect::<Vec<_>>();
         let t4 = pointers.iter().filter_map(|pointer| pak.read::<T4>(pointer)).collect::<Vec<_>>();
-        return Ok((t1, t2, t3, t4));
+        Ok((t1, t2, t3, t4))
     }
 }
 
@@ -94,12 +169,13 @@ impl <T1, T2, T3, T4, T5> PakItemDeserializeGroup for (T1, T2, T3, T4, T5) where
     type ReturnType = (Vec<T1>, Vec<T2>, Vec<T3>, Vec<T4>, Vec<T5>);
 
     fn deserialize_group(pak : &Pak, pointers : HashSet<PakPointer>) -> PakResult<Self::ReturnType> {
+        let pointers = pak.result_order(pointers);
         let t1 = pointers.iter().filter_map(|pointer| pak.read::<T1>(pointer)).collect::<Vec<_>>();
         let t2 = pointers.iter().filter_map(|pointer| pak.read::<T2>(pointer)).collect::<Vec<_>>();
         let t3 = pointers.iter().filter_map(|pointer| pak.read::<T3>(pointer)).collect::<Vec<_>>();
         let t4 = pointers.iter().filter_map(|pointer| pak.read::<T4>(pointer)).collect::<Vec<_>>();
         let t5 = pointers.iter().filter_map(|pointer| pak.read::<T5>(pointer)).collect::<Vec<_>>();
-        return Ok((t1, t2, t3, t4, t5));
+        Ok((t1, t2, t3, t4, t5))
     }
 }
 
@@ -107,13 +183,14 @@ impl <T1, T2, T3, T4, T5, T6> PakItemDeserializeGroup for (T1, T2, T3, T4, T5, T
     type ReturnType = (Vec<T1>, Vec<T2>, Vec<T3>, Vec<T4>, Vec<T5>, Vec<T6>);
 
     fn deserialize_group(pak : &Pak, pointers : HashSet<PakPointer>) -> PakResult<Self::ReturnType> {
+        let pointers = pak.result_order(pointers);
         let t1 = pointers.iter().filter_map(|pointer| pak.read::<T1>(pointer)).collect::<Vec<_>>();
         let t2 = pointers.iter().filter_map(|pointer| pak.read::<T2>(pointer)).collect::<Vec<_>>();
         let t3 = pointers.iter().filter_map(|pointer| pak.read::<T3>(pointer)).collect::<Vec<_>>();
         let t4 = pointers.iter().filter_map(|pointer| pak.read::<T4>(pointer)).collect::<Vec<_>>();
         let t5 = pointers.iter().filter_map(|pointer| pak.read::<T5>(pointer)).collect::<Vec<_>>();
         let t6 = pointers.iter().filter_map(|pointer| pak.read::<T6>(pointer)).collect::<Vec<_>>();
-        return Ok((t1, t2, t3, t4, t5, t6));
+        Ok((t1, t2, t3, t4, t5, t6))
     }
 }
 
@@ -121,6 +198,7 @@ impl <T1, T2, T3, T4, T5, T6, T7> PakItemDeserializeGroup for (T1, T2, T3, T4, T
     type ReturnType = (Vec<T1>, Vec<T2>, Vec<T3>, Vec<T4>, Vec<T5>, Vec<T6>, Vec<T7>);
 
     fn deserialize_group(pak : &Pak, pointers : HashSet<PakPointer>) -> PakResult<Self::ReturnType> {
+        let pointers = pak.result_order(pointers);
         let t1 = pointers.iter().filter_map(|pointer| pak.read::<T1>(pointer)).collect::<Vec<_>>();
         let t2 = pointers.iter().filter_map(|pointer| pak.read::<T2>(pointer)).collect::<Vec<_>>();
         let t3 = pointers.iter().filter_map(|pointer| pak.read::<T3>(pointer)).collect::<Vec<_>>();
@@ -128,7 +206,7 @@ impl <T1, T2, T3, T4, T5, T6, T7> PakItemDeserializeGroup for (T1, T2, T3, T4, T
         let t5 = pointers.iter().filter_map(|pointer| pak.read::<T5>(pointer)).collect::<Vec<_>>();
         let t6 = pointers.iter().filter_map(|pointer| pak.read::<T6>(pointer)).collect::<Vec<_>>();
         let t7 = pointers.iter().filter_map(|pointer| pak.read::<T7>(pointer)).collect::<Vec<_>>();
-        return Ok((t1, t2, t3, t4, t5, t6, t7));
+        Ok((t1, t2, t3, t4, t5, t6, t7))
     }
 }
 
@@ -136,6 +214,7 @@ impl <T1, T2, T3, T4, T5, T6, T7, T8> PakItemDeserializeGroup for (T1, T2, T3, T
     type ReturnType = (Vec<T1>, Vec<T2>, Vec<T3>, Vec<T4>, Vec<T5>, Vec<T6>, Vec<T7>, Vec<T8>);
 
     fn deserialize_group(pak : &Pak, pointers : HashSet<PakPointer>) -> PakResult<Self::ReturnType> {
+        let pointers = pak.result_order(pointers);
         let t1 = pointers.iter().filter_map(|pointer| pak.read::<T1>(pointer)).collect::<Vec<_>>();
         let t2 = pointers.iter().filter_map(|pointer| pak.read::<T2>(pointer)).collect::<Vec<_>>();
         let t3 = pointers.iter().filter_map(|pointer| pak.read::<T3>(pointer)).collect::<Vec<_>>();
@@ -144,6 +223,6 @@ impl <T1, T2, T3, T4, T5, T6, T7, T8> PakItemDeserializeGroup for (T1, T2, T3, T
         let t6 = pointers.iter().filter_map(|pointer| pak.read::<T6>(pointer)).collect::<Vec<_>>();
         let t7 = pointers.iter().filter_map(|pointer| pak.read::<T7>(pointer)).collect::<Vec<_>>();
         let t8 = pointers.iter().filter_map(|pointer| pak.read::<T8>(pointer)).collect::<Vec<_>>();
-        return Ok((t1, t2, t3, t4, t5, t6, t7, t8));
+        Ok((t1, t2, t3, t4, t5, t6, t7, t8))
     }
 }
\ No newline at end of file
@@ -0,0 +1,102 @@
+use std::{any::Any, collections::HashSet, ops::Bound, sync::Arc};
+use crate::{error::{PakError, PakResult}, index::PakIndex, pointer::PakTypedPointer, query::PakQueryExpression, value::PakValue, Pak};
+
+/// A structure that a [PakIndexKind](crate::kind::PakIndexKind) decoded, which the pak keeps for every later query against the index.
+pub type PakDecodedKind = Arc<dyn Any + Send + Sync>;
+
+//==============================================================================================
+//        PakIndexKind
+//==============================================================================================
+
+/// A custom kind of index, for structures that a b-tree can't answer well, like tries, suffix arrays or spatial indices. A kind is attached to
+/// an index key with [PakBuilder::with_index_kind](crate::PakBuilder::with_index_kind), which builds the kind's structure alongside the key's
+/// tree. Paks know about the kinds they were built with, and other kinds are registered with [Pak::with_index_kind](crate::Pak::with_index_kind).
+/// Since the tree is always built too, a pak can still be queried without the kind, just without the kind's operations.
+pub trait PakIndexKind : Send + Sync {
+    /// A name that is unique to the kind. It is stored in the pak so readers know which kind built the structure.
+    fn name(&self) -> &str;
+
+    /// Builds the serialized structure from every value of the index and the ordinal of the item it belongs to.
+    fn build(&self, entries : &[(PakValue, u32)]) -> PakResult<Vec<u8>>;
+
+    /// Answers the query against the serialized structure, returning the ordinals of the matching items. Returns `None` if the kind can't answer
+    /// the query, in which case range queries fall back to the tree.
+    fn execute(&self, data : &[u8], query : &PakKindQuery) -> PakResult<Option<Vec<u32>>>;
+
+    /// Decodes the serialized structure once, so that a pak can keep it and answer every later query on the index with
+    /// [execute_decoded](crate::kind::PakIndexKind::execute_decoded). Kinds whose structures are costly to decode should override both.
+    /// By default the serialized bytes are kept as they are.
+    fn decode(&self, data : &[u8]) -> PakResult<PakDecodedKind> {
+        Ok(Arc::new(data.to_vec()))
+    }
+
+    /// Answers the query against a structure returned by [decode](crate::kind::PakIndexKind::decode), the same way as [execute](crate::kind::PakIndexKind::execute).
+    fn execute_decoded(&self, decoded : &(dyn Any + Send + Sync), query : &PakKindQuery) -> PakResult<Option<Vec<u32>>> {
+        match decoded.downcast_ref::<Vec<u8>>() {
+            Some(data) => self.execute(data, query),
+            None => Ok(None),
+        }
+    }
+
+    /// Checks the query directly against a single value, without the structure. This is how the [NaiveStore](crate::testing::NaiveStore) answers queries.
+    fn matches(&self, query : &PakKindQuery, value : &PakValue) -> bool {
+        match query {
+            PakKindQuery::Range(lower, upper) => crate::btree::range_contains(&(lower.as_ref(), upper.as_ref()), value),
+            PakKindQuery::Operation(_, _) => false,
+        }
+    }
+}
+
+/// A query that is handed to a [PakIndexKind](crate::kind::PakIndexKind).
+#[derive(Debug, Clone, PartialEq)]
+pub enum PakKindQuery {
+    /// Every value inside of the range. Regular [PakQuery](crate::query::PakQuery) queries are handed to the kind this way.
+    Range(Bound<PakValue>, Bound<PakValue>),
+    /// An operation that only the kind understands, like a substring search, along with its argument.
+    Operation(String, PakValue),
+}
+
+//==============================================================================================
+//        PakCustomQuery
+//==============================================================================================
+
+/// A query that runs an operation of a [PakIndexKind](crate::kind::PakIndexKind). It fails with
+/// [PakError::UnsupportedIndexOperation](crate::error::PakError::UnsupportedIndexOperation) if the index wasn't built with the kind.
+pub struct PakCustomQuery {
+    key : String,
+    kind : Arc<dyn PakIndexKind>,
+    query : PakKindQuery,
+}
+
+impl PakCustomQuery {
+    pub fn new(key : &str, kind : Arc<dyn PakIndexKind>, query : PakKindQuery) -> Self {
+        Self { key : key.to_string(), kind, query }
+    }
+
+    fn unsupported(&self) -> PakError {
+        let operation = match &self.query {
+            PakKindQuery::Range(_, _) => "range",
+            PakKindQuery::Operation(operation, _) => operation,
+        };
+        PakError::UnsupportedIndexOperation(self.key.clone(), self.kind.name().to_string(), operation.to_string())
+    }
+}
+
+impl PakQueryExpression for PakCustomQuery {
+    fn execute(&self, pak : &Pak) -> PakResult<HashSet<PakTypedPointer>> {
+        let Some(tree) = pak.query_tree(&self.key)? else { return Ok(HashSet::new()) };
+        let Some((name, pointer)) = tree.custom() else { return Err(self.unsupported()) };
+        if name != self.kind.name() { return Err(self.unsupported()) }
+        let Some(ordinals) = pak.execute_kind(&self.key, self.kind.as_ref(), pointer, &self.query)? else { return Err(self.unsupported()) };
+        resolve_ordinals(pak, ordinals)
+    }
+
+    fn matches(&self, indices : &[PakIndex]) -> bool {
+        indices.iter().any(|index| index.key == self.key && self.kind.matches(&self.query, &index.value))
+    }
+}
+
+pub(crate) fn resolve_ordinals(pak : &Pak, ordinals : Vec<u32>) -> PakResult<HashSet<PakTypedPointer>> {
+    let table = pak.ordinals()?;
+    Ok(ordinals.into_iter().filter_map(|ordinal| table.get(ordinal as usize).cloned()).collect())
+}
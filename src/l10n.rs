@@ -0,0 +1,71 @@
+use std::collections::HashSet;
+use crate::{error::{PakError, PakResult}, index::PakIndex, item::{PakItemDeserialize, PakItemSerialize}, pointer::PakPointer, value::PakValue, Pak, PakBuilder};
+
+/// The reserved index key that localized strings are stored under, with their key and language joined into one value.
+pub const PAK_L10N_KEY : &str = "__pak_l10n";
+
+/// The reserved index key that the language of each localized string is stored under.
+pub const PAK_LANG_KEY : &str = "__pak_lang";
+
+/// Joins a string key and a language into the value they are indexed with. The separator is a control character, so no real key or
+/// language tag can make two pairs collide.
+fn compound(key : &str, lang : &str) -> String {
+    format!("{key}\u{1f}{lang}")
+}
+
+//==============================================================================================
+//        PakLocalizedText
+//==============================================================================================
+
+/// A localized string, paked as its UTF-8 bytes with no encoding around them. Add them with [PakBuilder::pak_l10n](crate::PakBuilder::pak_l10n)
+/// and look them up with [Pak::localize](crate::Pak::localize).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PakLocalizedText(pub String);
+
+impl PakItemSerialize for PakLocalizedText {
+    fn into_bytes(&self) -> PakResult<Vec<u8>> {
+        Ok(self.0.as_bytes().to_vec())
+    }
+}
+
+impl PakItemDeserialize for PakLocalizedText {
+    fn from_bytes(bytes : &[u8]) -> PakResult<Self> {
+        let text = String::from_utf8(bytes.to_vec()).map_err(|error| Box::new(bincode::ErrorKind::InvalidUtf8Encoding(error.utf8_error())))?;
+        Ok(PakLocalizedText(text))
+    }
+}
+
+impl PakBuilder {
+    /// Adds the text of a string key in one language, like `("menu.start", "fr", "Commencer")`. Every key can be given once per language,
+    /// and paking the same pair twice fails with [PakError::DuplicateLocalization](crate::error::PakError::DuplicateLocalization).
+    pub fn pak_l10n(&mut self, key : &str, lang : &str, text : &str) -> PakResult<PakPointer> {
+        let value = compound(key, lang);
+        if !self.localized.insert(value.clone()) { return Err(PakError::DuplicateLocalization(key.to_string(), lang.to_string())) }
+        let indices = vec![PakIndex::new(PAK_L10N_KEY, value), PakIndex::new(PAK_LANG_KEY, lang)];
+        self.pak_internal::<PakLocalizedText>(text.as_bytes().to_vec(), indices)
+    }
+}
+
+impl Pak {
+    /// Looks up the text of a string key, trying each language of the fallback chain in order, like `&["fr-CA", "fr", "en"]`. Returns
+    /// `None` if the key has no text in any of them.
+    pub fn localize(&self, key : &str, fallback_chain : &[&str]) -> PakResult<Option<String>> {
+        if !self.fetch_indices()?.contains_key(PAK_L10N_KEY) { return Ok(None) }
+        let tree = self.get_tree(PAK_L10N_KEY)?;
+        for lang in fallback_chain {
+            let Some(pointer) = tree.get(&compound(key, lang).into())?.into_iter().next() else { continue };
+            return Ok(Some(self.get::<PakLocalizedText>(&pointer.into_pointer())?.0))
+        }
+        Ok(None)
+    }
+
+    /// Every language the pak has localized strings in.
+    pub fn languages(&self) -> PakResult<HashSet<String>> {
+        if !self.fetch_indices()?.contains_key(PAK_LANG_KEY) { return Ok(HashSet::new()) }
+        self.distinct(PAK_LANG_KEY)?.filter_map(|value| match value {
+            Ok(PakValue::String(lang)) => Some(Ok(lang)),
+            Ok(_) => None,
+            Err(error) => Some(Err(error)),
+        }).collect()
+    }
+}
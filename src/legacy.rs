@@ -0,0 +1,97 @@
+use std::collections::{BTreeMap, HashMap};
+use serde::{Deserialize, Serialize};
+use crate::{error::PakResult, pointer::{to_usize, PakTypedPointer, PakUntypedPointer}, value::PakValue, Pak, PakBuilder, PakVaultReference};
+
+//==============================================================================================
+//        Legacy Driver
+//==============================================================================================
+
+// Paks written by pak-db 0.1 have the same layout as V1, but their meta stops at the author, which makes them revision 0, and their index
+// trees hold pointers to the items instead of ordinals. They have no ordinal table either, so the items the trees point at are given
+// ordinals in the order they sit in the vault, the first time the ordinals are needed. Items that were paked without any indices can't be
+// found that way, so they have no ordinal, but the pointers handed out for them still read them.
+
+/// A typed pointer as pak-db 0.1 wrote it, before pointers carried a generation.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PakLegacyPointer {
+    offset : u64,
+    size : u64,
+    type_name : String,
+}
+
+impl PakLegacyPointer {
+    pub(crate) fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+/// The metadata of an index tree as pak-db 0.1 wrote it.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PakLegacyTreeMeta {
+    pub(crate) pages : HashMap<usize, PakUntypedPointer>,
+}
+
+/// A page of an index tree as pak-db 0.1 wrote it.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PakLegacyTreePage {
+    pub(crate) values : Vec<PakLegacyTreePageEntry>,
+    pub(crate) next : Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PakLegacyTreePageEntry {
+    pub(crate) key : PakValue,
+    pub(crate) values : Vec<PakLegacyPointer>,
+    pub(crate) previous : Option<usize>,
+}
+
+/// Reads the metadata of the tree at the pointer.
+pub(crate) fn tree_meta(pak : &Pak, pointer : PakUntypedPointer) -> PakResult<PakLegacyTreeMeta> {
+    Ok(bincode::deserialize(&pak.read_bytes(&pointer.as_pointer())?)?)
+}
+
+/// Reads the tree page at the pointer.
+pub(crate) fn tree_page(pak : &Pak, pointer : PakUntypedPointer) -> PakResult<PakLegacyTreePage> {
+    Ok(bincode::deserialize(&pak.read_bytes(&pointer.as_pointer())?)?)
+}
+
+/// Every item the index trees point at, in the order they sit in the vault, which is the ordinal table of the pak. Every tree page is read
+/// once to find them.
+pub(crate) fn ordinals(pak : &Pak) -> PakResult<Vec<PakTypedPointer>> {
+    let mut items = BTreeMap::new();
+    for pointer in pak.fetch_indices()?.values() {
+        for page in tree_meta(pak, *pointer)?.pages.into_values() {
+            for entry in tree_page(pak, page)?.values {
+                for item in entry.values {
+                    items.entry(item.offset).or_insert_with(|| PakTypedPointer::new(item.offset, item.size, &item.type_name));
+                }
+            }
+        }
+    }
+    Ok(items.into_values().collect())
+}
+
+/// The offset of the first index structure in the vault. Pak-db 0.1 paked every tree after the items, so everything before it is an item.
+pub(crate) fn items_end(pak : &Pak) -> PakResult<u64> {
+    let mut end = pak.layout.vault_len;
+    for pointer in pak.fetch_indices()?.values() {
+        end = end.min(pointer.as_pointer().offset());
+        end = tree_meta(pak, *pointer)?.pages.values().map(|page| page.as_pointer().offset()).fold(end, u64::min);
+    }
+    Ok(end)
+}
+
+/// Puts the pak back together in a builder at the current revision. The items keep their bytes and their offsets, so every pointer that
+/// was handed out for them stays valid, including the ones to items without indices, and the trees are built again from their entries.
+pub(crate) fn rebuild(pak : &Pak) -> PakResult<PakBuilder> {
+    let items = pak.ordinals()?;
+    let indices = pak.item_indices()?;
+    let end = items_end(pak)?;
+    let mut vault = pak.read_vault()?;
+    vault.truncate(to_usize(end)?);
+    let mut builder = PakBuilder::new().with_name(pak.name()).with_description(pak.description()).with_author(pak.author());
+    builder.vault = vault;
+    builder.size_in_bytes = end;
+    builder.chunks = items.iter().zip(indices).map(|(pointer, indices)| PakVaultReference { pointer : pointer.clone().with_generation(None), indices }).collect();
+    Ok(builder)
+}
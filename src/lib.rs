@@ -1,27 +1,103 @@
 #![doc = include_str!("../README.md")]
 #![doc(html_logo_url = "https://raw.githubusercontent.com/MrVintage710/pak/refs/heads/main/docs/icon.png")]
 
-use std::{cell::RefCell, collections::HashMap, fmt::Debug, fs::{self, File}, io::{BufReader, Cursor, Read, Seek, SeekFrom}, path::Path};
-use btree::{PakTree, PakTreeBuilder};
-use index::PakIndex;
-use item::{PakItemDeserialize, PakItemDeserializeGroup, PakItemSearchable, PakItemSerialize};
-use meta::{PakMeta, PakSizing};
-use pointer::{PakPointer, PakTypedPointer, PakUntypedPointer};
-use query::PakQueryExpression;
+use std::{cell::{Cell, OnceCell, RefCell}, collections::{HashMap, HashSet}, fmt::Debug, fs::{self, File}, io::{BufReader, Cursor, Read, Seek, SeekFrom, Write}, path::Path, sync::Arc};
+use aggregate::{Aggregate, PakHistogram};
+use envelope::{PakEnvelope, PakVersioned};
+use btree::{PakDuplicateKeys, PakIndexBuild, PakTree, PakTreeBuilder, PakTreeBulkLoader};
+use id::{PakId, PAK_ID_KEY};
+use path::{normalize_path, PakBlob, PAK_PATH_KEY, PAK_SIDECAR_KEY};
+use index::{PakIndex, PakIndexReader};
+use kind::PakIndexKind;
+use item::{ErasedPakItem, PakItemDeserialize, PakItemDeserializeGroup, PakItemSearchable, PakItemSerialize};
+use format::{PakFormat, PakLayout};
+use hash::{PakHashMap, PakIndexMap};
+use meta::{PakBlockChecksums, PakCompression, PakEncoding, PakManifestEntry, PakMeta};
+use pointer::{PakPointer, PakTypedPointer};
+use query::{PakQueryExpression, PakUnknownKeys};
+use schema::{PakSchema, PakSchemaDescriptor};
+use recover::PakSalvage;
+use value::{PakValue, PakValueKind};
+use window::PakWindow;
 
 use crate::error::PakResult;
 
 #[cfg(test)]
 mod test;
 
+extern crate self as pak_db;
+
 pub mod meta;
+pub mod format;
 pub mod item;
 pub mod index;
 pub mod value;
 pub(crate) mod btree;
+pub(crate) mod sort;
+pub(crate) mod legacy;
+#[cfg(feature = "roaring")]
+pub(crate) mod bitmap;
 pub mod query;
 pub mod error;
 pub mod pointer;
+pub mod aggregate;
+pub mod id;
+pub mod inline;
+pub mod l10n;
+pub mod validity;
+pub mod path;
+pub mod plan;
+pub mod set;
+pub mod snapshot;
+pub mod diff;
+pub mod convert;
+pub mod direct;
+pub mod replay;
+pub mod window;
+pub mod cache;
+pub mod content;
+pub mod hash;
+pub mod coverage;
+pub mod access;
+pub mod audit;
+pub mod budget;
+pub mod recover;
+pub mod results;
+pub mod scan;
+pub mod testing;
+#[cfg(test)]
+pub(crate) mod golden;
+pub mod schema;
+pub mod envelope;
+pub mod foreign;
+pub mod kind;
+pub mod suffix;
+pub mod ngram;
+pub mod text;
+#[cfg(feature = "encryption")]
+pub mod crypto;
+#[cfg(feature = "async")]
+pub mod stream;
+#[cfg(feature = "fuse")]
+pub mod vfs;
+#[cfg(any(feature = "zip", feature = "tar"))]
+pub mod archive;
+#[cfg(feature = "serve")]
+pub mod serve;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+#[cfg(any(feature = "parallel", feature = "async"))]
+pub(crate) mod fork;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "bench")]
+pub mod bench;
+#[cfg(feature = "proptest")]
+pub mod strategy;
 
 //==============================================================================================
 //        Pak File
@@ -29,40 +105,982 @@ pub mod pointer;
 
 /// Represents a Pak file. This struct provides access to the metadata and data stored within the Pak file.
 pub struct Pak {
-    sizing : PakSizing,
+    layout : PakLayout,
     meta : PakMeta,
-    source : RefCell<Box<dyn PakSource>>
+    source : RefCell<Box<dyn PakSource>>,
+    indices : OnceCell<PakIndexMap>,
+    ordinals : OnceCell<Vec<PakTypedPointer>>,
+    inlined : RefCell<inline::PakInlined>,
+    ids : OnceCell<HashMap<u64, PakId>>,
+    item_paths : OnceCell<HashMap<u64, String>>,
+    kinds : HashMap<String, Arc<dyn PakIndexKind>>,
+    decoded_kinds : RefCell<HashMap<String, kind::PakDecodedKind>>,
+    checksum_retries : u32,
+    ordered_results : bool,
+    unknown_keys : PakUnknownKeys,
+    cache : RefCell<cache::PakChunkCache>,
+    recorder : RefCell<Option<access::PakAccessRecorder>>,
+    budget : RefCell<Option<budget::PakBudgetMeter>>,
+    priority : Cell<cache::PakPriority>,
+    scheduler : Arc<cache::PakReadScheduler>,
+    #[cfg(any(feature = "parallel", feature = "async"))]
+    forks : Option<Arc<fork::PakSourceFactory>>,
+    #[cfg(feature = "parallel")]
+    parallel_threads : Option<usize>,
+    #[cfg(feature = "encryption")]
+    cipher : Option<crypto::PakCipher>,
+    #[cfg(feature = "encryption")]
+    item_ciphers : HashMap<String, crypto::PakCipher>,
 }
 
 impl Pak {
     /// Creates a new Pak instance from a [PakSource](crate::PakSource).
+    /// Any [PakFormat](crate::format::PakFormat) can be opened, and the format is read from the start of the source.
     pub fn new<S>(mut source : S) -> PakResult<Self> where S : PakSource + 'static {
-        let sizing_pointer = PakPointer::new_untyped(0, 24);
-        let sizing_buffer = source.read(&sizing_pointer, 0)?;
-        let sizing : PakSizing = bincode::deserialize(&sizing_buffer)?;
+        let (meta, layout) = PakFormat::detect(&mut source)?.driver().open(&mut source)?;
+        Ok(Self::from_parts(layout, meta, source))
+    }
+    
+    pub(crate) fn from_parts<S>(layout : PakLayout, meta : PakMeta, source : S) -> Self where S : PakSource + 'static {
+        Self {
+            layout,
+            meta,
+            source : RefCell::new(Box::new(source)),
+            indices : OnceCell::new(),
+            ordinals : OnceCell::new(),
+            inlined : RefCell::new(inline::PakInlined::new()),
+            ids : OnceCell::new(),
+            item_paths : OnceCell::new(),
+            kinds : HashMap::new(),
+            decoded_kinds : RefCell::new(HashMap::new()),
+            checksum_retries : 2,
+            ordered_results : false,
+            unknown_keys : PakUnknownKeys::default(),
+            cache : RefCell::new(cache::PakChunkCache::default()),
+            recorder : RefCell::new(None),
+            budget : RefCell::new(None),
+            priority : Cell::new(cache::PakPriority::default()),
+            scheduler : Arc::default(),
+            #[cfg(any(feature = "parallel", feature = "async"))]
+            forks : None,
+            #[cfg(feature = "parallel")]
+            parallel_threads : None,
+            #[cfg(feature = "encryption")]
+            cipher : None,
+            #[cfg(feature = "encryption")]
+            item_ciphers : HashMap::new(),
+        }.with_index_kind(suffix::PakSuffixIndex).with_index_kind(ngram::PakTrigramIndex).with_index_kind(text::PakTextIndex::default())
+    }
+    
+    /// Loads an encrypted Pak from the file path, failing with [PakError::WrongKey](crate::error::PakError::WrongKey) if it wasn't encrypted with the key.
+    #[cfg(feature = "encryption")]
+    pub fn open_with_key<P>(path : P, key : &crypto::PakKey) -> PakResult<Self> where P : AsRef<Path> {
+        Self::new_from_file(path)?.with_key(key)
+    }
+    
+    /// Loads an encrypted Pak from any source, like [new](crate::Pak::new) followed by [with_key](crate::Pak::with_key).
+    #[cfg(feature = "encryption")]
+    pub fn new_encrypted<S>(source : S, key : &crypto::PakKey) -> PakResult<Self> where S : PakSource + 'static {
+        Self::new(source)?.with_key(key)
+    }
+    
+    /// Loads a Pak that was encrypted with a password. The key is derived from the password with the Argon2id parameters stored in the header.
+    #[cfg(feature = "encryption")]
+    pub fn open_with_password<P>(path : P, password : &str) -> PakResult<Self> where P : AsRef<Path> {
+        Self::new_from_file(path)?.with_password(password)
+    }
+    
+    /// Unlocks an encrypted pak with the key. Paks that aren't encrypted are returned as they are.
+    #[cfg(feature = "encryption")]
+    pub fn with_key(mut self, key : &crypto::PakKey) -> PakResult<Self> {
+        if let Some(encryption) = &self.meta.encryption {
+            self.cipher = Some(crypto::PakCipher::unlock(key, encryption)?);
+        }
+        Ok(self)
+    }
+    
+    /// Unlocks an encrypted pak with a password, failing with [PakError::WrongKey](crate::error::PakError::WrongKey) if the pak wasn't encrypted with one.
+    #[cfg(feature = "encryption")]
+    pub fn with_password(self, password : &str) -> PakResult<Self> {
+        let Some(kdf) = self.meta.encryption.as_ref().and_then(|encryption| encryption.kdf.clone()) else { return Err(error::PakError::WrongKey) };
+        let key = crypto::PakKey::derive(password, &kdf)?;
+        self.with_key(&key)
+    }
+    
+    /// Gives the pak the key that some of its items were encrypted with by [PakBuilder::pak_encrypted](crate::PakBuilder::pak_encrypted).
+    /// Fails with [PakError::WrongKey](crate::error::PakError::WrongKey) if the key doesn't match, and with
+    /// [PakError::KeyRequired](crate::error::PakError::KeyRequired) if no item was encrypted with the key id.
+    #[cfg(feature = "encryption")]
+    pub fn with_item_key(mut self, key_id : &str, key : &crypto::PakKey) -> PakResult<Self> {
+        let encryption = self.meta.protection.as_ref().and_then(|protection| protection.keys.iter().find(|(id, _)| id == key_id));
+        let Some((_, encryption)) = encryption else { return Err(error::PakError::KeyRequired(key_id.to_string())) };
+        let cipher = crypto::PakCipher::unlock(key, encryption)?;
+        self.item_ciphers.insert(key_id.to_string(), cipher);
+        Ok(self)
+    }
+    
+    /// Writes a copy of this encrypted pak to the path with its vault encrypted under a new key, for rotating keys. Every chunk keeps its
+    /// offset and size, so the layout and the indices are untouched, and items protected with item keys keep them. The pak has to be
+    /// unlocked with its current key first.
+    #[cfg(feature = "encryption")]
+    pub fn repack_with_key(&self, path : impl AsRef<Path>, key : &crypto::PakKey) -> PakResult<()> {
+        self.repack_encrypted(path.as_ref(), key, None)
+    }
+    
+    /// Like [repack_with_key](crate::Pak::repack_with_key), with a key derived from a new password.
+    #[cfg(feature = "encryption")]
+    pub fn repack_with_password(&self, path : impl AsRef<Path>, password : &str) -> PakResult<()> {
+        self.repack_with_password_kdf(path, password, meta::PakKdf::new()?)
+    }
+    
+    /// Like [repack_with_password](crate::Pak::repack_with_password), with custom Argon2id costs.
+    #[cfg(feature = "encryption")]
+    pub fn repack_with_password_kdf(&self, path : impl AsRef<Path>, password : &str, kdf : meta::PakKdf) -> PakResult<()> {
+        let key = crypto::PakKey::derive(password, &kdf)?;
+        self.repack_encrypted(path.as_ref(), &key, Some(kdf))
+    }
+    
+    #[cfg(feature = "encryption")]
+    fn repack_encrypted(&self, path : &Path, key : &crypto::PakKey, kdf : Option<meta::PakKdf>) -> PakResult<()> {
+        let Some(old) = &self.cipher else { return Err(error::PakError::MissingKey) };
+        let new = crypto::PakCipher::new(key, crypto::random_salt()?, old.algorithm());
         
-        let meta_pointer = PakPointer::new_untyped(24, sizing.meta_size);
-        let meta_buffer = source.read(&meta_pointer, 0)?;
-        let meta : PakMeta = bincode::deserialize(&meta_buffer)?;
-
-        Ok(Self { sizing, source : RefCell::new(Box::new(source)), meta })
+        // The vault doesn't record where its chunks are, so they are found by walking everything that points into it.
+        let mut chunks = std::collections::BTreeMap::new();
+        chunks.insert(self.meta.ordinals.as_pointer().offset(), self.meta.ordinals.as_pointer().size());
+        chunks.extend(self.meta.manifest.map(|pointer| (pointer.as_pointer().offset(), pointer.as_pointer().size())));
+        chunks.extend(self.meta.inlined.map(|pointer| (pointer.as_pointer().offset(), pointer.as_pointer().size())));
+        chunks.extend(self.ordinals()?.iter().map(|pointer| (pointer.offset(), pointer.clone().into_pointer().size())));
+        for (key, pointer) in self.fetch_indices()? {
+            chunks.insert(pointer.as_pointer().offset(), pointer.as_pointer().size());
+            chunks.extend(self.get_tree(key)?.chunks()?.into_iter().map(|pointer| (pointer.as_pointer().offset(), pointer.as_pointer().size())));
+        }
+        
+        let mut vault = self.read_vault()?;
+        if chunks.values().sum::<u64>() != self.layout.vault_len {
+            return Err(error::PakError::InvalidHeader("vault".to_string(), "some of its chunks can't be reached from the header".to_string()))
+        }
+        for (offset, size) in chunks {
+            let range = pointer::byte_range(offset, size)?;
+            let sealed = new.seal(offset, &old.open(offset, &vault[range.clone()])?)?;
+            vault[range].copy_from_slice(&sealed);
+        }
+        
+        let mut meta = self.meta.clone();
+        meta.encryption = Some(new.encryption(kdf)?);
+        if let Some(checksums) = &meta.checksums {
+            meta.checksums = Some(PakBlockChecksums::build(&vault, checksums.block_size));
+        }
+        if meta.item_checksums.is_some() {
+            let items = self.ordinals()?.iter().map(|pointer| (pointer.offset(), pointer.clone().into_pointer().size()));
+            meta.item_checksums = Some(meta::PakItemChecksums::build(&vault, items)?);
+        }
+        if meta.vault_hash.is_some() { meta.vault_hash = Some(PakMeta::hash_vault(&vault)) }
+        let (out, _) = self.layout.format.driver().write(&meta, self.fetch_indices()?, &vault)?;
+        write_atomic(path, &out)
+    }
+    
+    /// The hash and size of every item, indexed by ordinal, or `None` if the pak was built without
+    /// [with_manifest](crate::PakBuilder::with_manifest).
+    pub fn manifest_hashes(&self) -> PakResult<Option<Vec<PakManifestEntry>>> {
+        self.meta.manifest.map(|pointer| self.read_err(&pointer.as_pointer())).transpose()
+    }
+    
+    /// Streams the whole vault from the source and checks it against the hash the pak was built with, for validating a download before
+    /// anything is read from it. The vault holds every item and index structure, and the rest of the file is checked as it is parsed.
+    /// Fails with [PakError::VaultHashMismatch](crate::error::PakError::VaultHashMismatch) if any of it changed, and with
+    /// [PakError::MissingVaultHash](crate::error::PakError::MissingVaultHash) if the pak wasn't built
+    /// [with_vault_hash](crate::PakBuilder::with_vault_hash).
+    pub fn verify(&self) -> PakResult<()> {
+        use sha2::Digest;
+        
+        let Some(expected) = self.meta.vault_hash else { return Err(error::PakError::MissingVaultHash) };
+        let mut hasher = sha2::Sha256::new();
+        let mut offset = 0;
+        while offset < self.layout.vault_len {
+            let size = (self.layout.vault_len - offset).min(VERIFY_CHUNK_SIZE);
+            hasher.update(self.source.borrow_mut().read(&PakPointer::new_untyped(offset, size), self.get_vault_start())?);
+            offset += size;
+        }
+        if <[u8; 32]>::from(hasher.finalize()) != expected { return Err(error::PakError::VaultHashMismatch) }
+        Ok(())
+    }
+    
+    /// Returns true if the vault of the pak is encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.meta.encryption.is_some()
+    }
+    
+    /// The cipher the vault is encrypted with, if it is. See [with_cipher_algorithm](crate::PakBuilder::with_cipher_algorithm).
+    pub fn cipher_algorithm(&self) -> Option<meta::PakCipherAlgorithm> {
+        self.meta.encryption.as_ref().map(|encryption| encryption.algorithm)
+    }
+    
+    /// How the items of the pak are compressed, if they are. See [with_compression](crate::PakBuilder::with_compression).
+    pub fn compression(&self) -> Option<PakCompression> {
+        self.meta.compression
+    }
+    
+    /// How the index structures of the pak are compressed, if they are. See [with_index_compression](crate::PakBuilder::with_index_compression).
+    pub fn index_compression(&self) -> Option<PakCompression> {
+        self.meta.index_compression
+    }
+    
+    /// Sets how many more times a block of the vault is read when it doesn't match its checksum, which defaults to 2. This only applies to
+    /// paks built with [with_block_checksums](crate::PakBuilder::with_block_checksums).
+    pub fn with_checksum_retries(mut self, retries : u32) -> Self {
+        self.checksum_retries = retries;
+        self
+    }
+    
+    /// Returns the items of every [query](crate::Pak::query) in the order they sit in the pak, instead of the arbitrary order the matches
+    /// are collected in, which changes from run to run. This makes query results reproducible for snapshot tests and replays, at the cost
+    /// of sorting the matches.
+    pub fn with_ordered_results(mut self) -> Self {
+        self.ordered_results = true;
+        self
+    }
+    
+    /// Sets what queries do when they compare against a key the pak has no index for. By default they match nothing, see
+    /// [PakUnknownKeys](crate::query::PakUnknownKeys).
+    pub fn with_unknown_keys(mut self, unknown_keys : PakUnknownKeys) -> Self {
+        self.unknown_keys = unknown_keys;
+        self
+    }
+    
+    /// Keeps the most recently read items and tree pages in memory, up to `capacity` bytes. The cache is off by default.
+    pub fn with_cache(self, capacity : u64) -> Self {
+        self.cache.replace(cache::PakChunkCache::new(capacity));
+        self
+    }
+    
+    /// Saves which chunks are in the cache right now, so a later session can prefetch them with [load_cache_state](crate::Pak::load_cache_state).
+    pub fn save_cache_state(&self, path : impl AsRef<Path>) -> PakResult<()> {
+        let state = cache::PakCacheState { generation : self.meta.generation, size : self.size(), chunks : self.cache.borrow().hot() };
+        write_atomic(path.as_ref(), &bincode::serialize(&state)?)
+    }
+    
+    /// Reads the chunks of a state saved with [save_cache_state](crate::Pak::save_cache_state) into the cache, most recently used last so they
+    /// keep their order, and returns how many were read. A state that was saved for a different build of the pak is ignored. The cache has to
+    /// be turned on with [with_cache](crate::Pak::with_cache) first, and chunks that no longer fit in it are skipped.
+    pub fn load_cache_state(&self, path : impl AsRef<Path>) -> PakResult<usize> {
+        let state : cache::PakCacheState = bincode::deserialize(&fs::read(path)?)?;
+        if state.generation != self.meta.generation || state.size != self.size() { return Ok(0) }
+        let mut loaded = 0;
+        for (offset, size) in state.chunks.into_iter().rev() {
+            if offset.checked_add(size).is_none_or(|end| end > self.layout.vault_len) { continue }
+            // Chunks that can't be read, like items whose key hasn't been given, are left to fail when they are asked for.
+            if self.read_bytes(&PakPointer::new_untyped(offset, size)).is_ok() { loaded += 1 }
+        }
+        Ok(loaded)
+    }
+    
+    /// Registers a custom [PakIndexKind](crate::kind::PakIndexKind), so queries against indices that were built with it are answered by it.
+    /// Paks returned by a [PakBuilder](crate::PakBuilder) already know the kinds they were built with.
+    pub fn with_index_kind(mut self, kind : impl PakIndexKind + 'static) -> Self {
+        self.register_index_kind(Arc::new(kind));
+        self
+    }
+    
+    fn register_index_kind(&mut self, kind : Arc<dyn PakIndexKind>) {
+        self.kinds.insert(kind.name().to_string(), kind);
+    }
+    
+    /// Answers the query with the structure the kind built for the key. The structure is read and decoded by the kind the first time the key
+    /// is queried, and kept for the life of the pak.
+    pub(crate) fn execute_kind(&self, key : &str, kind : &dyn PakIndexKind, pointer : pointer::PakUntypedPointer, query : &kind::PakKindQuery) -> PakResult<Option<Vec<u32>>> {
+        let cached = self.decoded_kinds.borrow().get(key).cloned();
+        let decoded = match cached {
+            Some(decoded) => decoded,
+            None => {
+                let decoded = kind.decode(&self.read_bytes(&pointer.as_pointer())?)?;
+                self.decoded_kinds.borrow_mut().insert(key.to_string(), decoded.clone());
+                decoded
+            },
+        };
+        kind.execute_decoded(decoded.as_ref(), query)
+    }
+    
+    /// Returns the registered index kind with the name, if there is one.
+    pub(crate) fn index_kind_named(&self, name : &str) -> Option<&dyn PakIndexKind> {
+        self.kinds.get(name).map(|kind| kind.as_ref())
+    }
+    
+    /// Loads a Pak from the file through a [PakDirectSource](crate::direct::PakDirectSource), bypassing the operating system's page cache.
+    pub fn open_direct<P>(path : P) -> PakResult<Self> where P : AsRef<Path> {
+        Self::new(direct::PakDirectSource::open(path)?)
     }
     
     /// Loads a Pak from the specified file path. This will not load the entire pak file into memory, just the header.
     pub fn new_from_file<P>(path : P) -> PakResult<Self> where P : AsRef<Path> {
-        let file = File::open(path)?;
+        let file = File::open(path.as_ref())?;
+        #[cfg(any(feature = "parallel", feature = "async"))]
+        return Self::from_shared_file(file);
+        #[cfg(not(any(feature = "parallel", feature = "async")))]
         Self::new(BufReader::new(file))
     }
     
+    /// Reads a damaged or truncated pak file and salvages whatever items can still be found, for data rescue and post-mortem tooling. See [PakSalvage](crate::recover::PakSalvage).
+    pub fn open_recover<P>(path : P) -> PakResult<PakSalvage> where P : AsRef<Path> {
+        Ok(PakSalvage::recover(fs::read(path)?))
+    }
+    
+    /// Loads a Pak from the file path, failing with [PakError::SchemaMismatch](crate::error::PakError::SchemaMismatch) unless it was built with the same [PakSchema](crate::schema::PakSchema).
+    pub fn open_with_schema<S>(path : impl AsRef<Path>) -> PakResult<Self> where S : PakSchema {
+        let pak = Self::new_from_file(path)?;
+        let expected = S::descriptor();
+        match &pak.meta.schema {
+            Some(found) if found.hash() == expected.hash() => Ok(pak),
+            Some(found) => Err(error::PakError::SchemaMismatch(found.diff(&expected).join("\n"))),
+            None => Err(error::PakError::SchemaMismatch("the pak was built without a schema".to_string())),
+        }
+    }
+    
     /// Loads an object from the pak file via queried indices. This will only load the necessary data into memory.
     pub fn query<T>(&self, query : impl PakQueryExpression) -> PakResult<T::ReturnType> where T : PakItemDeserializeGroup  {
         let pointers = query.execute(self)?.into_iter().map(|i| i.into_pointer()).collect();
         T::deserialize_group(self, pointers)
     }
     
+    /// Loads the items of a versioned type that match the query, decoding each one with [get_versioned](crate::Pak::get_versioned) so
+    /// items written by older versions of the type are migrated. Matches of other types are skipped.
+    pub fn query_versioned<T>(&self, query : impl PakQueryExpression) -> PakResult<Vec<T>> where T : PakVersioned {
+        let pointers = query.execute(self)?.into_iter().map(|i| i.into_pointer()).filter(|pointer| pointer.type_is_match::<T>()).collect();
+        self.result_order(pointers).iter().map(|pointer| self.get_versioned(pointer)).collect()
+    }
+    
+    /// Runs a query and returns its matches as a [PakResultSet](crate::results::PakResultSet), which can be narrowed, ordered and paged
+    /// before any items are read.
+    pub fn results(&self, query : impl PakQueryExpression) -> PakResult<results::PakResultSet<'_>> {
+        results::PakResultSet::new(self, query)
+    }
+    
+    /// Runs a query like [query](crate::Pak::query), failing with [PakError::BudgetExceeded](crate::error::PakError::BudgetExceeded) as
+    /// soon as it reads more than the budget allows. Reading the items that match counts towards the budget too.
+    pub fn query_with_budget<T>(&self, query : impl PakQueryExpression, budget : budget::PakBudget) -> PakResult<T::ReturnType> where T : PakItemDeserializeGroup {
+        self.with_budget(budget, |pak| pak.query::<T>(query))
+    }
+    
+    /// Runs the closure with every read it makes through the pak counted against the budget. Budgets can be nested, and the inner one
+    /// applies until its closure returns. Reads made under the inner budget count towards the outer one as well.
+    pub fn with_budget<R, F>(&self, budget : budget::PakBudget, f : F) -> PakResult<R> where F : FnOnce(&Self) -> PakResult<R> {
+        let outer = self.budget.take();
+        self.budget.replace(Some(budget::PakBudgetMeter::new(budget, outer)));
+        let result = f(self);
+        let Some(meter) = self.budget.take() else { return result };
+        let exceeded = meter.exceeded();
+        self.budget.replace(meter.into_outer());
+        match exceeded {
+            Some(error) => Err(error),
+            None => result,
+        }
+    }
+    
+    /// Reads a single item from the pak. The pointer can come from a query, [pointer_of](crate::Pak::pointer_of), or from another item that stored it.
+    /// Items paked with [pak_versioned](crate::PakBuilder::pak_versioned) fail with [PakError::VersionedItem](crate::error::PakError::VersionedItem),
+    /// since only [get_versioned](crate::Pak::get_versioned) knows which version of the type they can be decoded as.
+    pub fn get<T>(&self, pointer : &PakPointer) -> PakResult<T> where T : PakItemDeserialize {
+        self.read_err(pointer)
+    }
+    
+    /// Reads the raw bytes of the item at the pointer without deserializing them. This lets items be handed to custom decoders or other languages without going through serde.
+    /// Fails with [PakError::StalePointer](crate::error::PakError::StalePointer) if the pointer was handed out by a different generation of the pak,
+    /// and with [PakError::PointerOutOfBounds](crate::error::PakError::PointerOutOfBounds) if it reaches past the end of the vault.
+    pub fn read_bytes(&self, pointer : &PakPointer) -> PakResult<Vec<u8>> {
+        if let Some(generation) = pointer.generation() && generation != self.meta.generation {
+            return Err(error::PakError::StalePointer(generation, self.meta.generation))
+        }
+        if let Some(meter) = self.budget.borrow_mut().as_mut() { meter.charge_read()? }
+        let priority = self.priority.get();
+        if priority == cache::PakPriority::Foreground && let Some(recorder) = self.recorder.borrow_mut().as_mut() { recorder.record(pointer.offset()) }
+        if let Some(bytes) = self.cache.borrow_mut().get(pointer.offset(), pointer.size()) { return Ok(bytes) }
+        if let Some(meter) = self.budget.borrow_mut().as_mut() { meter.charge_bytes(pointer.size())? }
+        let bytes = self.read_uncached(pointer)?;
+        match priority {
+            cache::PakPriority::Foreground => self.cache.borrow_mut().insert(pointer.offset(), pointer.size(), &bytes),
+            cache::PakPriority::Background => self.cache.borrow_mut().insert_spare(pointer.offset(), pointer.size(), &bytes),
+        }
+        Ok(bytes)
+    }
+    
+    /// Reads the bytes at the pointer straight from the source, for reads that shouldn't take up room in the cache.
+    pub(crate) fn read_uncached(&self, pointer : &PakPointer) -> PakResult<Vec<u8>> {
+        if let Some(bytes) = self.inlined_bytes(pointer) { return Ok(bytes) }
+        let bytes = self.read_opened(pointer)?;
+        match self.compression_of(pointer.offset()) {
+            Some(compression) => compression.decompress(pointer.offset(), &bytes),
+            None => Ok(bytes),
+        }
+    }
+    
+    /// Reads the bytes at the pointer from the source with the encryption taken off, but still compressed.
+    fn read_opened(&self, pointer : &PakPointer) -> PakResult<Vec<u8>> {
+        let bytes = self.read_stored(pointer)?;
+        if let Some(checksums) = &self.meta.item_checksums { checksums.check(pointer, &bytes)? }
+        let bytes = match self.is_encrypted() {
+            true => self.open_layer(None, pointer.offset(), bytes)?,
+            false => bytes,
+        };
+        match self.meta.protection.as_ref().and_then(|protection| protection.key_of(pointer.offset())) {
+            Some(key_id) => self.open_layer(Some(key_id), pointer.offset(), bytes),
+            None => Ok(bytes),
+        }
+    }
+    
+    /// Reads the bytes at the pointer from the source as they are stored, held to the block checksums but not to the item checksums, so the
+    /// pointer can cover part of a chunk.
+    fn read_stored(&self, pointer : &PakPointer) -> PakResult<Vec<u8>> {
+        if pointer.offset().checked_add(pointer.size()).is_none_or(|end| end > self.layout.vault_len) {
+            return Err(error::PakError::PointerOutOfBounds(pointer.offset(), pointer.size()))
+        }
+        let _turn = self.scheduler.turn(self.priority.get());
+        match &self.meta.checksums {
+            Some(checksums) => checksums.read(self.source.borrow_mut().as_mut(), self.get_vault_start(), self.layout.vault_len, pointer, self.checksum_retries),
+            None => self.source.borrow_mut().read(pointer, self.get_vault_start()),
+        }
+    }
+    
+    /// The size of the item at the pointer once its encryption and compression are taken off. A compressed item records its size at its
+    /// start, so only that much of it is read unless it is encrypted, and nothing is decompressed.
+    pub(crate) fn item_len(&self, pointer : &PakPointer) -> PakResult<u64> {
+        if let Some(bytes) = self.inlined_bytes(pointer) { return Ok(bytes.len() as u64) }
+        let Some(compression) = self.compression_of(pointer.offset()) else {
+            #[cfg(feature = "encryption")]
+            return Ok(pointer.size().saturating_sub(self.seal_layers(pointer.offset()) * crate::crypto::PAK_TAG_SIZE));
+            #[cfg(not(feature = "encryption"))]
+            return Ok(pointer.size());
+        };
+        let bytes = match self.seal_layers(pointer.offset()) {
+            0 => self.read_stored(&PakPointer::new_untyped(pointer.offset(), pointer.size().min(compression.header_size())))?,
+            _ => self.read_opened(pointer)?,
+        };
+        compression.decompressed_size(pointer.offset(), &bytes)
+    }
+    
+    /// Returns true if the chunk at the offset is encrypted or compressed, so it can only be decoded as a whole.
+    pub(crate) fn decodes_whole(&self, offset : u64) -> bool {
+        self.seal_layers(offset) > 0 || self.compression_of(offset).is_some()
+    }
+    
+    /// How the chunk at the offset was compressed when it was paked, if it was. The items are paked before anything else, so they are every
+    /// chunk before the ordinal table, and the index structures are every chunk after it. The ordinal table itself is never compressed,
+    /// which marks where one ends and the other begins.
+    pub(crate) fn compression_of(&self, offset : u64) -> Option<PakCompression> {
+        let ordinals = self.meta.ordinals.as_pointer().offset();
+        if offset > ordinals { return self.meta.index_compression }
+        let protected = self.meta.protection.as_ref().is_some_and(|protection| protection.key_of(offset).is_some());
+        if offset == ordinals || protected { return None }
+        self.meta.compression
+    }
+    
+    /// The number of layers of encryption around the chunk at the offset. Each of them adds a tag to the end of the chunk.
+    pub(crate) fn seal_layers(&self, offset : u64) -> u64 {
+        let protected = self.meta.protection.as_ref().is_some_and(|protection| protection.key_of(offset).is_some());
+        self.is_encrypted() as u64 + protected as u64
+    }
+    
+    /// Opens one layer of encryption around a chunk, with the pak's key or with the item key of the id.
+    #[cfg(feature = "encryption")]
+    fn open_layer(&self, key_id : Option<&str>, offset : u64, bytes : Vec<u8>) -> PakResult<Vec<u8>> {
+        let cipher = match key_id {
+            Some(key_id) => self.item_ciphers.get(key_id),
+            None => self.cipher.as_ref(),
+        };
+        match cipher {
+            Some(cipher) => cipher.open(offset, &bytes),
+            None => Err(missing_key(key_id)),
+        }
+    }
+    
+    /// Without the encryption feature there are never any keys, so no layer can be opened.
+    #[cfg(not(feature = "encryption"))]
+    fn open_layer(&self, key_id : Option<&str>, _offset : u64, _bytes : Vec<u8>) -> PakResult<Vec<u8>> {
+        Err(missing_key(key_id))
+    }
+    
+    /// Starts recording the order chunks are read in, for [preload_list](crate::Pak::preload_list). Anything recorded before is forgotten.
+    pub fn record_access(self) -> Self {
+        self.recorder.replace(Some(access::PakAccessRecorder::default()));
+        self
+    }
+    
+    /// The items that have been read since [record_access](crate::Pak::record_access), by ordinal, in the order they were first read. Reads
+    /// of the index structures are left out, since they are laid out by the build.
+    pub fn preload_list(&self) -> PakResult<access::PakPreloadList> {
+        let ordinals = self.ordinal_offsets()?;
+        let recorder = self.recorder.borrow();
+        let order = recorder.as_ref().map(|recorder| recorder.order()).unwrap_or_default();
+        Ok(access::PakPreloadList::new(order.iter().filter_map(|offset| ordinals.get(offset).copied()).collect()))
+    }
+    
+    /// Reads every item in the list, in order, into the cache turned on with [with_cache](crate::Pak::with_cache). Returns how many were read.
+    /// The reads are made in the [background](crate::cache::PakPriority::Background), so they only fill room the cache has spare.
+    pub fn preload(&self, list : &access::PakPreloadList) -> PakResult<usize> {
+        self.with_priority(cache::PakPriority::Background, |pak| {
+            let mut loaded = 0;
+            for ordinal in &list.ordinals {
+                let Some(pointer) = pak.pointer_of(*ordinal)? else { continue };
+                pak.read_bytes(&pointer)?;
+                loaded += 1;
+            }
+            Ok(loaded)
+        })
+    }
+    
+    /// Runs the closure with every read it makes through the pak at the priority, then goes back to the priority from before. [Extract
+    /// streams](crate::Pak::extract_stream) started in the closure keep reading at the priority after it returns.
+    pub fn with_priority<R, F>(&self, priority : cache::PakPriority, f : F) -> R where F : FnOnce(&Self) -> R {
+        let outer = self.priority.replace(priority);
+        let result = f(self);
+        self.priority.set(outer);
+        result
+    }
+    
+    /// Writes a copy of this pak to the path with its items laid out in the order of the list, followed by the items that aren't in it in
+    /// ordinal order. Ordinals stay the same, and the indices are built again after the items. The copy is a new generation of the pak, since
+    /// the pointers handed out by this one don't point at the same items in it, but the pointers that items store to each other in
+    /// [ref fields](crate::index::PakIndex::reference) are rewritten. Encrypted paks need their keys, including the item keys of any
+    /// protected items, and are sealed again under fresh salts. Custom [index kinds](crate::kind::PakIndexKind) have to be registered.
+    pub fn repack_ordered(&self, path : impl AsRef<Path>, list : &access::PakPreloadList) -> PakResult<()> {
+        let len = self.ordinals()?.len();
+        let mut order = Vec::with_capacity(len);
+        let mut placed = vec![false; len];
+        for ordinal in list.ordinals.iter().copied().chain(0..len as u32) {
+            if let Some(false) = placed.get(ordinal as usize) {
+                placed[ordinal as usize] = true;
+                order.push(ordinal as usize);
+            }
+        }
+        self.repack_in_order(path.as_ref(), order)
+    }
+    
+    /// Writes a copy of this pak to the path with its items laid out by the rank `order` gives each of them, lowest first. Items with the
+    /// same rank keep their ordinal order. Ranking by type, by path or by how often items are read keeps related items next to each other,
+    /// and also closes any gaps in the vault. Like [repack_ordered](crate::Pak::repack_ordered), the ordinal table, the indices, the ids and
+    /// the references between items follow the items to their new place. Pointers that items store without marking them as references can't
+    /// be seen by the pak and are not rewritten.
+    pub fn repack_reordered<F>(&self, path : impl AsRef<Path>, order : F) -> PakResult<()> where F : Fn(&PakPointer) -> u64 {
+        let items = self.ordinals()?;
+        let mut ranked = items.iter().enumerate().map(|(ordinal, pointer)| (order(&pointer.clone().into_pointer()), ordinal)).collect::<Vec<_>>();
+        ranked.sort();
+        self.repack_in_order(path.as_ref(), ranked.into_iter().map(|(_, ordinal)| ordinal).collect())
+    }
+    
+    /// Lays out the items in the order of their ordinals in `order`, which must hold every ordinal once.
+    fn repack_in_order(&self, path : &Path, order : Vec<usize>) -> PakResult<()> {
+        self.rebuild(self.rebuilder()?, order)?.build_file(path)?;
+        Ok(())
+    }
+    
+    /// Paks the items into the builder in the order of their ordinals in `order`, sealed with the keys of the builder. Everything after the
+    /// items is built again from the entries of the indices when the builder is built. Items that hold references are rewritten to point at
+    /// where their targets end up.
+    fn rebuild(&self, mut builder : PakBuilder, order : Vec<usize>) -> PakResult<PakBuilder> {
+        if self.meta.revision == 0 {
+            return Err(error::PakError::InvalidHeader("meta".to_string(), "paks written by pak-db 0.1 have to be upgraded before they are rebuilt".to_string()))
+        }
+        let items = self.ordinals()?;
+        let generations = (self.meta.generation, builder.generation);
+        let mut indices = self.item_indices()?;
+        let references = self.item_references(&indices)?;
+        let mut chunks = items.iter().map(|pointer| self.read_opened(&pointer.clone().into_pointer())).collect::<PakResult<Vec<_>>>()?;
+        #[cfg(feature = "encryption")]
+        let protection = |ordinal : usize| self.meta.protection.as_ref().and_then(|protection| protection.chunks.get(&items[ordinal].offset()).copied());
+        #[cfg(feature = "encryption")]
+        let sealed_size = |ordinal : usize, len : usize| len as u64 + (builder.encryption.is_some() as u64 + protection(ordinal).is_some() as u64) * crypto::PAK_TAG_SIZE;
+        #[cfg(not(feature = "encryption"))]
+        let sealed_size = |_ : usize, len : usize| len as u64;
+        let mut sizes = chunks.iter().enumerate().map(|(ordinal, bytes)| sealed_size(ordinal, bytes.len())).collect::<Vec<_>>();
+        let mut offsets = vec![0; items.len()];
+        
+        // Compressed items can change size when their references are rewritten, which moves every item after them, so the offsets are
+        // worked out again until they settle.
+        let mut rewritten = HashMap::new();
+        for round in 0.. {
+            let mut offset = 0;
+            for ordinal in &order {
+                offsets[*ordinal] = offset;
+                offset += sizes[*ordinal];
+            }
+            let mut settled = true;
+            for (ordinal, targets) in references.iter().enumerate().filter(|(_, targets)| !targets.is_empty()) {
+                let item_offset = items[ordinal].offset();
+                let compression = self.compression_of(item_offset);
+                let mut bytes = match compression {
+                    Some(compression) => compression.decompress(item_offset, &chunks[ordinal])?,
+                    None => chunks[ordinal].clone(),
+                };
+                for target in targets {
+                    let to = items[*target].clone().with_offset(offsets[*target]).with_size(sizes[*target]);
+                    pointer::rewrite_pointers(&mut bytes, &items[*target], &to, generations)?;
+                }
+                if let Some(compression) = compression { bytes = compression.compress(&bytes)? }
+                let size = sealed_size(ordinal, bytes.len());
+                if size != sizes[ordinal] {
+                    sizes[ordinal] = size;
+                    settled = false;
+                }
+                rewritten.insert(ordinal, bytes);
+            }
+            if settled { break }
+            if round == REPACK_ROUNDS {
+                return Err(error::PakError::InvalidHeader("vault".to_string(), "the compressed items that hold references never settled on a layout".to_string()))
+            }
+        }
+        for (ordinal, targets) in references.iter().enumerate() {
+            if let Some(bytes) = rewritten.remove(&ordinal) { chunks[ordinal] = bytes }
+            for index in &mut indices[ordinal] {
+                let PakValue::Uint(offset) = index.value else { continue };
+                if let Some(target) = targets.iter().find(|target| items[**target].offset() == offset) { index.value = PakValue::Uint(offsets[*target]) }
+            }
+        }
+        
+        let mut table = items.iter().map(|pointer| pointer.clone().with_generation(None)).collect::<Vec<_>>();
+        for ordinal in order {
+            let offset = builder.size_in_bytes;
+            #[allow(unused_mut)]
+            let mut bytes = std::mem::take(&mut chunks[ordinal]);
+            #[cfg(feature = "encryption")]
+            {
+                if let Some(key) = protection(ordinal) {
+                    bytes = builder.item_keys[key as usize].1.seal(offset, &bytes)?;
+                    builder.protected_chunks.insert(offset, key);
+                }
+                if let Some((cipher, _)) = &builder.encryption { bytes = cipher.seal(offset, &bytes)? }
+            }
+            table[ordinal] = table[ordinal].clone().with_offset(offset).with_size(bytes.len() as u64);
+            builder.size_in_bytes += bytes.len() as u64;
+            builder.vault.extend(bytes);
+        }
+        builder.chunks = table.into_iter().zip(indices).map(|(pointer, indices)| PakVaultReference { pointer, indices }).collect();
+        Ok(builder)
+    }
+    
+    /// A builder that builds this pak again the way it was built, as far as the meta and the index trees record it, at the next generation.
+    /// Every key gets a fresh salt, and the key of every protected item has to have been given to the pak.
+    fn rebuilder(&self) -> PakResult<PakBuilder> {
+        let mut builder = PakBuilder::new().with_name(self.name()).with_description(self.description()).with_author(self.author());
+        builder.schema = self.meta.schema.clone();
+        builder.header_encoding = self.meta.header_encoding;
+        builder.generation = self.meta.generation + 1;
+        builder.block_checksums = self.meta.checksums.as_ref().map(|checksums| checksums.block_size);
+        builder.item_checksums = self.meta.item_checksums.is_some();
+        builder.vault_hash = self.meta.vault_hash.is_some();
+        builder.manifest = self.meta.manifest.is_some();
+        builder.inline_items = self.meta.inlined.map(|pointer| self.read_err::<inline::PakInlineTable>(&pointer.as_pointer())).transpose()?.map(|table| table.max_size);
+        builder.compression = self.meta.compression;
+        builder.index_compression = self.meta.index_compression;
+        builder.format = self.layout.format;
+        builder.duplicate_keys = PakDuplicateKeys::Inline;
+        for key in self.fetch_indices()?.keys() {
+            let tree = self.get_tree(key)?;
+            if let Some(limit) = tree.overflow_limit()? { builder.duplicate_keys = PakDuplicateKeys::Overflow(limit) }
+            #[cfg(feature = "roaring")]
+            if tree.bitmap().is_some() { builder.bitmap_keys.insert(key.clone()); }
+            if let Some((name, _)) = tree.custom() {
+                let Some(kind) = self.kinds.get(name) else {
+                    return Err(error::PakError::InvalidHeader("index".to_string(), format!("{key} is built with the index kind {name}, which has to be registered to rebuild it")))
+                };
+                builder.custom_indices.insert(key.clone(), kind.clone());
+            }
+        }
+        #[cfg(feature = "encryption")]
+        {
+            if let Some(encryption) = &self.meta.encryption {
+                let Some(cipher) = &self.cipher else { return Err(error::PakError::MissingKey) };
+                builder.encryption = Some((cipher.with_fresh_salt()?, encryption.kdf.clone()));
+                builder.cipher_algorithm = cipher.algorithm();
+            }
+            if let Some(protection) = &self.meta.protection {
+                builder.item_keys = protection.keys.iter().map(|(key_id, _)| match self.item_ciphers.get(key_id) {
+                    Some(cipher) => Ok((key_id.clone(), cipher.with_fresh_salt()?)),
+                    None => Err(missing_key(Some(key_id))),
+                }).collect::<PakResult<_>>()?;
+            }
+        }
+        Ok(builder)
+    }
+    
+    /// The entries of every index, by the ordinal of the item they belong to, which is what the pak was built from.
+    pub(crate) fn item_indices(&self) -> PakResult<Vec<Vec<PakIndex>>> {
+        let mut indices = vec![Vec::new(); self.ordinals()?.len()];
+        for key in self.fetch_indices()?.keys() {
+            self.get_tree(key)?.walk(|value, postings| {
+                postings.for_each_ordinal_chunk(|ordinals| {
+                    for ordinal in ordinals {
+                        indices[*ordinal as usize].push(PakIndex { key : key.clone(), value : value.clone() });
+                    }
+                    true
+                })?;
+                Ok(true)
+            })?;
+        }
+        Ok(indices)
+    }
+    
+    /// The ordinals of the items that every item points to, found through the indices it marked with [PakIndex::reference](crate::index::PakIndex::reference).
+    fn item_references(&self, indices : &[Vec<PakIndex>]) -> PakResult<Vec<Vec<usize>>> {
+        let ordinals = self.ordinal_offsets()?;
+        Ok(indices.iter().map(|indices| {
+            let keys = indices.iter().filter(|index| index.key == pointer::PAK_REF_KEY).filter_map(|index| match &index.value {
+                PakValue::String(key) => Some(key.as_str()),
+                _ => None,
+            }).collect::<HashSet<_>>();
+            let mut targets = indices.iter().filter(|index| keys.contains(index.key.as_str())).filter_map(|index| match index.value {
+                PakValue::Uint(offset) => ordinals.get(&offset).map(|ordinal| *ordinal as usize),
+                _ => None,
+            }).collect::<Vec<_>>();
+            targets.sort_unstable();
+            targets.dedup();
+            targets
+        }).collect())
+    }
+    
+    /// The ordinal of every item, by the offset of the item.
+    fn ordinal_offsets(&self) -> PakResult<HashMap<u64, u32>> {
+        Ok(self.ordinals()?.iter().enumerate().map(|(ordinal, pointer)| (pointer.offset(), ordinal as u32)).collect())
+    }
+    
+    /// Opens a [PakWindow](crate::window::PakWindow) over the item at the pointer, a bounded [Read](std::io::Read) + [Seek](std::io::Seek) view of its bytes.
+    pub fn window(&self, pointer : &PakPointer) -> PakWindow<'_> {
+        PakWindow::new(self, pointer)
+    }
+    
+    /// Streams the bytes of the item at the pointer in chunks of 64 KiB, so large items can be sent on without holding all of them in memory.
+    /// Chunks are read on a thread of the stream's own, and only as the stream is polled for them. See [PakExtractStream](crate::stream::PakExtractStream).
+    #[cfg(feature = "async")]
+    pub fn extract_stream(&self, pointer : &PakPointer) -> PakResult<stream::PakExtractStream> {
+        stream::PakExtractStream::new(self, pointer)
+    }
+    
+    /// Reads an item that may have been written by an older version of its type. Items in a [PakEnvelope](crate::envelope::PakEnvelope) are
+    /// handed to [decode_version](crate::envelope::PakVersioned::decode_version) along with their version, and items without one are decoded as the current version.
+    pub fn get_versioned<T>(&self, pointer : &PakPointer) -> PakResult<T> where T : PakVersioned {
+        if !pointer.type_is_match::<T>() { return Err(error::PakError::type_mismatch::<T>(pointer, None)) }
+        let buffer = self.read_bytes(pointer)?;
+        if pointer.version().is_none() { return T::decode_version(T::VERSION, &buffer) }
+        let envelope : PakEnvelope = bincode::deserialize(&buffer)?;
+        T::decode_version(envelope.version, &envelope.payload)
+    }
+    
+    /// Returns the pointer to the item with the given ordinal. Every paked item gets a dense ordinal, starting at 0, in the order it was added to the [PakBuilder](crate::PakBuilder).
+    pub fn pointer_of(&self, ordinal : u32) -> PakResult<Option<PakPointer>> {
+        Ok(self.ordinals()?.get(ordinal as usize).cloned().map(PakTypedPointer::into_pointer))
+    }
+    
+    /// The number of items that were paked, not counting the internal index structures.
+    pub fn item_count(&self) -> PakResult<u32> {
+        Ok(self.ordinals()?.len() as u32)
+    }
+    
+    /// Returns the pointer to the item that was paked with the stable id, if there is one.
+    pub fn pointer_by_id(&self, id : impl Into<PakId>) -> PakResult<Option<PakPointer>> {
+        if !self.fetch_indices()?.contains_key(PAK_ID_KEY) { return Ok(None) }
+        let pointers = self.get_tree(PAK_ID_KEY)?.get(&id.into().into())?;
+        Ok(pointers.into_iter().next().map(PakTypedPointer::into_pointer))
+    }
+    
+    /// Returns the stable id that the item at the pointer was paked with, if it has one. The first call reads the whole id index.
+    pub fn id_of(&self, pointer : &PakPointer) -> PakResult<Option<PakId>> {
+        if self.ids.get().is_none() {
+            let mut ids = HashMap::new();
+            if self.fetch_indices()?.contains_key(PAK_ID_KEY) {
+                self.get_tree(PAK_ID_KEY)?.walk(|value, postings| {
+                    let Some(id) = PakId::from_value(value) else { return Ok(true) };
+                    for pointer in postings.pointers()? {
+                        ids.insert(pointer?.offset(), id);
+                    }
+                    Ok(true)
+                })?;
+            }
+            let _ = self.ids.set(ids);
+        }
+        Ok(self.ids.get().and_then(|ids| ids.get(&pointer.offset()).copied()))
+    }
+    
+    /// Returns the pointer to the item at the path, if there is one. Paths are normalized the same way they are when items are paked.
+    pub fn pointer_by_path(&self, path : &str) -> PakResult<Option<PakPointer>> {
+        if !self.fetch_indices()?.contains_key(PAK_PATH_KEY) { return Ok(None) }
+        let pointers = self.get_tree(PAK_PATH_KEY)?.get(&normalize_path(path)?.into())?;
+        Ok(pointers.into_iter().next().map(PakTypedPointer::into_pointer))
+    }
+    
+    /// Returns the path that the item at the pointer was paked with, if it has one. The first call reads the whole path index.
+    pub fn path_of(&self, pointer : &PakPointer) -> PakResult<Option<&str>> {
+        if self.item_paths.get().is_none() {
+            let mut paths = HashMap::new();
+            for (path, pointer) in self.paths()? {
+                paths.insert(pointer.offset(), path);
+            }
+            let _ = self.item_paths.set(paths);
+        }
+        Ok(self.item_paths.get().and_then(|paths| paths.get(&pointer.offset())).map(String::as_str))
+    }
+    
+    /// Loads the metadata of the blob at the path, if it was paked with [PakBuilder::pak_blob_with_meta](crate::PakBuilder::pak_blob_with_meta).
+    pub fn blob_meta<M>(&self, path : &str) -> PakResult<Option<M>> where M : PakItemDeserialize {
+        match self.sidecar_pointer(path)? {
+            Some(pointer) => Ok(Some(self.read_err(&pointer)?)),
+            None => Ok(None),
+        }
+    }
+    
+    /// Loads the blob at the path together with its metadata, or `None` if there is no blob with metadata at the path. Each is read as an
+    /// item of its own, so both are held to their checksums.
+    pub fn blob_with_meta<M>(&self, path : &str) -> PakResult<Option<(PakBlob, M)>> where M : PakItemDeserialize {
+        let (Some(blob), Some(meta)) = (self.pointer_by_path(path)?, self.sidecar_pointer(path)?) else { return Ok(None) };
+        if !meta.type_is_match::<M>() { return Err(error::PakError::type_mismatch::<M>(&meta, Some(PAK_SIDECAR_KEY))) }
+        Ok(Some((self.read_err(&blob)?, self.read_err(&meta)?)))
+    }
+    
+    fn sidecar_pointer(&self, path : &str) -> PakResult<Option<PakPointer>> {
+        if !self.fetch_indices()?.contains_key(PAK_SIDECAR_KEY) { return Ok(None) }
+        let pointers = self.get_tree(PAK_SIDECAR_KEY)?.get(&normalize_path(path)?.into())?;
+        Ok(pointers.into_iter().next().map(PakTypedPointer::into_pointer))
+    }
+    
+    /// Loads the item at the path, if there is one. See [PakBuilder::pak_with_path](crate::PakBuilder::pak_with_path).
+    pub fn by_path<T>(&self, path : &str) -> PakResult<Option<T>> where T : PakItemDeserialize {
+        match self.pointer_by_path(path)? {
+            Some(pointer) => Ok(Some(self.read_err(&pointer)?)),
+            None => Ok(None),
+        }
+    }
+    
+    /// Every path in the pak with the pointer to its item, in order of the paths.
+    pub fn paths(&self) -> PakResult<Vec<(String, PakPointer)>> {
+        let mut paths = Vec::new();
+        if !self.fetch_indices()?.contains_key(PAK_PATH_KEY) { return Ok(paths) }
+        self.get_tree(PAK_PATH_KEY)?.walk(|value, postings| {
+            let PakValue::String(path) = value else { return Ok(true) };
+            for pointer in postings.pointers()? {
+                paths.push((path.clone(), pointer?.clone().into_pointer()));
+            }
+            Ok(true)
+        })?;
+        Ok(paths)
+    }
+    
+    /// Loads the item that was paked with the stable id, if there is one. See [PakBuilder::pak_with_id](crate::PakBuilder::pak_with_id).
+    pub fn by_id<T>(&self, id : impl Into<PakId>) -> PakResult<Option<T>> where T : PakItemDeserialize {
+        match self.pointer_by_id(id)? {
+            Some(pointer) => Ok(Some(self.read_err(&pointer)?)),
+            None => Ok(None),
+        }
+    }
+    
+    /// Takes a read-only snapshot of the pak, pinned to its current generation, for running a batch of related reads against one
+    /// consistent view. See [PakSnapshot](crate::snapshot::PakSnapshot).
+    pub fn snapshot(&self) -> PakResult<snapshot::PakSnapshot<'_>> {
+        snapshot::PakSnapshot::new(self)
+    }
+    
+    /// Returns true if any item matches the query. Single queries stop at the first matching index entry, so no pointer sets or items are loaded.
+    pub fn exists(&self, query : impl PakQueryExpression) -> PakResult<bool> {
+        query.exists(self)
+    }
+    
+    /// Returns the schema the pak was built with, if it has one.
+    pub fn schema(&self) -> Option<&PakSchemaDescriptor> {
+        self.meta.schema.as_ref()
+    }
+    
+    /// Returns the kind of values held by the index, or `None` if the pak has no index with the key.
+    pub fn index_kind(&self, key : &str) -> Option<PakValueKind> {
+        self.meta.index_kinds.get(key).copied()
+    }
+    
+    /// Opens a read-only [PakIndexReader](crate::index::PakIndexReader) over an index, for ordered traversal and custom scans.
+    pub fn index(&self, key : &str) -> PakResult<PakIndexReader<'_>> {
+        Ok(PakIndexReader::new(key, self.get_tree(key)?))
+    }
+    
+    /// Runs a full-text search against an index built with a [PakTextIndex](crate::text::PakTextIndex), returning up to `limit` items of type
+    /// `T` with their scores, ranked from the best match to the worst with BM25. Items that contain more of the query's terms, rarer terms, or the terms more often
+    /// in shorter texts score higher. Items of other types are skipped.
+    ///
+    /// Words and `"quoted phrases"` can be combined with `AND`, `OR`, `NOT` and parentheses, and words next to each other are joined with `OR`.
+    /// `key:word` searches another index instead, matching its exact value if it doesn't have a text index, like `"rusty sword" AND kind:weapon`.
+    pub fn search<T>(&self, key : &str, text : &str, limit : usize) -> PakResult<Vec<(f32, T)>> where T : PakItemDeserialize {
+        let scores = text::search(self, key, text)?;
+        
+        let table = self.ordinals()?;
+        let mut hits = Vec::new();
+        for (ordinal, score) in scores {
+            if hits.len() >= limit { break }
+            let Some(pointer) = table.get(ordinal as usize) else { continue };
+            let pointer = pointer.clone().into_pointer();
+            if !pointer.type_is_match::<T>() { continue }
+            hits.push((score, self.get::<T>(&pointer)?));
+        }
+        Ok(hits)
+    }
+    
+    /// Finds the words in the item's values under `key` that match a [search](crate::Pak::search), so they can be shown to explain why the item
+    /// matched. The values are split into terms the same way the index was built, and each value gets its own [PakHighlight](crate::text::PakHighlight).
+    pub fn highlight<T>(&self, item : &T, key : &str, text : &str) -> PakResult<Vec<text::PakHighlight>> where T : PakItemSearchable {
+        let values = item.get_indices().into_iter().filter(|index| index.key == key).filter_map(|index| index.value.as_string()).collect();
+        text::highlight(self, key, text, values)
+    }
+    
+    /// Groups the items in an index by their value and computes the [Aggregate](crate::aggregate::Aggregate) for each group. The groups are returned in the order of their values. This is computed from the index alone, so none of the items are loaded.
+    pub fn group_by(&self, key : &str, aggregate : Aggregate) -> PakResult<Vec<(PakValue, u64)>> {
+        let tree = self.get_tree(key)?;
+        let mut groups = Vec::new();
+        tree.walk(|value, postings| {
+            groups.push((value.clone(), aggregate.apply(postings)));
+            Ok(true)
+        })?;
+        Ok(groups)
+    }
+    
+    /// Returns every distinct value stored in an index, in order. This is useful for things like filter dropdowns, since only the index is read.
+    /// The values are read a page at a time as the iterator reaches them, so taking the first few of a large index only reads a few pages.
+    pub fn distinct(&self, key : &str) -> PakResult<index::PakDistinctIter<'_>> {
+        Ok(self.get_tree(key)?.keys())
+    }
+    
+    /// Builds an equi-depth histogram with up to `buckets` buckets over the numeric values of an index. Non-numeric values are ignored.
+    pub fn histogram(&self, key : &str, buckets : usize) -> PakResult<PakHistogram> {
+        let groups = self.numeric_groups(key)?;
+        Ok(PakHistogram::equi_depth(&groups, buckets))
+    }
+    
+    /// Returns the value at the given percentile (between 0.0 and 1.0) of a numeric index, using the nearest-rank method. Non-numeric values are ignored, and `None` is returned if the index has no numeric values.
+    pub fn percentile(&self, key : &str, percentile : f64) -> PakResult<Option<PakValue>> {
+        let groups = self.numeric_groups(key)?;
+        Ok(aggregate::percentile(&groups, percentile))
+    }
+    
+    fn numeric_groups(&self, key : &str) -> PakResult<Vec<(PakValue, u64)>> {
+        let groups = self.group_by(key, Aggregate::Count)?;
+        Ok(groups.into_iter().filter(|(value, _)| value.is_numeric()).collect())
+    }
+    
+    /// Writes the raw bytes of the item at the pointer out to a file, creating or truncating it. This is handy for debugging and for handing items to external tools.
+    pub fn extract(&self, pointer : &PakPointer, path : impl AsRef<Path>) -> PakResult<()> {
+        let bytes = self.read_bytes(pointer)?;
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+    
+    /// Writes every item that passes the filter into the directory as `<ordinal>.bin`, creating the directory if needed. Returns the number of items that were written.
+    pub fn extract_all<F>(&self, dir : impl AsRef<Path>, mut filter : F) -> PakResult<usize> where F : FnMut(&PakPointer) -> bool {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let mut written = 0;
+        for (ordinal, pointer) in self.ordinals()?.iter().enumerate() {
+            let pointer = pointer.clone().into_pointer();
+            if !filter(&pointer) { continue }
+            self.extract(&pointer, dir.join(format!("{ordinal}.bin")))?;
+            written += 1;
+        }
+        Ok(written)
+    }
+    
     /// Returns the size of the pak file in bytes.
     pub fn size(&self) -> u64 {
-        24 + self.sizing.meta_size + self.sizing.indices_size + self.sizing.vault_size
+        self.layout.size
     }
     
     /// Returns the name given to the pak file.
@@ -85,39 +1103,84 @@ impl Pak {
         &self.meta.description
     }
     
+    /// Returns the generation of the pak file. Every pointer the pak hands out is tagged with it.
+    pub fn generation(&self) -> u64 {
+        self.meta.generation
+    }
+    
+    /// A hash of the layout, the index map and the parts of the meta reads depend on, as they are in the source right now, which changes
+    /// when the file underneath the pak is rewritten, even by a build of the same generation. The meta isn't hashed whole, since its maps
+    /// don't serialize in a fixed order.
+    pub(crate) fn source_fingerprint(&self) -> PakResult<[u8; 32]> {
+        use sha2::Digest;
+        let mut source = self.source.borrow_mut();
+        let (meta, layout) = PakFormat::detect(source.as_mut())?.driver().open(source.as_mut())?;
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(bincode::serialize(&(meta.generation, &meta.ordinals, &meta.inlined, &meta.vault_hash))?);
+        hasher.update(bincode::serialize(&(&layout.indices, layout.vault_start, layout.vault_len, layout.size))?);
+        hasher.update(source.read(&layout.indices, 0)?);
+        Ok(hasher.finalize().into())
+    }
+    
+    /// The pointers of a query's matches in the order their items are returned in. See [with_ordered_results](crate::Pak::with_ordered_results).
+    pub(crate) fn result_order(&self, pointers : HashSet<PakPointer>) -> Vec<PakPointer> {
+        let mut pointers = pointers.into_iter().collect::<Vec<_>>();
+        if self.ordered_results { pointers.sort_by_key(|pointer| pointer.offset()) }
+        pointers
+    }
+    
     pub(crate) fn read_err<T>(&self, pointer : &PakPointer) -> PakResult<T> where T : PakItemDeserialize {
-        if !pointer.type_is_match::<T>() { return Err(error::PakError::TypeMismatchError(pointer.type_name().to_string(), std::any::type_name::<T>().to_string())) }
-        let buffer = self.source.borrow_mut().read(pointer, self.get_vault_start())?;
+        if !pointer.type_is_match::<T>() { return Err(error::PakError::type_mismatch::<T>(pointer, None)) }
+        if let Some(version) = pointer.version() { return Err(error::PakError::VersionedItem(pointer.type_name().to_string(), version)) }
+        let buffer = self.read_bytes(pointer)?;
         let res = T::from_bytes(&buffer)?;
         Ok(res)
     }
     
     pub(crate) fn read<T>(&self, pointer : &PakPointer) -> Option<T> where T : PakItemDeserialize {
-        let res = self.read_err(pointer);
-        match res {
-            Ok(res) => Some(res),
-            Err(_) => None,
-        }
+        self.read_err(pointer).ok()
     }
     
-    pub(crate) fn get_tree(&self, key : &str) -> PakResult<PakTree> {
+    pub(crate) fn ordinals(&self) -> PakResult<&[PakTypedPointer]> {
+        if let Some(ordinals) = self.ordinals.get() { return Ok(ordinals) }
+        let ordinals = match self.meta.revision {
+            0 => legacy::ordinals(self)?,
+            _ => self.read_err::<Vec<PakTypedPointer>>(&self.meta.ordinals.as_pointer())?,
+        };
+        let ordinals = ordinals.into_iter().map(|pointer| pointer.with_generation(Some(self.meta.generation))).collect();
+        Ok(self.ordinals.get_or_init(|| ordinals))
+    }
+    
+    pub(crate) fn get_tree(&self, key : &str) -> PakResult<PakTree<'_>> {
         PakTree::new(self, key)
     }
     
-    pub(crate) fn fetch_indices(&self) -> PakResult<HashMap<String, PakUntypedPointer>> {
-        let pointer = PakPointer::new_untyped(self.get_indices_start(), self.sizing.indices_size);
-        let buffer = self.source.borrow_mut().read(&pointer, 0)?;
-        let indices = bincode::deserialize(&buffer)?;
-        Ok(indices)
+    /// The tree a query on the key reads, or `None` if the pak has no index with the key and unknown keys match nothing.
+    pub(crate) fn query_tree(&self, key : &str) -> PakResult<Option<PakTree<'_>>> {
+        if self.unknown_keys == PakUnknownKeys::Empty && !self.fetch_indices()?.contains_key(key) { return Ok(None) }
+        Ok(Some(self.get_tree(key)?))
+    }
+    
+    /// The map from index keys to their trees. It is read from the source the first time it is needed and kept for the life of the pak.
+    pub(crate) fn fetch_indices(&self) -> PakResult<&PakIndexMap> {
+        if let Some(indices) = self.indices.get() { return Ok(indices) }
+        let buffer = self.source.borrow_mut().read(&self.layout.indices, 0)?;
+        let indices = self.meta.deserialize_indices(&buffer)?;
+        Ok(self.indices.get_or_init(|| indices))
+    }
+    
+    /// The whole vault as it is stored, without opening any of its chunks.
+    pub(crate) fn read_vault(&self) -> PakResult<Vec<u8>> {
+        self.source.borrow_mut().read(&PakPointer::new_untyped(0, self.layout.vault_len), self.get_vault_start())
     }
     
     pub(crate) fn get_vault_start(&self) -> u64 {
-        // To be honest, I'm not sure why this start is offset by 8, it just is and I am to scared to ask.
-        24 + self.sizing.meta_size + self.sizing.indices_size + 8
+        self.layout.vault_start
     }
     
-    pub(crate) fn get_indices_start(&self) -> u64 {
-        24 + self.sizing.meta_size
+    /// The layout the pak was written with.
+    pub fn format(&self) -> PakFormat {
+        self.layout.format
     }
     
 }
@@ -130,15 +1193,27 @@ impl Pak {
 pub trait PakSource {
     ///Returns data from the source based on a [PakPointer](crate::PakPointer)
     fn read(&mut self, pointer : &PakPointer, offset : u64) -> PakResult<Vec<u8>>;
+    
+    ///Returns the total size of the source in bytes, if it is known. This is used to check the header when a pak is opened.
+    fn size(&mut self) -> PakResult<Option<u64>> {
+        Ok(None)
+    }
 }
 
 impl <R> PakSource for R where R : Read + Seek {
     fn read(&mut self, pointer : &PakPointer, offset : u64) -> PakResult<Vec<u8>> {
-        let mut buffer = vec![0u8; pointer.size() as usize];
-        self.seek(SeekFrom::Start(pointer.offset() + offset))?;
+        let mut buffer = vec![0u8; pointer::to_usize(pointer.size())?];
+        self.seek(SeekFrom::Start(pointer.offset().checked_add(offset).ok_or(error::PakError::OffsetOverflow(offset))?))?;
         self.read_exact(&mut buffer)?;
         Ok(buffer)
     }
+    
+    fn size(&mut self) -> PakResult<Option<u64>> {
+        let position = self.stream_position()?;
+        let len = self.seek(SeekFrom::End(0))?;
+        self.seek(SeekFrom::Start(position))?;
+        Ok(Some(len))
+    }
 }
 
 //==============================================================================================
@@ -148,8 +1223,40 @@ impl <R> PakSource for R where R : Read + Seek {
 /// When it is time to create the pak file, this struct is used to build it. Remember that this struct doen't have the ability to read data that has been paked or delete data that has been paked.
 pub struct PakBuilder {
     chunks : Vec<PakVaultReference>,
+    ids : HashSet<PakId>,
+    paths : HashSet<String>,
+    localized : HashSet<String>,
     size_in_bytes : u64,
     vault : Vec<u8>,
+    duplicate_keys : PakDuplicateKeys,
+    index_build : PakIndexBuild,
+    #[cfg(feature = "roaring")]
+    bitmap_keys : std::collections::HashSet<String>,
+    custom_indices : HashMap<String, Arc<dyn PakIndexKind>>,
+    atomic_write : bool,
+    schema : Option<PakSchemaDescriptor>,
+    header_encoding : PakEncoding,
+    generation : u64,
+    block_checksums : Option<u64>,
+    item_checksums : bool,
+    vault_hash : bool,
+    manifest : bool,
+    inline_items : Option<u64>,
+    inlined : HashMap<u32, Vec<u8>>,
+    content : Option<content::PakContentChunks>,
+    audit : Option<audit::PakIndexAuditor>,
+    compression : Option<PakCompression>,
+    index_compression : Option<PakCompression>,
+    format : PakFormat,
+    alignment : Option<u64>,
+    #[cfg(feature = "encryption")]
+    encryption : Option<(crypto::PakCipher, Option<meta::PakKdf>)>,
+    #[cfg(feature = "encryption")]
+    cipher_algorithm : meta::PakCipherAlgorithm,
+    #[cfg(feature = "encryption")]
+    item_keys : Vec<(String, crypto::PakCipher)>,
+    #[cfg(feature = "encryption")]
+    protected_chunks : std::collections::BTreeMap<u64, u32>,
     name: String,
     description: String,
     author: String,
@@ -161,7 +1268,39 @@ impl PakBuilder {
         Self {
             vault : Vec::new(),
             chunks : Vec::new(),
+            ids : HashSet::new(),
+            paths : HashSet::new(),
+            localized : HashSet::new(),
             size_in_bytes : 0,
+            duplicate_keys : PakDuplicateKeys::default(),
+            index_build : PakIndexBuild::default(),
+            #[cfg(feature = "roaring")]
+            bitmap_keys : std::collections::HashSet::new(),
+            custom_indices : HashMap::new(),
+            atomic_write : true,
+            schema : None,
+            header_encoding : PakEncoding::default(),
+            generation : 0,
+            block_checksums : None,
+            item_checksums : false,
+            vault_hash : false,
+            manifest : false,
+            inline_items : None,
+            inlined : HashMap::new(),
+            content : None,
+            audit : None,
+            compression : None,
+            index_compression : None,
+            format : PakFormat::default(),
+            alignment : None,
+            #[cfg(feature = "encryption")]
+            encryption : None,
+            #[cfg(feature = "encryption")]
+            cipher_algorithm : meta::PakCipherAlgorithm::default(),
+            #[cfg(feature = "encryption")]
+            item_keys : Vec::new(),
+            #[cfg(feature = "encryption")]
+            protected_chunks : std::collections::BTreeMap::new(),
             name: String::new(),
             description: String::new(),
             author: String::new(),
@@ -171,22 +1310,155 @@ impl PakBuilder {
     /// Adds an item to the pak file that does not support searching. Takes anything that implements [PakItemSerialize](crate::PakItemSerialize).
     pub fn pak_no_search<T: PakItemSerialize>(&mut self, item : T) -> PakResult<PakPointer> {
         let bytes = item.into_bytes()?;
-        let pointer = PakPointer::new_typed::<T>(self.size_in_bytes, bytes.len() as u64);
-        self.size_in_bytes += bytes.len() as u64;
-        self.vault.extend(bytes);
-        self.chunks.push(PakVaultReference { pointer: pointer.clone().into_typed::<T>(), indices: vec![] });
-        Ok(pointer)
+        self.pak_internal::<T>(bytes, vec![])
     }
     
     /// Adds an item to the pak file that supports searching. Takes anything that implements [PakItemSerialize](crate::PakItemSerialize) and [PakItemSearchable](crate::PakItemSearchable).
     pub fn pak<T : PakItemSerialize + PakItemSearchable>(&mut self, item : T) -> PakResult<PakPointer> {
         let indices = item.get_indices();
         let bytes = item.into_bytes()?;
-        let pointer = PakPointer::new_typed::<T>(self.size_in_bytes, bytes.len() as u64);
+        self.pak_internal::<T>(bytes, indices)
+    }
+    
+    /// Adds a searchable item that is encrypted with the item key of the id, while the rest of the pak stays readable. Reading it from a pak
+    /// that hasn't been given the key with [Pak::with_item_key](crate::Pak::with_item_key) fails with
+    /// [PakError::KeyRequired](crate::error::PakError::KeyRequired). Its indices are not encrypted, so it can still be found by queries.
+    #[cfg(feature = "encryption")]
+    pub fn pak_encrypted<T : PakItemSerialize + PakItemSearchable>(&mut self, item : T, key_id : &str) -> PakResult<PakPointer> {
+        let Some(index) = self.item_keys.iter().position(|(id, _)| id == key_id) else { return Err(error::PakError::KeyRequired(key_id.to_string())) };
+        let indices = item.get_indices();
+        let offset = self.next_offset();
+        let bytes = self.item_keys[index].1.seal(offset, &item.into_bytes()?)?;
+        self.protected_chunks.insert(offset, index as u32);
+        self.pak_internal::<T>(bytes, indices)
+    }
+    
+    /// Adds a searchable item to the pak file with a stable [PakId](crate::id::PakId), so it can be found later with [Pak::by_id](crate::Pak::by_id). Ids must be unique within a pak.
+    pub fn pak_with_id<T : PakItemSerialize + PakItemSearchable>(&mut self, id : impl Into<PakId>, item : T) -> PakResult<PakPointer> {
+        let mut indices = item.get_indices();
+        indices.push(self.claim_id(id.into())?);
+        let bytes = item.into_bytes()?;
+        self.pak_internal::<T>(bytes, indices)
+    }
+    
+    /// Adds an item to the pak file with a stable [PakId](crate::id::PakId) and no other searchable indices.
+    pub fn pak_no_search_with_id<T : PakItemSerialize>(&mut self, id : impl Into<PakId>, item : T) -> PakResult<PakPointer> {
+        let indices = vec![self.claim_id(id.into())?];
+        let bytes = item.into_bytes()?;
+        self.pak_internal::<T>(bytes, indices)
+    }
+    
+    /// Adds an item whose type is only known at runtime. It is paked exactly like [pak](crate::PakBuilder::pak) would pak the concrete type.
+    pub fn pak_dyn(&mut self, item : Box<dyn ErasedPakItem>) -> PakResult<PakPointer> {
+        let bytes = item.erased_bytes()?;
+        let offset = self.next_offset();
+        self.pak_chunk(PakTypedPointer::new(offset, bytes.len() as u64, item.type_name()), bytes, item.erased_indices())
+    }
+    
+    /// Adds a searchable item wrapped in a [PakEnvelope](crate::envelope::PakEnvelope) that records its type and version, so it can still be
+    /// decoded after the type changes. See [Pak::get_versioned](crate::Pak::get_versioned).
+    pub fn pak_versioned<T : PakItemSerialize + PakItemSearchable + PakVersioned>(&mut self, item : T) -> PakResult<PakPointer> {
+        let indices = item.get_indices();
+        let envelope = PakEnvelope { type_tag: std::any::type_name::<T>().to_string(), version: T::VERSION, payload: item.into_bytes()? };
+        let bytes = bincode::serialize(&envelope)?;
+        let offset = self.next_offset();
+        self.pak_chunk(PakTypedPointer::new(offset, bytes.len() as u64, std::any::type_name::<T>()).with_version(T::VERSION), bytes, indices)
+    }
+    
+    /// Adds a searchable item at a path, so it can be found with [Pak::by_path](crate::Pak::by_path) and shows up in a mounted pak. Paths
+    /// are normalized with [normalize_path](crate::path::normalize_path) and must be unique within a pak.
+    pub fn pak_with_path<T : PakItemSerialize + PakItemSearchable>(&mut self, path : &str, item : T) -> PakResult<PakPointer> {
+        let mut indices = item.get_indices();
+        indices.push(self.claim_path(path)?);
+        let bytes = item.into_bytes()?;
+        self.pak_internal::<T>(bytes, indices)
+    }
+    
+    /// Adds the contents of a file at a path as a [PakBlob](crate::path::PakBlob).
+    pub fn pak_blob(&mut self, path : &str, bytes : impl Into<PakBlob>) -> PakResult<PakPointer> {
+        let indices = vec![self.claim_path(path)?];
+        self.pak_internal::<PakBlob>(bytes.into().0, indices)
+    }
+    
+    /// Adds a blob like [pak_blob](crate::PakBuilder::pak_blob), along with a metadata item that describes it, like the dimensions and
+    /// format of a texture or its import settings. [Pak::blob_with_meta](crate::Pak::blob_with_meta) reads both.
+    pub fn pak_blob_with_meta<M : PakItemSerialize>(&mut self, path : &str, bytes : impl Into<PakBlob>, meta : M) -> PakResult<PakPointer> {
+        let meta = meta.into_bytes()?;
+        let path_index = self.claim_path(path)?;
+        self.pak_internal::<M>(meta, vec![PakIndex::new(PAK_SIDECAR_KEY, path_index.value.clone())])?;
+        self.pak_internal::<PakBlob>(bytes.into().0, vec![path_index])
+    }
+    
+    fn claim_path(&mut self, path : &str) -> PakResult<PakIndex> {
+        let path = normalize_path(path)?;
+        if !self.paths.insert(path.clone()) { return Err(error::PakError::DuplicatePath(path)) }
+        Ok(PakIndex::new(PAK_PATH_KEY, path))
+    }
+    
+    fn claim_id(&mut self, id : PakId) -> PakResult<PakIndex> {
+        if !self.ids.insert(id) { return Err(error::PakError::DuplicateId(id.to_string())) }
+        Ok(PakIndex::new(PAK_ID_KEY, id))
+    }
+    
+    fn pak_internal<T>(&mut self, bytes : Vec<u8>, indices : Vec<PakIndex>) -> PakResult<PakPointer> {
+        let offset = self.next_offset();
+        self.pak_chunk(PakTypedPointer::new(offset, bytes.len() as u64, std::any::type_name::<T>()), bytes, indices)
+    }
+    
+    /// The offset the next chunk is paked at. With an [alignment](crate::PakBuilder::with_alignment), the vault is padded with zeros first
+    /// until the chunk would start on a multiple of it in the file.
+    fn next_offset(&mut self) -> u64 {
+        if let Some(alignment) = self.alignment {
+            let start = format::V2_VAULT_START + self.size_in_bytes;
+            let padding = start.next_multiple_of(alignment) - start;
+            self.vault.resize(self.vault.len() + padding as usize, 0);
+            self.size_in_bytes += padding;
+        }
+        self.size_in_bytes
+    }
+    
+    fn pak_chunk(&mut self, pointer : PakTypedPointer, bytes : Vec<u8>, mut indices : Vec<PakIndex>) -> PakResult<PakPointer> {
+        if let Some(index) = indices.iter().find(|index| index::is_reserved_key(&index.key)) { return Err(error::PakError::ReservedKey(index.key.clone())) }
+        self.audit_item(&pointer.clone().with_generation(Some(self.generation)).into_pointer(), &indices)?;
+        #[cfg(feature = "encryption")]
+        let protected = self.protected_chunks.contains_key(&pointer.offset());
+        #[cfg(not(feature = "encryption"))]
+        let protected = false;
+        let content_key = match &self.content {
+            Some(content) if !protected => {
+                let hash = content::content_hash(&bytes);
+                let identity = pointer.clone().into_pointer();
+                let key = (identity.type_name().to_string(), identity.version(), hash);
+                if let Some(&ordinal) = content.get(&key) {
+                    let chunk = &mut self.chunks[ordinal];
+                    chunk.indices.extend(indices);
+                    return Ok(chunk.pointer.clone().with_generation(Some(self.generation)).into_pointer())
+                }
+                indices.push(PakIndex::new(content::PAK_HASH_KEY, content::to_hex(&hash)));
+                Some(key)
+            },
+            _ => None,
+        };
+        if let (Some(content), Some(key)) = (&mut self.content, content_key) { content.insert(key, self.chunks.len()); }
+        let (pointer, bytes) = match self.compression {
+            Some(compression) if !protected => {
+                let bytes = compression.compress(&bytes)?;
+                (pointer.with_size(bytes.len() as u64), bytes)
+            },
+            _ => (pointer, bytes),
+        };
+        #[cfg(feature = "encryption")]
+        let (pointer, bytes) = match &self.encryption {
+            Some((cipher, _)) => {
+                let bytes = cipher.seal(pointer.offset(), &bytes)?;
+                (pointer.with_size(bytes.len() as u64), bytes)
+            },
+            None => (pointer, bytes),
+        };
         self.size_in_bytes += bytes.len() as u64;
         self.vault.extend(bytes);
-        self.chunks.push(PakVaultReference { pointer: pointer.clone().into_typed::<T>(), indices: indices.clone() });
-        Ok(pointer)
+        self.chunks.push(PakVaultReference { pointer: pointer.clone(), indices });
+        Ok(pointer.with_generation(Some(self.generation)).into_pointer())
     }
     
     /// The current size of the pak file in bytes.
@@ -199,6 +1471,11 @@ impl PakBuilder {
         self.chunks.len()
     }
     
+    /// Returns true if nothing has been paked yet.
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+    
     /// Adds a name to the pak file's metadata.
     pub fn with_name(mut self, name: &str) -> Self {
         self.name = name.to_string();
@@ -217,6 +1494,204 @@ impl PakBuilder {
         self
     }
     
+    /// Sets how items that share the same index value are stored. By default, large posting lists are moved out of the index pages into overflow chunks. See [PakDuplicateKeys](crate::index::PakDuplicateKeys).
+    pub fn with_duplicate_keys(mut self, duplicate_keys : PakDuplicateKeys) -> Self {
+        self.duplicate_keys = duplicate_keys;
+        self
+    }
+    
+    /// Sets how the entries of every index are sorted before the trees are paked. Use [External](crate::index::PakIndexBuild::External) when
+    /// the indices are too large to build in memory.
+    pub fn with_index_build(mut self, index_build : PakIndexBuild) -> Self {
+        self.index_build = index_build;
+        self
+    }
+    
+    /// Builds a bitmap index for the key alongside its tree. This is meant for low cardinality keys like booleans or enums, where each
+    /// value is shared by many items. Queries that only touch bitmap indices are combined with bitwise operations instead of pointer sets.
+    #[cfg(feature = "roaring")]
+    pub fn with_bitmap_index(mut self, key : &str) -> Self {
+        self.bitmap_keys.insert(key.to_string());
+        self
+    }
+    
+    /// Builds a custom [PakIndexKind](crate::kind::PakIndexKind) for the key alongside its tree. Queries against the key are routed through the kind.
+    pub fn with_index_kind(mut self, key : &str, kind : impl PakIndexKind + 'static) -> Self {
+        self.custom_indices.insert(key.to_string(), Arc::new(kind));
+        self
+    }
+    
+    /// Sets whether [build_file](crate::PakBuilder::build_file) writes atomically. When enabled, which is the default, the pak is written to a
+    /// temporary file next to the target, synced to disk and then renamed over the target, so a crash never leaves a half written pak behind.
+    /// When disabled, the pak is written straight to the target path.
+    pub fn with_atomic_write(mut self, atomic_write : bool) -> Self {
+        self.atomic_write = atomic_write;
+        self
+    }
+    
+    /// Sets how the index map and the tree metadata are encoded. [Compact](crate::meta::PakEncoding::Compact) makes the header much smaller
+    /// for paks with many index keys, at a small cost when the header is read.
+    pub fn with_header_encoding(mut self, header_encoding : PakEncoding) -> Self {
+        self.header_encoding = header_encoding;
+        self
+    }
+    
+    /// Sets the generation of the pak, which defaults to 0. Bump it whenever a pak is rebuilt to replace an older one, like for hot reloading
+    /// or patches, so pointers from the old pak fail with [PakError::StalePointer](crate::error::PakError::StalePointer) instead of reading the wrong bytes.
+    pub fn with_generation(mut self, generation : u64) -> Self {
+        self.generation = generation;
+        self
+    }
+    
+    /// Encrypts every chunk of the vault with the key. This has to be set before anything is paked, and fails with
+    /// [PakError::EncryptionAfterPak](crate::error::PakError::EncryptionAfterPak) otherwise. The pak can only be read with
+    /// [Pak::open_with_key](crate::Pak::open_with_key) afterwards. See [PakEncryption](crate::meta::PakEncryption).
+    #[cfg(feature = "encryption")]
+    pub fn with_encryption(mut self, key : &crypto::PakKey) -> PakResult<Self> {
+        // Sealing grows every chunk, so chunks that were paked in the clear can't be sealed in place without moving the ones after them.
+        if !self.is_empty() { return Err(error::PakError::EncryptionAfterPak(self.len())) }
+        self.encryption = Some((crypto::PakCipher::new(key, crypto::random_salt()?, self.cipher_algorithm), None));
+        Ok(self)
+    }
+    
+    /// Adds a key that items can be encrypted with by [pak_encrypted](crate::PakBuilder::pak_encrypted), under an id that readers give the
+    /// key back with. Every id has one key, since the items sealed under it can only be opened with that key, so adding a key under an id
+    /// that already has one fails with [PakError::DuplicateKeyId](crate::error::PakError::DuplicateKeyId).
+    #[cfg(feature = "encryption")]
+    pub fn with_item_key(mut self, key_id : &str, key : &crypto::PakKey) -> PakResult<Self> {
+        if self.item_keys.iter().any(|(id, _)| id == key_id) { return Err(error::PakError::DuplicateKeyId(key_id.to_string())) }
+        let cipher = crypto::PakCipher::new(key, crypto::random_salt()?, self.cipher_algorithm);
+        self.item_keys.push((key_id.to_string(), cipher));
+        Ok(self)
+    }
+    
+    /// Encrypts the vault with a key derived from the password with Argon2id, so the pak can be opened with
+    /// [Pak::open_with_password](crate::Pak::open_with_password) without managing keys. Like [with_encryption](crate::PakBuilder::with_encryption),
+    /// this has to be set before anything is paked.
+    #[cfg(feature = "encryption")]
+    pub fn with_password(self, password : &str) -> PakResult<Self> {
+        self.with_password_kdf(password, meta::PakKdf::new()?)
+    }
+    
+    /// Like [with_password](crate::PakBuilder::with_password), with custom Argon2id costs.
+    #[cfg(feature = "encryption")]
+    pub fn with_password_kdf(mut self, password : &str, kdf : meta::PakKdf) -> PakResult<Self> {
+        if !self.is_empty() { return Err(error::PakError::EncryptionAfterPak(self.len())) }
+        let key = crypto::PakKey::derive(password, &kdf)?;
+        self.encryption = Some((crypto::PakCipher::new(&key, crypto::random_salt()?, self.cipher_algorithm), Some(kdf)));
+        Ok(self)
+    }
+    
+    /// Sets the cipher the vault and the item keys seal their chunks with, which defaults to
+    /// [XChaCha20Poly1305](crate::meta::PakCipherAlgorithm::XChaCha20Poly1305). Keys given before or after are both sealed with it, so like
+    /// [with_encryption](crate::PakBuilder::with_encryption), this fails with [PakError::EncryptionAfterPak](crate::error::PakError::EncryptionAfterPak)
+    /// once anything has been paked.
+    #[cfg(feature = "encryption")]
+    pub fn with_cipher_algorithm(mut self, algorithm : meta::PakCipherAlgorithm) -> PakResult<Self> {
+        if !self.is_empty() { return Err(error::PakError::EncryptionAfterPak(self.len())) }
+        self.cipher_algorithm = algorithm;
+        if let Some((cipher, _)) = &mut self.encryption { *cipher = cipher.with_algorithm(algorithm) }
+        for (_, cipher) in &mut self.item_keys { *cipher = cipher.with_algorithm(algorithm) }
+        Ok(self)
+    }
+    
+    /// Stores a checksum for every `block_size` bytes of the vault. Reads are checked against them, and blocks that don't match are read again
+    /// before the read fails, which makes paks on flaky storage safe to read. See [PakBlockChecksums](crate::meta::PakBlockChecksums).
+    pub fn with_block_checksums(mut self, block_size : u64) -> Self {
+        self.block_checksums = Some(block_size);
+        self
+    }
+    
+    /// Stores a checksum for every item, so items that were truncated or corrupted on disk fail to read with
+    /// [PakError::ItemChecksumMismatch](crate::error::PakError::ItemChecksumMismatch) instead of deserializing into garbage. See
+    /// [PakItemChecksums](crate::meta::PakItemChecksums).
+    pub fn with_item_checksums(mut self) -> Self {
+        self.item_checksums = true;
+        self
+    }
+    
+    /// Stores a SHA-256 hash of the whole vault in the header, so downloaded paks can be checked with [Pak::verify](crate::Pak::verify)
+    /// before they are used.
+    pub fn with_vault_hash(mut self) -> Self {
+        self.vault_hash = true;
+        self
+    }
+    
+    /// Sets the [PakFormat](crate::format::PakFormat) the pak file is laid out with, which defaults to [V1](crate::format::PakFormat::V1).
+    pub fn with_format(mut self, format : PakFormat) -> Self {
+        self.format = format;
+        self
+    }
+    
+    /// Pads the vault so every item and index page starts on a multiple of `alignment` bytes in the file, which has to be a power of two.
+    /// Reads through a [PakDirectSource](crate::direct::PakDirectSource) with the same alignment then start on a sector boundary, and items
+    /// no larger than the alignment are read with a single block. Only [V2](crate::format::PakFormat::V2) paks have a vault that starts at a
+    /// fixed offset, so this lays the pak out as V2. Call this before paking any items, since the ones before it are left where they are.
+    pub fn with_alignment(mut self, alignment : u64) -> PakResult<Self> {
+        if !alignment.is_power_of_two() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("the alignment {alignment} isn't a power of two")).into())
+        }
+        self.alignment = Some(alignment);
+        self.format = PakFormat::V2;
+        Ok(self)
+    }
+    
+    /// Stores the hash and size of every item in a manifest, which can be read back with [Pak::manifest_hashes](crate::Pak::manifest_hashes).
+    pub fn with_manifest(mut self) -> Self {
+        self.manifest = true;
+        self
+    }
+    
+    /// Copies every item of at most `max_size` bytes into the tree pages whose posting lists hold it, so a query gets tiny items like
+    /// localization strings along with the pages it reads, and never seeks into the vault for them. The items are still written to the
+    /// vault as well, since that is where their pointers read them from, so the pak grows by their size for every index they are in. Items
+    /// in overflow chunks and items encrypted with [pak_encrypted](crate::PakBuilder::pak_encrypted) are never inlined. See
+    /// [size_classes](crate::PakBuilder::size_classes) for picking the size.
+    pub fn with_inline_items(mut self, max_size : u64) -> Self {
+        self.inline_items = Some(max_size);
+        self
+    }
+    
+    /// How many of the items paked so far, and how many bytes of them, fall into each of the [size classes](crate::inline::PAK_SIZE_CLASSES).
+    pub fn size_classes(&self) -> Vec<inline::PakSizeClass> {
+        inline::size_classes(self.chunks.iter().map(|chunk| chunk.pointer.clone().into_pointer().size()))
+    }
+    
+    /// Makes the content hash of every item its identity. Items are indexed by their hash, so they can be found with
+    /// [Pak::by_hash](crate::Pak::by_hash), and an item with the same type and bytes as one that was paked before isn't stored again. The
+    /// pointer to the earlier copy is returned instead, and the indices of both are kept. Items encrypted with their own key are left out.
+    /// Call this before paking any items.
+    pub fn with_content_addressing(mut self) -> Self {
+        self.content = Some(PakHashMap::default());
+        self
+    }
+    
+    /// Compresses every item on its own before it is written to the vault, and [Pak](crate::Pak) decompresses it again when it is read. This
+    /// shrinks paks of text-heavy items a lot, at the cost of decompressing each item as it is read. Items are compressed before they are
+    /// encrypted, and items encrypted with [pak_encrypted](crate::PakBuilder::pak_encrypted) are left as they are. Call this before paking
+    /// any items.
+    #[cfg(feature = "zstd")]
+    pub fn with_compression(mut self, compression : PakCompression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+    
+    /// Compresses the index map in the header and every index structure in the vault, like the tree pages, the overflow chunks and the
+    /// bitmaps. Paks with hundreds of thousands of index entries are mostly index, so this can shrink them a lot even when the items are
+    /// small. Each tree page is decompressed when it is read, so turn on the [cache](crate::Pak::with_cache) for paks that are queried
+    /// often. The ordinal table is left as it is.
+    #[cfg(feature = "zstd")]
+    pub fn with_index_compression(mut self, compression : PakCompression) -> Self {
+        self.index_compression = Some(compression);
+        self
+    }
+    
+    /// Stores the [PakSchema](crate::schema::PakSchema) in the pak, so it can be checked when the pak is opened with [Pak::open_with_schema](crate::Pak::open_with_schema).
+    pub fn with_schema<S>(mut self) -> Self where S : PakSchema {
+        self.schema = Some(S::descriptor());
+        self
+    }
+    
     /// Sets the name of the pak file's metadata.
     pub fn set_name(&mut self, name: &str) {
         self.name = name.to_string();
@@ -234,79 +1709,325 @@ impl PakBuilder {
     
     /// Builds the pak file and writes it to the specified path. This also returns a [Pak](crate::Pak) object that is attached to that file.
     pub fn build_file(self, path : impl AsRef<Path>) -> PakResult<Pak> {
-        let (out, sizing, meta) = self.build_internal()?;
+        let atomic_write = self.atomic_write;
+        let setup = self.reader_setup();
+        let (out, layout, meta) = self.build_internal()?;
         
-        fs::write(&path, out)?;
-        let pak  = Pak {
-            sizing,
-            meta,
-            source: RefCell::new(Box::new(BufReader::new(File::open(path)?))),
-        };
+        if atomic_write {
+            write_atomic(path.as_ref(), &out)?;
+        } else {
+            fs::write(&path, out)?;
+        }
+        let mut pak = Pak::from_parts(layout, meta, BufReader::new(File::open(path)?));
+        setup(&mut pak);
         Ok(pak)
     }
     
+    /// Encrypts the pak with the key and writes it to the path, like [with_encryption](crate::PakBuilder::with_encryption) followed by
+    /// [build_file](crate::PakBuilder::build_file), but the key doesn't have to be known before the items are paked. A builder that isn't
+    /// empty is built in memory and then sealed as a whole, which moves every item, so the pak is a generation after the pointers handed out
+    /// while paking, and they fail with [PakError::StalePointer](crate::error::PakError::StalePointer). References between the items are
+    /// rewritten like they are by [repack_reordered](crate::Pak::repack_reordered).
+    ///
+    /// The pak is sealed with the builder's [cipher algorithm](crate::meta::PakCipherAlgorithm), which is XChaCha20-Poly1305 unless
+    /// [with_cipher_algorithm](crate::PakBuilder::with_cipher_algorithm) was called. Call it with
+    /// [Aes256Gcm](crate::meta::PakCipherAlgorithm::Aes256Gcm) before paking anything to seal the pak with AES-256-GCM instead.
+    #[cfg(feature = "encryption")]
+    pub fn build_file_encrypted(self, path : impl AsRef<Path>, key : &crypto::PakKey) -> PakResult<Pak> {
+        if self.is_empty() { return self.with_encryption(key)?.build_file(path) }
+        let (atomic_write, algorithm) = (self.atomic_write, self.cipher_algorithm);
+        let pak = self.build_in_memory()?;
+        let mut builder = pak.rebuilder()?;
+        builder.encryption = Some((crypto::PakCipher::new(key, crypto::random_salt()?, algorithm), None));
+        builder.cipher_algorithm = algorithm;
+        builder.atomic_write = atomic_write;
+        pak.rebuild(builder, (0..pak.ordinals()?.len()).collect())?.build_file(path)
+    }
+    
     /// Builds the pak file and writes it to the specified path. This also returns a [Pak](crate::Pak) object that is attached to that slice of memory.
     pub fn build_in_memory(self) -> PakResult<Pak> {
-        let (out, sizing, meta) = self.build_internal()?;
-        
-        let pak = Pak {
-            sizing,
-            meta,
-            source: RefCell::new(Box::new(Cursor::new(out))),
-        };
+        let setup = self.reader_setup();
+        let (out, layout, meta) = self.build_internal()?;
+        let mut pak = Pak::from_parts(layout, meta, Cursor::new(out));
+        setup(&mut pak);
         Ok(pak)
     }
     
-    fn build_internal(mut self)  -> PakResult<(Vec<u8>, PakSizing, PakMeta)> {
-        let mut map : HashMap<String, PakTreeBuilder> = HashMap::new();
-        for chunk in &self.chunks {
-            for index in &chunk.indices{
-                map.entry(index.key.clone())
-                    .or_insert(PakTreeBuilder::new(6))
-                    .access()
-                    .insert(index.value.clone(), chunk.pointer.clone())
-                ;
+    #[cfg(feature = "encryption")]
+    fn protection(&self) -> PakResult<Option<meta::PakProtection>> {
+        if self.item_keys.is_empty() { return Ok(None) }
+        let keys = self.item_keys.iter().map(|(id, cipher)| Ok((id.clone(), cipher.encryption(None)?))).collect::<PakResult<Vec<_>>>()?;
+        Ok(Some(meta::PakProtection { keys, chunks : self.protected_chunks.clone() }))
+    }
+    
+    /// The bytes of an item that has already been paked, with the encryption and compression taken off again.
+    fn item_bytes(&self, pointer : &PakTypedPointer) -> PakResult<Vec<u8>> {
+        let pointer = pointer.clone().into_pointer();
+        let bytes = self.vault[pointer::byte_range(pointer.offset(), pointer.size())?].to_vec();
+        #[cfg(feature = "encryption")]
+        let bytes = match &self.encryption {
+            Some((cipher, _)) => cipher.open(pointer.offset(), &bytes)?,
+            None => bytes,
+        };
+        #[cfg(feature = "encryption")]
+        if let Some(index) = self.protected_chunks.get(&pointer.offset()) {
+            return self.item_keys[*index as usize].1.open(pointer.offset(), &bytes)
+        }
+        match self.compression {
+            Some(compression) => compression.decompress(pointer.offset(), &bytes),
+            None => Ok(bytes),
+        }
+    }
+    
+    /// The items of at most `max_size` bytes by ordinal, for [with_inline_items](crate::PakBuilder::with_inline_items).
+    fn inline_items(&self, ordinals : &[PakTypedPointer], max_size : u64) -> PakResult<HashMap<u32, Vec<u8>>> {
+        let mut items = HashMap::new();
+        for (ordinal, pointer) in ordinals.iter().enumerate() {
+            #[cfg(feature = "encryption")]
+            if self.protected_chunks.contains_key(&pointer.offset()) { continue }
+            let bytes = self.item_bytes(pointer)?;
+            if bytes.len() as u64 <= max_size { items.insert(ordinal as u32, bytes); }
+        }
+        Ok(items)
+    }
+    
+    /// Hands the pak returned by a build everything it needs that isn't stored in the file, like the index kinds and the key.
+    fn reader_setup(&self) -> impl FnOnce(&mut Pak) + use<> {
+        let kinds = self.custom_indices.values().cloned().collect::<Vec<_>>();
+        #[cfg(feature = "encryption")]
+        let cipher = self.encryption.as_ref().map(|(cipher, _)| cipher.clone());
+        #[cfg(feature = "encryption")]
+        let item_ciphers = self.item_keys.iter().cloned().collect::<HashMap<_, _>>();
+        move |pak| {
+            kinds.into_iter().for_each(|kind| pak.register_index_kind(kind));
+            #[cfg(feature = "encryption")]
+            {
+                pak.cipher = cipher;
+                pak.item_ciphers = item_ciphers;
             }
         }
+    }
+    
+    fn build_internal(self)  -> PakResult<(Vec<u8>, PakLayout, PakMeta)> {
+        let format = self.format;
+        let laid_out = self.lay_out()?;
+        let (out, layout) = format.driver().write(&laid_out.meta, &laid_out.indices, &laid_out.vault)?;
+        Ok((out, layout, laid_out.meta))
+    }
+    
+    /// Works out the layout of the pak the builder would build, without laying out its bytes or writing anything. The items and the index
+    /// structures are still paked into the vault, so the plan matches the real build exactly, which lets CI check size budgets or catch
+    /// changes to the layout cheaply.
+    pub fn plan(self) -> PakResult<plan::PakPlan> {
+        let format = self.format;
+        let laid_out = self.lay_out()?;
+        let (layout, size) = format.driver().plan(&laid_out.meta, &laid_out.indices, laid_out.vault.len() as u64)?;
+        let indices = laid_out.indices.iter().map(|(key, pointer)| {
+            let index = plan::PakPlannedIndex {
+                kind : laid_out.meta.index_kinds.get(key).copied().unwrap_or(PakValueKind::Void),
+                root : pointer.as_pointer(),
+                size : laid_out.index_sizes.get(key).copied().unwrap_or_default(),
+            };
+            (key.clone(), index)
+        }).collect();
+        Ok(plan::PakPlan {
+            format,
+            size,
+            meta_size : bincode::serialized_size(&laid_out.meta)?,
+            index_map : layout.indices,
+            vault_start : layout.vault_start,
+            vault_size : layout.vault_len,
+            item_size : laid_out.items.iter().map(|pointer| pointer.size()).sum(),
+            items : laid_out.items,
+            indices,
+        })
+    }
+    
+    /// Paks the index structures into the vault and puts together the meta, leaving only the file itself to be laid out.
+    fn lay_out(mut self) -> PakResult<PakLaidOut> {
+        // Every chunk paked so far is an item, so its position is its ordinal. The index structures are paked after this point, and are
+        // never deduplicated.
+        self.content = None;
+        let ordinals = self.chunks.iter().map(|chunk| chunk.pointer.clone()).collect::<Vec<_>>();
+        let items = ordinals.iter().map(|pointer| pointer.clone().into_pointer()).collect::<Vec<_>>();
+        let manifest = match self.manifest {
+            true => Some(ordinals.iter().map(|pointer| Ok(PakManifestEntry::new(pointer.clone().into_pointer().type_name(), &self.item_bytes(pointer)?))).collect::<PakResult<Vec<_>>>()?),
+            false => None,
+        };
+        // The inlined items are paked after the tree pages that hold them, so only the size is recorded for the meta.
+        if let Some(max_size) = self.inline_items { self.inlined = self.inline_items(&ordinals, max_size)? }
+        let inlined = self.inline_items.map(|max_size| inline::PakInlineTable { max_size, items : Vec::new() });
+        // The items are compressed with the item compression and the structures after the ordinal table with the index compression. The
+        // ordinal table sits between the two and is never compressed, so readers can tell them apart by it.
+        let compression = self.compression.take();
+        let ordinals = self.pak_no_search(ordinals)?.as_untyped();
+        self.compression = self.index_compression;
+        let manifest = manifest.map(|manifest| self.pak_no_search(manifest)).transpose()?.map(|pointer| pointer.as_untyped());
+        let inlined = inlined.map(|inlined| self.pak_no_search(inlined)).transpose()?.map(|pointer| pointer.as_untyped());
         
-        let mut pointer_map : HashMap<String, PakUntypedPointer> = HashMap::new();
-        for (key, tree) in map {
-            let pointer = tree.into_pak(&mut self)?;
-            pointer_map.insert(key, pointer.as_untyped());
+        let mut map : PakHashMap<String, PakTreeBuilder> = PakHashMap::default();
+        let mut runs : PakHashMap<String, sort::PakRunSorter> = PakHashMap::default();
+        let mut index_kinds : PakHashMap<String, PakValueKind> = PakHashMap::default();
+        #[cfg(feature = "roaring")]
+        let mut bitmaps : PakHashMap<String, bitmap::PakBitmapBuilder> = PakHashMap::default();
+        let mut custom_entries : PakHashMap<String, Vec<(PakValue, u32)>> = PakHashMap::default();
+        for (ordinal, chunk) in self.chunks.iter_mut().enumerate() {
+            // The indices are moved out of the chunks, so an external build doesn't hold on to the entries it has spilled.
+            for index in std::mem::take(&mut chunk.indices) {
+                let kind = index_kinds.entry(index.key.clone()).or_insert(PakValueKind::Void);
+                *kind = kind.merge(index.value.kind());
+                if self.custom_indices.contains_key(&index.key) {
+                    custom_entries.entry(index.key.clone()).or_default().push((index.value.clone(), ordinal as u32));
+                }
+                #[cfg(feature = "roaring")]
+                if self.bitmap_keys.contains(&index.key) {
+                    bitmaps.entry(index.key.clone()).or_default().insert(index.value.clone(), ordinal as u32);
+                }
+                match &self.index_build {
+                    PakIndexBuild::InMemory => {
+                        map.entry(index.key)
+                            .or_insert(PakTreeBuilder::new(6))
+                            .access()
+                            .insert(index.value, ordinal as u32)
+                        ;
+                    },
+                    PakIndexBuild::External { run_entries, directory } => {
+                        runs.entry(index.key)
+                            .or_insert_with(|| sort::PakRunSorter::new(directory.clone(), *run_entries))
+                            .push(index.value, ordinal as u32)?
+                        ;
+                    },
+                }
+            }
+        }
+        
+        let duplicate_keys = self.duplicate_keys;
+        let mut pointer_map = PakIndexMap::default();
+        let mut index_sizes : HashMap<String, u64> = HashMap::new();
+        for key in index_kinds.keys() {
+            let index_start = self.vault.len() as u64;
+            #[cfg(feature = "roaring")]
+            let bitmap = bitmaps.remove(key).map(|bitmap| bitmap.into_pak(&mut self)).transpose()?;
+            #[cfg(not(feature = "roaring"))]
+            let bitmap = None;
+            let custom = match (self.custom_indices.get(key).cloned(), custom_entries.remove(key)) {
+                (Some(kind), Some(entries)) => {
+                    let bytes = kind.build(&entries)?;
+                    Some((kind.name().to_string(), self.pak_internal::<PakIndexKindData>(bytes, vec![])?.as_untyped()))
+                },
+                _ => None,
+            };
+            let pointer = match (map.remove(key), runs.remove(key)) {
+                (Some(tree), _) => tree.into_pak(&mut self, duplicate_keys, bitmap, custom)?,
+                (None, Some(run)) => {
+                    let mut loader = PakTreeBulkLoader::new(6, duplicate_keys);
+                    run.merge(|value, ordinal| loader.push(&mut self, value, ordinal))?;
+                    loader.into_pak(&mut self, bitmap, custom)?
+                },
+                (None, None) => continue,
+            };
+            pointer_map.insert(key.clone(), pointer.as_untyped());
+            index_sizes.insert(key.clone(), self.vault.len() as u64 - index_start);
         }
         
+        #[cfg(feature = "encryption")]
+        let protection = self.protection()?;
+        #[cfg(not(feature = "encryption"))]
+        let protection = None;
+        let item_checksums = match self.item_checksums {
+            true => Some(meta::PakItemChecksums::build(&self.vault, items.iter().map(|pointer| (pointer.offset(), pointer.size())))?),
+            false => None,
+        };
         let meta = PakMeta {
             name: self.name,
             description: self.description,
             author: self.author,
             version: "1.0".to_string(),
+            revision: meta::PAK_META_REVISION,
+            ordinals,
+            index_kinds,
+            schema: self.schema,
+            header_encoding: self.header_encoding,
+            generation: self.generation,
+            checksums: self.block_checksums.map(|block_size| PakBlockChecksums::build(&self.vault, block_size)),
+            #[cfg(feature = "encryption")]
+            encryption: self.encryption.as_ref().map(|(cipher, kdf)| cipher.encryption(kdf.clone())).transpose()?,
+            #[cfg(not(feature = "encryption"))]
+            encryption: None,
+            protection,
+            manifest,
+            inlined,
+            compression,
+            index_compression: self.index_compression,
+            item_checksums,
+            vault_hash: self.vault_hash.then(|| PakMeta::hash_vault(&self.vault)),
         };
-        
-        let sizing = PakSizing {
-            meta_size: bincode::serialized_size(&meta)?,
-            indices_size: bincode::serialized_size(&pointer_map)?,
-            vault_size: bincode::serialized_size(&self.vault)?,
-        };
-        
-        let mut sizing_out = bincode::serialize(&sizing)?;
-        let mut meta_out = bincode::serialize(&meta)?;
-        let mut pointer_map_out = bincode::serialize(&pointer_map)?;
-        let mut vault_out = bincode::serialize(&self.vault)?;
-        
-        let mut out = Vec::<u8>::new();
-        out.append(&mut sizing_out);
-        out.append(&mut meta_out);
-        out.append(&mut pointer_map_out);
-        out.append(&mut vault_out);
-        Ok((out, sizing, meta))
+        Ok(PakLaidOut { meta, indices : pointer_map, vault : self.vault, items, index_sizes })
     }
     
 }
 
+impl Default for PakBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How many times a reordered repack works out the offsets of its items before it gives up on them settling.
+const REPACK_ROUNDS : usize = 16;
+
+/// How much of the vault [Pak::verify](crate::Pak::verify) reads at a time.
+const VERIFY_CHUNK_SIZE : u64 = 1024 * 1024;
+
+fn missing_key(key_id : Option<&str>) -> error::PakError {
+    match key_id {
+        Some(key_id) => error::PakError::KeyRequired(key_id.to_string()),
+        None => error::PakError::MissingKey,
+    }
+}
+
+/// Writes the bytes to a temporary file in the same directory as the path, syncs it, and then renames it over the path.
+fn write_atomic(path : &Path, bytes : &[u8]) -> PakResult<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    let temp_path = dir.join(format!(".{file_name}.{}.tmp", std::process::id()));
+    
+    let result = (|| {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)?;
+        // Syncing the directory makes the rename itself durable. Not every platform can open a directory, so this is best effort.
+        #[cfg(unix)]
+        if let Ok(dir) = File::open(dir) { let _ = dir.sync_all(); }
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Everything a pak is made of, before it is laid out into a file.
+struct PakLaidOut {
+    meta : PakMeta,
+    indices : PakIndexMap,
+    vault : Vec<u8>,
+    items : Vec<PakPointer>,
+    /// The number of vault bytes taken up by the structures of each index.
+    index_sizes : HashMap<String, u64>,
+}
+
 //==============================================================================================
 //        PakVaultReference
 //==============================================================================================
 
+/// The type name that the structures of custom index kinds are paked under.
+struct PakIndexKindData;
+
 #[derive(Debug, Clone)]
 pub(crate) struct PakVaultReference {
     pointer : PakTypedPointer,
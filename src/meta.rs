@@ -1,12 +1,409 @@
-use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use bincode::Options;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::{error::{PakError, PakResult}, hash::{PakHashMap, PakIndexMap}, pointer::{to_usize, PakPointer, PakUntypedPointer}, schema::PakSchemaDescriptor, value::PakValueKind, PakSource};
+
+/// The revision of the meta that this version of the crate writes. The meta starts with the four fields that paks written by pak-db 0.1
+/// stop at, and every field after them is behind the revision, which is written right after the author. The revision covers how the index
+/// structures the meta points to are encoded too, like the tree pages and their [overflow chunks](crate::index::PakDuplicateKeys), and how
+/// the [encryption](crate::meta::PakEncryption) is described. Adding a field to any of them needs a new revision, along with a way to keep
+/// reading the ones before it.
+pub const PAK_META_REVISION : u32 = 1;
 
 /// The metadata for a Pak file. Each pak file has this data embedded within the header.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PakMeta {
     pub name: String,
     pub version: String,
     pub description: String,
     pub author: String,
+    /// The revision every field after this one is written with, see [PAK_META_REVISION](crate::meta::PAK_META_REVISION). Paks written by
+    /// pak-db 0.1 have no revision and are read as revision 0, with every field after this one left empty.
+    pub revision: u32,
+    /// Points to the table of item pointers, indexed by each item's ordinal.
+    pub ordinals: PakUntypedPointer,
+    /// The kind of values held by each index, used to reject queries that compare against the wrong kind.
+    pub index_kinds: PakHashMap<String, PakValueKind>,
+    /// The schema the pak was built with, if any.
+    pub schema: Option<PakSchemaDescriptor>,
+    /// How the index map and the tree metadata are encoded.
+    pub header_encoding: PakEncoding,
+    /// The generation of the pak. Pointers handed out by the pak are tagged with it, so pointers from another generation can be rejected.
+    pub generation: u64,
+    /// The checksum of every block of the vault, if the pak was built with them.
+    pub checksums: Option<PakBlockChecksums>,
+    /// How the vault is encrypted, if it is.
+    pub encryption: Option<PakEncryption>,
+    /// The items that were encrypted on their own, if there are any.
+    pub protection: Option<PakProtection>,
+    /// Points to the manifest of item hashes, if the pak was built with one.
+    pub manifest: Option<PakUntypedPointer>,
+    /// Points to the table of inlined items, if the pak was built with [with_inline_items](crate::PakBuilder::with_inline_items).
+    pub inlined: Option<PakUntypedPointer>,
+    /// How the items are compressed, if they are.
+    pub compression: Option<PakCompression>,
+    /// How the index map and the index structures in the vault are compressed, if they are.
+    pub index_compression: Option<PakCompression>,
+    /// The checksum of every item, if the pak was built with them.
+    pub item_checksums: Option<PakItemChecksums>,
+    /// The SHA-256 hash of the whole vault as it is stored, if the pak was built with one. See [Pak::verify](crate::Pak::verify).
+    pub vault_hash: Option<[u8; 32]>,
+}
+
+impl PakMeta {
+    /// Reads the meta from the bytes of its section, picking the fields to read by the revision that follows the first four.
+    pub(crate) fn decode(bytes : &[u8]) -> PakResult<Self> {
+        let header : PakMetaHeader = bincode::deserialize(bytes)?;
+        let rest = bytes.get(to_usize(bincode::serialized_size(&header)?)?..).unwrap_or_default();
+        if rest.is_empty() { return Ok(header.into_meta()) }
+        let Some(revision) = rest.get(..4) else {
+            return Err(PakError::InvalidHeader("meta".to_string(), "it ends in the middle of its revision".to_string()))
+        };
+        match u32::from_le_bytes(revision.try_into().unwrap()) {
+            PAK_META_REVISION => Ok(bincode::deserialize(bytes)?),
+            revision => Err(PakError::UnsupportedRevision(revision)),
+        }
+    }
+    
+    /// Hashes the vault as it is stored, for [vault_hash](crate::meta::PakMeta::vault_hash).
+    pub(crate) fn hash_vault(vault : &[u8]) -> [u8; 32] {
+        Sha256::digest(vault).into()
+    }
+    
+    /// Encodes the index map the way it is stored in the header.
+    pub(crate) fn serialize_indices(&self, indices : &PakIndexMap) -> PakResult<Vec<u8>> {
+        let bytes = self.header_encoding.serialize(indices)?;
+        match self.index_compression {
+            Some(compression) => compression.compress(&bytes),
+            None => Ok(bytes),
+        }
+    }
+    
+    /// Decodes the index map from the bytes stored in the header.
+    pub(crate) fn deserialize_indices(&self, bytes : &[u8]) -> PakResult<PakIndexMap> {
+        let Some(compression) = self.index_compression else { return self.header_encoding.deserialize(bytes) };
+        let bytes = compression.decompress(0, bytes).map_err(|error| match error {
+            PakError::DecompressionFailed(_) => PakError::InvalidHeader("indices".to_string(), "the index map couldn't be decompressed".to_string()),
+            error => error,
+        })?;
+        self.header_encoding.deserialize(&bytes)
+    }
+}
+
+/// The fields every meta starts with, which were the whole meta of paks written by pak-db 0.1.
+#[derive(Serialize, Deserialize)]
+struct PakMetaHeader {
+    name: String,
+    version: String,
+    description: String,
+    author: String,
+}
+
+impl PakMetaHeader {
+    /// The meta of a pak written by pak-db 0.1, which is revision 0.
+    fn into_meta(self) -> PakMeta {
+        PakMeta {
+            name: self.name,
+            version: self.version,
+            description: self.description,
+            author: self.author,
+            revision: 0,
+            ordinals: PakUntypedPointer::default(),
+            index_kinds: PakHashMap::default(),
+            schema: None,
+            header_encoding: PakEncoding::Fixed,
+            generation: 0,
+            checksums: None,
+            encryption: None,
+            protection: None,
+            manifest: None,
+            inlined: None,
+            compression: None,
+            index_compression: None,
+            item_checksums: None,
+            vault_hash: None,
+        }
+    }
+}
+
+//==============================================================================================
+//        PakEncoding
+//==============================================================================================
+
+/// How the index map and the metadata of each tree are encoded. The sizing and meta are always fixed width, so the encoding can be read
+/// before anything that depends on it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PakEncoding {
+    /// Every integer takes its full width. This is the fastest to decode.
+    #[default]
+    Fixed,
+    /// Integers and lengths are written as varints, which makes the header a lot smaller for paks with many index keys.
+    Compact,
+}
+
+impl PakEncoding {
+    pub(crate) fn serialize<T>(self, value : &T) -> PakResult<Vec<u8>> where T : Serialize + ?Sized {
+        Ok(match self {
+            PakEncoding::Fixed => bincode::serialize(value)?,
+            PakEncoding::Compact => bincode::options().serialize(value)?,
+        })
+    }
+    
+    pub(crate) fn deserialize<T>(self, bytes : &[u8]) -> PakResult<T> where T : DeserializeOwned {
+        Ok(match self {
+            PakEncoding::Fixed => bincode::deserialize(bytes)?,
+            PakEncoding::Compact => bincode::options().allow_trailing_bytes().deserialize(bytes)?,
+        })
+    }
+}
+
+//==============================================================================================
+//        PakCompression
+//==============================================================================================
+
+/// How the items of a pak are compressed, set with [with_compression](crate::PakBuilder::with_compression), or its index structures, set
+/// with [with_index_compression](crate::PakBuilder::with_index_compression). Every chunk is compressed on its own, so chunks can still be
+/// read one at a time, and is compressed before it is encrypted.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PakCompression {
+    /// Zstandard at the level, from 1 to 22, or 0 for its default. Reading and writing these paks needs the `zstd` feature.
+    Zstd(i32),
+}
+
+impl PakCompression {
+    #[cfg(feature = "zstd")]
+    pub(crate) fn compress(self, bytes : &[u8]) -> PakResult<Vec<u8>> {
+        match self {
+            PakCompression::Zstd(level) => Ok(zstd::bulk::compress(bytes, level)?),
+        }
+    }
+    
+    #[cfg(not(feature = "zstd"))]
+    pub(crate) fn compress(self, _bytes : &[u8]) -> PakResult<Vec<u8>> {
+        Err(self.unavailable())
+    }
+    
+    /// Decompresses the chunk at the offset, failing with [PakError::DecompressionFailed](crate::error::PakError::DecompressionFailed) if
+    /// its bytes aren't what was compressed. The output is capped at the size the chunk recorded, so a chunk can't decompress past it.
+    #[cfg(feature = "zstd")]
+    pub(crate) fn decompress(self, offset : u64, bytes : &[u8]) -> PakResult<Vec<u8>> {
+        use std::io::Read;
+        let size = self.decompressed_size(offset, bytes)?;
+        match self {
+            PakCompression::Zstd(_) => {
+                let decoder = zstd::stream::read::Decoder::with_buffer(bytes).map_err(|_| PakError::DecompressionFailed(offset))?;
+                let mut decompressed = Vec::new();
+                decoder.take(size.saturating_add(1)).read_to_end(&mut decompressed).map_err(|_| PakError::DecompressionFailed(offset))?;
+                if decompressed.len() as u64 != size { return Err(PakError::DecompressionFailed(offset)) }
+                Ok(decompressed)
+            },
+        }
+    }
+    
+    #[cfg(not(feature = "zstd"))]
+    pub(crate) fn decompress(self, _offset : u64, _bytes : &[u8]) -> PakResult<Vec<u8>> {
+        Err(self.unavailable())
+    }
+    
+    /// The size of the chunk at the offset once it is decompressed, which the compressor records in the first
+    /// [header_size](crate::meta::PakCompression::header_size) bytes of the chunk, so it is known without decompressing anything.
+    #[cfg(feature = "zstd")]
+    pub(crate) fn decompressed_size(self, offset : u64, bytes : &[u8]) -> PakResult<u64> {
+        match self {
+            PakCompression::Zstd(_) => match zstd::zstd_safe::get_frame_content_size(bytes) {
+                Ok(Some(size)) => Ok(size),
+                _ => Err(PakError::DecompressionFailed(offset)),
+            },
+        }
+    }
+    
+    #[cfg(not(feature = "zstd"))]
+    pub(crate) fn decompressed_size(self, _offset : u64, _bytes : &[u8]) -> PakResult<u64> {
+        Err(self.unavailable())
+    }
+    
+    /// The most bytes at the start of a chunk that [decompressed_size](crate::meta::PakCompression::decompressed_size) needs.
+    pub(crate) fn header_size(self) -> u64 {
+        match self {
+            PakCompression::Zstd(_) => 18,
+        }
+    }
+    
+    #[cfg(not(feature = "zstd"))]
+    fn unavailable(self) -> PakError {
+        match self {
+            PakCompression::Zstd(_) => PakError::CompressionUnavailable("zstd".to_string()),
+        }
+    }
+}
+
+//==============================================================================================
+//        PakEncryption
+//==============================================================================================
+
+/// How the vault of an encrypted pak is encrypted. Every chunk of the vault is sealed on its own, so items can still be read one at a time.
+/// The cipher is either XChaCha20-Poly1305 or AES-256-GCM, and `algorithm` records which one, so readers pick the right one from the header
+/// without being told. Builders use XChaCha20-Poly1305 unless [with_cipher_algorithm](crate::PakBuilder::with_cipher_algorithm) says otherwise.
+/// The header isn't encrypted, so the names of the index keys of an encrypted pak are not secret, but their values are. Its fields are part
+/// of the [meta revision](crate::meta::PAK_META_REVISION).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PakEncryption {
+    /// Random bytes that make the nonces of this pak different from those of every other pak.
+    pub salt: [u8; 16],
+    /// Nothing, sealed with the key, so a wrong key is caught as soon as the pak is opened.
+    pub check: Vec<u8>,
+    /// How the key is derived from a password, if the pak was encrypted with one.
+    pub kdf: Option<PakKdf>,
+    /// The cipher the chunks are sealed with.
+    pub algorithm: PakCipherAlgorithm,
+}
+
+/// The AEAD cipher an encrypted pak is sealed with, set with [with_cipher_algorithm](crate::PakBuilder::with_cipher_algorithm). Readers
+/// pick it up from the header, so opening a pak works the same for both.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PakCipherAlgorithm {
+    /// XChaCha20-Poly1305, which is fast everywhere, even without hardware support for AES.
+    #[default]
+    XChaCha20Poly1305,
+    /// AES-256-GCM, for platforms and policies that call for AES. Each pak seals its chunks with its own subkey, derived from the key and
+    /// the salt, since the 96 bit nonces of GCM are too short to hold a random salt safely.
+    Aes256Gcm,
+}
+
+/// The keys of the items that were encrypted on their own with [PakBuilder::pak_encrypted](crate::PakBuilder::pak_encrypted), while the rest of
+/// the pak stays readable. Each key is sealed like a whole [PakEncryption](crate::meta::PakEncryption) and is looked up by its id.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PakProtection {
+    pub keys: Vec<(String, PakEncryption)>,
+    /// The offset of every protected chunk and the index of the key it was encrypted with.
+    pub chunks: BTreeMap<u64, u32>,
+}
+
+impl PakProtection {
+    /// The id of the key that the chunk at the offset was encrypted with, if it is protected.
+    pub fn key_of(&self, offset : u64) -> Option<&str> {
+        let index = *self.chunks.get(&offset)?;
+        self.keys.get(index as usize).map(|(id, _)| id.as_str())
+    }
+}
+
+/// The salt and costs that Argon2id derives the key of a password protected pak with.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PakKdf {
+    pub salt: [u8; 16],
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+//==============================================================================================
+//        PakManifestEntry
+//==============================================================================================
+
+/// The SHA-256 hash and size of an item's bytes, as they are before any encryption. A pak built with
+/// [with_manifest](crate::PakBuilder::with_manifest) stores one for every item, in the order of the item ordinals, so audit tooling can
+/// check a pak against a published manifest. See [Pak::manifest_hashes](crate::Pak::manifest_hashes).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PakManifestEntry {
+    pub type_name: String,
+    pub size: u64,
+    pub hash: [u8; 32],
+}
+
+impl PakManifestEntry {
+    pub(crate) fn new(type_name : &str, bytes : &[u8]) -> Self {
+        Self { type_name : type_name.to_string(), size : bytes.len() as u64, hash : Sha256::digest(bytes).into() }
+    }
+    
+    /// The hash as lowercase hex, the way checksums are usually published.
+    pub fn hash_hex(&self) -> String {
+        self.hash.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+}
+
+//==============================================================================================
+//        PakItemChecksums
+//==============================================================================================
+
+/// A CRC32 checksum of every item as it is stored in the vault, by the offset of the item along with its size. Every read of a whole item
+/// is checked against it, so an item that was truncated or has rotted on disk fails with
+/// [PakError::ItemChecksumMismatch](crate::error::PakError::ItemChecksumMismatch) instead of deserializing into garbage. See
+/// [with_item_checksums](crate::PakBuilder::with_item_checksums).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PakItemChecksums {
+    pub items: BTreeMap<u64, (u64, u32)>,
+}
+
+impl PakItemChecksums {
+    /// Checksums the chunks at each offset and size in the vault.
+    pub(crate) fn build(vault : &[u8], items : impl IntoIterator<Item = (u64, u64)>) -> PakResult<Self> {
+        let items = items.into_iter().map(|(offset, size)| {
+            let bytes = vault.get(crate::pointer::byte_range(offset, size)?).ok_or_else(|| PakError::InvalidHeader("ordinals".to_string(), format!("the item at {offset} is past the end of the vault")))?;
+            Ok((offset, (size, crc32fast::hash(bytes))))
+        }).collect::<PakResult<_>>()?;
+        Ok(Self { items })
+    }
+    
+    /// Checks bytes that were read at the pointer. Reads that don't cover exactly one item, like the parts of an item read through a
+    /// [PakWindow](crate::window::PakWindow), have nothing to check against.
+    pub(crate) fn check(&self, pointer : &PakPointer, bytes : &[u8]) -> PakResult<()> {
+        match self.items.get(&pointer.offset()) {
+            Some(&(size, checksum)) if size == pointer.size() && crc32fast::hash(bytes) != checksum => Err(PakError::ItemChecksumMismatch(pointer.offset())),
+            _ => Ok(()),
+        }
+    }
+}
+
+//==============================================================================================
+//        PakBlockChecksums
+//==============================================================================================
+
+/// A CRC32 checksum for every block of the vault, for paks that are read from flaky storage like network drives. Every read is widened to
+/// whole blocks and checked against the checksums, and blocks that don't match are read again before the read fails with
+/// [PakError::ChecksumMismatch](crate::error::PakError::ChecksumMismatch).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PakBlockChecksums {
+    pub block_size: u64,
+    pub blocks: Vec<u32>,
+}
+
+impl PakBlockChecksums {
+    pub(crate) fn build(vault : &[u8], block_size : u64) -> Self {
+        let block_size = block_size.max(1);
+        Self { block_size, blocks : vault.chunks(usize::try_from(block_size).unwrap_or(usize::MAX)).map(crc32fast::hash).collect() }
+    }
+    
+    /// Reads the bytes at the pointer, reading a block up to `retries` more times for as long as it doesn't match its checksum.
+    pub(crate) fn read(&self, source : &mut dyn PakSource, vault_start : u64, vault_len : u64, pointer : &PakPointer, retries : u32) -> PakResult<Vec<u8>> {
+        let Some(end) = pointer.offset().checked_add(pointer.size()).filter(|end| pointer.size() > 0 && *end <= vault_len) else {
+            return source.read(pointer, vault_start)
+        };
+        let block_size = usize::try_from(self.block_size).unwrap_or(usize::MAX);
+        
+        let first = pointer.offset() / self.block_size;
+        let last = (end - 1) / self.block_size;
+        let region_start = first * self.block_size;
+        let region_end = ((last + 1) * self.block_size).min(vault_len);
+        let mut region = source.read(&PakPointer::new_untyped(region_start, region_end - region_start), vault_start)?;
+        
+        for block in first..=last {
+            let start = to_usize((block - first) * self.block_size)?;
+            let end = start.saturating_add(block_size).min(region.len());
+            let mut attempts = 1;
+            while self.blocks.get(to_usize(block)?) != Some(&crc32fast::hash(&region[start..end])) {
+                if attempts > retries { return Err(PakError::ChecksumMismatch(block, attempts)) }
+                let bytes = source.read(&PakPointer::new_untyped(region_start + start as u64, (end - start) as u64), vault_start)?;
+                region[start..end].copy_from_slice(&bytes);
+                attempts += 1;
+            }
+        }
+        
+        let offset = to_usize(pointer.offset() - region_start)?;
+        region.truncate(offset + to_usize(pointer.size())?);
+        region.drain(..offset);
+        Ok(region)
+    }
 }
 
 /// This carries the size information of each part of the Pak file. this is always the first 24 bytes of the file.
@@ -15,4 +412,84 @@ pub struct PakSizing {
     pub meta_size: u64,
     pub indices_size: u64,
     pub vault_size: u64,
-}
\ No newline at end of file
+}
+impl PakSizing {
+    /// The largest meta or index section that will be read when the length of the source isn't known.
+    pub const MAX_HEADER_SECTION : u64 = 64 * 1024 * 1024;
+    
+    /// Checks that the sizes make sense before anything is allocated for them. If the length of the source is known, the sections must add up to it exactly.
+    pub fn validate(&self, source_len : Option<u64>) -> PakResult<()> {
+        let invalid = |field : &str, reason : String| Err(PakError::InvalidHeader(field.to_string(), reason));
+        
+        let fields = [("meta_size", self.meta_size), ("indices_size", self.indices_size), ("vault_size", self.vault_size)];
+        match source_len {
+            Some(len) => for (field, size) in fields {
+                if size > len { return invalid(field, format!("{size} is larger than the whole source, which is {len} bytes")) }
+            },
+            None => for (field, size) in &fields[..2] {
+                if *size > Self::MAX_HEADER_SECTION { return invalid(field, format!("{size} is larger than the limit of {} bytes", Self::MAX_HEADER_SECTION)) }
+            },
+        }
+        if self.vault_size < 8 { return invalid("vault_size", format!("{} is smaller than the vault's length prefix", self.vault_size)) }
+        
+        let Some(total) = 24u64.checked_add(self.meta_size).and_then(|size| size.checked_add(self.indices_size)).and_then(|size| size.checked_add(self.vault_size)) else {
+            return invalid("sizing", "the section sizes overflow".to_string())
+        };
+        if let Some(len) = source_len && total != len {
+            return invalid("sizing", format!("the sections add up to {total} bytes but the source is {len} bytes"))
+        }
+        Ok(())
+    }
+}
+
+//==============================================================================================
+//        PakTrailer
+//==============================================================================================
+
+/// The magic bytes at the very end of a pak file that has a backup table of contents.
+pub const PAK_TRAILER_MAGIC : &[u8; 8] = b"PAKTOC01";
+
+/// A redundant copy of the sizing, meta and index map that is written after the vault, so the header can be rebuilt if the start of the file
+/// is damaged. The copy is followed by its size as a u64 and then the [PAK_TRAILER_MAGIC](crate::meta::PAK_TRAILER_MAGIC).
+pub struct PakTrailer {
+    pub sizing : PakSizing,
+    pub meta : PakMeta,
+    pub indices : PakIndexMap,
+}
+
+impl PakTrailer {
+    pub(crate) fn write(out : &mut Vec<u8>, sizing : &PakSizing, meta : &PakMeta, indices : &PakIndexMap) -> PakResult<()> {
+        let toc = bincode::serialize(&(sizing, meta, indices))?;
+        out.extend_from_slice(&toc);
+        out.extend_from_slice(&(toc.len() as u64).to_le_bytes());
+        out.extend_from_slice(PAK_TRAILER_MAGIC);
+        Ok(())
+    }
+    
+    /// The number of bytes [write](PakTrailer::write) adds to the end of a pak.
+    pub(crate) fn size_of(sizing : &PakSizing, meta : &PakMeta, indices : &PakIndexMap) -> PakResult<u64> {
+        Ok(bincode::serialized_size(&(sizing, meta, indices))? + 16)
+    }
+    
+    /// The number of bytes at the end of the source that belong to the trailer, or 0 if the source doesn't have one.
+    pub(crate) fn size_in(source : &mut dyn PakSource, source_len : u64) -> PakResult<u64> {
+        if source_len < 16 { return Ok(0) }
+        let footer = source.read(&PakPointer::new_untyped(source_len - 16, 16), 0)?;
+        if &footer[8..] != PAK_TRAILER_MAGIC { return Ok(0) }
+        let toc_size = u64::from_le_bytes(footer[..8].try_into().unwrap());
+        match toc_size.checked_add(16) {
+            Some(size) if size <= source_len => Ok(size),
+            _ => Err(PakError::InvalidHeader("trailer".to_string(), format!("a table of contents of {toc_size} bytes doesn't fit in the source"))),
+        }
+    }
+    
+    /// Reads the trailer from the end of a whole pak file, if it has an intact one.
+    pub fn read(data : &[u8]) -> Option<Self> {
+        let footer = data.get(data.len().checked_sub(16)?..)?;
+        if &footer[8..] != PAK_TRAILER_MAGIC { return None }
+        let toc_size = usize::try_from(u64::from_le_bytes(footer[..8].try_into().ok()?)).ok()?;
+        let toc = data.get(data.len().checked_sub(16)?.checked_sub(toc_size)?..data.len() - 16)?;
+        let (sizing, meta, indices) = bincode::deserialize(toc).ok()?;
+        Some(Self { sizing, meta, indices })
+    }
+}
@@ -0,0 +1,114 @@
+use std::{collections::{BTreeMap, BTreeSet}, sync::Arc};
+use serde::{Deserialize, Serialize};
+use crate::{error::PakResult, kind::{PakCustomQuery, PakIndexKind, PakKindQuery}, value::PakValue};
+
+//==============================================================================================
+//        PakTrigramIndex
+//==============================================================================================
+
+/// The name of the operation that [PakTrigramIndex](crate::ngram::PakTrigramIndex) answers.
+pub const LIKE_OPERATION : &str = "like";
+
+/// A built-in [PakIndexKind](crate::kind::PakIndexKind) for `LIKE` style matching. It maps every run of three characters to the values that
+/// contain it, so a pattern like `%ell%` only has to check the values that contain `ell` instead of every value. This is much smaller than a
+/// [PakSuffixIndex](crate::suffix::PakSuffixIndex), at the cost of verifying each candidate. Query it with [like](crate::ngram::like).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PakTrigramIndex;
+
+#[derive(Serialize, Deserialize)]
+struct PakTrigrams {
+    texts : Vec<(String, u32)>,
+    /// The indices of the texts that contain each trigram, in ascending order.
+    postings : BTreeMap<String, Vec<u32>>,
+}
+
+fn trigrams(text : &str) -> impl Iterator<Item = String> {
+    let chars = text.chars().collect::<Vec<_>>();
+    (0..chars.len().saturating_sub(2)).map(move |start| chars[start..start + 3].iter().collect())
+}
+
+impl PakIndexKind for PakTrigramIndex {
+    fn name(&self) -> &str {
+        "pak_trigram"
+    }
+    
+    fn build(&self, entries : &[(PakValue, u32)]) -> PakResult<Vec<u8>> {
+        let texts = entries.iter().filter_map(|(value, ordinal)| Some((value.as_string()?, *ordinal))).collect::<Vec<_>>();
+        let mut postings = BTreeMap::<String, Vec<u32>>::new();
+        for (index, (text, _)) in texts.iter().enumerate() {
+            for trigram in trigrams(text).collect::<BTreeSet<_>>() {
+                postings.entry(trigram).or_default().push(index as u32);
+            }
+        }
+        Ok(bincode::serialize(&PakTrigrams { texts, postings })?)
+    }
+    
+    fn execute(&self, data : &[u8], query : &PakKindQuery) -> PakResult<Option<Vec<u32>>> {
+        let PakKindQuery::Operation(operation, PakValue::String(pattern)) = query else { return Ok(None) };
+        if operation != LIKE_OPERATION { return Ok(None) }
+        let index : PakTrigrams = bincode::deserialize(data)?;
+        
+        // Every literal run of the pattern has to appear in a match, so each of their trigrams narrows down the candidates.
+        let mut candidates : Option<BTreeSet<u32>> = None;
+        for literal in pattern.split(['%', '_']) {
+            for trigram in trigrams(literal) {
+                let posting = index.postings.get(&trigram).map(|posting| posting.iter().copied().collect()).unwrap_or_default();
+                candidates = Some(match candidates {
+                    Some(candidates) => candidates.intersection(&posting).copied().collect(),
+                    None => posting,
+                });
+            }
+        }
+        let candidates = candidates.unwrap_or_else(|| (0..index.texts.len() as u32).collect());
+        
+        let ordinals = candidates.into_iter()
+            .map(|text| &index.texts[text as usize])
+            .filter(|(text, _)| like_matches(text, pattern))
+            .map(|(_, ordinal)| *ordinal)
+            .collect::<BTreeSet<_>>();
+        Ok(Some(ordinals.into_iter().collect()))
+    }
+    
+    fn matches(&self, query : &PakKindQuery, value : &PakValue) -> bool {
+        match (query, value) {
+            (PakKindQuery::Operation(operation, PakValue::String(pattern)), PakValue::String(value)) => operation == LIKE_OPERATION && like_matches(value, pattern),
+            (PakKindQuery::Range(lower, upper), value) => crate::btree::range_contains(&(lower.as_ref(), upper.as_ref()), value),
+            _ => false,
+        }
+    }
+}
+
+/// Checks the text against a `LIKE` pattern, where `%` matches any number of characters and `_` matches exactly one.
+pub(crate) fn like_matches(text : &str, pattern : &str) -> bool {
+    let (text, pattern) = (text.chars().collect::<Vec<_>>(), pattern.chars().collect::<Vec<_>>());
+    let (mut t, mut p) = (0, 0);
+    // The last `%` seen and the position in the text it was matched up to, for backtracking.
+    let mut backtrack : Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('%') => {
+                backtrack = Some((p, t));
+                p += 1;
+            },
+            Some(&c) if c == '_' || c == text[t] => {
+                p += 1;
+                t += 1;
+            },
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    backtrack = Some((star, matched + 1));
+                    p = star + 1;
+                    t = matched + 1;
+                },
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '%')
+}
+
+/// Finds every item whose value for the key matches the `LIKE` pattern, where `%` matches any number of characters and `_` matches exactly one.
+/// The key must have a [PakTrigramIndex](crate::ngram::PakTrigramIndex).
+pub fn like(key : &str, pattern : &str) -> PakCustomQuery {
+    PakCustomQuery::new(key, Arc::new(PakTrigramIndex), PakKindQuery::Operation(LIKE_OPERATION.to_string(), PakValue::from(pattern)))
+}
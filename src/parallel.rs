@@ -0,0 +1,80 @@
+use std::{collections::HashSet, num::NonZeroUsize, path::Path, sync::{mpsc, Arc, Mutex}, thread};
+use crate::{error::PakResult, fork::PakFork, pointer::PakTypedPointer, query::PakQueryExpression, Pak, PakSource};
+
+pub use crate::fork::PakSourceFactory;
+
+impl Pak {
+    /// Loads a Pak from the file path like [new_from_file](crate::Pak::new_from_file), and lets its queries run branches at the same
+    /// time on as many threads as the machine has cores. Each worker reads through a duplicate of the file's handle rather than opening
+    /// the path again, so a file that was replaced since is never mixed in.
+    pub fn open_parallel<P>(path : P) -> PakResult<Self> where P : AsRef<Path> {
+        Ok(Self::new_from_file(path)?.with_parallel_threads(default_threads()))
+    }
+    
+    /// Lets queries run the branches of their unions and intersections at the same time, on a pool of worker threads that each read
+    /// through their own source from the factory. Only branches with a [PakQueryNode](crate::query::PakQueryNode) tree can be sent to
+    /// another thread. The pool has as many threads as the machine has cores, unless [with_parallel_threads](crate::Pak::with_parallel_threads)
+    /// says otherwise.
+    pub fn with_parallel_sources<F>(mut self, factory : F) -> Self where F : Fn() -> PakResult<Box<dyn PakSource>> + Send + Sync + 'static {
+        self.forks = Some(Arc::new(factory));
+        self.parallel_threads.get_or_insert_with(default_threads);
+        self
+    }
+    
+    /// Lets queries run branches on at most the given number of worker threads, or on none with 0. Paks opened with
+    /// [new_from_file](crate::Pak::new_from_file) give the workers duplicates of the file's handle, other paks need
+    /// [with_parallel_sources](crate::Pak::with_parallel_sources) to run anything in parallel.
+    pub fn with_parallel_threads(mut self, threads : usize) -> Self {
+        self.parallel_threads = Some(threads);
+        self
+    }
+
+    /// The copy of the pak the workers open and how many of them there may be, or `None` if the pak doesn't run branches in parallel or
+    /// can't open more sources.
+    pub(crate) fn parallel_fork(&self) -> PakResult<Option<(PakFork, usize)>> {
+        match self.parallel_threads {
+            Some(0) | None => Ok(None),
+            Some(threads) => Ok(self.fork()?.map(|fork| (fork, threads))),
+        }
+    }
+}
+
+fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+/// Runs the first branch, and any branch without a tree, on this thread, and hands every other branch to a pool of worker threads, which
+/// each open one copy of the pak and take branches until none are left. Results are merged in the order they finish. The workers read at
+/// the priority of the pak, so the branches of a background query wait for the foreground reads of the pak and its other copies.
+pub(crate) fn execute_branches<F>(pak : &Pak, (fork, threads) : (PakFork, usize), branches : &[&dyn PakQueryExpression], mut merge : F) -> PakResult<()> where F : FnMut(HashSet<PakTypedPointer>) {
+    let mut local = Vec::new();
+    let mut remote = Vec::new();
+    for (i, branch) in branches.iter().enumerate() {
+        match branch.to_node() {
+            Some(node) if i > 0 => remote.push(node),
+            _ => local.push(*branch),
+        }
+    }
+    let workers = threads.min(remote.len());
+    let queue = Mutex::new(remote);
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..workers {
+            let (fork, queue, sender) = (&fork, &queue, sender.clone());
+            scope.spawn(move || {
+                let pak = match fork.open() {
+                    Ok(pak) => pak,
+                    Err(error) => { let _ = sender.send(Err(error)); return },
+                };
+                while let Some(node) = queue.lock().ok().and_then(|mut queue| queue.pop()) {
+                    if sender.send(node.execute(&pak)).is_err() { return }
+                }
+            });
+        }
+        drop(sender);
+        for branch in local { merge(branch.execute(pak)?) }
+        for results in receiver { merge(results?) }
+        Ok(())
+    })
+}
+
@@ -0,0 +1,56 @@
+use crate::error::{PakError, PakResult};
+use crate::item::{PakItemDeserialize, PakItemSerialize};
+
+/// The reserved index key that item paths are stored under.
+pub const PAK_PATH_KEY : &str = "__pak_path";
+
+/// The reserved index key that the size of imported files is stored under, in bytes.
+pub const PAK_SIZE_KEY : &str = "__pak_size";
+
+/// The reserved index key that the modification time of imported files is stored under, in seconds since the unix epoch.
+pub const PAK_MTIME_KEY : &str = "__pak_mtime";
+
+/// The reserved index key that the metadata sidecar of a blob is stored under, with the path of its blob as the value.
+pub const PAK_SIDECAR_KEY : &str = "__pak_sidecar";
+
+/// Brings a path into the form it is indexed with: segments separated by `/`, with no leading, trailing or repeated separators and no `.`
+/// segments. Backslashes are treated as separators. Paths that are empty or climb out with `..` are rejected.
+pub fn normalize_path(path : &str) -> PakResult<String> {
+    let mut segments = Vec::new();
+    for segment in path.split(['/', '\\']) {
+        match segment {
+            "" | "." => continue,
+            ".." => return Err(PakError::InvalidPath(path.to_string())),
+            segment => segments.push(segment),
+        }
+    }
+    if segments.is_empty() { return Err(PakError::InvalidPath(path.to_string())) }
+    Ok(segments.join("/"))
+}
+
+//==============================================================================================
+//        PakBlob
+//==============================================================================================
+
+/// The contents of a file, paked as they are with no encoding around them, so a [PakWindow](crate::window::PakWindow) over a blob reads
+/// exactly the bytes of the file. Add them with [PakBuilder::pak_blob](crate::PakBuilder::pak_blob).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PakBlob(pub Vec<u8>);
+
+impl PakItemSerialize for PakBlob {
+    fn into_bytes(&self) -> PakResult<Vec<u8>> {
+        Ok(self.0.clone())
+    }
+}
+
+impl PakItemDeserialize for PakBlob {
+    fn from_bytes(bytes : &[u8]) -> PakResult<Self> {
+        Ok(PakBlob(bytes.to_vec()))
+    }
+}
+
+impl From<Vec<u8>> for PakBlob {
+    fn from(bytes : Vec<u8>) -> Self {
+        PakBlob(bytes)
+    }
+}
@@ -0,0 +1,45 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::{format::PakFormat, pointer::PakPointer, value::PakValueKind};
+
+//==============================================================================================
+//        PakPlan
+//==============================================================================================
+
+/// The layout of a pak as [PakBuilder::plan](crate::PakBuilder::plan) works it out, without building the file. Offsets of items and
+/// index roots are relative to the start of the vault, the same as the pointers a built pak hands out, so two plans can be compared to
+/// catch layout drift.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PakPlan {
+    pub format : PakFormat,
+    /// The size of the whole file, including any trailer.
+    pub size : u64,
+    pub meta_size : u64,
+    /// Where the index map sits in the file.
+    pub index_map : PakPointer,
+    /// Where the vault starts in the file.
+    pub vault_start : u64,
+    pub vault_size : u64,
+    /// The number of vault bytes taken up by the items themselves.
+    pub item_size : u64,
+    /// Every item, in the order of its ordinal.
+    pub items : Vec<PakPointer>,
+    pub indices : BTreeMap<String, PakPlannedIndex>,
+}
+
+impl PakPlan {
+    /// The number of vault bytes that aren't items, which is the index structures, the ordinal table and the manifest.
+    pub fn overhead(&self) -> u64 {
+        self.vault_size - self.item_size
+    }
+}
+
+/// An index of a planned pak.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PakPlannedIndex {
+    pub kind : PakValueKind,
+    /// The root page of the index's tree.
+    pub root : PakPointer,
+    /// The number of vault bytes taken up by the tree and any bitmap or custom structures built for the index.
+    pub size : u64,
+}
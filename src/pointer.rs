@@ -1,4 +1,18 @@
+use std::{marker::PhantomData, ops::Range, sync::Arc};
 use serde::{Deserialize, Serialize};
+use crate::{error::{PakError, PakResult}, item::PakItemDeserialize, Pak};
+
+/// Converts an offset or a size from a pak into a `usize`, failing with [PakError::OffsetOverflow](crate::error::PakError::OffsetOverflow)
+/// on targets where it doesn't fit instead of truncating it. Paks can be larger than 4 GiB, which 32-bit targets can't index.
+pub(crate) fn to_usize(value : u64) -> PakResult<usize> {
+    usize::try_from(value).map_err(|_| PakError::OffsetOverflow(value))
+}
+
+/// The bytes from `offset` to `offset + size`, as a range of indices into a buffer.
+pub(crate) fn byte_range(offset : u64, size : u64) -> PakResult<Range<usize>> {
+    let end = offset.checked_add(size).ok_or(PakError::OffsetOverflow(offset))?;
+    Ok(to_usize(offset)?..to_usize(end)?)
+}
 
 //==============================================================================================
 //        PakPointer
@@ -57,10 +71,32 @@ impl PakPointer {
     
     pub fn type_is_match<T>(&self) -> bool {
         match self {
-            Self::Typed(ptr) => ptr.type_name == std::any::type_name::<T>(),
+            Self::Typed(ptr) => ptr.base_type_name() == std::any::type_name::<T>(),
             Self::Untyped(_) => true,
         }
     }
+    
+    /// The version the item was written with, if it was paked in a [PakEnvelope](crate::envelope::PakEnvelope).
+    pub fn version(&self) -> Option<u32> {
+        match self {
+            Self::Typed(ptr) => ptr.version(),
+            Self::Untyped(_) => None,
+        }
+    }
+    
+    /// The generation of the pak that handed out the pointer, if it is tagged with one. See [PakBuilder::with_generation](crate::PakBuilder::with_generation).
+    pub fn generation(&self) -> Option<u64> {
+        match self {
+            Self::Typed(ptr) => ptr.generation,
+            Self::Untyped(_) => None,
+        }
+    }
+}
+
+impl AsRef<PakPointer> for PakPointer {
+    fn as_ref(&self) -> &PakPointer {
+        self
+    }
 }
 
 impl Clone for PakPointer {
@@ -77,21 +113,62 @@ impl Clone for PakPointer {
 //==============================================================================================
 
 /// A typed pointer. This tells you what rust type is stored at the location pointed to. You can check it with a type at runtime to fail requests that have a type mismatch.
+/// The type name is shared, so cloning a pointer never allocates.
 #[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize, Hash)]
 pub struct PakTypedPointer {
     offset : u64,
     size : u64,
-    type_name : String,
+    type_name : Arc<str>,
+    generation : Option<u64>,
 }
 
 impl PakTypedPointer {
     pub fn new(offset : u64, size : u64, type_name : &str) -> Self {
-        Self { offset, size, type_name : type_name.to_string() }
+        Self { offset, size, type_name : Arc::from(type_name), generation : None }
+    }
+    
+    /// Tags the pointer with the generation of the pak it belongs to, or removes the tag.
+    pub(crate) fn with_generation(mut self, generation : Option<u64>) -> Self {
+        self.generation = generation;
+        self
     }
     
     pub fn into_pointer(self) -> PakPointer {
         PakPointer::Typed(self)
     }
+    
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+    
+    /// Moves the pointer to another offset, for repacks that move the items.
+    pub(crate) fn with_offset(mut self, offset : u64) -> Self {
+        self.offset = offset;
+        self
+    }
+    
+    /// Changes the size of the pointer, for chunks that change size when they are compressed or encrypted.
+    pub(crate) fn with_size(mut self, size : u64) -> Self {
+        self.size = size;
+        self
+    }
+    
+    /// Enveloped items keep their version after an `@` at the end of their type name.
+    pub(crate) fn with_version(mut self, version : u32) -> Self {
+        self.type_name = Arc::from(format!("{}@{version}", self.type_name));
+        self
+    }
+    
+    fn base_type_name(&self) -> &str {
+        match self.type_name.rsplit_once('@') {
+            Some((base, _)) => base,
+            None => &self.type_name,
+        }
+    }
+    
+    fn version(&self) -> Option<u32> {
+        self.type_name.rsplit_once('@').and_then(|(_, version)| version.parse().ok())
+    }
 }
 
 //==============================================================================================
@@ -113,4 +190,91 @@ impl PakUntypedPointer {
     pub fn as_pointer(&self) -> PakPointer {
         PakPointer::Untyped(*self)
     }
-}
\ No newline at end of file
+}
+//==============================================================================================
+//        PakRef
+//==============================================================================================
+
+/// The reserved index key that marks which indices of an item hold pointers to other items. See [PakIndex::reference](crate::index::PakIndex::reference).
+pub const PAK_REF_KEY : &str = "__pak_ref";
+
+/// A pointer to an item of type `T`, for storing references between items. It serializes exactly like a [PakPointer](crate::pointer::PakPointer),
+/// but the type of the item is checked at compile time when the reference is followed.
+#[derive(Serialize, Deserialize)]
+#[serde(transparent, bound = "")]
+pub struct PakRef<T> {
+    pointer : PakPointer,
+    #[serde(skip)]
+    item : PhantomData<fn() -> T>,
+}
+
+impl<T> PakRef<T> {
+    pub fn new(pointer : PakPointer) -> Self {
+        Self { pointer, item : PhantomData }
+    }
+    
+    /// The pointer to the referenced item.
+    pub fn pointer(&self) -> &PakPointer {
+        &self.pointer
+    }
+    
+    /// Loads the referenced item from the pak.
+    pub fn get(&self, pak : &Pak) -> PakResult<T> where T : PakItemDeserialize {
+        pak.get::<T>(&self.pointer)
+    }
+}
+
+impl<T> From<PakPointer> for PakRef<T> {
+    fn from(pointer : PakPointer) -> Self {
+        Self::new(pointer)
+    }
+}
+
+impl<T> AsRef<PakPointer> for PakRef<T> {
+    fn as_ref(&self) -> &PakPointer {
+        &self.pointer
+    }
+}
+
+impl<T> Clone for PakRef<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.pointer.clone())
+    }
+}
+
+impl<T> std::fmt::Debug for PakRef<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PakRef").field(&self.pointer).finish()
+    }
+}
+
+impl<T> PartialEq for PakRef<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.pointer == other.pointer
+    }
+}
+
+impl<T> Eq for PakRef<T> {}
+
+/// Rewrites every pointer to `from` stored in the bytes of an item to point at `to` instead, for repacks that move the items. Pointers are
+/// found by their encoding, which starts with the offset and size of the item and, for typed pointers, its type name. Typed pointers tagged
+/// with the generation of the old pak are tagged with the new one.
+pub(crate) fn rewrite_pointers(bytes : &mut [u8], from : &PakTypedPointer, to : &PakTypedPointer, generations : (u64, u64)) -> PakResult<()> {
+    let untyped = bincode::serialize(&PakPointer::new_untyped(from.offset, from.size))?;
+    let typed = bincode::serialize(&PakPointer::Typed(from.clone().with_generation(None)))?;
+    // The generation is the last field of a typed pointer, and a pointer without one ends in a single zero byte.
+    let typed = &typed[..typed.len() - 1];
+    let mut at = 0;
+    while at < bytes.len() {
+        let rest = &bytes[at..];
+        let len = if rest.starts_with(typed) { typed.len() } else if rest.starts_with(&untyped) { untyped.len() } else { at += 1; continue };
+        bytes[at + 4..at + 12].copy_from_slice(&to.offset.to_le_bytes());
+        bytes[at + 12..at + 20].copy_from_slice(&to.size.to_le_bytes());
+        let generation = at + len;
+        if len == typed.len() && bytes.get(generation) == Some(&1) && bytes.get(generation + 1..generation + 9) == Some(&generations.0.to_le_bytes()[..]) {
+            bytes[generation + 1..generation + 9].copy_from_slice(&generations.1.to_le_bytes());
+        }
+        at += len;
+    }
+    Ok(())
+}
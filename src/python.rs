@@ -0,0 +1,126 @@
+//! Python bindings, so paks can be opened and queried from scripts and notebooks. Build the module with [maturin](https://www.maturin.rs)
+//! from the root of the crate, which picks up the `python` feature from `pyproject.toml`:
+//!
+//! ```python
+//! import pak_db
+//!
+//! pak = pak_db.open("items.pak")
+//! for pointer in pak.query('kind == "sword" and damage > 10'):
+//!     data = pak.get_bytes(pointer)
+//! ```
+//!
+//! Items come back as their raw bytes, since their Rust types aren't known on the Python side.
+
+use pyo3::{create_exception, exceptions::PyException, prelude::*, types::{PyBytes, PyDict}};
+use crate::{error::PakError, pointer::PakPointer, query::{self, PakQueryExpression}};
+
+create_exception!(pak_db, PakException, PyException, "Raised when a pak can't be opened, queried or read.");
+
+impl From<PakError> for PyErr {
+    fn from(error : PakError) -> Self {
+        PakException::new_err(error.to_string())
+    }
+}
+
+//==============================================================================================
+//        Pak
+//==============================================================================================
+
+/// An opened pak. Paks keep their source behind a `RefCell`, so they stay on the thread that opened them.
+#[pyclass(name = "Pak", module = "pak_db", unsendable)]
+pub struct PyPak(crate::Pak);
+
+#[pymethods]
+impl PyPak {
+    #[staticmethod]
+    fn open(path : &str) -> PyResult<Self> {
+        Ok(Self(crate::Pak::new_from_file(path)?))
+    }
+
+    /// The items matching the query text, in the order they sit in the pak. See [parse](crate::query::parse) for the syntax.
+    fn query(&self, text : &str) -> PyResult<Vec<PyPakPointer>> {
+        let mut pointers = query::parse(text)?.execute(&self.0)?.into_iter().map(|pointer| pointer.into_pointer()).collect::<Vec<_>>();
+        pointers.sort_by_key(|pointer| pointer.offset());
+        Ok(pointers.into_iter().map(PyPakPointer).collect())
+    }
+
+    fn get_bytes<'py>(&self, py : Python<'py>, pointer : &PyPakPointer) -> PyResult<Bound<'py, PyBytes>> {
+        Ok(PyBytes::new(py, &self.0.read_bytes(&pointer.0)?))
+    }
+
+    /// The item paked at the path, if there is one.
+    fn get_path<'py>(&self, py : Python<'py>, path : &str) -> PyResult<Option<Bound<'py, PyBytes>>> {
+        let Some(pointer) = self.0.pointer_by_path(path)? else { return Ok(None) };
+        Ok(Some(PyBytes::new(py, &self.0.read_bytes(&pointer)?)))
+    }
+
+    fn paths(&self) -> PyResult<Vec<(String, PyPakPointer)>> {
+        Ok(self.0.paths()?.into_iter().map(|(path, pointer)| (path, PyPakPointer(pointer))).collect())
+    }
+
+    fn metadata<'py>(&self, py : Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let metadata = PyDict::new(py);
+        metadata.set_item("name", self.0.name())?;
+        metadata.set_item("version", self.0.version())?;
+        metadata.set_item("author", self.0.author())?;
+        metadata.set_item("description", self.0.description())?;
+        metadata.set_item("generation", self.0.generation())?;
+        metadata.set_item("format", self.0.format().version())?;
+        metadata.set_item("size", self.0.size())?;
+        metadata.set_item("item_count", self.0.item_count()?)?;
+        Ok(metadata)
+    }
+
+    fn __len__(&self) -> PyResult<usize> {
+        Ok(self.0.item_count()? as usize)
+    }
+}
+
+//==============================================================================================
+//        Pointer
+//==============================================================================================
+
+/// Where an item is in a pak, as returned by a query.
+#[pyclass(name = "Pointer", module = "pak_db", frozen)]
+pub struct PyPakPointer(PakPointer);
+
+#[pymethods]
+impl PyPakPointer {
+    #[getter]
+    fn offset(&self) -> u64 {
+        self.0.offset()
+    }
+
+    #[getter]
+    fn size(&self) -> u64 {
+        self.0.size()
+    }
+
+    #[getter]
+    fn type_name(&self) -> &str {
+        self.0.type_name()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Pointer(type_name={:?}, offset={}, size={})", self.0.type_name(), self.0.offset(), self.0.size())
+    }
+}
+
+//==============================================================================================
+//        Module
+//==============================================================================================
+
+#[pyfunction]
+fn open(path : &str) -> PyResult<PyPak> {
+    PyPak::open(path)
+}
+
+#[pymodule]
+#[pyo3(name = "pak_db")]
+pub(crate) fn pak_db_module(module : &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyPak>()?;
+    module.add_class::<PyPakPointer>()?;
+    module.add("PakException", module.py().get_type::<PakException>())?;
+    module.add_function(wrap_pyfunction!(open, module)?)?;
+    Ok(())
+}
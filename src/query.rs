@@ -1,8 +1,14 @@
 #![doc = include_str!("../docs/queries.md")]
 
-use std::{collections::HashSet, ops::{BitAnd, BitOr}};
-use crate::{error::PakResult, pointer::PakTypedPointer};
-use super::{value::PakValue, Pak};
+use std::{collections::HashSet, fmt::{self, Display}, ops::{BitAnd, BitOr, Bound}};
+use serde::{Deserialize, Serialize};
+use crate::{error::{PakError, PakResult}, index::PakIndex, item::PakItemDeserialize, kind::{resolve_ordinals, PakKindQuery}, pointer::PakTypedPointer, scan::PakQueryFilter};
+use super::{value::{IntoPakValue, PakValue}, Pak};
+
+#[cfg(feature = "roaring")]
+use crate::bitmap::PakBitmapIndex;
+#[cfg(feature = "roaring")]
+pub use crate::bitmap::PakBitmapSet;
 
 //==============================================================================================
 //        Pak Query
@@ -10,17 +16,162 @@ use super::{value::PakValue, Pak};
 
 pub trait PakQueryExpression {
     fn execute(&self, pak : &Pak) -> PakResult<HashSet<PakTypedPointer>>;
+    
+    /// Checks the expression directly against the indices of a single item, without a pak. This is how the [NaiveStore](crate::testing::NaiveStore) answers queries.
+    /// Expressions that don't override it match no items there.
+    fn matches(&self, _indices : &[PakIndex]) -> bool {
+        false
+    }
+    
+    /// Returns true if at least one item matches the expression. Expressions should override this when they can answer without building the full result set.
+    fn exists(&self, pak : &Pak) -> PakResult<bool> {
+        Ok(!self.execute(pak)?.is_empty())
+    }
+    
+    /// About how many items match the expression, worked out from the lengths of posting lists without reading them. Intersections use
+    /// this to pick the side to run first. Returns `None` when there is no cheap estimate.
+    fn estimate(&self, _pak : &Pak) -> PakResult<Option<u64>> {
+        Ok(None)
+    }
+    
+    /// Keeps the candidates that match the expression, probing the indices for each of them instead of finding every match. This is how
+    /// an intersection checks its larger side. Returns `None` when part of the expression can't be probed.
+    fn probe(&self, _pak : &Pak, _candidates : &HashSet<PakTypedPointer>) -> PakResult<Option<HashSet<PakTypedPointer>>> {
+        Ok(None)
+    }
+    
+    /// Answers the expression using only bitmap indices. Returns `None` when any part of the expression isn't backed by a bitmap index, in which case [execute](crate::query::PakQueryExpression::execute) should be used.
+    #[cfg(feature = "roaring")]
+    fn execute_bitmap(&self, _pak : &Pak) -> PakResult<Option<PakBitmapSet>> {
+        Ok(None)
+    }
+    
+    /// The expression as a [PakQueryNode](crate::query::PakQueryNode) tree, which can be printed, hashed or serialized to be logged, cached,
+    /// or sent to another process and replayed. Returns `None` for expressions with parts that have no tree form, like custom index queries.
+    fn to_node(&self) -> Option<PakQueryNode> {
+        None
+    }
+    
+    /// Narrows the expression down to the items of type `T` that the predicate keeps, like `"kind".equals("sword").filter(|sword : &Sword|
+    /// sword.name.contains("fire"))`. Every item the expression matches is read to be checked, so this is only meant for fields that
+    /// have no index. See [PakQueryFilter](crate::scan::PakQueryFilter).
+    fn filter<T, F>(self, predicate : F) -> PakQueryFilter<Self, T, F> where Self : Sized, T : PakItemDeserialize, F : Fn(&T) -> bool {
+        PakQueryFilter::new(self, predicate)
+    }
+}
+
+/// Runs the branches of a union or intersection and hands the results of each to `merge`. With the `parallel` feature, and a pak that
+/// can [open more sources](crate::Pak::with_parallel_sources), the branches run at the same time and are merged as they finish.
+pub(crate) fn execute_branches<F>(pak : &Pak, branches : &[&dyn PakQueryExpression], mut merge : F) -> PakResult<()> where F : FnMut(HashSet<PakTypedPointer>) {
+    #[cfg(feature = "parallel")]
+    if branches.len() > 1 && let Some(fork) = pak.parallel_fork()? { return crate::parallel::execute_branches(pak, fork, branches, merge) }
+    for branch in branches { merge(branch.execute(pak)?) }
+    Ok(())
+}
+
+/// Keeps the pointers that are in every set handed to it.
+fn intersect(results : &mut Option<HashSet<PakTypedPointer>>, other : HashSet<PakTypedPointer>) {
+    match results {
+        Some(results) => results.retain(|pointer| other.contains(pointer)),
+        None => *results = Some(other),
+    }
+}
+
+/// Intersects the branches. When any of them can be [estimated](PakQueryExpression::estimate), the one expected to match the fewest
+/// items runs first, and the rest only [probe](PakQueryExpression::probe) its matches, stopping as soon as none are left. Otherwise
+/// every branch runs in full, at the same time where the pak allows it.
+fn execute_intersection(pak : &Pak, branches : &[&dyn PakQueryExpression]) -> PakResult<HashSet<PakTypedPointer>> {
+    let estimates = branches.iter().map(|branch| branch.estimate(pak)).collect::<PakResult<Vec<_>>>()?;
+    if estimates.iter().all(Option::is_none) {
+        let mut results = None;
+        execute_branches(pak, branches, |other| intersect(&mut results, other))?;
+        return Ok(results.unwrap_or_default())
+    }
+    let mut order = (0..branches.len()).collect::<Vec<_>>();
+    order.sort_by_key(|branch| estimates[*branch].unwrap_or(u64::MAX));
+    let mut order = order.into_iter().map(|branch| branches[branch]);
+    let Some(first) = order.next() else { return Ok(HashSet::new()) };
+    let mut results = first.execute(pak)?;
+    for branch in order {
+        if results.is_empty() { break }
+        results = match branch.probe(pak, &results)? {
+            Some(results) => results,
+            None => {
+                let other = branch.execute(pak)?;
+                results.into_iter().filter(|pointer| other.contains(pointer)).collect()
+            },
+        };
+    }
+    Ok(results)
+}
+
+/// Keeps the candidates that match any of the branches, probing each one only with the candidates the ones before it didn't match.
+fn probe_union(pak : &Pak, branches : &[&dyn PakQueryExpression], candidates : &HashSet<PakTypedPointer>) -> PakResult<Option<HashSet<PakTypedPointer>>> {
+    let mut results = HashSet::new();
+    for branch in branches {
+        let rest = candidates.iter().filter(|pointer| !results.contains(*pointer)).cloned().collect::<HashSet<_>>();
+        if rest.is_empty() { break }
+        let Some(found) = branch.probe(pak, &rest)? else { return Ok(None) };
+        results.extend(found);
+    }
+    Ok(Some(results))
+}
+
+/// Keeps the candidates that match every branch, probing each one only with the candidates the ones before it kept.
+fn probe_intersection(pak : &Pak, branches : &[&dyn PakQueryExpression], candidates : &HashSet<PakTypedPointer>) -> PakResult<Option<HashSet<PakTypedPointer>>> {
+    let mut results = candidates.clone();
+    for branch in branches {
+        if results.is_empty() { break }
+        let Some(found) = branch.probe(pak, &results)? else { return Ok(None) };
+        results = found;
+    }
+    Ok(Some(results))
 }
 
 pub struct PakQueryUnion(Box<dyn PakQueryExpression>, Box<dyn PakQueryExpression>);
 
+impl PakQueryUnion {
+    pub(crate) fn new(a : Box<dyn PakQueryExpression>, b : Box<dyn PakQueryExpression>) -> Self {
+        Self(a, b)
+    }
+}
+
 impl PakQueryExpression for PakQueryUnion {
     fn execute(&self, pak : &Pak) -> PakResult<HashSet<PakTypedPointer>> {
-        let results_a = self.0.execute(pak)?;
-        let results_b = self.1.execute(pak)?;
-        let results = results_a.into_iter().chain(results_b.into_iter()).collect::<HashSet<_>>();
+        #[cfg(feature = "roaring")]
+        if let Some(set) = self.execute_bitmap(pak)? { return set.into_pointers(pak) }
+        let mut results = HashSet::new();
+        execute_branches(pak, &[self.0.as_ref(), self.1.as_ref()], |other| results.extend(other))?;
         Ok(results)
     }
+    
+    fn matches(&self, indices : &[PakIndex]) -> bool {
+        self.0.matches(indices) || self.1.matches(indices)
+    }
+    
+    fn exists(&self, pak : &Pak) -> PakResult<bool> {
+        Ok(self.0.exists(pak)? || self.1.exists(pak)?)
+    }
+    
+    fn estimate(&self, pak : &Pak) -> PakResult<Option<u64>> {
+        let (Some(a), Some(b)) = (self.0.estimate(pak)?, self.1.estimate(pak)?) else { return Ok(None) };
+        Ok(Some(a.saturating_add(b)))
+    }
+    
+    fn probe(&self, pak : &Pak, candidates : &HashSet<PakTypedPointer>) -> PakResult<Option<HashSet<PakTypedPointer>>> {
+        probe_union(pak, &[self.0.as_ref(), self.1.as_ref()], candidates)
+    }
+    
+    #[cfg(feature = "roaring")]
+    fn execute_bitmap(&self, pak : &Pak) -> PakResult<Option<PakBitmapSet>> {
+        let Some(set_a) = self.0.execute_bitmap(pak)? else { return Ok(None) };
+        let Some(set_b) = self.1.execute_bitmap(pak)? else { return Ok(None) };
+        Ok(Some(set_a.union(set_b)))
+    }
+    
+    fn to_node(&self) -> Option<PakQueryNode> {
+        Some(PakQueryNode::combine(self.0.to_node()?, self.1.to_node()?, false))
+    }
 }
 
 impl<B> BitOr<B> for PakQueryUnion where B : PakQueryExpression + 'static {
@@ -53,11 +204,40 @@ impl <B> BitOr<B> for PakQuery where B : PakQueryExpression + 'static {
 
 pub struct PakQueryIntersection(Box::<dyn PakQueryExpression>, Box::<dyn PakQueryExpression>);
 
+impl PakQueryIntersection {
+    pub(crate) fn new(a : Box<dyn PakQueryExpression>, b : Box<dyn PakQueryExpression>) -> Self {
+        Self(a, b)
+    }
+}
+
 impl PakQueryExpression for PakQueryIntersection {
     fn execute(&self, pak : &Pak) -> PakResult<HashSet<PakTypedPointer>> {
-        let results_a = self.0.execute(pak)?;
-        let results_b = self.1.execute(pak)?;
-        Ok(results_a.into_iter().filter(|e| results_b.contains(e)).collect())
+        #[cfg(feature = "roaring")]
+        if let Some(set) = self.execute_bitmap(pak)? { return set.into_pointers(pak) }
+        execute_intersection(pak, &[self.0.as_ref(), self.1.as_ref()])
+    }
+    
+    fn matches(&self, indices : &[PakIndex]) -> bool {
+        self.0.matches(indices) && self.1.matches(indices)
+    }
+    
+    fn estimate(&self, pak : &Pak) -> PakResult<Option<u64>> {
+        Ok([self.0.estimate(pak)?, self.1.estimate(pak)?].into_iter().flatten().min())
+    }
+    
+    fn probe(&self, pak : &Pak, candidates : &HashSet<PakTypedPointer>) -> PakResult<Option<HashSet<PakTypedPointer>>> {
+        probe_intersection(pak, &[self.0.as_ref(), self.1.as_ref()], candidates)
+    }
+    
+    #[cfg(feature = "roaring")]
+    fn execute_bitmap(&self, pak : &Pak) -> PakResult<Option<PakBitmapSet>> {
+        let Some(set_a) = self.0.execute_bitmap(pak)? else { return Ok(None) };
+        let Some(set_b) = self.1.execute_bitmap(pak)? else { return Ok(None) };
+        Ok(Some(set_a.intersection(set_b)))
+    }
+    
+    fn to_node(&self) -> Option<PakQueryNode> {
+        Some(PakQueryNode::combine(self.0.to_node()?, self.1.to_node()?, true))
     }
 }
 
@@ -90,6 +270,20 @@ impl <B> BitAnd<B> for PakQueryIntersection where B : PakQueryExpression + 'stat
 //        Pak Query Expression
 //==============================================================================================
 
+/// What a query does when it compares against a key the pak has no index for, set for each pak with
+/// [with_unknown_keys](crate::Pak::with_unknown_keys).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PakUnknownKeys {
+    /// The comparison matches nothing, as if the index existed and was empty. This is the default, and lets the same queries run against
+    /// paks that were built with fewer indices.
+    #[default]
+    Empty,
+    /// The query fails with [PakError::UnknownIndex](crate::error::PakError::UnknownIndex), which catches typos in keys and indices that
+    /// were forgotten at build time.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum PakQuery {
     Equal(String, PakValue),
     GreaterThan(String, PakValue),
@@ -118,6 +312,65 @@ impl PakQuery {
     pub fn less_than_or_equal(key : &str, value : impl Into<PakValue>) -> Self {
         PakQuery::LessThanEqual(key.to_string(), value.into())
     }
+    
+    pub(crate) fn value(&self) -> &PakValue {
+        match self {
+            PakQuery::Equal(_, value) => value,
+            PakQuery::GreaterThan(_, value) => value,
+            PakQuery::LessThan(_, value) => value,
+            PakQuery::GreaterThanEqual(_, value) => value,
+            PakQuery::LessThanEqual(_, value) => value,
+        }
+    }
+    
+    /// Fails if the index holds a kind of value that can't be compared with the query's value.
+    fn check_kind(&self, pak : &Pak) -> PakResult<()> {
+        let Some(kind) = pak.index_kind(self.key()) else { return Ok(()) };
+        let value_kind = self.value().kind();
+        if kind.accepts(value_kind) { return Ok(()) }
+        Err(PakError::ValueKindMismatch(self.key().to_string(), kind.to_string(), value_kind.to_string()))
+    }
+    
+    pub(crate) fn key(&self) -> &str {
+        match self {
+            PakQuery::Equal(key, _) => key,
+            PakQuery::GreaterThan(key, _) => key,
+            PakQuery::LessThan(key, _) => key,
+            PakQuery::GreaterThanEqual(key, _) => key,
+            PakQuery::LessThanEqual(key, _) => key,
+        }
+    }
+    
+    fn bounds(&self) -> (Bound<&PakValue>, Bound<&PakValue>) {
+        match self {
+            PakQuery::Equal(_, value) => (Bound::Included(value), Bound::Included(value)),
+            PakQuery::GreaterThan(_, value) => (Bound::Excluded(value), Bound::Unbounded),
+            PakQuery::LessThan(_, value) => (Bound::Unbounded, Bound::Excluded(value)),
+            PakQuery::GreaterThanEqual(_, value) => (Bound::Included(value), Bound::Unbounded),
+            PakQuery::LessThanEqual(_, value) => (Bound::Unbounded, Bound::Included(value)),
+        }
+    }
+}
+
+impl Display for PakQuery {
+    /// Writes the query in the syntax [parse](crate::query::parse) reads, like `age >= 26`.
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = match self {
+            PakQuery::Equal(..) => "==",
+            PakQuery::GreaterThan(..) => ">",
+            PakQuery::LessThan(..) => "<",
+            PakQuery::GreaterThanEqual(..) => ">=",
+            PakQuery::LessThanEqual(..) => "<=",
+        };
+        write!(f, "{} {operator} ", self.key())?;
+        match self.value() {
+            PakValue::String(string) => write!(f, "\"{}\"", string.replace('\\', "\\\\").replace('"', "\\\"")),
+            PakValue::Float(bits) => write!(f, "{:?}", f64::from_bits(*bits)),
+            PakValue::Float32(bits) => write!(f, "{:?}", f32::from_bits(*bits)),
+            PakValue::Void => f.write_str("void"),
+            value => write!(f, "{value:?}"),
+        }
+    }
 }
 
 pub fn equals(key : &str, value : impl Into<PakValue>) -> PakQuery {
@@ -142,27 +395,560 @@ pub fn less_than_equal(key : &str, value : impl Into<PakValue>) -> PakQuery {
 
 impl PakQueryExpression for PakQuery {
     fn execute(&self, pak : &Pak) -> PakResult<HashSet<PakTypedPointer>> {
+        self.check_kind(pak)?;
+        let Some(tree) = pak.query_tree(self.key())? else { return Ok(HashSet::new()) };
+        if let Some((name, pointer)) = tree.custom() && let Some(kind) = pak.index_kind_named(name) {
+            let (lower, upper) = self.bounds();
+            let query = PakKindQuery::Range(lower.cloned(), upper.cloned());
+            if let Some(ordinals) = pak.execute_kind(self.key(), kind, pointer, &query)? {
+                return resolve_ordinals(pak, ordinals)
+            }
+        }
         match self {
-            PakQuery::Equal(key, pak_value) => {
-                let tree = pak.get_tree(key)?;
-                tree.get(pak_value)
-            },
-            PakQuery::GreaterThan(key, pak_value) => {
-                let tree = pak.get_tree(key)?;
-                tree.get_greater(pak_value)
+            PakQuery::Equal(_, pak_value) => tree.get(pak_value),
+            PakQuery::GreaterThan(_, pak_value) => tree.get_greater(pak_value),
+            PakQuery::LessThan(_, pak_value) => tree.get_less(pak_value),
+            PakQuery::GreaterThanEqual(_, pak_value) => tree.get_greater_eq(pak_value),
+            PakQuery::LessThanEqual(_, pak_value) => tree.get_less_eq(pak_value),
+        }
+    }
+    
+    fn matches(&self, indices : &[PakIndex]) -> bool {
+        let bounds = self.bounds();
+        indices.iter().any(|index| index.key == self.key() && crate::btree::range_contains(&bounds, &index.value))
+    }
+    
+    fn exists(&self, pak : &Pak) -> PakResult<bool> {
+        self.check_kind(pak)?;
+        let Some(tree) = pak.query_tree(self.key())? else { return Ok(false) };
+        let mut found = false;
+        tree.range(self.bounds(), |_, postings| {
+            found = !postings.is_empty();
+            Ok(!found)
+        })?;
+        Ok(found)
+    }
+    
+    fn estimate(&self, pak : &Pak) -> PakResult<Option<u64>> {
+        // A single value is counted by walking one path down the tree, but a range would read every page it covers just to be counted.
+        let PakQuery::Equal(..) = self else { return Ok(None) };
+        self.check_kind(pak)?;
+        let Some(tree) = pak.query_tree(self.key())? else { return Ok(Some(0)) };
+        if tree.custom().is_some() { return Ok(None) }
+        Ok(Some(tree.count(self.bounds())?))
+    }
+    
+    fn probe(&self, pak : &Pak, candidates : &HashSet<PakTypedPointer>) -> PakResult<Option<HashSet<PakTypedPointer>>> {
+        self.check_kind(pak)?;
+        let Some(tree) = pak.query_tree(self.key())? else { return Ok(Some(HashSet::new())) };
+        if tree.custom().is_some() { return Ok(None) }
+        Ok(Some(tree.probe(self.bounds(), candidates)?))
+    }
+    
+    #[cfg(feature = "roaring")]
+    fn execute_bitmap(&self, pak : &Pak) -> PakResult<Option<PakBitmapSet>> {
+        self.check_kind(pak)?;
+        let Some(tree) = pak.query_tree(self.key())? else { return Ok(None) };
+        let Some(pointer) = tree.bitmap() else { return Ok(None) };
+        let index = PakBitmapIndex::read(pak, &pointer)?;
+        Ok(Some(index.range(self.bounds())?))
+    }
+    
+    fn to_node(&self) -> Option<PakQueryNode> {
+        Some(PakQueryNode::Compare(self.clone()))
+    }
+}
+
+impl PakQueryExpression for Box<dyn PakQueryExpression> {
+    fn execute(&self, pak : &Pak) -> PakResult<HashSet<PakTypedPointer>> {
+        self.as_ref().execute(pak)
+    }
+    
+    fn matches(&self, indices : &[PakIndex]) -> bool {
+        self.as_ref().matches(indices)
+    }
+    
+    fn exists(&self, pak : &Pak) -> PakResult<bool> {
+        self.as_ref().exists(pak)
+    }
+    
+    fn estimate(&self, pak : &Pak) -> PakResult<Option<u64>> {
+        self.as_ref().estimate(pak)
+    }
+    
+    fn probe(&self, pak : &Pak, candidates : &HashSet<PakTypedPointer>) -> PakResult<Option<HashSet<PakTypedPointer>>> {
+        self.as_ref().probe(pak, candidates)
+    }
+    
+    #[cfg(feature = "roaring")]
+    fn execute_bitmap(&self, pak : &Pak) -> PakResult<Option<PakBitmapSet>> {
+        self.as_ref().execute_bitmap(pak)
+    }
+    
+    fn to_node(&self) -> Option<PakQueryNode> {
+        self.as_ref().to_node()
+    }
+}
+
+//==============================================================================================
+//        Pak Query Node
+//==============================================================================================
+
+/// A query expression as a tree, built by [PakQueryBuilder](crate::query::PakQueryBuilder) or [parse](crate::query::parse), or taken
+/// from any expression with [to_node](crate::query::PakQueryExpression::to_node). The tree can be looked into, prints in the syntax
+/// `parse` reads, and can be hashed or serialized, so a query can be logged, cached, or sent from an editor to the game and replayed.
+/// Values that compare as equal hash the same, so `age == 30u32` and `age == 30i64` are the same cache entry. An empty `All` or `Any`
+/// matches nothing.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PakQueryNode {
+    Compare(PakQuery),
+    /// Items that match every one of the nodes.
+    All(Vec<PakQueryNode>),
+    /// Items that match any of the nodes.
+    Any(Vec<PakQueryNode>),
+}
+
+impl PakQueryNode {
+    /// Combines the nodes with `All` or `Any`, leaving a single node on its own.
+    fn join(mut nodes : Vec<PakQueryNode>, all : bool) -> Self {
+        match (nodes.len(), all) {
+            (1, _) => nodes.remove(0),
+            (_, true) => PakQueryNode::All(nodes),
+            (_, false) => PakQueryNode::Any(nodes),
+        }
+    }
+}
+
+impl PakQueryNode {
+    /// Joins two nodes, folding in the nodes of either side that are already joined the same way.
+    fn combine(a : PakQueryNode, b : PakQueryNode, all : bool) -> Self {
+        let mut nodes = Vec::new();
+        for node in [a, b] {
+            match (node, all) {
+                (PakQueryNode::All(inner), true) | (PakQueryNode::Any(inner), false) => nodes.extend(inner),
+                (node, _) => nodes.push(node),
+            }
+        }
+        PakQueryNode::join(nodes, all)
+    }
+}
+
+impl From<PakQuery> for PakQueryNode {
+    fn from(query : PakQuery) -> Self {
+        PakQueryNode::Compare(query)
+    }
+}
+
+impl PakQueryExpression for PakQueryNode {
+    fn execute(&self, pak : &Pak) -> PakResult<HashSet<PakTypedPointer>> {
+        let nodes = match self {
+            PakQueryNode::Compare(query) => return query.execute(pak),
+            PakQueryNode::All(nodes) | PakQueryNode::Any(nodes) => nodes,
+        };
+        #[cfg(feature = "roaring")]
+        if let Some(set) = self.execute_bitmap(pak)? { return set.into_pointers(pak) }
+        let branches = nodes.iter().map(|node| node as &dyn PakQueryExpression).collect::<Vec<_>>();
+        if let PakQueryNode::All(_) = self { return execute_intersection(pak, &branches) }
+        let mut results = HashSet::new();
+        execute_branches(pak, &branches, |other| results.extend(other))?;
+        Ok(results)
+    }
+    
+    fn matches(&self, indices : &[PakIndex]) -> bool {
+        match self {
+            PakQueryNode::Compare(query) => query.matches(indices),
+            PakQueryNode::All(nodes) => !nodes.is_empty() && nodes.iter().all(|node| node.matches(indices)),
+            PakQueryNode::Any(nodes) => nodes.iter().any(|node| node.matches(indices)),
+        }
+    }
+    
+    fn exists(&self, pak : &Pak) -> PakResult<bool> {
+        match self {
+            PakQueryNode::Compare(query) => query.exists(pak),
+            PakQueryNode::All(_) => Ok(!self.execute(pak)?.is_empty()),
+            PakQueryNode::Any(nodes) => {
+                for node in nodes {
+                    if node.exists(pak)? { return Ok(true) }
+                }
+                Ok(false)
             },
-            PakQuery::LessThan(key, pak_value) => {
-                let tree = pak.get_tree(key)?;
-                tree.get_less(pak_value)
+        }
+    }
+    
+    fn estimate(&self, pak : &Pak) -> PakResult<Option<u64>> {
+        let estimates = match self {
+            PakQueryNode::Compare(query) => return query.estimate(pak),
+            PakQueryNode::All(nodes) | PakQueryNode::Any(nodes) => nodes.iter().map(|node| node.estimate(pak)).collect::<PakResult<Vec<_>>>()?,
+        };
+        Ok(match self {
+            PakQueryNode::All(_) => estimates.into_iter().flatten().min(),
+            _ => estimates.into_iter().sum(),
+        })
+    }
+    
+    fn probe(&self, pak : &Pak, candidates : &HashSet<PakTypedPointer>) -> PakResult<Option<HashSet<PakTypedPointer>>> {
+        let nodes = match self {
+            PakQueryNode::Compare(query) => return query.probe(pak, candidates),
+            PakQueryNode::All(nodes) | PakQueryNode::Any(nodes) => nodes.iter().map(|node| node as &dyn PakQueryExpression).collect::<Vec<_>>(),
+        };
+        match self {
+            PakQueryNode::All(_) if nodes.is_empty() => Ok(Some(HashSet::new())),
+            PakQueryNode::All(_) => probe_intersection(pak, &nodes, candidates),
+            _ => probe_union(pak, &nodes, candidates),
+        }
+    }
+    
+    #[cfg(feature = "roaring")]
+    fn execute_bitmap(&self, pak : &Pak) -> PakResult<Option<PakBitmapSet>> {
+        let nodes = match self {
+            PakQueryNode::Compare(query) => return query.execute_bitmap(pak),
+            PakQueryNode::All(nodes) | PakQueryNode::Any(nodes) => nodes,
+        };
+        let mut result : Option<PakBitmapSet> = None;
+        for node in nodes {
+            let Some(set) = node.execute_bitmap(pak)? else { return Ok(None) };
+            result = Some(match (result, self) {
+                (None, _) => set,
+                (Some(result), PakQueryNode::All(_)) => result.intersection(set),
+                (Some(result), _) => result.union(set),
+            });
+        }
+        Ok(result)
+    }
+    
+    fn to_node(&self) -> Option<PakQueryNode> {
+        Some(self.clone())
+    }
+}
+
+impl Display for PakQueryNode {
+    /// Writes the tree in the syntax [parse](crate::query::parse) reads. Groups are only put in parentheses where `and` binding tighter
+    /// than `or` would change their meaning.
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (nodes, separator) = match self {
+            PakQueryNode::Compare(query) => return query.fmt(f),
+            PakQueryNode::All(nodes) => (nodes, " and "),
+            PakQueryNode::Any(nodes) => (nodes, " or "),
+        };
+        for (i, node) in nodes.iter().enumerate() {
+            if i > 0 { f.write_str(separator)? }
+            match (self, node) {
+                (PakQueryNode::All(_), PakQueryNode::Any(_)) | (PakQueryNode::Any(_), PakQueryNode::Any(_)) => write!(f, "({node})")?,
+                _ => node.fmt(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+//==============================================================================================
+//        Pak Query Builder
+//==============================================================================================
+
+/// Builds a [PakQueryNode](crate::query::PakQueryNode) one comparison at a time, without the precedence surprises of `&` and `|`:
+///
+/// ```rust
+/// # use pak_db::query::Query;
+/// let query = Query::where_("age").gt(26).and().where_("first_name").eq("John");
+/// ```
+///
+/// `and` binds tighter than `or`, the same as in [parse](crate::query::parse), and [group](crate::query::PakQueryJoin::group) nests a
+/// whole query as a single term. The builder can be passed straight to [Pak::query](crate::Pak::query).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PakQueryBuilder {
+    /// The terms of every `or`, each of which is the terms of an `and`.
+    groups : Vec<Vec<PakQueryNode>>,
+}
+
+/// The name [PakQueryBuilder](crate::query::PakQueryBuilder) goes by when starting a query.
+pub type Query = PakQueryBuilder;
+
+impl PakQueryBuilder {
+    /// Starts a query with a comparison against the index with the key.
+    pub fn where_(key : &str) -> PakQueryField {
+        PakQueryField { groups : Vec::new(), key : key.to_string(), or : true }
+    }
+    
+    /// Starts a query with a whole query nested as a single term.
+    pub fn group(query : impl Into<PakQueryNode>) -> Self {
+        PakQueryJoin { groups : Vec::new(), or : true }.group(query)
+    }
+    
+    pub fn and(self) -> PakQueryJoin {
+        PakQueryJoin { groups : self.groups, or : false }
+    }
+    
+    pub fn or(self) -> PakQueryJoin {
+        PakQueryJoin { groups : self.groups, or : true }
+    }
+    
+    pub fn build(self) -> PakQueryNode {
+        PakQueryNode::join(self.groups.into_iter().map(|terms| PakQueryNode::join(terms, true)).collect(), false)
+    }
+}
+
+impl From<PakQueryBuilder> for PakQueryNode {
+    fn from(builder : PakQueryBuilder) -> Self {
+        builder.build()
+    }
+}
+
+impl PakQueryExpression for PakQueryBuilder {
+    fn execute(&self, pak : &Pak) -> PakResult<HashSet<PakTypedPointer>> {
+        self.clone().build().execute(pak)
+    }
+    
+    fn matches(&self, indices : &[PakIndex]) -> bool {
+        self.clone().build().matches(indices)
+    }
+    
+    fn exists(&self, pak : &Pak) -> PakResult<bool> {
+        self.clone().build().exists(pak)
+    }
+    
+    fn estimate(&self, pak : &Pak) -> PakResult<Option<u64>> {
+        self.clone().build().estimate(pak)
+    }
+    
+    fn probe(&self, pak : &Pak, candidates : &HashSet<PakTypedPointer>) -> PakResult<Option<HashSet<PakTypedPointer>>> {
+        self.clone().build().probe(pak, candidates)
+    }
+    
+    #[cfg(feature = "roaring")]
+    fn execute_bitmap(&self, pak : &Pak) -> PakResult<Option<PakBitmapSet>> {
+        self.clone().build().execute_bitmap(pak)
+    }
+    
+    fn to_node(&self) -> Option<PakQueryNode> {
+        Some(self.clone().build())
+    }
+}
+
+impl Display for PakQueryBuilder {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.clone().build().fmt(f)
+    }
+}
+
+/// A query waiting for its next term, after [and](crate::query::PakQueryBuilder::and) or [or](crate::query::PakQueryBuilder::or).
+pub struct PakQueryJoin {
+    groups : Vec<Vec<PakQueryNode>>,
+    or : bool,
+}
+
+impl PakQueryJoin {
+    pub fn where_(self, key : &str) -> PakQueryField {
+        PakQueryField { groups : self.groups, key : key.to_string(), or : self.or }
+    }
+    
+    /// Adds a whole query as a single term, like putting it in parentheses.
+    pub fn group(mut self, query : impl Into<PakQueryNode>) -> PakQueryBuilder {
+        match self.groups.last_mut() {
+            Some(terms) if !self.or => terms.push(query.into()),
+            _ => self.groups.push(vec![query.into()]),
+        }
+        PakQueryBuilder { groups : self.groups }
+    }
+}
+
+/// A query waiting for the comparison against the key given to `where_`.
+pub struct PakQueryField {
+    groups : Vec<Vec<PakQueryNode>>,
+    key : String,
+    or : bool,
+}
+
+impl PakQueryField {
+    fn compare(self, query : PakQuery) -> PakQueryBuilder {
+        PakQueryJoin { groups : self.groups, or : self.or }.group(query)
+    }
+    
+    pub fn eq(self, value : impl IntoPakValue) -> PakQueryBuilder {
+        let query = PakQuery::Equal(self.key.clone(), value.into_pak_value());
+        self.compare(query)
+    }
+    
+    pub fn gt(self, value : impl IntoPakValue) -> PakQueryBuilder {
+        let query = PakQuery::GreaterThan(self.key.clone(), value.into_pak_value());
+        self.compare(query)
+    }
+    
+    pub fn lt(self, value : impl IntoPakValue) -> PakQueryBuilder {
+        let query = PakQuery::LessThan(self.key.clone(), value.into_pak_value());
+        self.compare(query)
+    }
+    
+    pub fn ge(self, value : impl IntoPakValue) -> PakQueryBuilder {
+        let query = PakQuery::GreaterThanEqual(self.key.clone(), value.into_pak_value());
+        self.compare(query)
+    }
+    
+    pub fn le(self, value : impl IntoPakValue) -> PakQueryBuilder {
+        let query = PakQuery::LessThanEqual(self.key.clone(), value.into_pak_value());
+        self.compare(query)
+    }
+}
+
+//==============================================================================================
+//        Query Text
+//==============================================================================================
+
+/// Parses a query written as text, for places where queries can't be built in Rust, like bindings to other languages or a command line.
+/// Comparisons look like `key >= value`, using `==`, `>`, `<`, `>=` or `<=`, and can be combined with `and`, `or` and parentheses, where
+/// `and` binds tighter. Values are quoted strings, `true` or `false`, or numbers, so `(kind == "sword" or kind == "axe") and damage > 10`.
+pub fn parse(text : &str) -> PakResult<PakQueryNode> {
+    let mut parser = PakQueryParser { tokens : lex(text)?, position : 0 };
+    let query = parser.or()?;
+    match parser.next() {
+        None => Ok(query),
+        Some(token) => Err(PakError::InvalidQuery(format!("unexpected {token:?}"))),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PakQueryToken {
+    Key(String),
+    Value(PakValue),
+    Operator(&'static str),
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+fn lex(text : &str) -> PakResult<Vec<PakQueryToken>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => { chars.next(); },
+            '(' => { chars.next(); tokens.push(PakQueryToken::Open) },
+            ')' => { chars.next(); tokens.push(PakQueryToken::Close) },
+            '"' | '\'' => {
+                chars.next();
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => string.extend(chars.next()),
+                        Some(end) if end == c => break,
+                        Some(c) => string.push(c),
+                        None => return Err(PakError::InvalidQuery("a quoted string is never closed".to_string())),
+                    }
+                }
+                tokens.push(PakQueryToken::Value(PakValue::String(string)));
             },
-            PakQuery::GreaterThanEqual(key, pak_value) => {
-                let tree = pak.get_tree(key)?;
-                tree.get_greater_eq(pak_value)
+            '=' | '<' | '>' | '!' | '&' | '|' => {
+                let mut operator = String::new();
+                while let Some(&c) = chars.peek() && "=<>!&|".contains(c) {
+                    operator.push(c);
+                    chars.next();
+                }
+                let token = match operator.as_str() {
+                    "=" | "==" => PakQueryToken::Operator("=="),
+                    ">" => PakQueryToken::Operator(">"),
+                    "<" => PakQueryToken::Operator("<"),
+                    ">=" => PakQueryToken::Operator(">="),
+                    "<=" => PakQueryToken::Operator("<="),
+                    "&&" => PakQueryToken::And,
+                    "||" => PakQueryToken::Or,
+                    _ => return Err(PakError::InvalidQuery(format!("{operator} isn't an operator"))),
+                };
+                tokens.push(token);
             },
-            PakQuery::LessThanEqual(key, pak_value) => {
-                let tree = pak.get_tree(key)?;
-                tree.get_less_eq(pak_value)
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "()\"'=<>!&|".contains(c) { break }
+                    word.push(c);
+                    chars.next();
+                }
+                let token = match word.as_str() {
+                    "and" | "AND" => PakQueryToken::And,
+                    "or" | "OR" => PakQueryToken::Or,
+                    "true" => PakQueryToken::Value(PakValue::Boolean(true)),
+                    "false" => PakQueryToken::Value(PakValue::Boolean(false)),
+                    "void" => PakQueryToken::Value(PakValue::Void),
+                    _ => match number(&word) {
+                        Some(value) => PakQueryToken::Value(value),
+                        None => PakQueryToken::Key(word),
+                    },
+                };
+                tokens.push(token);
             },
         }
     }
-}
\ No newline at end of file
+    Ok(tokens)
+}
+
+fn number(word : &str) -> Option<PakValue> {
+    if !word.starts_with(|c : char| c.is_ascii_digit() || c == '-') { return None }
+    if let Ok(value) = word.parse::<i64>() { return Some(value.into()) }
+    if let Ok(value) = word.parse::<u64>() { return Some(value.into()) }
+    if let Ok(value) = word.parse::<i128>() { return Some(PakValue::Int128(value)) }
+    if let Ok(value) = word.parse::<u128>() { return Some(PakValue::Uint128(value)) }
+    word.parse::<f64>().ok().map(PakValue::from)
+}
+
+struct PakQueryParser {
+    tokens : Vec<PakQueryToken>,
+    position : usize,
+}
+
+impl PakQueryParser {
+    fn peek(&self) -> Option<&PakQueryToken> {
+        self.tokens.get(self.position)
+    }
+    
+    fn next(&mut self) -> Option<PakQueryToken> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+    
+    fn or(&mut self) -> PakResult<PakQueryNode> {
+        let mut nodes = vec![self.and()?];
+        while self.peek() == Some(&PakQueryToken::Or) {
+            self.next();
+            nodes.push(self.and()?);
+        }
+        Ok(PakQueryNode::join(nodes, false))
+    }
+    
+    fn and(&mut self) -> PakResult<PakQueryNode> {
+        let mut nodes = vec![self.comparison()?];
+        while self.peek() == Some(&PakQueryToken::And) {
+            self.next();
+            nodes.push(self.comparison()?);
+        }
+        Ok(PakQueryNode::join(nodes, true))
+    }
+    
+    fn comparison(&mut self) -> PakResult<PakQueryNode> {
+        let key = match self.next() {
+            Some(PakQueryToken::Open) => {
+                let query = self.or()?;
+                return match self.next() {
+                    Some(PakQueryToken::Close) => Ok(query),
+                    _ => Err(PakError::InvalidQuery("a parenthesis is never closed".to_string())),
+                }
+            },
+            Some(PakQueryToken::Key(key)) => key,
+            Some(token) => return Err(PakError::InvalidQuery(format!("expected a key but found {token:?}"))),
+            None => return Err(PakError::InvalidQuery("the query ends where a key was expected".to_string())),
+        };
+        let Some(PakQueryToken::Operator(operator)) = self.next() else {
+            return Err(PakError::InvalidQuery(format!("{key} has to be followed by a comparison")))
+        };
+        let Some(PakQueryToken::Value(value)) = self.next() else {
+            return Err(PakError::InvalidQuery(format!("{key} {operator} has to be followed by a value")))
+        };
+        let query = match operator {
+            "==" => PakQuery::Equal(key, value),
+            ">" => PakQuery::GreaterThan(key, value),
+            "<" => PakQuery::LessThan(key, value),
+            ">=" => PakQuery::GreaterThanEqual(key, value),
+            _ => PakQuery::LessThanEqual(key, value),
+        };
+        Ok(PakQueryNode::Compare(query))
+    }
+}
@@ -0,0 +1,172 @@
+use std::io::Cursor;
+use crate::{error::{PakError, PakResult}, item::PakItemDeserialize, meta::{PakMeta, PakSizing, PakTrailer}, pointer::{byte_range, to_usize, PakPointer, PakTypedPointer}, Pak};
+
+//==============================================================================================
+//        PakSalvage
+//==============================================================================================
+
+/// The items that could be rescued from a damaged pak, created with [Pak::open_recover](crate::Pak::open_recover). Items are found through
+/// the pak's ordinal table, which records the type and location of every item. When the header is intact the table is found through it. If
+/// it isn't, the backup table of contents at the end of the file is used, and as a last resort the file is scanned for the table. Indices are
+/// only usable if an intact copy of the header was found, see [into_pak](crate::recover::PakSalvage::into_pak).
+pub struct PakSalvage {
+    data : Vec<u8>,
+    vault_start : u64,
+    meta : Option<PakMeta>,
+    trailer : Option<PakTrailer>,
+    items : Vec<PakTypedPointer>,
+    lost : usize,
+    indices_intact : bool,
+}
+
+impl PakSalvage {
+    pub(crate) fn recover(data : Vec<u8>) -> Self {
+        let mut salvage = Self { data, vault_start : 0, meta : None, trailer : None, items : Vec::new(), lost : 0, indices_intact : false };
+        if !salvage.recover_from_header() && !salvage.recover_from_trailer() {
+            salvage.recover_from_scan();
+        }
+        salvage
+    }
+
+    /// Uses the header to find the ordinal table. This works as long as the start of the file and the table are both intact.
+    fn recover_from_header(&mut self) -> bool {
+        let Some(sizing) = self.data.get(..24).and_then(|bytes| bincode::deserialize::<PakSizing>(bytes).ok()) else { return false };
+        let Some(meta_end) = 24u64.checked_add(sizing.meta_size).filter(|end| *end <= self.data.len() as u64) else { return false };
+        let Some(meta_bytes) = to_usize(meta_end).ok().and_then(|end| self.data.get(24..end)) else { return false };
+        let Ok(meta) = PakMeta::decode(meta_bytes) else { return false };
+        let Some(vault_start) = meta_end.checked_add(sizing.indices_size).and_then(|start| start.checked_add(8)) else { return false };
+
+        let table = meta.ordinals.as_pointer();
+        let Some(items) = vault_start.checked_add(table.offset()).and_then(|position| table_at(&self.data, position)) else { return false };
+        if table_end(&items) != table.offset() { return false }
+
+        let indices = byte_range(meta_end, vault_start - 8 - meta_end).ok().and_then(|range| self.data.get(range));
+        self.indices_intact = indices.is_some_and(|bytes| meta.deserialize_indices(bytes).is_ok());
+        self.meta = Some(meta);
+        self.keep(vault_start, items);
+        true
+    }
+
+    /// Uses the backup table of contents to find the ordinal table. This works when the start of the file was damaged but its end wasn't.
+    fn recover_from_trailer(&mut self) -> bool {
+        let Some(trailer) = PakTrailer::read(&self.data) else { return false };
+        let sizing = &trailer.sizing;
+        let Some(vault_start) = 32u64.checked_add(sizing.meta_size).and_then(|start| start.checked_add(sizing.indices_size)) else { return false };
+        let table = trailer.meta.ordinals.as_pointer();
+        let Some(items) = vault_start.checked_add(table.offset()).and_then(|position| table_at(&self.data, position)) else { return false };
+        if table_end(&items) != table.offset() { return false }
+        
+        self.indices_intact = true;
+        self.meta = Some(trailer.meta.clone());
+        self.trailer = Some(trailer);
+        self.keep(vault_start, items);
+        true
+    }
+
+    /// Looks for the ordinal table anywhere in the file. Since items are laid out back to back and the table is paked right after the last
+    /// item, the table also tells us where the vault starts. The largest table that fits is taken, since smaller lists of pointers can show up
+    /// inside of the index structures.
+    fn recover_from_scan(&mut self) {
+        let mut best : Option<(u64, Vec<PakTypedPointer>)> = None;
+        for position in 0..self.data.len() {
+            let Some(items) = table_at(&self.data, position as u64) else { continue };
+            let end = table_end(&items);
+            if end > position as u64 { continue }
+            if best.as_ref().is_none_or(|(_, best)| items.len() > best.len()) {
+                best = Some((position as u64 - end, items));
+            }
+        }
+        if let Some((vault_start, items)) = best {
+            self.keep(vault_start, items);
+        }
+    }
+
+    fn keep(&mut self, vault_start : u64, items : Vec<PakTypedPointer>) {
+        let len = self.data.len() as u64;
+        self.vault_start = vault_start;
+        let total = items.len();
+        self.items = items.into_iter().take_while(|item| {
+            let pointer = item.clone().into_pointer();
+            vault_start.checked_add(pointer.offset() + pointer.size()).is_some_and(|end| end <= len)
+        }).collect();
+        self.lost = total - self.items.len();
+    }
+
+    /// The pointers of every item that was salvaged, in ordinal order.
+    pub fn pointers(&self) -> impl Iterator<Item = PakPointer> + '_ {
+        self.items.iter().cloned().map(PakTypedPointer::into_pointer)
+    }
+
+    /// The number of items that were salvaged.
+    pub fn item_count(&self) -> usize {
+        self.items.len()
+    }
+
+    /// The number of items that are listed in the ordinal table but whose bytes are missing from the file.
+    pub fn lost_count(&self) -> usize {
+        self.lost
+    }
+
+    /// The metadata of the pak, if the header could still be read.
+    pub fn meta(&self) -> Option<&PakMeta> {
+        self.meta.as_ref()
+    }
+
+    /// Returns true if the header and the index map could both be read. When this is true the pak can most likely be opened normally.
+    pub fn indices_intact(&self) -> bool {
+        self.indices_intact
+    }
+
+    /// Turns the salvage back into a fully working [Pak](crate::Pak), indices included. If the header was damaged, it is rebuilt from the backup
+    /// table of contents first. This fails if no intact copy of the header was found.
+    pub fn into_pak(mut self) -> PakResult<Pak> {
+        if !self.indices_intact { return Err(PakError::InvalidHeader("sizing".to_string(), "no intact copy of the header was found".to_string())) }
+        if let Some(trailer) = self.trailer.take() {
+            let mut header = bincode::serialize(&trailer.sizing)?;
+            header.extend(bincode::serialize(&trailer.meta)?);
+            header.extend(trailer.meta.serialize_indices(&trailer.indices)?);
+            self.data[..header.len()].copy_from_slice(&header);
+        }
+        Pak::new(Cursor::new(self.data))
+    }
+
+    /// Reads the raw bytes of a salvaged item.
+    pub fn read_bytes(&self, pointer : &PakPointer) -> PakResult<Vec<u8>> {
+        let start = self.vault_start.checked_add(pointer.offset()).ok_or(PakError::OffsetOverflow(pointer.offset()))?;
+        match self.data.get(byte_range(start, pointer.size())?) {
+            Some(bytes) => Ok(bytes.to_vec()),
+            None => Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()),
+        }
+    }
+
+    /// Loads a salvaged item.
+    pub fn get<T>(&self, pointer : &PakPointer) -> PakResult<T> where T : PakItemDeserialize {
+        if !pointer.type_is_match::<T>() { return Err(PakError::type_mismatch::<T>(pointer, None)) }
+        T::from_bytes(&self.read_bytes(pointer)?)
+    }
+}
+
+/// Tries to read an ordinal table at the position. A table is only accepted if its pointers start at the beginning of the vault and follow each
+/// other without gaps, which is very unlikely to happen by chance.
+fn table_at(data : &[u8], position : u64) -> Option<Vec<PakTypedPointer>> {
+    let bytes = data.get(to_usize(position).ok()?..)?;
+    let len = u64::from_le_bytes(bytes.get(..8)?.try_into().ok()?);
+    // Every pointer takes at least 24 bytes, which keeps garbage lengths from allocating.
+    if len == 0 || len > (bytes.len() as u64 - 8) / 24 { return None }
+
+    let items = bincode::deserialize::<Vec<PakTypedPointer>>(bytes).ok()?;
+    let mut expected = 0u64;
+    for item in &items {
+        let pointer = item.clone().into_pointer();
+        if pointer.offset() != expected || pointer.type_name().is_empty() { return None }
+        expected = expected.checked_add(pointer.size())?;
+    }
+    Some(items)
+}
+
+fn table_end(items : &[PakTypedPointer]) -> u64 {
+    items.last().map(|item| {
+        let pointer = item.clone().into_pointer();
+        pointer.offset() + pointer.size()
+    }).unwrap_or(0)
+}
@@ -0,0 +1,115 @@
+use std::{collections::BTreeMap, fs::File, io::{self, BufReader, BufWriter, Write}, path::Path};
+use serde::{Deserialize, Serialize};
+use crate::{error::{PakError, PakResult}, pointer::{to_usize, PakPointer}, PakSource};
+
+/// One thing a source was asked, in the order a [PakRecordingSource] saw it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+enum PakCaptureEntry {
+    Size(Option<u64>),
+    Read(u64, Vec<u8>),
+}
+
+//==============================================================================================
+//        PakRecordingSource
+//==============================================================================================
+
+/// A source that passes every read on to another source and logs it to a capture file, along with the bytes it returned. Replaying the
+/// capture with a [PakReplaySource] makes the same reads come back the same way, so a query that misbehaves on a large pak can be reproduced
+/// from a capture that only holds the parts of the pak the query touched. Entries are written as they happen, so the capture is
+/// complete even if the program doesn't exit cleanly.
+pub struct PakRecordingSource<S> {
+    source : S,
+    capture : BufWriter<File>,
+}
+
+impl<S> PakRecordingSource<S> where S : PakSource {
+    /// Records the reads made through the source to a new capture file at the path, replacing any file that was there.
+    pub fn new(source : S, path : impl AsRef<Path>) -> PakResult<Self> {
+        Ok(Self { source, capture : BufWriter::new(File::create(path)?) })
+    }
+
+    fn log(&mut self, entry : &PakCaptureEntry) -> PakResult<()> {
+        bincode::serialize_into(&mut self.capture, entry)?;
+        self.capture.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S> PakSource for PakRecordingSource<S> where S : PakSource {
+    fn read(&mut self, pointer : &PakPointer, offset : u64) -> PakResult<Vec<u8>> {
+        let bytes = self.source.read(pointer, offset)?;
+        let start = pointer.offset().checked_add(offset).ok_or(PakError::OffsetOverflow(offset))?;
+        self.log(&PakCaptureEntry::Read(start, bytes.clone()))?;
+        Ok(bytes)
+    }
+
+    fn size(&mut self) -> PakResult<Option<u64>> {
+        let size = self.source.size()?;
+        self.log(&PakCaptureEntry::Size(size))?;
+        Ok(size)
+    }
+}
+
+//==============================================================================================
+//        PakReplaySource
+//==============================================================================================
+
+/// A source that serves the reads logged by a [PakRecordingSource] instead of reading a pak. Any read that falls inside a recorded one is
+/// answered, and reads of bytes that were never recorded fail, since the rest of the pak isn't in the capture.
+pub struct PakReplaySource {
+    size : Option<u64>,
+    reads : BTreeMap<u64, Vec<u8>>,
+}
+
+impl PakReplaySource {
+    /// Loads a capture written by a [PakRecordingSource].
+    pub fn open(path : impl AsRef<Path>) -> PakResult<Self> {
+        let mut capture = BufReader::new(File::open(path)?);
+        let mut source = Self { size : None, reads : BTreeMap::new() };
+        loop {
+            let entry = match bincode::deserialize_from(&mut capture) {
+                Ok(entry) => entry,
+                Err(error) if matches!(&*error, bincode::ErrorKind::Io(io) if io.kind() == io::ErrorKind::UnexpectedEof) => break,
+                Err(error) => return Err(error.into()),
+            };
+            match entry {
+                PakCaptureEntry::Size(size) => source.size = size,
+                PakCaptureEntry::Read(start, bytes) => {
+                    // The same bytes can be read more than once, so only the longest read at each offset is kept.
+                    let longest = source.reads.entry(start).or_default();
+                    if bytes.len() > longest.len() { *longest = bytes }
+                },
+            }
+        }
+        Ok(source)
+    }
+
+    /// The number of distinct reads in the capture.
+    pub fn len(&self) -> usize {
+        self.reads.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reads.is_empty()
+    }
+}
+
+impl PakSource for PakReplaySource {
+    fn read(&mut self, pointer : &PakPointer, offset : u64) -> PakResult<Vec<u8>> {
+        let start = pointer.offset().checked_add(offset).ok_or(PakError::OffsetOverflow(offset))?;
+        let len = to_usize(pointer.size())?;
+        for (&recorded, bytes) in self.reads.range(..=start).rev() {
+            let skip = to_usize(start - recorded)?;
+            if let Some(bytes) = bytes.get(skip..skip.saturating_add(len)) { return Ok(bytes.to_vec()) }
+        }
+        Err(io::Error::new(io::ErrorKind::NotFound, format!("the capture has no read of {len} bytes at {start}")).into())
+    }
+
+    fn size(&mut self) -> PakResult<Option<u64>> {
+        Ok(self.size)
+    }
+}
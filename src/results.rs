@@ -0,0 +1,103 @@
+use std::collections::{HashMap, HashSet};
+use crate::{error::PakResult, item::PakItemDeserialize, pointer::PakPointer, query::PakQueryExpression, Pak};
+
+/// A shorter name for [PakResultSet], for chaining refinements onto [Pak::results](crate::Pak::results).
+pub type ResultSet<'p> = PakResultSet<'p>;
+
+//==============================================================================================
+//        PakResultSet
+//==============================================================================================
+
+/// The items matching a query, from [Pak::results](crate::Pak::results), which can be narrowed further before any of them are read. Each
+/// call to [and](PakResultSet::and) only runs the new expression and keeps the matches that are already in the set, so a search that is
+/// narrowed step by step never runs its earlier steps again. Clone a set to keep an earlier step around to go back to.
+///
+/// Ordering and paging are only applied once the set is read with [pointers](PakResultSet::pointers) or [items](PakResultSet::items).
+/// Until an order is given, items come back in the order they sit in the pak.
+#[derive(Clone)]
+pub struct PakResultSet<'p> {
+    pak : &'p Pak,
+    pointers : Vec<PakPointer>,
+    order : Option<(String, bool)>,
+    page : Option<(usize, usize)>,
+}
+
+impl<'p> PakResultSet<'p> {
+    pub(crate) fn new(pak : &'p Pak, query : impl PakQueryExpression) -> PakResult<Self> {
+        let mut pointers = query.execute(pak)?.into_iter().map(|pointer| pointer.into_pointer()).collect::<Vec<_>>();
+        pointers.sort_by_key(|pointer| pointer.offset());
+        Ok(Self { pak, pointers, order : None, page : None })
+    }
+
+    /// Keeps only the items that also match the query.
+    pub fn and(mut self, query : impl PakQueryExpression) -> PakResult<Self> {
+        let matches = query.execute(self.pak)?.into_iter().map(|pointer| pointer.offset()).collect::<HashSet<_>>();
+        self.pointers.retain(|pointer| matches.contains(&pointer.offset()));
+        Ok(self)
+    }
+
+    /// Orders the items by their value in the index, lowest first. Items that aren't in the index come last, in the order they sit in the pak.
+    pub fn order_by(mut self, key : &str) -> Self {
+        self.order = Some((key.to_string(), false));
+        self
+    }
+
+    /// Orders the items by their value in the index, highest first. Items that aren't in the index still come last.
+    pub fn order_by_descending(mut self, key : &str) -> Self {
+        self.order = Some((key.to_string(), true));
+        self
+    }
+
+    /// Only reads the page with the given number, counting from 0, where every page holds `size` items.
+    pub fn page(mut self, number : usize, size : usize) -> Self {
+        self.page = Some((number, size));
+        self
+    }
+
+    /// The number of items in the set, without paging.
+    pub fn len(&self) -> usize {
+        self.pointers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pointers.is_empty()
+    }
+
+    /// The pointers to the items of the page, in order.
+    pub fn pointers(&self) -> PakResult<Vec<PakPointer>> {
+        let ordered = match &self.order {
+            Some((key, descending)) => self.ordered(key, *descending)?,
+            None => self.pointers.clone(),
+        };
+        Ok(match self.page {
+            Some((number, size)) => ordered.into_iter().skip(number.saturating_mul(size)).take(size).collect(),
+            None => ordered,
+        })
+    }
+
+    /// Reads the items of the page that are of type `T`, in order.
+    pub fn items<T>(&self) -> PakResult<Vec<T>> where T : PakItemDeserialize {
+        self.pointers()?.iter().filter(|pointer| pointer.type_is_match::<T>()).map(|pointer| self.pak.get(pointer)).collect()
+    }
+
+    /// Walks the index in order, collecting the items of the set as they come up, and stops once every item has been found.
+    fn ordered(&self, key : &str, descending : bool) -> PakResult<Vec<PakPointer>> {
+        let mut remaining = self.pointers.iter().map(|pointer| (pointer.offset(), pointer)).collect::<HashMap<_, _>>();
+        let mut groups = Vec::<Vec<PakPointer>>::new();
+        self.pak.index(key)?.scan(|_, postings| {
+            let mut group = Vec::new();
+            for pointer in postings.pointers()? {
+                if let Some(pointer) = remaining.remove(&pointer?.offset()) { group.push(pointer.clone()) }
+            }
+            if !group.is_empty() {
+                group.sort_by_key(|pointer| pointer.offset());
+                groups.push(group);
+            }
+            Ok(!remaining.is_empty())
+        })?;
+        if descending { groups.reverse() }
+        let mut ordered = groups.into_iter().flatten().collect::<Vec<_>>();
+        ordered.extend(self.pointers.iter().filter(|pointer| remaining.contains_key(&pointer.offset())).cloned());
+        Ok(ordered)
+    }
+}
@@ -0,0 +1,103 @@
+use std::{collections::HashSet, marker::PhantomData, ops::{BitAnd, BitOr}};
+use crate::{error::PakResult, index::PakIndex, item::{PakItemDeserialize, PakItemSerialize}, pointer::PakTypedPointer, query::{PakQueryExpression, PakQueryIntersection, PakQueryUnion}, value::PakValue, Pak};
+
+//==============================================================================================
+//        PakQueryFilter
+//==============================================================================================
+
+/// An expression narrowed down by a predicate on the items themselves, made with
+/// [filter](crate::query::PakQueryExpression::filter). This is a fallback for fields that have no index: every item the expression
+/// matches is read and deserialized to be checked, so keep the indexed part of the query as selective as possible. Only items of type
+/// `T` can pass the filter.
+///
+/// The predicate can't be written out as a [PakQueryNode](crate::query::PakQueryNode), so filtered expressions can't be sent to a
+/// remote pak or run on another thread.
+pub struct PakQueryFilter<Q, T, F> {
+    query : Q,
+    predicate : F,
+    item : PhantomData<fn(&T)>,
+}
+
+impl<Q, T, F> PakQueryFilter<Q, T, F> {
+    pub(crate) fn new(query : Q, predicate : F) -> Self {
+        Self { query, predicate, item : PhantomData }
+    }
+}
+
+impl<Q, T, F> PakQueryExpression for PakQueryFilter<Q, T, F> where Q : PakQueryExpression, T : PakItemDeserialize, F : Fn(&T) -> bool {
+    fn execute(&self, pak : &Pak) -> PakResult<HashSet<PakTypedPointer>> {
+        let mut results = HashSet::new();
+        for pointer in self.query.execute(pak)? {
+            let untyped = pointer.clone().into_pointer();
+            if !untyped.type_is_match::<T>() { continue }
+            if (self.predicate)(&pak.get::<T>(&untyped)?) { results.insert(pointer); }
+        }
+        Ok(results)
+    }
+
+    /// The predicate is checked against the item that a [NaiveStore](crate::testing::NaiveStore) keeps among the indices of every item it
+    /// is given. Indices that don't carry an item of type `T` never pass the filter.
+    fn matches(&self, indices : &[PakIndex]) -> bool {
+        self.query.matches(indices) && indexed_item::<T>(indices).is_some_and(|item| (self.predicate)(&item))
+    }
+}
+
+impl<Q, T, F, B> BitAnd<B> for PakQueryFilter<Q, T, F> where Self : PakQueryExpression + 'static, B : PakQueryExpression + 'static {
+    type Output = PakQueryIntersection;
+
+    fn bitand(self, rhs : B) -> Self::Output {
+        PakQueryIntersection::new(Box::new(self), Box::new(rhs))
+    }
+}
+
+impl<Q, T, F, B> BitOr<B> for PakQueryFilter<Q, T, F> where Self : PakQueryExpression + 'static, B : PakQueryExpression + 'static {
+    type Output = PakQueryUnion;
+
+    fn bitor(self, rhs : B) -> Self::Output {
+        PakQueryUnion::new(Box::new(self), Box::new(rhs))
+    }
+}
+
+/// The reserved index keys that a [NaiveStore](crate::testing::NaiveStore) keeps the type and the bytes of an item under, so a filter can
+/// check its predicate without a pak. Index values have no kind for raw bytes, so the bytes are written out in hex.
+pub(crate) const PAK_ITEM_TYPE_KEY : &str = "__pak_item_type";
+pub(crate) const PAK_ITEM_BYTES_KEY : &str = "__pak_item_bytes";
+
+/// The indices that carry the item itself, or none if it can't be serialized.
+pub(crate) fn item_indices<T>(item : &T) -> Vec<PakIndex> where T : PakItemSerialize {
+    let Ok(bytes) = item.into_bytes() else { return Vec::new() };
+    let hex = bytes.iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+    vec![PakIndex::new(PAK_ITEM_TYPE_KEY, std::any::type_name::<T>()), PakIndex::new(PAK_ITEM_BYTES_KEY, hex)]
+}
+
+/// The item carried by the indices, if they carry one of type `T`.
+fn indexed_item<T>(indices : &[PakIndex]) -> Option<T> where T : PakItemDeserialize {
+    let value = |key : &str| match &indices.iter().find(|index| index.key == key)?.value {
+        PakValue::String(value) => Some(value),
+        _ => None,
+    };
+    if value(PAK_ITEM_TYPE_KEY)? != std::any::type_name::<T>() { return None }
+    let hex = value(PAK_ITEM_BYTES_KEY)?;
+    let bytes = (0..hex.len()).step_by(2).map(|at| u8::from_str_radix(hex.get(at..at + 2)?, 16).ok()).collect::<Option<Vec<_>>>()?;
+    T::from_bytes(&bytes).ok()
+}
+
+//==============================================================================================
+//        Pak
+//==============================================================================================
+
+impl Pak {
+    /// Reads every item of type `T` in the pak and returns the ones the predicate keeps, in the order they sit in the pak. This is a full
+    /// scan of the pak, for questions about fields that have no index. To narrow down a query that does use indices, use
+    /// [filter](crate::query::PakQueryExpression::filter) instead.
+    pub fn scan<T, F>(&self, mut predicate : F) -> PakResult<Vec<T>> where T : PakItemDeserialize, F : FnMut(&T) -> bool {
+        let mut items = Vec::new();
+        for pointer in self.ordinals()? {
+            let pointer = pointer.clone().into_pointer();
+            if !pointer.type_is_match::<T>() { continue }
+            let item = self.get::<T>(&pointer)?;
+            if predicate(&item) { items.push(item) }
+        }
+        Ok(items)
+    }
+}
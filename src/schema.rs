@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "derive")]
+pub use pak_db_derive::PakSchema;
+
+//==============================================================================================
+//        PakSchema
+//==============================================================================================
+
+/// Describes the item types that a pak is expected to hold and the index keys each of them is paked with. This is usually derived on a
+/// marker struct:
+///
+/// ```ignore
+/// #[derive(PakSchema)]
+/// #[pak_schema(Person(first_name, last_name, age), Pet(name, age))]
+/// struct GameSchema;
+/// ```
+///
+/// The schema's hash is stored in the pak with [PakBuilder::with_schema](crate::PakBuilder::with_schema), and
+/// [Pak::open_with_schema](crate::Pak::open_with_schema) refuses files whose schema doesn't match.
+pub trait PakSchema {
+    fn descriptor() -> PakSchemaDescriptor;
+}
+
+//==============================================================================================
+//        PakSchemaDescriptor
+//==============================================================================================
+
+/// The description of a schema that is stored in the pak's header. Items and fields are kept sorted, so the order they were declared in
+/// doesn't change the hash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PakSchemaDescriptor {
+    pub items : Vec<PakSchemaItem>,
+}
+
+/// One item type in a [PakSchemaDescriptor](crate::schema::PakSchemaDescriptor).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PakSchemaItem {
+    /// The path of the type the way the schema names it, like `Person` or `items::Sword`.
+    pub type_name : String,
+    pub fields : Vec<String>,
+}
+
+impl PakSchemaItem {
+    pub fn new(type_name : &str, fields : &[&str]) -> Self {
+        let mut fields = fields.iter().map(|field| field.to_string()).collect::<Vec<_>>();
+        fields.sort();
+        fields.dedup();
+        Self { type_name : type_name.to_string(), fields }
+    }
+    
+    /// Returns true if the item type is the type with the full name, like the one a pointer records. The schema's path only has to match
+    /// the end of the full name, since the schema names types the way they are written where it is declared.
+    pub fn is_type(&self, full_name : &str) -> bool {
+        full_name == self.type_name || full_name.strip_suffix(&self.type_name).is_some_and(|prefix| prefix.ends_with("::"))
+    }
+}
+
+impl PakSchemaDescriptor {
+    pub fn new(mut items : Vec<PakSchemaItem>) -> Self {
+        items.sort_by(|a, b| a.type_name.cmp(&b.type_name));
+        Self { items }
+    }
+    
+    /// A stable FNV-1a hash of the schema. It only depends on the type paths and the fields the schema was declared with, so it is the same
+    /// across builds, compilers and platforms.
+    pub fn hash(&self) -> u64 {
+        let mut hash = 0xcbf2_9ce4_8422_2325u64;
+        let mut write = |bytes : &[u8]| for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        };
+        for item in &self.items {
+            write(item.type_name.as_bytes());
+            write(b":");
+            for field in &item.fields {
+                write(field.as_bytes());
+                write(b",");
+            }
+            write(b";");
+        }
+        hash
+    }
+    
+    /// Lists what changed going from this schema to the other one, one change per line. Added types and fields start with `+` and removed
+    /// ones start with `-`.
+    pub fn diff(&self, other : &PakSchemaDescriptor) -> Vec<String> {
+        let mut changes = Vec::new();
+        for item in &self.items {
+            match other.items.iter().find(|other| other.type_name == item.type_name) {
+                Some(other) => {
+                    changes.extend(item.fields.iter().filter(|field| !other.fields.contains(field)).map(|field| format!("- {}.{field}", item.type_name)));
+                    changes.extend(other.fields.iter().filter(|field| !item.fields.contains(field)).map(|field| format!("+ {}.{field}", item.type_name)));
+                },
+                None => changes.push(format!("- {}", item.type_name)),
+            }
+        }
+        for item in other.items.iter().filter(|other| !self.items.iter().any(|item| item.type_name == other.type_name)) {
+            changes.push(format!("+ {}", item.type_name));
+        }
+        changes
+    }
+}
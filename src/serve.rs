@@ -0,0 +1,248 @@
+//! Serves a pak over a socket, so tools running in another process, like an editor, can query the paks a game has mounted. The game
+//! hands a listener to [serve], and the tool connects with a [RemotePak]:
+//!
+//! ```no_run
+//! # use pak_db::{serve::RemotePak, query::Query};
+//! # #[derive(serde::Deserialize)] struct Sword;
+//! let mut remote = RemotePak::connect("127.0.0.1:7878")?;
+//! let swords = remote.query::<Sword>(Query::where_("damage").gt(10u32))?;
+//! # Ok::<(), pak_db::error::PakError>(())
+//! ```
+//!
+//! Requests and responses are bincode, each written with its length in front as a little endian u64. Queries are sent as their
+//! [PakQueryNode](crate::query::PakQueryNode), so only queries with an expression tree can be run remotely.
+
+use std::{io::{self, Read, Write}, net::{TcpListener, TcpStream, ToSocketAddrs}, time::Duration};
+#[cfg(unix)]
+use std::{os::unix::net::{UnixListener, UnixStream}, path::Path};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use crate::{envelope::PakEnvelope, error::{PakError, PakResult}, item::PakItemDeserialize, pointer::PakPointer, query::{PakQueryExpression, PakQueryNode}, Pak};
+
+/// The largest response that is read or sent, so a broken peer can't make the other side allocate without bound. Queries that match more
+/// than this are answered with an error, and their items have to be read a few at a time.
+pub const PAK_SERVE_MAX_FRAME : u64 = 16 << 20;
+
+/// The largest request the server reads. Requests only hold a query or a pointer, so they are much smaller than responses.
+pub const PAK_SERVE_MAX_REQUEST : u64 = 1 << 20;
+
+/// How long the server waits on a client to send the next request or to take the response, before it drops the connection.
+pub const PAK_SERVE_TIMEOUT : Duration = Duration::from_secs(30);
+
+//==============================================================================================
+//        Protocol
+//==============================================================================================
+
+/// A request sent to [serve].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PakRequest {
+    /// The pointers to the items matching the query.
+    Query(PakQueryNode),
+    /// The pointers and the bytes of the items matching the query, in one round trip.
+    Fetch(PakQueryNode),
+    /// The bytes of the item at the pointer.
+    Read(PakPointer),
+}
+
+/// The answer to a [PakRequest]. Pointers come back in the order their items sit in the pak.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PakResponse {
+    Pointers(Vec<PakPointer>),
+    Items(Vec<(PakPointer, Vec<u8>)>),
+    Bytes(Vec<u8>),
+    /// The request failed on the server, with the message of the error.
+    Error(String),
+}
+
+fn write_frame<T : Serialize>(stream : &mut impl Write, message : &T) -> PakResult<()> {
+    let bytes = bincode::serialize(message)?;
+    write_bytes(stream, &bytes)
+}
+
+fn write_bytes(stream : &mut impl Write, bytes : &[u8]) -> PakResult<()> {
+    stream.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    stream.write_all(bytes)?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Reads the next message, or `None` if the peer closed the connection before sending one.
+fn read_frame<T : DeserializeOwned>(stream : &mut impl Read, max_len : u64) -> PakResult<Option<T>> {
+    let mut len = [0u8; 8];
+    match stream.read_exact(&mut len) {
+        Ok(()) => {},
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error.into()),
+    }
+    let len = u64::from_le_bytes(len);
+    if len > max_len {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("a message of {len} bytes is larger than the limit of {max_len}")).into())
+    }
+    let mut bytes = vec![0u8; crate::pointer::to_usize(len)?];
+    stream.read_exact(&mut bytes)?;
+    Ok(Some(bincode::deserialize(&bytes)?))
+}
+
+//==============================================================================================
+//        Server
+//==============================================================================================
+
+/// A listener [serve] can accept connections from. It is implemented for TCP listeners, and for unix socket listeners on unix, which give
+/// every connection they accept a read and write timeout of [PAK_SERVE_TIMEOUT].
+pub trait PakListener {
+    type Stream : Read + Write;
+
+    fn accept_stream(&self) -> io::Result<Self::Stream>;
+}
+
+impl PakListener for TcpListener {
+    type Stream = TcpStream;
+
+    fn accept_stream(&self) -> io::Result<TcpStream> {
+        let stream = self.accept()?.0;
+        stream.set_read_timeout(Some(PAK_SERVE_TIMEOUT))?;
+        stream.set_write_timeout(Some(PAK_SERVE_TIMEOUT))?;
+        Ok(stream)
+    }
+}
+
+#[cfg(unix)]
+impl PakListener for UnixListener {
+    type Stream = UnixStream;
+
+    fn accept_stream(&self) -> io::Result<UnixStream> {
+        let stream = self.accept()?.0;
+        stream.set_read_timeout(Some(PAK_SERVE_TIMEOUT))?;
+        stream.set_write_timeout(Some(PAK_SERVE_TIMEOUT))?;
+        Ok(stream)
+    }
+}
+
+/// Answers the requests of every client that connects to the listener. A pak can't leave the thread that opened it, so the server is single
+/// client: it blocks that thread, answers one connection at a time, and only returns if accepting a connection fails. Other clients wait
+/// until the current one disconnects, or until it has been idle for the timeout of the listener. A client that breaks its connection is
+/// dropped without stopping the server.
+pub fn serve(pak : &Pak, listener : impl PakListener) -> PakResult<()> {
+    loop {
+        let stream = listener.accept_stream()?;
+        let _ = serve_connection(pak, stream);
+    }
+}
+
+/// Answers the requests on one connection until the client closes it. Games that poll their own sockets can call this instead of [serve].
+/// Only pointers to items the pak holds are read, so a client can't make the server read anything else, like its index structures.
+pub fn serve_connection(pak : &Pak, mut stream : impl Read + Write) -> PakResult<()> {
+    while let Some(request) = read_frame::<PakRequest>(&mut stream, PAK_SERVE_MAX_REQUEST)? {
+        let response = respond(pak, request).unwrap_or_else(|error| PakResponse::Error(error.to_string()));
+        let mut bytes = bincode::serialize(&response)?;
+        if bytes.len() as u64 > PAK_SERVE_MAX_FRAME {
+            let message = format!("the response of {} bytes is larger than the limit of {PAK_SERVE_MAX_FRAME}", bytes.len());
+            bytes = bincode::serialize(&PakResponse::Error(message))?;
+        }
+        write_bytes(&mut stream, &bytes)?;
+    }
+    Ok(())
+}
+
+fn respond(pak : &Pak, request : PakRequest) -> PakResult<PakResponse> {
+    let matching = |query : PakQueryNode| -> PakResult<Vec<PakPointer>> {
+        let mut pointers = query.execute(pak)?.into_iter().map(|pointer| pointer.into_pointer()).collect::<Vec<_>>();
+        pointers.sort_by_key(|pointer| pointer.offset());
+        Ok(pointers)
+    };
+    Ok(match request {
+        PakRequest::Query(query) => PakResponse::Pointers(matching(query)?),
+        PakRequest::Fetch(query) => {
+            let items = matching(query)?.into_iter().map(|pointer| Ok((pointer.clone(), pak.read_bytes(&pointer)?))).collect::<PakResult<_>>()?;
+            PakResponse::Items(items)
+        },
+        PakRequest::Read(pointer) => {
+            let table = pak.ordinals()?;
+            let item = crate::btree::ordinal_of(table, pointer.offset()).map(|ordinal| table[ordinal as usize].clone().into_pointer());
+            if item.is_none_or(|item| item.size() != pointer.size()) {
+                return Err(PakError::InvalidQuery(format!("there is no item of {} bytes at {}", pointer.size(), pointer.offset())))
+            }
+            PakResponse::Bytes(pak.read_bytes(&pointer)?)
+        },
+    })
+}
+
+//==============================================================================================
+//        RemotePak
+//==============================================================================================
+
+/// A connection to a pak that is being served with [serve]. The pointers it hands out carry the generation of the served pak, so reading
+/// one after the server has mounted a new build fails with [PakError::StalePointer](crate::error::PakError::StalePointer).
+pub struct RemotePak<S : Read + Write = TcpStream> {
+    stream : S,
+}
+
+impl RemotePak<TcpStream> {
+    pub fn connect(address : impl ToSocketAddrs) -> PakResult<Self> {
+        Ok(Self::new(TcpStream::connect(address)?))
+    }
+}
+
+#[cfg(unix)]
+impl RemotePak<UnixStream> {
+    pub fn connect_unix(path : impl AsRef<Path>) -> PakResult<Self> {
+        Ok(Self::new(UnixStream::connect(path)?))
+    }
+}
+
+impl <S : Read + Write> RemotePak<S> {
+    /// Talks to a server over a stream that is already connected.
+    pub fn new(stream : S) -> Self {
+        Self { stream }
+    }
+
+    fn request(&mut self, request : &PakRequest) -> PakResult<PakResponse> {
+        write_frame(&mut self.stream, request)?;
+        match read_frame(&mut self.stream, PAK_SERVE_MAX_FRAME)? {
+            Some(PakResponse::Error(message)) => Err(PakError::RemoteError(message)),
+            Some(response) => Ok(response),
+            None => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the server closed the connection").into()),
+        }
+    }
+
+    fn node(query : &impl PakQueryExpression) -> PakResult<PakQueryNode> {
+        query.to_node().ok_or_else(|| PakError::InvalidQuery("the query has no expression tree, so it can't be sent to a remote pak".to_string()))
+    }
+
+    /// The pointers to every item matching the query, in the order they sit in the pak.
+    pub fn query_pointers(&mut self, query : impl PakQueryExpression) -> PakResult<Vec<PakPointer>> {
+        match self.request(&PakRequest::Query(Self::node(&query)?))? {
+            PakResponse::Pointers(pointers) => Ok(pointers),
+            _ => Err(PakError::RemoteError("the server answered a query with something other than pointers".to_string())),
+        }
+    }
+
+    /// Loads the items of type `T` that match the query. Matching items of other types are skipped, but are still sent by the server.
+    pub fn query<T>(&mut self, query : impl PakQueryExpression) -> PakResult<Vec<T>> where T : PakItemDeserialize {
+        let PakResponse::Items(items) = self.request(&PakRequest::Fetch(Self::node(&query)?))? else {
+            return Err(PakError::RemoteError("the server answered a fetch with something other than items".to_string()))
+        };
+        items.into_iter().filter(|(pointer, _)| pointer.type_is_match::<T>()).map(|(pointer, bytes)| decode(&pointer, &bytes)).collect()
+    }
+
+    pub fn read_bytes(&mut self, pointer : &PakPointer) -> PakResult<Vec<u8>> {
+        match self.request(&PakRequest::Read(pointer.clone()))? {
+            PakResponse::Bytes(bytes) => Ok(bytes),
+            _ => Err(PakError::RemoteError("the server answered a read with something other than bytes".to_string())),
+        }
+    }
+
+    pub fn get<T>(&mut self, pointer : &PakPointer) -> PakResult<T> where T : PakItemDeserialize {
+        if !pointer.type_is_match::<T>() { return Err(PakError::type_mismatch::<T>(pointer, None)) }
+        let bytes = self.read_bytes(pointer)?;
+        decode(pointer, &bytes)
+    }
+}
+
+/// Deserializes the bytes of an item the way [Pak::get](crate::Pak::get) does, unwrapping versioned items from their envelope.
+fn decode<T : PakItemDeserialize>(pointer : &PakPointer, bytes : &[u8]) -> PakResult<T> {
+    if pointer.version().is_some() {
+        let envelope : PakEnvelope = bincode::deserialize(bytes)?;
+        return T::from_bytes(&envelope.payload)
+    }
+    T::from_bytes(bytes)
+}
@@ -0,0 +1,185 @@
+use std::{collections::HashMap, path::Path};
+use crate::{error::PakResult, id::PakId, item::PakItemDeserialize, pointer::PakPointer, query::PakQueryExpression, Pak};
+
+//==============================================================================================
+//        PakSet
+//==============================================================================================
+
+/// A collection of opened paks that can be queried as if they were one. This is useful when content is split across several paks, like a
+/// base game and the mods that are mounted on top of it. Results are tagged with the index of the pak they came from.
+///
+/// Every pak is mounted with a priority. When two paks have an item with the same stable [PakId](crate::id::PakId) or the same path, only
+/// the item from the pak with the highest priority is visible, and the others are shadowed. Paks with the same priority are ordered by when they were
+/// mounted, so a later mount wins.
+#[derive(Default)]
+pub struct PakSet {
+    mounts : Vec<PakMount>,
+}
+
+struct PakMount {
+    pak : Pak,
+    priority : i32,
+}
+
+impl PakSet {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        Self { mounts: Vec::new() }
+    }
+
+    /// Adds an opened pak to the set with a priority of 0 and returns its index in the set.
+    pub fn mount(&mut self, pak : Pak) -> usize {
+        self.mount_with_priority(pak, 0)
+    }
+
+    /// Adds an opened pak to the set with a priority and returns its index in the set. Items in paks with a higher priority shadow items
+    /// with the same stable id or path in paks with a lower priority.
+    pub fn mount_with_priority(&mut self, pak : Pak, priority : i32) -> usize {
+        self.mounts.push(PakMount { pak, priority });
+        self.mounts.len() - 1
+    }
+
+    /// Opens the pak file at the path and adds it to the set with a priority of 0, returning its index in the set.
+    pub fn mount_file<P>(&mut self, path : P) -> PakResult<usize> where P : AsRef<Path> {
+        Ok(self.mount(Pak::new_from_file(path)?))
+    }
+
+    /// Returns the pak with the index, if it is mounted.
+    pub fn get(&self, index : usize) -> Option<&Pak> {
+        self.mounts.get(index).map(|mount| &mount.pak)
+    }
+
+    /// Returns the priority that the pak with the index was mounted with.
+    pub fn priority(&self, index : usize) -> Option<i32> {
+        self.mounts.get(index).map(|mount| mount.priority)
+    }
+
+    /// Iterates over every mounted pak with its index, in mount order.
+    pub fn paks(&self) -> impl Iterator<Item = (usize, &Pak)> {
+        self.mounts.iter().map(|mount| &mount.pak).enumerate()
+    }
+
+    /// The indices of the mounted paks from the lowest to the highest priority. Later paks in this order override earlier ones.
+    pub fn mount_order(&self) -> Vec<usize> {
+        let mut order = (0..self.mounts.len()).collect::<Vec<_>>();
+        order.sort_by_key(|index| (self.mounts[*index].priority, *index));
+        order
+    }
+
+    /// The number of mounted paks.
+    pub fn len(&self) -> usize {
+        self.mounts.len()
+    }
+
+    /// Returns true if no paks are mounted.
+    pub fn is_empty(&self) -> bool {
+        self.mounts.is_empty()
+    }
+
+    /// Returns the index of the highest priority pak that has an item with the id, along with the pointer to that item.
+    pub fn resolve_id(&self, id : impl Into<PakId>) -> PakResult<Option<(usize, PakPointer)>> {
+        let id = id.into();
+        for index in self.mount_order().into_iter().rev() {
+            if let Some(pointer) = self.mounts[index].pak.pointer_by_id(id)? {
+                return Ok(Some((index, pointer)))
+            }
+        }
+        Ok(None)
+    }
+
+    /// Loads the item with the id from the highest priority pak that has it.
+    pub fn by_id<T>(&self, id : impl Into<PakId>) -> PakResult<Option<PakSetItem<T>>> where T : PakItemDeserialize {
+        match self.resolve_id(id)? {
+            Some((pak, pointer)) => {
+                let item = self.mounts[pak].pak.get::<T>(&pointer)?;
+                Ok(Some(PakSetItem { pak, pointer, item }))
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the index of the highest priority pak that has an item at the path, along with the pointer to that item.
+    pub fn resolve_path(&self, path : &str) -> PakResult<Option<(usize, PakPointer)>> {
+        for index in self.mount_order().into_iter().rev() {
+            if let Some(pointer) = self.mounts[index].pak.pointer_by_path(path)? {
+                return Ok(Some((index, pointer)))
+            }
+        }
+        Ok(None)
+    }
+
+    /// Loads the item at the path from the highest priority pak that has it. This is how a mod replaces a file of the base game.
+    pub fn by_path<T>(&self, path : &str) -> PakResult<Option<PakSetItem<T>>> where T : PakItemDeserialize {
+        match self.resolve_path(path)? {
+            Some((pak, pointer)) => {
+                let item = self.mounts[pak].pak.get::<T>(&pointer)?;
+                Ok(Some(PakSetItem { pak, pointer, item }))
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Runs the query against every mounted pak, returning the pointers that matched along with the index of the pak they belong to. Items
+    /// that are shadowed by a higher priority pak are left out, even if the item that shadows them doesn't match the query.
+    pub fn query_pointers(&self, query : impl PakQueryExpression) -> PakResult<Vec<(usize, PakPointer)>> {
+        let mut id_winners = HashMap::<PakId, Option<usize>>::new();
+        let mut path_winners = HashMap::<String, Option<usize>>::new();
+        let mut results = Vec::new();
+        for index in self.mount_order() {
+            let pak = &self.mounts[index].pak;
+            for pointer in query.execute(pak)? {
+                let pointer = pointer.into_pointer();
+                if let Some(id) = pak.id_of(&pointer)? {
+                    let winner = match id_winners.get(&id) {
+                        Some(winner) => *winner,
+                        None => {
+                            let winner = self.resolve_id(id)?.map(|(winner, _)| winner);
+                            id_winners.insert(id, winner);
+                            winner
+                        },
+                    };
+                    if winner != Some(index) { continue }
+                }
+                if let Some(path) = pak.path_of(&pointer)? {
+                    let winner = match path_winners.get(path) {
+                        Some(winner) => *winner,
+                        None => {
+                            let winner = self.resolve_path(path)?.map(|(winner, _)| winner);
+                            path_winners.insert(path.to_string(), winner);
+                            winner
+                        },
+                    };
+                    if winner != Some(index) { continue }
+                }
+                results.push((index, pointer));
+            }
+        }
+        Ok(results)
+    }
+
+    /// Runs the query against every mounted pak and loads every matching item of type `T` that isn't shadowed. Items of other types are skipped.
+    pub fn query<T>(&self, query : impl PakQueryExpression) -> PakResult<Vec<PakSetItem<T>>> where T : PakItemDeserialize {
+        let mut results = Vec::new();
+        for (pak, pointer) in self.query_pointers(query)? {
+            if !pointer.type_is_match::<T>() { continue }
+            let item = self.mounts[pak].pak.get::<T>(&pointer)?;
+            results.push(PakSetItem { pak, pointer, item });
+        }
+        Ok(results)
+    }
+
+    /// Returns true if any mounted pak has an item that matches the query and isn't shadowed.
+    pub fn exists(&self, query : impl PakQueryExpression) -> PakResult<bool> {
+        Ok(!self.query_pointers(query)?.is_empty())
+    }
+}
+
+/// An item that was loaded from a [PakSet](crate::set::PakSet), tagged with where it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PakSetItem<T> {
+    /// The index of the pak in the set that the item came from.
+    pub pak : usize,
+    /// The pointer to the item inside of that pak.
+    pub pointer : PakPointer,
+    pub item : T,
+}
@@ -0,0 +1,71 @@
+use crate::{error::{PakError, PakResult}, id::PakId, item::{PakItemDeserialize, PakItemDeserializeGroup}, pointer::PakPointer, query::PakQueryExpression, Pak};
+
+//==============================================================================================
+//        PakSnapshot
+//==============================================================================================
+
+/// A read-only view of a pak pinned to the generation it was taken at, from [Pak::snapshot](crate::Pak::snapshot). A batch of related
+/// queries run through one snapshot all see the same pak: the index map and the ordinal table are loaded when the snapshot is taken, and
+/// every read first checks that the source still holds the pak the snapshot was taken of, failing with
+/// [PakError::SourceChanged](crate::error::PakError::SourceChanged) if the file was rewritten underneath it, and with
+/// [PakError::StalePointer](crate::error::PakError::StalePointer) for pointers from another generation, instead of mixing results from
+/// two builds. The check reads the header and the index map of the source again, so it costs a couple of small reads per call.
+pub struct PakSnapshot<'p> {
+    pak : &'p Pak,
+    generation : u64,
+    fingerprint : [u8; 32],
+}
+
+impl<'p> PakSnapshot<'p> {
+    pub(crate) fn new(pak : &'p Pak) -> PakResult<Self> {
+        pak.fetch_indices()?;
+        pak.ordinals()?;
+        Ok(Self { pak, generation : pak.generation(), fingerprint : pak.source_fingerprint()? })
+    }
+    
+    /// The generation the snapshot is pinned to.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+    
+    /// Checks that the source still holds the pak the snapshot was taken of.
+    fn check(&self) -> PakResult<&'p Pak> {
+        match self.pak.source_fingerprint() {
+            Ok(fingerprint) if fingerprint == self.fingerprint => Ok(self.pak),
+            Ok(_) | Err(PakError::InvalidHeader(..) | PakError::UnsupportedFormat(_) | PakError::BincodeError(_)) => Err(PakError::SourceChanged),
+            Err(error) => Err(error),
+        }
+    }
+    
+    /// Checks that the pointer wasn't handed out by a different generation than the snapshot's.
+    fn check_pointer(&self, pointer : &PakPointer) -> PakResult<&'p Pak> {
+        match pointer.generation() {
+            Some(generation) if generation != self.generation => Err(PakError::StalePointer(generation, self.generation)),
+            _ => self.check(),
+        }
+    }
+    
+    pub fn query<T>(&self, query : impl PakQueryExpression) -> PakResult<T::ReturnType> where T : PakItemDeserializeGroup {
+        self.check()?.query::<T>(query)
+    }
+    
+    pub fn exists(&self, query : impl PakQueryExpression) -> PakResult<bool> {
+        self.check()?.exists(query)
+    }
+    
+    pub fn get<T>(&self, pointer : &PakPointer) -> PakResult<T> where T : PakItemDeserialize {
+        self.check_pointer(pointer)?.get(pointer)
+    }
+    
+    pub fn read_bytes(&self, pointer : &PakPointer) -> PakResult<Vec<u8>> {
+        self.check_pointer(pointer)?.read_bytes(pointer)
+    }
+    
+    pub fn by_id<T>(&self, id : impl Into<PakId>) -> PakResult<Option<T>> where T : PakItemDeserialize {
+        self.check()?.by_id(id)
+    }
+    
+    pub fn by_path<T>(&self, path : &str) -> PakResult<Option<T>> where T : PakItemDeserialize {
+        self.check()?.by_path(path)
+    }
+}
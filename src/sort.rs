@@ -0,0 +1,90 @@
+use std::{cmp::Reverse, collections::BinaryHeap, fs::{self, File}, io::{BufReader, BufWriter, Write}, path::PathBuf, sync::atomic::{AtomicUsize, Ordering}};
+use crate::{error::PakResult, value::PakValue};
+
+/// Keeps the names of run files unique between builders that run at the same time.
+static NEXT_RUN : AtomicUsize = AtomicUsize::new(0);
+
+//==============================================================================================
+//        PakRunSorter
+//==============================================================================================
+
+/// Sorts the entries of an index that don't fit in memory. Entries are buffered until there are `run_entries` of them, which are sorted and
+/// spilled to a run file. Once every entry has been pushed, the runs are merged back into a single sorted stream. The run files are removed
+/// when the sorter is dropped.
+pub(crate) struct PakRunSorter {
+    directory : PathBuf,
+    run_entries : usize,
+    buffer : Vec<(PakValue, u32)>,
+    runs : Vec<(PathBuf, usize)>,
+}
+
+impl PakRunSorter {
+    pub(crate) fn new(directory : PathBuf, run_entries : usize) -> Self {
+        Self { directory, run_entries : run_entries.max(1), buffer : Vec::new(), runs : Vec::new() }
+    }
+
+    pub(crate) fn push(&mut self, value : PakValue, ordinal : u32) -> PakResult<()> {
+        self.buffer.push((value, ordinal));
+        if self.buffer.len() >= self.run_entries { self.spill()? }
+        Ok(())
+    }
+
+    fn spill(&mut self) -> PakResult<()> {
+        if self.buffer.is_empty() { return Ok(()) }
+        self.buffer.sort();
+        let path = self.directory.join(format!(".pak-run-{}-{}", std::process::id(), NEXT_RUN.fetch_add(1, Ordering::Relaxed)));
+        // The run is recorded before it is written, so a failed write still gets cleaned up.
+        self.runs.push((path.clone(), self.buffer.len()));
+        let mut writer = BufWriter::new(File::create(&path)?);
+        for entry in &self.buffer {
+            bincode::serialize_into(&mut writer, entry)?;
+        }
+        writer.flush()?;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Visits every entry in ascending order of value and then ordinal. Entries that never filled a run are merged straight from memory.
+    pub(crate) fn merge<F>(mut self, mut visitor : F) -> PakResult<()> where F : FnMut(PakValue, u32) -> PakResult<()> {
+        if self.runs.is_empty() {
+            self.buffer.sort();
+            for (value, ordinal) in std::mem::take(&mut self.buffer) {
+                visitor(value, ordinal)?;
+            }
+            return Ok(())
+        }
+        self.spill()?;
+
+        let mut readers = self.runs.iter().map(|(path, len)| Ok(PakRunReader { reader : BufReader::new(File::open(path)?), remaining : *len })).collect::<PakResult<Vec<_>>>()?;
+        let mut heap = BinaryHeap::new();
+        for (run, reader) in readers.iter_mut().enumerate() {
+            if let Some(entry) = reader.next()? { heap.push(Reverse((entry, run))) }
+        }
+        while let Some(Reverse(((value, ordinal), run))) = heap.pop() {
+            if let Some(entry) = readers[run].next()? { heap.push(Reverse((entry, run))) }
+            visitor(value, ordinal)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for PakRunSorter {
+    fn drop(&mut self) {
+        for (path, _) in &self.runs {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+struct PakRunReader {
+    reader : BufReader<File>,
+    remaining : usize,
+}
+
+impl PakRunReader {
+    fn next(&mut self) -> PakResult<Option<(PakValue, u32)>> {
+        if self.remaining == 0 { return Ok(None) }
+        self.remaining -= 1;
+        Ok(Some(bincode::deserialize_from(&mut self.reader)?))
+    }
+}
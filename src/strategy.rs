@@ -0,0 +1,94 @@
+use proptest::{collection::vec, prelude::*};
+use serde::{Deserialize, Serialize};
+use crate::{error::PakResult, index::PakIndex, item::PakItemSearchable, value::PakValue, Pak, PakBuilder};
+
+//==============================================================================================
+//        PakValue Strategies
+//==============================================================================================
+
+/// Generates any [PakValue](crate::value::PakValue). Floats are always finite, since NaN has no place in an ordered index.
+pub fn pak_value() -> impl Strategy<Value = PakValue> {
+    prop_oneof![
+        any::<String>().prop_map(PakValue::String),
+        any::<f64>().prop_filter("finite", |float| float.is_finite()).prop_map(|float| PakValue::Float(float.to_bits())),
+        any::<i64>().prop_map(PakValue::Int),
+        any::<u64>().prop_map(PakValue::Uint),
+        any::<bool>().prop_map(PakValue::Boolean),
+        Just(PakValue::Void),
+        any::<f32>().prop_filter("finite", |float| float.is_finite()).prop_map(|float| PakValue::Float32(float.to_bits())),
+        any::<i128>().prop_map(PakValue::Int128),
+        any::<u128>().prop_map(PakValue::Uint128),
+        (any::<i64>(), 0..19u8).prop_map(|(mantissa, scale)| PakValue::Decimal(mantissa as i128, scale)),
+    ]
+}
+
+/// Generates a [PakIndex](crate::index::PakIndex) with one of the keys and any value.
+pub fn pak_index(keys : &'static [&'static str]) -> impl Strategy<Value = PakIndex> {
+    (prop::sample::select(keys), pak_value()).prop_map(|(key, value)| PakIndex::new(key, value))
+}
+
+//==============================================================================================
+//        Generated Paks
+//==============================================================================================
+
+/// An item used by generated paks. Values are drawn from small domains so that queries have plenty of matches and duplicates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PakFixtureItem {
+    pub id : u64,
+    pub number : u64,
+    pub name : String,
+    pub flag : bool,
+}
+
+impl PakItemSearchable for PakFixtureItem {
+    fn get_indices(&self) -> Vec<PakIndex> {
+        vec![
+            PakIndex::new("number", self.number),
+            PakIndex::new("name", self.name.clone()),
+            PakIndex::new("flag", self.flag),
+        ]
+    }
+}
+
+/// Generates between 1 and `max_items` fixture items with unique ids.
+pub fn pak_fixture(max_items : usize) -> impl Strategy<Value = Vec<PakFixtureItem>> {
+    vec((0..20u64, "[a-e]{1,2}", any::<bool>()), 1..max_items.max(2)).prop_map(|items| {
+        items.into_iter().enumerate().map(|(id, (number, name, flag))| PakFixtureItem { id : id as u64, number, name, flag }).collect()
+    })
+}
+
+/// Builds fixture items into a pak that lives in memory.
+pub fn build_fixture(items : &[PakFixtureItem]) -> PakResult<Pak> {
+    fixture_builder(items)?.build_in_memory()
+}
+
+/// Builds fixture items into the raw bytes of a pak file, which is useful as a starting point for corrupted input.
+pub fn build_fixture_bytes(items : &[PakFixtureItem]) -> PakResult<Vec<u8>> {
+    let (bytes, _, _) = fixture_builder(items)?.build_internal()?;
+    Ok(bytes)
+}
+
+fn fixture_builder(items : &[PakFixtureItem]) -> PakResult<PakBuilder> {
+    let mut builder = PakBuilder::new();
+    for item in items {
+        builder.pak(item.clone())?;
+    }
+    Ok(builder)
+}
+
+//==============================================================================================
+//        Corruption
+//==============================================================================================
+
+/// Damages the bytes by overwriting a few of them and sometimes truncating the end.
+pub fn corrupted(bytes : Vec<u8>) -> impl Strategy<Value = Vec<u8>> {
+    let len = bytes.len().max(1);
+    (vec((0..len, any::<u8>()), 1..8), prop::option::of(0..len)).prop_map(move |(writes, truncate)| {
+        let mut bytes = bytes.clone();
+        for (position, byte) in writes {
+            if let Some(slot) = bytes.get_mut(position) { *slot = byte }
+        }
+        if let Some(len) = truncate { bytes.truncate(len) }
+        bytes
+    })
+}
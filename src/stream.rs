@@ -0,0 +1,145 @@
+use std::{io::Read, pin::Pin, sync::{mpsc, Arc, Mutex}, task::{Context, Poll, Waker}, thread};
+use bytes::Bytes;
+use futures::Stream;
+use crate::{error::{PakError, PakResult}, fork::PakFork, pointer::PakPointer, window::PakWindow, Pak};
+
+//==============================================================================================
+//        PakExtractStream
+//==============================================================================================
+
+/// A [Stream](futures::Stream) over the bytes of a single item, created with [Pak::extract_stream](crate::Pak::extract_stream). The stream
+/// owns everything it reads with, so it can be sent to any task. The first poll starts a thread of its own that opens a copy of the pak
+/// and reads the item a chunk at a time, so polling never blocks on IO. The thread stays at most a chunk ahead, so a consumer that stops
+/// polling, like a slow client, stops the reads too. Items that are encrypted or compressed have to be decoded as a whole, so the thread
+/// reads them in one go and only hands them out in chunks. Paks that can't open another source, like the ones built in memory, read the
+/// item when the stream is created. The thread reads at the [priority](crate::cache::PakPriority) the pak had when the stream was created,
+/// so a background stream yields to the foreground reads of the pak.
+pub struct PakExtractStream {
+    len : u64,
+    chunk_size : usize,
+    state : PakStreamState,
+}
+
+enum PakStreamState {
+    /// Not polled yet, with the copy of the pak the thread will read through.
+    Forked(Box<PakFork>, PakPointer),
+    /// The bytes of the item, read when the stream was created.
+    Read(Bytes),
+    /// Chunks come from the reading thread, which wakes the waker whenever it hands one over.
+    Reading(mpsc::Receiver<PakResult<Bytes>>, Arc<Mutex<Option<Waker>>>),
+    Done,
+}
+
+impl PakExtractStream {
+    /// The size of the chunks if none is given.
+    pub const DEFAULT_CHUNK_SIZE : usize = 64 * 1024;
+
+    pub(crate) fn new(pak : &Pak, pointer : &PakPointer) -> PakResult<Self> {
+        let window = pak.window(pointer);
+        let len = window.len();
+        let state = match pak.fork()? {
+            Some(fork) => PakStreamState::Forked(Box::new(fork), pointer.clone()),
+            None => PakStreamState::Read(Bytes::from(read_all(window)?)),
+        };
+        Ok(Self { len, chunk_size : Self::DEFAULT_CHUNK_SIZE, state })
+    }
+
+    /// Sets the largest number of bytes in a chunk.
+    pub fn with_chunk_size(mut self, chunk_size : usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// The size of the whole item in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if the item has no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Starts the thread that reads the item through the copy of the pak.
+    fn spawn(fork : PakFork, pointer : PakPointer, chunk_size : usize, waker : Waker) -> PakStreamState {
+        // A bound of one keeps the thread from reading more than a chunk ahead of the consumer.
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let waker = Arc::new(Mutex::new(Some(waker)));
+        let wake = waker.clone();
+        thread::spawn(move || {
+            let wake = || if let Some(waker) = wake.lock().ok().and_then(|mut waker| waker.take()) { waker.wake() };
+            match fork.open() {
+                Ok(pak) => {
+                    let mut window = pak.window(&pointer);
+                    loop {
+                        let next = next_chunk(&mut window, chunk_size).transpose();
+                        let last = !matches!(next, Some(Ok(_)));
+                        if let Some(next) = next && sender.send(next).is_err() { return }
+                        if last { break }
+                        wake();
+                    }
+                },
+                Err(error) => { let _ = sender.send(Err(error)); },
+            }
+            drop(sender);
+            wake();
+        });
+        PakStreamState::Reading(receiver, waker)
+    }
+}
+
+impl Stream for PakExtractStream {
+    type Item = PakResult<Bytes>;
+
+    fn poll_next(self : Pin<&mut Self>, cx : &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match &mut this.state {
+                PakStreamState::Forked(..) => {
+                    let PakStreamState::Forked(fork, pointer) = std::mem::replace(&mut this.state, PakStreamState::Done) else { unreachable!() };
+                    this.state = Self::spawn(*fork, pointer, this.chunk_size, cx.waker().clone());
+                },
+                PakStreamState::Read(bytes) if bytes.is_empty() => this.state = PakStreamState::Done,
+                PakStreamState::Read(bytes) => return Poll::Ready(Some(Ok(bytes.split_to(this.chunk_size.min(bytes.len()))))),
+                PakStreamState::Reading(receiver, waker) => {
+                    // The waker is stored before the channel is checked, so a chunk handed over in between still wakes the task.
+                    if let Ok(mut waker) = waker.lock() { *waker = Some(cx.waker().clone()) }
+                    return match receiver.try_recv() {
+                        Ok(Ok(chunk)) => Poll::Ready(Some(Ok(chunk))),
+                        Ok(Err(error)) => {
+                            this.state = PakStreamState::Done;
+                            Poll::Ready(Some(Err(error)))
+                        },
+                        Err(mpsc::TryRecvError::Empty) => Poll::Pending,
+                        Err(mpsc::TryRecvError::Disconnected) => {
+                            this.state = PakStreamState::Done;
+                            Poll::Ready(None)
+                        },
+                    }
+                },
+                PakStreamState::Done => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+fn next_chunk(window : &mut PakWindow<'_>, chunk_size : usize) -> PakResult<Option<Bytes>> {
+    let mut chunk = vec![0u8; chunk_size];
+    let count = window.read(&mut chunk).map_err(io_error)?;
+    if count == 0 { return Ok(None) }
+    chunk.truncate(count);
+    Ok(Some(Bytes::from(chunk)))
+}
+
+fn read_all(mut window : PakWindow<'_>) -> PakResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    window.read_to_end(&mut bytes).map_err(io_error)?;
+    Ok(bytes)
+}
+
+fn io_error(error : std::io::Error) -> PakError {
+    match error.downcast::<PakError>() {
+        Ok(error) => error,
+        Err(error) => PakError::FileError(error),
+    }
+}
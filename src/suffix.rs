@@ -0,0 +1,84 @@
+use std::{any::Any, cmp::Ordering, collections::BTreeSet, sync::Arc};
+use serde::{Deserialize, Serialize};
+use crate::{error::PakResult, kind::{PakCustomQuery, PakDecodedKind, PakIndexKind, PakKindQuery}, value::PakValue};
+
+//==============================================================================================
+//        PakSuffixIndex
+//==============================================================================================
+
+/// The name of the operation that [PakSuffixIndex](crate::suffix::PakSuffixIndex) answers.
+pub const CONTAINS_OPERATION : &str = "contains";
+
+/// A built-in [PakIndexKind](crate::kind::PakIndexKind) that answers substring queries with a suffix array. Every suffix of every string value
+/// is sorted, so the suffixes that start with the pattern sit next to each other and are found with two binary searches instead of a scan.
+/// Attach it with [PakBuilder::with_index_kind](crate::PakBuilder::with_index_kind) and query it with [contains](crate::suffix::contains).
+/// Matching is case sensitive and values that aren't strings are ignored.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PakSuffixIndex;
+
+#[derive(Serialize, Deserialize)]
+struct PakSuffixArray {
+    texts : Vec<(String, u32)>,
+    /// Every suffix as the index of its text and the byte offset it starts at, sorted by the suffix.
+    suffixes : Vec<(u32, u32)>,
+}
+
+impl PakSuffixArray {
+    fn suffix(&self, (text, offset) : (u32, u32)) -> &[u8] {
+        &self.texts[text as usize].0.as_bytes()[offset as usize..]
+    }
+}
+
+impl PakIndexKind for PakSuffixIndex {
+    fn name(&self) -> &str {
+        "pak_suffix_array"
+    }
+    
+    fn build(&self, entries : &[(PakValue, u32)]) -> PakResult<Vec<u8>> {
+        let texts = entries.iter().filter_map(|(value, ordinal)| Some((value.as_string()?, *ordinal))).collect::<Vec<_>>();
+        let mut array = PakSuffixArray { texts, suffixes : Vec::new() };
+        for (index, (text, _)) in array.texts.iter().enumerate() {
+            // Patterns are valid utf-8, so they can only ever match at the start of a character.
+            array.suffixes.extend(text.char_indices().map(|(offset, _)| (index as u32, offset as u32)));
+        }
+        let mut suffixes = std::mem::take(&mut array.suffixes);
+        suffixes.sort_by(|a, b| array.suffix(*a).cmp(array.suffix(*b)));
+        array.suffixes = suffixes;
+        Ok(bincode::serialize(&array)?)
+    }
+    
+    fn execute(&self, data : &[u8], query : &PakKindQuery) -> PakResult<Option<Vec<u32>>> {
+        self.execute_decoded(self.decode(data)?.as_ref(), query)
+    }
+    
+    fn decode(&self, data : &[u8]) -> PakResult<PakDecodedKind> {
+        Ok(Arc::new(bincode::deserialize::<PakSuffixArray>(data)?))
+    }
+    
+    fn execute_decoded(&self, decoded : &(dyn Any + Send + Sync), query : &PakKindQuery) -> PakResult<Option<Vec<u32>>> {
+        let PakKindQuery::Operation(operation, PakValue::String(pattern)) = query else { return Ok(None) };
+        if operation != CONTAINS_OPERATION { return Ok(None) }
+        let Some(array) = decoded.downcast_ref::<PakSuffixArray>() else { return Ok(None) };
+        let pattern = pattern.as_bytes();
+        
+        // Suffixes that start with the pattern compare as equal to it, which makes them one contiguous run in the sorted array.
+        let compare = |suffix : &[u8]| suffix[..suffix.len().min(pattern.len())].cmp(pattern);
+        let start = array.suffixes.partition_point(|entry| compare(array.suffix(*entry)) == Ordering::Less);
+        let end = array.suffixes.partition_point(|entry| compare(array.suffix(*entry)) != Ordering::Greater);
+        let ordinals = array.suffixes[start..end].iter().map(|(text, _)| array.texts[*text as usize].1).collect::<BTreeSet<_>>();
+        Ok(Some(ordinals.into_iter().collect()))
+    }
+    
+    fn matches(&self, query : &PakKindQuery, value : &PakValue) -> bool {
+        match (query, value) {
+            (PakKindQuery::Operation(operation, PakValue::String(pattern)), PakValue::String(value)) => operation == CONTAINS_OPERATION && value.contains(pattern.as_str()),
+            (PakKindQuery::Range(lower, upper), value) => crate::btree::range_contains(&(lower.as_ref(), upper.as_ref()), value),
+            _ => false,
+        }
+    }
+}
+
+/// Finds every item whose value for the key contains the pattern. The key must have a [PakSuffixIndex](crate::suffix::PakSuffixIndex).
+pub fn contains(key : &str, pattern : &str) -> PakCustomQuery {
+    PakCustomQuery::new(key, Arc::new(PakSuffixIndex), PakKindQuery::Operation(CONTAINS_OPERATION.to_string(), PakValue::from(pattern)))
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
-use crate::{index::{PakIndex, PakIndexIdentifier}, item::PakItemSearchable, pointer::PakPointer, value::IntoPakValue, Pak, PakBuilder};
+use std::collections::HashSet;
+use crate::{aggregate::Aggregate, envelope::PakVersioned, error::PakResult, id::PakId, set::PakSet, index::{PakDuplicateKeys, PakIndex, PakIndexIdentifier}, item::PakItemSearchable, pointer::PakPointer, value::{IntoPakValue, PakValue}, Pak, PakBuilder};
 
 //==============================================================================================
 //        Person
@@ -14,11 +15,138 @@ struct Person {
 
 impl PakItemSearchable for Person {
     fn get_indices(&self) -> Vec<PakIndex> {
-        let mut indices = Vec::new();
-        indices.push(PakIndex::new("first_name", self.first_name.clone()));
-        indices.push(PakIndex::new("last_name", self.last_name.clone()));
-        indices.push(PakIndex::new("age", self.age));
-        indices
+        vec![
+            PakIndex::new("first_name", self.first_name.clone()),
+            PakIndex::new("last_name", self.last_name.clone()),
+            PakIndex::new("age", self.age),
+        ]
+    }
+}
+
+//==============================================================================================
+//        PersonV1
+//==============================================================================================
+
+/// Version 1 of a person only had a full name.
+#[derive(Serialize, Deserialize)]
+struct PersonV1 {
+    name: String,
+    age: u32,
+}
+
+impl PakVersioned for Person {
+    const VERSION : u32 = 2;
+
+    fn decode_version(version : u32, payload : &[u8]) -> PakResult<Self> {
+        match version {
+            1 => {
+                let old : PersonV1 = bincode::deserialize(payload)?;
+                let (first_name, last_name) = old.name.split_once(' ').unwrap_or((&old.name, ""));
+                Ok(Person { first_name: first_name.to_string(), last_name: last_name.to_string(), age: old.age })
+            },
+            _ => Ok(bincode::deserialize(payload)?),
+        }
+    }
+}
+
+//==============================================================================================
+//        LegacyCitizen
+//==============================================================================================
+
+/// The item of tests/fixtures/baseline-0.1.pak, which was written by pak-db 0.1. Its path is part of the fixture, since pointers are typed
+/// by the name of the type, so it must not move.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct LegacyCitizen {
+    name: String,
+    age: u32,
+    city: String,
+}
+
+/// Opens the fixture that was written by pak-db 0.1, with 150 citizens in it after a note that has no indices.
+fn baseline_fixture() -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("baseline-0.1.pak")
+}
+
+//==============================================================================================
+//        PakIndexedValue
+//==============================================================================================
+
+/// An item that is nothing but the indices it is paked with.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct PakIndexedValue(Vec<PakIndex>);
+
+impl PakItemSearchable for PakIndexedValue {
+    fn get_indices(&self) -> Vec<PakIndex> {
+        self.0.clone()
+    }
+}
+
+//==============================================================================================
+//        Derived Items
+//==============================================================================================
+
+#[cfg(feature = "derive")]
+mod derived {
+    use serde::{Deserialize, Serialize};
+    use crate::item::PakItem;
+    
+    pub mod v1 {
+        use super::*;
+        
+        #[derive(Serialize, Deserialize, PakItem)]
+        #[pak(version = 1)]
+        pub struct Monster {
+            pub name : String,
+        }
+    }
+    
+    pub mod v2 {
+        use super::*;
+        
+        #[derive(Serialize, Deserialize, PakItem)]
+        #[pak(version = 2, migrate_from = "super::v1::Monster")]
+        pub struct Monster {
+            pub name : String,
+            pub health : u32,
+        }
+        
+        impl From<v1::Monster> for Monster {
+            fn from(old : v1::Monster) -> Self {
+                Monster { name: old.name, health: 10 }
+            }
+        }
+    }
+    
+    #[derive(Serialize, Deserialize, PakItem, Debug, PartialEq)]
+    #[pak(version = 3, migrate_from = "v2::Monster")]
+    pub struct Monster {
+        #[pak(index)]
+        pub name : String,
+        #[pak(index = "hp")]
+        pub health : u32,
+        pub boss : bool,
+    }
+    
+    impl From<v2::Monster> for Monster {
+        fn from(old : v2::Monster) -> Self {
+            Monster { name: old.name, health: old.health, boss: false }
+        }
+    }
+    
+    #[derive(Serialize, Deserialize, PakItem)]
+    pub struct Tamer {
+        #[pak(index)]
+        pub name : String,
+    }
+    
+    #[derive(Serialize, Deserialize, PakItem)]
+    pub struct Companion {
+        #[pak(index)]
+        pub name : String,
+        #[pak(ref = "Tamer")]
+        pub tamer : crate::pointer::PakRef<Tamer>,
+        #[pak(ref = "Monster", index = "rival_of")]
+        pub rival : crate::pointer::PakPointer,
     }
 }
 
@@ -51,16 +179,20 @@ impl IntoPakValue for PetKind {
 
 impl PakItemSearchable for Pet {
     fn get_indices(&self) -> Vec<PakIndex> {
-        let mut indices = Vec::new();
-        indices.push(PakIndex::new("name", self.name.clone()));
-        indices.push(PakIndex::new("age", self.age));
-        indices.push(PakIndex::new("kind", self.kind.clone()));
-        indices
+        vec![
+            PakIndex::new("name", self.name.clone()),
+            PakIndex::new("age", self.age),
+            PakIndex::new("kind", self.kind.clone()),
+        ]
     }
 }
 
 /// This is the unofficial build test, this runs in every test
 pub fn build_data_base() -> Pak {
+    data_base_builder().build_in_memory().unwrap()
+}
+
+fn data_base_builder() -> PakBuilder {
     let mut builder = PakBuilder::new();
     
     let person1 = Person {
@@ -132,7 +264,7 @@ pub fn build_data_base() -> Pak {
     builder.pak(pet2).unwrap();
     builder.pak(pet3).unwrap();
     
-    builder.build_in_memory().unwrap()
+    builder
 }
 
 #[test]
@@ -213,3 +345,2968 @@ fn compound_intersection_query() {
     assert_eq!(people.len(), 2);
     assert_eq!(pets.len(), 0);
 }
+
+#[test]
+fn query_text() {
+    use crate::{error::PakError, query};
+    
+    let pak = build_data_base();
+    
+    let (people, pets) = pak.query::<(Person, Pet)>(query::parse("age < 30 or first_name == 'John'").unwrap()).unwrap();
+    assert_eq!((people.len(), pets.len()), (4, 3));
+    let (people, pets) = pak.query::<(Person, Pet)>(query::parse(r#"(age > 25 && first_name = "John")"#).unwrap()).unwrap();
+    assert_eq!((people.len(), pets.len()), (2, 0));
+    assert!(matches!(query::parse("age >"), Err(PakError::InvalidQuery(_))));
+    assert!(matches!(query::parse("(age > 3"), Err(PakError::InvalidQuery(_))));
+    assert!(matches!(query::parse("age != 3"), Err(PakError::InvalidQuery(_))));
+}
+
+#[test]
+fn fluent_queries() {
+    use crate::query::{self, PakQueryNode, Query};
+    
+    let pak = build_data_base();
+    
+    let query = Query::where_("age").gt(25u32).and().where_("first_name").eq("John");
+    assert_eq!(query.to_string(), r#"age > 25 and first_name == "John""#);
+    let (people, pets) = pak.query::<(Person, Pet)>(query).unwrap();
+    assert_eq!((people.len(), pets.len()), (2, 0));
+    
+    let query = Query::where_("age").lt(26u32).or().where_("first_name").eq("John").and().where_("last_name").eq("Doe").build();
+    assert!(matches!(&query, PakQueryNode::Any(nodes) if nodes.len() == 2));
+    assert_eq!(query.to_string(), r#"age < 26 or first_name == "John" and last_name == "Doe""#);
+    assert_eq!(query::parse(&query.to_string()).unwrap(), query);
+    
+    let query = Query::where_("last_name").eq("Doe").and().group(Query::where_("first_name").eq("Jane").or().where_("age").ge(30u32)).build();
+    assert_eq!(query.to_string(), r#"last_name == "Doe" and (first_name == "Jane" or age >= 30)"#);
+    assert_eq!(query::parse(&query.to_string()).unwrap(), query);
+    assert_eq!(pak.query::<(Person,)>(query).unwrap().len(), 2);
+}
+
+#[test]
+fn query_serialization() {
+    use crate::query::{PakQueryExpression, PakQueryNode, Query};
+    
+    let pak = build_data_base();
+    let query = ("first_name".equals("John") | "age".less_than(26)) & "last_name".equals("Doe") & "age".greater_than(1);
+    let node = query.to_node().unwrap();
+    assert_eq!(node.to_string(), r#"(first_name == "John" or age < 26) and last_name == "Doe" and age > 1"#);
+    
+    let bytes = bincode::serialize(&node).unwrap();
+    let replayed : PakQueryNode = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(replayed, node);
+    assert_eq!(pak.query::<(Person,)>(replayed).unwrap().len(), pak.query::<(Person,)>(query).unwrap().len());
+    
+    let cache = HashSet::from([Query::where_("age").eq(30u32).build()]);
+    assert!(cache.contains(&Query::where_("age").eq(30i64).build()));
+}
+
+#[test]
+fn budgeted_queries() {
+    use crate::{budget::PakBudget, error::PakError};
+    
+    let mut builder = PakBuilder::new();
+    for i in 0..200u32 {
+        builder.pak(Person { first_name: format!("Person {i}"), last_name: "Family".to_string(), age: i }).unwrap();
+    }
+    let pak = builder.build_in_memory().unwrap();
+    
+    let people = pak.query_with_budget::<(Person,)>("age".equals(42u32), PakBudget::new().with_max_reads(10).with_max_bytes(32 * 1024)).unwrap();
+    assert_eq!(people[0].first_name, "Person 42");
+    let result = pak.query_with_budget::<(Person,)>("last_name".equals("Family"), PakBudget::new().with_max_reads(50));
+    assert!(matches!(result, Err(PakError::BudgetExceeded(limit, 50)) if limit == "reads"));
+    let result = pak.query_with_budget::<(Person,)>("age".greater_than(100u32), PakBudget::new().with_max_bytes(1024));
+    assert!(matches!(result, Err(PakError::BudgetExceeded(limit, 1024)) if limit == "bytes"));
+    assert_eq!(pak.query::<(Person,)>("last_name".equals("Family")).unwrap().len(), 200);
+    
+    // Reads under an inner budget are charged to the outer one too, so a looser inner budget can't read past the outer limit.
+    let result = pak.with_budget(PakBudget::new().with_max_reads(5), |pak| {
+        pak.query_with_budget::<(Person,)>("age".equals(1u32), PakBudget::new().with_max_reads(10))?;
+        pak.query_with_budget::<(Person,)>("age".equals(2u32), PakBudget::new().with_max_reads(10))
+    });
+    assert!(matches!(result, Err(PakError::BudgetExceeded(limit, 5)) if limit == "reads"));
+    let people = pak.with_budget(PakBudget::new().with_max_reads(20), |pak| pak.query_with_budget::<(Person,)>("age".equals(3u32), PakBudget::new().with_max_reads(10)));
+    assert_eq!(people.unwrap().len(), 1);
+}
+
+#[test]
+fn group_by_count() {
+    let pak = build_data_base();
+    
+    let groups = pak.group_by("last_name", Aggregate::Count).unwrap();
+    
+    assert_eq!(groups, vec![
+        (PakValue::from("Brown"), 1),
+        (PakValue::from("Doe"), 2),
+        (PakValue::from("Jacob"), 1),
+        (PakValue::from("Johnson"), 1),
+        (PakValue::from("Smith"), 1),
+    ]);
+}
+
+#[test]
+fn distinct_values() {
+    let pak = build_data_base();
+    
+    let names = pak.distinct("first_name").unwrap().collect::<PakResult<Vec<_>>>().unwrap();
+    
+    assert_eq!(names, vec![
+        PakValue::from("Alice"),
+        PakValue::from("Bob"),
+        PakValue::from("Charlie"),
+        PakValue::from("Jane"),
+        PakValue::from("John"),
+    ]);
+}
+
+#[test]
+fn percentile_and_histogram() {
+    let pak = build_data_base();
+    
+    // Ages are 3, 5, 7, 25, 28, 30, 35, 40, 45
+    assert_eq!(pak.percentile("age", 0.5).unwrap(), Some(PakValue::from(28u32)));
+    assert_eq!(pak.percentile("age", 1.0).unwrap(), Some(PakValue::from(45u32)));
+    assert_eq!(pak.percentile("age", 0.0).unwrap(), Some(PakValue::from(3u32)));
+    
+    let histogram = pak.histogram("age", 3).unwrap();
+    assert_eq!(histogram.buckets.len(), 3);
+    assert_eq!(histogram.total(), 9);
+    assert_eq!(histogram.buckets[0].high, PakValue::from(7u32));
+}
+
+#[test]
+fn index_reader_range() {
+    let pak = build_data_base();
+    let index = pak.index("age").unwrap();
+    
+    let entries = index.range(PakValue::from(7u32)..PakValue::from(30u32)).unwrap();
+    let ages = entries.iter().map(|entry| entry.value.clone()).collect::<Vec<_>>();
+    assert_eq!(ages, vec![PakValue::from(7u32), PakValue::from(25u32), PakValue::from(28u32)]);
+    
+    assert_eq!(index.get(30u32).unwrap().len(), 1);
+    assert_eq!(index.entries().unwrap().len(), 9);
+}
+
+#[test]
+fn range_queries_on_split_tree() {
+    let mut builder = PakBuilder::new();
+    for i in 0..500u32 {
+        builder.pak(Person { first_name: format!("{i}"), last_name: "Many".to_string(), age: i }).unwrap();
+    }
+    let pak = builder.build_in_memory().unwrap();
+    
+    let ages = |people : Vec<Person>| people.into_iter().map(|person| person.age).collect::<HashSet<_>>();
+    
+    let people = pak.query::<(Person,)>("age".less_than(100)).unwrap();
+    assert_eq!(ages(people), (0..100).collect());
+    let people = pak.query::<(Person,)>("age".greater_than_or_equal(321)).unwrap();
+    assert_eq!(ages(people), (321..500).collect());
+    let people = pak.query::<(Person,)>("age".equals(257)).unwrap();
+    assert_eq!(ages(people), HashSet::from([257]));
+    
+    let values = pak.distinct("age").unwrap().collect::<PakResult<Vec<_>>>().unwrap();
+    assert_eq!(values, (0..500u32).map(PakValue::from).collect::<Vec<_>>());
+    
+    // Nothing past the tree's meta is read until the values are asked for.
+    let path = std::env::temp_dir().join(format!("pak-distinct-{}.pak", std::process::id()));
+    builder = PakBuilder::new();
+    for i in 0..500u32 {
+        builder.pak(Person { first_name: format!("{i}"), last_name: "Many".to_string(), age: i }).unwrap();
+    }
+    builder.build_file(&path).unwrap();
+    let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+    let pak = Pak::new(CountingSource { data : std::fs::read(&path).unwrap(), reads : reads.clone() }).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let mut values = pak.distinct("age").unwrap();
+    let opened = reads.get();
+    assert_eq!(values.next().unwrap().unwrap(), PakValue::from(0u32));
+    assert!(reads.get() > opened);
+}
+
+#[test]
+fn query_exists() {
+    let pak = build_data_base();
+    
+    assert!(pak.exists("first_name".equals("John")).unwrap());
+    assert!(!pak.exists("first_name".equals("Zed")).unwrap());
+    assert!(pak.exists("first_name".equals("Zed") | "age".greater_than(44)).unwrap());
+    assert!(!pak.exists("first_name".equals("Jane") & "age".greater_than(44)).unwrap());
+}
+
+#[test]
+fn overflowing_posting_lists() {
+    let build = |duplicate_keys| {
+        let mut builder = PakBuilder::new().with_duplicate_keys(duplicate_keys);
+        for i in 0..300u32 {
+            builder.pak(Person { first_name: format!("{i}"), last_name: "Many".to_string(), age: i % 2 }).unwrap();
+        }
+        builder.build_in_memory().unwrap()
+    };
+    
+    let inline = build(PakDuplicateKeys::Inline);
+    let overflow = build(PakDuplicateKeys::Overflow(16));
+    
+    for pak in [inline, overflow] {
+        assert_eq!(pak.query::<(Person,)>("last_name".equals("Many")).unwrap().len(), 300);
+        assert_eq!(pak.query::<(Person,)>("age".equals(1)).unwrap().len(), 150);
+        assert_eq!(pak.group_by("age", Aggregate::Count).unwrap(), vec![(PakValue::from(0u32), 150), (PakValue::from(1u32), 150)]);
+        assert_eq!(pak.index("last_name").unwrap().get("Many").unwrap().len(), 300);
+        pak.get_tree("last_name").unwrap().walk(|_, postings| {
+            let pointers = postings.pointers()?.collect::<PakResult<HashSet<_>>>()?;
+            assert_eq!(pointers.len(), 300);
+            assert_eq!(postings.load()?.len(), 300);
+            Ok(true)
+        }).unwrap();
+    }
+}
+
+#[cfg(feature = "roaring")]
+#[test]
+fn bitmap_index_queries() {
+    use crate::query::PakQueryExpression;
+    
+    let mut builder = PakBuilder::new().with_bitmap_index("kind").with_bitmap_index("age");
+    for i in 0..200u32 {
+        let kind = if i % 3 == 0 { PetKind::Cat } else { PetKind::Dog };
+        builder.pak(Pet { name: format!("{i}"), age: i % 10, owner: PakPointer::new_untyped(0, 0), kind }).unwrap();
+    }
+    let pak = builder.build_in_memory().unwrap();
+    
+    let cats = "kind".equals("cat");
+    assert_eq!(cats.execute_bitmap(&pak).unwrap().unwrap().len(), 67);
+    
+    let query = "kind".equals("cat") & "age".less_than(5);
+    let expected = (0..200u32).filter(|i| i % 3 == 0 && i % 10 < 5).count();
+    assert!(query.execute_bitmap(&pak).unwrap().is_some());
+    assert_eq!(pak.query::<(Pet,)>(query).unwrap().len(), expected);
+    
+    let query = "kind".equals("cat") | "name".equals("1");
+    assert!(query.execute_bitmap(&pak).unwrap().is_none());
+    assert_eq!(pak.query::<(Pet,)>(query).unwrap().len(), 68);
+}
+
+#[test]
+fn item_ordinals() {
+    let pak = build_data_base();
+    
+    assert_eq!(pak.item_count().unwrap(), 9);
+    let person : Person = pak.get(&pak.pointer_of(1).unwrap().unwrap()).unwrap();
+    assert_eq!(person.first_name, "Jane");
+    let pet : Pet = pak.get(&pak.pointer_of(8).unwrap().unwrap()).unwrap();
+    assert_eq!(pet.name, "Bella");
+    assert!(pak.pointer_of(9).unwrap().is_none());
+}
+
+#[test]
+fn stable_ids() {
+    let mut builder = PakBuilder::new();
+    builder.pak_with_id(7u64, Person { first_name: "John".to_string(), last_name: "Doe".to_string(), age: 30 }).unwrap();
+    builder.pak_no_search_with_id(PakId::uuid(0x1234), Person { first_name: "Jane".to_string(), last_name: "Doe".to_string(), age: 25 }).unwrap();
+    assert!(builder.pak_with_id(7u64, Person { first_name: "Copy".to_string(), last_name: "Cat".to_string(), age: 1 }).is_err());
+    let pak = builder.build_in_memory().unwrap();
+    
+    let john = pak.by_id::<Person>(7u64).unwrap().unwrap();
+    assert_eq!(john.first_name, "John");
+    let jane = pak.by_id::<Person>(PakId::uuid(0x1234)).unwrap().unwrap();
+    assert_eq!(jane.first_name, "Jane");
+    assert!(pak.by_id::<Person>(8u64).unwrap().is_none());
+    assert_eq!(pak.query::<(Person,)>("last_name".equals("Doe")).unwrap().len(), 1);
+    
+    // Keys under the reserved prefix are left to the crate, other than references which items mark themselves.
+    let mut builder = PakBuilder::new();
+    let reserved = builder.pak(PakIndexedValue(vec![PakIndex::new("__pak_rank", 1u32)]));
+    assert!(matches!(reserved, Err(crate::error::PakError::ReservedKey(key)) if key == "__pak_rank"));
+    builder.pak(PakIndexedValue(vec![PakIndex::new("rank", 1u32), PakIndex::reference("rank")])).unwrap();
+}
+
+#[test]
+fn pak_set_query() {
+    let mut set = PakSet::new();
+    let base = set.mount(build_data_base());
+    let mut builder = PakBuilder::new();
+    builder.pak(Person { first_name: "John".to_string(), last_name: "Modded".to_string(), age: 99 }).unwrap();
+    let modded = set.mount(builder.build_in_memory().unwrap());
+    
+    let people = set.query::<Person>("first_name".equals("John")).unwrap();
+    assert_eq!(people.len(), 3);
+    assert_eq!(people.iter().filter(|person| person.pak == base).count(), 2);
+    assert_eq!(people.iter().filter(|person| person.pak == modded).map(|person| person.item.age).collect::<Vec<_>>(), vec![99]);
+    assert!(set.exists("last_name".equals("Modded")).unwrap());
+}
+
+#[test]
+fn pak_set_shadowing() {
+    let person = |first_name : &str, age : u32| Person { first_name: first_name.to_string(), last_name: "Doe".to_string(), age };
+    
+    let mut base = PakBuilder::new();
+    base.pak_with_id(1u64, person("John", 30)).unwrap();
+    base.pak_with_id(2u64, person("Jane", 25)).unwrap();
+    base.pak(person("Anon", 50)).unwrap();
+    
+    let mut high = PakBuilder::new();
+    high.pak_with_id(1u64, person("John", 31)).unwrap();
+    let mut low = PakBuilder::new();
+    low.pak_with_id(2u64, person("Jane", 99)).unwrap();
+    
+    let mut set = PakSet::new();
+    let high = set.mount_with_priority(high.build_in_memory().unwrap(), 10);
+    let base = set.mount(base.build_in_memory().unwrap());
+    set.mount_with_priority(low.build_in_memory().unwrap(), -10);
+    
+    let people = set.query::<Person>("last_name".equals("Doe")).unwrap();
+    let mut ages = people.iter().map(|person| (person.pak, person.item.age)).collect::<Vec<_>>();
+    ages.sort();
+    assert_eq!(ages, vec![(high, 31), (base, 25), (base, 50)]);
+    
+    // The base game's John is shadowed even though the override no longer matches the query.
+    assert!(!set.exists("age".equals(30)).unwrap());
+    assert_eq!(set.by_id::<Person>(2u64).unwrap().unwrap().item.age, 25);
+}
+
+#[test]
+fn pak_set_path_shadowing() {
+    let person = |first_name : &str, age : u32| Person { first_name: first_name.to_string(), last_name: "Doe".to_string(), age };
+    let mut base = PakBuilder::new();
+    base.pak_with_path("people/john.person", person("John", 30)).unwrap();
+    base.pak_with_path("people/jane.person", person("Jane", 25)).unwrap();
+    let mut mod_pak = PakBuilder::new();
+    mod_pak.pak_with_path("people/john.person", person("John", 31)).unwrap();
+    
+    let mut set = PakSet::new();
+    let modded = set.mount_with_priority(mod_pak.build_in_memory().unwrap(), 10);
+    let base = set.mount(base.build_in_memory().unwrap());
+    
+    let john = set.by_path::<Person>("people/john.person").unwrap().unwrap();
+    assert_eq!((john.pak, john.item.age), (modded, 31));
+    assert_eq!(set.resolve_path("people/jane.person").unwrap().unwrap().0, base);
+    assert!(set.resolve_path("people/bob.person").unwrap().is_none());
+    
+    let people = set.query::<Person>("last_name".equals("Doe")).unwrap();
+    let mut ages = people.iter().map(|person| (person.pak, person.item.age)).collect::<Vec<_>>();
+    ages.sort();
+    assert_eq!(ages, vec![(modded, 31), (base, 25)]);
+    assert!(!set.exists("age".equals(30)).unwrap());
+}
+
+#[test]
+fn extract_items() {
+    let pak = build_data_base();
+    let dir = std::env::temp_dir().join(format!("pak-extract-{}", std::process::id()));
+    
+    let written = pak.extract_all(&dir, |pointer| pointer.type_is_match::<Person>()).unwrap();
+    assert_eq!(written, 6);
+    
+    let bytes = std::fs::read(dir.join("1.bin")).unwrap();
+    let jane : Person = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(jane.first_name, "Jane");
+    assert!(!dir.join("8.bin").exists());
+    
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn read_raw_bytes() {
+    let pak = build_data_base();
+    let pointer = pak.pointer_of(8).unwrap().unwrap();
+    let bytes = pak.read_bytes(&pointer).unwrap();
+    assert_eq!(bytes.len() as u64, pointer.size());
+    
+    let bella : Pet = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(bella, pak.get::<Pet>(&pointer).unwrap());
+}
+
+#[test]
+fn item_window() {
+    use std::io::{Read, Seek, SeekFrom};
+    
+    let pak = build_data_base();
+    let pointer = pak.pointer_of(1).unwrap().unwrap();
+    let bytes = pak.read_bytes(&pointer).unwrap();
+    
+    let mut window = pak.window(&pointer);
+    let mut all = Vec::new();
+    window.read_to_end(&mut all).unwrap();
+    assert_eq!(all, bytes);
+    
+    window.seek(SeekFrom::End(-4)).unwrap();
+    let mut tail = Vec::new();
+    window.read_to_end(&mut tail).unwrap();
+    assert_eq!(tail, bytes[bytes.len() - 4..]);
+    
+    window.seek(SeekFrom::Start(0)).unwrap();
+    let jane : Person = bincode::deserialize_from(&mut window).unwrap();
+    assert_eq!(jane.first_name, "Jane");
+}
+
+#[test]
+fn header_validation() {
+    use crate::error::PakError;
+    use std::io::Cursor;
+    
+    let path = std::env::temp_dir().join(format!("pak-header-{}.pak", std::process::id()));
+    let mut builder = PakBuilder::new();
+    builder.pak(Person { first_name: "John".to_string(), last_name: "Doe".to_string(), age: 30 }).unwrap();
+    builder.build_file(&path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    
+    assert!(Pak::new(Cursor::new(bytes.clone())).is_ok());
+    
+    let truncated = bytes[..bytes.len() - 1].to_vec();
+    assert!(matches!(Pak::new(Cursor::new(truncated)), Err(PakError::InvalidHeader(field, _)) if field == "sizing"));
+    
+    let mut garbage = bytes.clone();
+    garbage[..8].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(matches!(Pak::new(Cursor::new(garbage)), Err(PakError::InvalidHeader(field, _)) if field == "meta_size"));
+}
+
+#[test]
+fn recover_damaged_pak() {
+    let path = std::env::temp_dir().join(format!("pak-recover-{}.pak", std::process::id()));
+    let mut builder = PakBuilder::new().with_name("people");
+    for age in 0..20 {
+        builder.pak(Person { first_name: format!("Person {age}"), last_name: "Doe".to_string(), age }).unwrap();
+    }
+    builder.build_file(&path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    
+    let salvage = Pak::open_recover(&path).unwrap();
+    assert!(salvage.indices_intact());
+    assert_eq!(salvage.meta().unwrap().name, "people");
+    assert_eq!(salvage.item_count(), 20);
+    
+    // Wipe the header and the backup at the end of the file, so the ordinal table has to be found by scanning.
+    let mut damaged = bytes.clone();
+    damaged[..64].fill(0xFF);
+    damaged.truncate(bytes.len() - 16);
+    std::fs::write(&path, &damaged).unwrap();
+    let salvage = Pak::open_recover(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    
+    assert!(salvage.meta().is_none());
+    assert_eq!(salvage.item_count(), 20);
+    assert_eq!(salvage.lost_count(), 0);
+    let ages = salvage.pointers().map(|pointer| salvage.get::<Person>(&pointer).unwrap().age).collect::<Vec<_>>();
+    assert_eq!(ages, (0..20).collect::<Vec<_>>());
+}
+
+#[test]
+fn rebuild_header_from_trailer() {
+    use crate::meta::PakTrailer;
+    
+    let path = std::env::temp_dir().join(format!("pak-trailer-{}.pak", std::process::id()));
+    data_base_builder().build_file(&path).unwrap();
+    let mut bytes = std::fs::read(&path).unwrap();
+    assert!(PakTrailer::read(&bytes).is_some());
+    assert_eq!(Pak::new_from_file(&path).unwrap().query::<(Person,)>("last_name".equals("Doe")).unwrap().len(), 2);
+    
+    bytes[..48].fill(0);
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(Pak::new_from_file(&path).is_err());
+    
+    let salvage = Pak::open_recover(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(salvage.indices_intact());
+    assert_eq!(salvage.item_count(), 9);
+    
+    let pak = salvage.into_pak().unwrap();
+    assert_eq!(pak.query::<(Person,)>("last_name".equals("Doe")).unwrap().len(), 2);
+}
+
+#[test]
+fn atomic_build_file() {
+    let dir = std::env::temp_dir().join(format!("pak-atomic-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("data.pak");
+    std::fs::write(&path, b"an old pak").unwrap();
+    
+    let pak = data_base_builder().build_file(&path).unwrap();
+    assert_eq!(pak.query::<(Person,)>("first_name".equals("John")).unwrap().len(), 2);
+    let files = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect::<Vec<_>>();
+    assert_eq!(files, vec!["data.pak"]);
+    
+    let pak = data_base_builder().with_atomic_write(false).build_file(&path).unwrap();
+    assert_eq!(pak.item_count().unwrap(), 9);
+    
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "proptest")]
+proptest::proptest! {
+    #[test]
+    fn prop_value_round_trip(value in crate::strategy::pak_value()) {
+        let bytes = bincode::serialize(&value).unwrap();
+        let decoded : PakValue = bincode::deserialize(&bytes).unwrap();
+        proptest::prop_assert_eq!(decoded, value);
+    }
+    
+    #[test]
+    fn prop_queries_match_scan(items in crate::strategy::pak_fixture(64), number in 0..20u64) {
+        use crate::strategy::PakFixtureItem;
+        
+        let pak = crate::strategy::build_fixture(&items).unwrap();
+        let ids = |found : Vec<PakFixtureItem>| found.into_iter().map(|item| item.id).collect::<HashSet<_>>();
+        let scan = |filter : &dyn Fn(&PakFixtureItem) -> bool| items.iter().filter(|item| filter(item)).map(|item| item.id).collect::<HashSet<_>>();
+        
+        proptest::prop_assert_eq!(ids(pak.query::<(PakFixtureItem,)>("number".equals(number)).unwrap()), scan(&|item| item.number == number));
+        proptest::prop_assert_eq!(ids(pak.query::<(PakFixtureItem,)>("number".less_than(number)).unwrap()), scan(&|item| item.number < number));
+        proptest::prop_assert_eq!(ids(pak.query::<(PakFixtureItem,)>("number".greater_than_or_equal(number)).unwrap()), scan(&|item| item.number >= number));
+        proptest::prop_assert_eq!(ids(pak.query::<(PakFixtureItem,)>("number".greater_than(number) & "flag".equals(true)).unwrap()), scan(&|item| item.number > number && item.flag));
+    }
+    
+    #[test]
+    fn prop_corrupted_input_does_not_panic(bytes in proptest::strategy::Strategy::prop_flat_map(crate::strategy::pak_fixture(16), |items| crate::strategy::corrupted(crate::strategy::build_fixture_bytes(&items).unwrap()))) {
+        let _ = Pak::new(std::io::Cursor::new(bytes.clone()));
+        let salvage = crate::recover::PakSalvage::recover(bytes);
+        for pointer in salvage.pointers() {
+            let _ = salvage.read_bytes(&pointer);
+        }
+    }
+}
+
+#[test]
+fn naive_store_agrees() {
+    use crate::testing::{assert_agrees, NaiveStore};
+    
+    let mut builder = PakBuilder::new();
+    let mut store = NaiveStore::new();
+    for i in 0..300u32 {
+        let person = Person { first_name: format!("Person {}", i % 7), last_name: ["Doe", "Smith", "Brown"][(i % 3) as usize].to_string(), age: (i * 37) % 90 };
+        store.pak(&mut builder, person).unwrap();
+    }
+    let pak = builder.build_in_memory().unwrap();
+    
+    for age in [0, 1, 20, 45, 89, 90] {
+        assert_agrees(&pak, &store, &"age".equals(age));
+        assert_agrees(&pak, &store, &"age".less_than(age));
+        assert_agrees(&pak, &store, &"age".greater_than(age));
+        assert_agrees(&pak, &store, &"age".less_than_or_equal(age));
+        assert_agrees(&pak, &store, &"age".greater_than_or_equal(age));
+        assert_agrees(&pak, &store, &("age".greater_than(age) & "last_name".equals("Smith")));
+        assert_agrees(&pak, &store, &("age".less_than(age) | "first_name".equals("Person 3")));
+    }
+}
+
+#[test]
+fn query_value_kind_mismatch() {
+    use crate::{error::PakError, value::PakValueKind};
+    
+    let pak = build_data_base();
+    assert_eq!(pak.index_kind("age"), Some(PakValueKind::Int));
+    assert_eq!(pak.index_kind("first_name"), Some(PakValueKind::String));
+    assert_eq!(pak.index_kind("missing"), None);
+    
+    assert!(matches!(pak.query::<(Person,)>("age".equals("thirty")), Err(PakError::ValueKindMismatch(..))));
+    assert!(matches!(pak.exists("first_name".greater_than(3)), Err(PakError::ValueKindMismatch(..))));
+    assert_eq!(pak.query::<(Person,)>("age".less_than(30.5)).unwrap().len(), 3);
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn schema_validation() {
+    use crate::{error::PakError, schema::PakSchema};
+    
+    #[derive(PakSchema)]
+    #[pak_schema(Person(first_name, last_name, age), Pet(name, age, kind))]
+    struct DataBaseSchema;
+    
+    #[derive(PakSchema)]
+    #[pak_schema(Person(first_name, age, nickname))]
+    struct OtherSchema;
+    
+    let path = std::env::temp_dir().join(format!("pak-schema-{}.pak", std::process::id()));
+    data_base_builder().with_schema::<DataBaseSchema>().build_file(&path).unwrap();
+    
+    let pak = Pak::open_with_schema::<DataBaseSchema>(&path).unwrap();
+    assert_eq!(pak.schema().unwrap().hash(), DataBaseSchema::descriptor().hash());
+    
+    let Err(PakError::SchemaMismatch(diff)) = Pak::open_with_schema::<OtherSchema>(&path) else { panic!("the schema should not match") };
+    std::fs::remove_file(&path).unwrap();
+    assert!(diff.contains("- Pet"));
+    assert!(diff.contains("- Person.last_name"));
+    assert!(diff.contains("+ Person.nickname"));
+    
+    // The hash only depends on what the schema declares, so it can be written down and checked against later builds.
+    let person = crate::schema::PakSchemaItem::new("Person", &["age", "first_name", "last_name"]);
+    let pet = crate::schema::PakSchemaItem::new("Pet", &["age", "kind", "name"]);
+    assert_eq!(DataBaseSchema::descriptor(), crate::schema::PakSchemaDescriptor::new(vec![pet, person]));
+    assert_eq!(DataBaseSchema::descriptor().hash(), 0x1301_7ce8_d0ec_6488);
+    assert!(DataBaseSchema::descriptor().items[0].is_type(std::any::type_name::<Person>()));
+    assert!(!DataBaseSchema::descriptor().items[0].is_type("pak_db::test::NotPerson"));
+}
+
+#[test]
+fn versioned_items() {
+    let mut builder = PakBuilder::new();
+    let current = builder.pak_versioned(Person { first_name: "Jane".to_string(), last_name: "Doe".to_string(), age: 25 }).unwrap();
+    let plain = builder.pak(Person { first_name: "John".to_string(), last_name: "Doe".to_string(), age: 30 }).unwrap();
+    
+    // Simulate an item written by an older build, where Person was still at version 1.
+    let envelope = crate::envelope::PakEnvelope {
+        type_tag: std::any::type_name::<Person>().to_string(),
+        version: 1,
+        payload: bincode::serialize(&PersonV1 { name: "Alice Smith".to_string(), age: 28 }).unwrap(),
+    };
+    let bytes = bincode::serialize(&envelope).unwrap();
+    let old = builder.pak_chunk(crate::pointer::PakTypedPointer::new(builder.size(), bytes.len() as u64, std::any::type_name::<Person>()).with_version(1), bytes, vec![PakIndex::new("last_name", "Smith")]).unwrap();
+    let pak = builder.build_in_memory().unwrap();
+    
+    assert_eq!(current.version(), Some(2));
+    assert_eq!(plain.version(), None);
+    assert_eq!(pak.get_versioned::<Person>(&current).unwrap().first_name, "Jane");
+    assert_eq!(pak.get_versioned::<Person>(&plain).unwrap().first_name, "John");
+    assert_eq!(pak.get_versioned::<Person>(&old).unwrap(), Person { first_name: "Alice".to_string(), last_name: "Smith".to_string(), age: 28 });
+    assert_eq!(pak.query_versioned::<Person>("last_name".equals("Doe")).unwrap().len(), 2);
+    assert_eq!(pak.query_versioned::<Person>("last_name".equals("Smith")).unwrap()[0].first_name, "Alice");
+    
+    // A plain read can't tell which version of the type it is decoding, so it refuses enveloped items rather than decode old layouts as new ones.
+    assert!(matches!(pak.get::<Person>(&old), Err(crate::error::PakError::VersionedItem(_, 1))));
+    assert!(matches!(pak.get::<Person>(&current), Err(crate::error::PakError::VersionedItem(_, 2))));
+    assert_eq!(pak.query::<(Person,)>("last_name".equals("Doe")).unwrap().len(), 1);
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn derived_item_migrations() {
+    use derived::{v1, Monster};
+    
+    let mut builder = PakBuilder::new();
+    builder.pak_versioned(Monster { name: "Dragon".to_string(), health: 500, boss: true }).unwrap();
+    
+    // An item that an older build wrote while the monster was still at version 1.
+    let envelope = crate::envelope::PakEnvelope {
+        type_tag: std::any::type_name::<Monster>().to_string(),
+        version: 1,
+        payload: bincode::serialize(&v1::Monster { name: "Slime".to_string() }).unwrap(),
+    };
+    let bytes = bincode::serialize(&envelope).unwrap();
+    let old = builder.pak_chunk(crate::pointer::PakTypedPointer::new(builder.size(), bytes.len() as u64, std::any::type_name::<Monster>()).with_version(1), bytes, vec![]).unwrap();
+    let pak = builder.build_in_memory().unwrap();
+    
+    assert_eq!(Monster::VERSION, 3);
+    let bosses = pak.query_versioned::<Monster>("hp".greater_than(100)).unwrap();
+    assert_eq!(bosses, vec![Monster { name: "Dragon".to_string(), health: 500, boss: true }]);
+    assert_eq!(pak.get_versioned::<Monster>(&old).unwrap(), Monster { name: "Slime".to_string(), health: 10, boss: false });
+}
+
+#[test]
+fn wide_value_variants() {
+    assert_eq!(PakValue::from(1.5f32), PakValue::Float32(1.5f32.to_bits()));
+    assert_eq!(PakValue::from(1.5f32), PakValue::from(1.5f64));
+    assert_eq!(PakValue::from(u128::MAX).as_u128(), Some(u128::MAX));
+    assert!(PakValue::from(u128::MAX) > PakValue::from(u64::MAX));
+    assert!(PakValue::from(i128::MIN) < PakValue::from(i64::MIN));
+    assert!(PakValue::from(-1i64) < PakValue::from(0u64));
+    assert_eq!(PakValue::from(7i128), PakValue::from(7u8));
+    
+    let mut builder = PakBuilder::new();
+    for id in [u128::MAX, u128::MAX - 1, 1u128 << 100, 5] {
+        builder.pak(PakIndexedValue(vec![PakIndex::new("id", id)])).unwrap();
+    }
+    let pak = builder.build_in_memory().unwrap();
+    assert_eq!(pak.query::<(PakIndexedValue,)>("id".greater_than(u64::MAX)).unwrap().len(), 3);
+    assert_eq!(pak.query::<(PakIndexedValue,)>("id".equals(u128::MAX - 1)).unwrap().len(), 1);
+}
+
+#[test]
+fn decimal_values() {
+    assert_eq!(PakValue::decimal(150, 2), PakValue::decimal(15, 1));
+    assert_eq!(PakValue::decimal(300, 2), PakValue::from(3u32));
+    assert!(PakValue::decimal(-150, 2) < PakValue::decimal(-149, 2));
+    assert!(PakValue::decimal(10, 1) < PakValue::decimal(1001, 3));
+    assert!(PakValue::decimal(1, 0) < PakValue::from(u128::MAX));
+    assert_eq!(format!("{:?}", PakValue::decimal(-5, 3)), "-0.005");
+    
+    let mut builder = PakBuilder::new();
+    for cents in [1999, 2000, 2001, 999, 10000] {
+        builder.pak(PakIndexedValue(vec![PakIndex::new("price", PakValue::decimal(cents, 2))])).unwrap();
+    }
+    let pak = builder.build_in_memory().unwrap();
+    assert_eq!(pak.query::<(PakIndexedValue,)>("price".less_than_or_equal(PakValue::decimal(20, 0))).unwrap().len(), 3);
+    
+    #[cfg(feature = "rust_decimal")]
+    {
+        let price = rust_decimal::Decimal::new(1999, 2);
+        assert_eq!(PakValue::from(price), PakValue::decimal(1999, 2));
+        assert_eq!(PakValue::from(price).as_decimal(), Some(price));
+    }
+}
+
+#[test]
+fn value_conversions() {
+    use crate::error::PakValueConversionError;
+    
+    assert_eq!(u8::try_from(PakValue::Uint(200)), Ok(200));
+    assert!(matches!(u8::try_from(PakValue::Uint(300)), Err(PakValueConversionError::OutOfRange(_, _))));
+    assert!(matches!(u32::try_from(PakValue::Int(-1)), Err(PakValueConversionError::OutOfRange(_, _))));
+    assert_eq!(i64::try_from(PakValue::from(u128::from(u64::MAX) >> 1)), Ok(i64::MAX));
+    assert!(matches!(i32::try_from(PakValue::from("12")), Err(PakValueConversionError::KindMismatch(_, _))));
+    assert!(matches!(u64::try_from(PakValue::from(1.0f64)), Err(PakValueConversionError::KindMismatch(_, _))));
+    
+    assert_eq!(f64::try_from(PakValue::from(1.5f32)), Ok(1.5));
+    assert_eq!(f32::try_from(PakValue::from(0.25f64)), Ok(0.25));
+    assert!(f32::try_from(PakValue::from(0.1f64)).is_err());
+    assert_eq!(bool::try_from(&PakValue::Boolean(true)), Ok(true));
+    assert_eq!(String::try_from(PakValue::from("name")), Ok("name".to_string()));
+    assert!(String::try_from(PakValue::Void).is_err());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_values() {
+    use serde_json::json;
+    
+    let values = [PakValue::from("text"), PakValue::from(-4i32), PakValue::from(u64::MAX), PakValue::from(2.5f64), PakValue::Boolean(false), PakValue::Void];
+    for value in values {
+        let json = value.to_json().unwrap();
+        assert_eq!(PakValue::from_json(&json).unwrap(), value);
+    }
+    
+    assert_eq!(PakValue::from(u128::MAX).to_json().unwrap(), json!(u128::MAX.to_string()));
+    assert_eq!(PakValue::from(-1i128).to_json().unwrap(), json!(-1));
+    assert_eq!(PakValue::decimal(1999, 2).to_json().unwrap(), json!("19.99"));
+    assert_eq!(PakValue::from(1.5f32).to_json().unwrap(), json!(1.5));
+    assert!(PakValue::from(f64::NAN).to_json().is_err());
+    assert!(PakValue::from_json(&json!([1, 2])).is_err());
+    assert!(PakValue::from_json(&json!({ "a" : 1 })).is_err());
+    assert_eq!(PakValue::try_from(json!(12)).unwrap(), PakValue::from(12u8));
+}
+
+#[test]
+fn std_type_values() {
+    use std::{borrow::Cow, num::NonZeroU32, path::PathBuf, time::{Duration, SystemTime, UNIX_EPOCH}};
+    
+    let name = "Jeff".to_string();
+    assert_eq!((&name).into_pak_value(), PakValue::from("Jeff"));
+    assert_eq!(Cow::Borrowed("Jeff").into_pak_value(), PakValue::from("Jeff"));
+    assert_eq!('J'.into_pak_value(), PakValue::from("J"));
+    assert_eq!(NonZeroU32::new(7).unwrap().into_pak_value(), PakValue::from(7u32));
+    assert_eq!(Duration::from_millis(3).into_pak_value(), PakValue::from(3_000_000u32));
+    assert_eq!((UNIX_EPOCH + Duration::from_secs(1)).into_pak_value(), PakValue::from(1_000_000_000u64));
+    assert_eq!((UNIX_EPOCH - Duration::from_secs(1)).into_pak_value(), PakValue::from(-1_000_000_000i64));
+    assert!(SystemTime::now().into_pak_value() > UNIX_EPOCH.into_pak_value());
+    assert_eq!(PathBuf::from("assets/icon.png").into_pak_value(), PakValue::from("assets/icon.png"));
+    assert_eq!([0u8, 171, 255].into_pak_value(), PakValue::from("00abff"));
+    
+    let index = PakIndex::new("first_name", &name);
+    assert_eq!(index.value, PakValue::from("Jeff"));
+}
+
+#[test]
+fn value_total_order() {
+    use std::{cmp::Ordering, hash::{BuildHasher, RandomState}};
+    
+    let values = vec![
+        PakValue::Void, PakValue::Boolean(false), PakValue::Boolean(true), PakValue::from(""), PakValue::from("a"),
+        PakValue::from(0i64), PakValue::from(0u64), PakValue::from(0.0f64), PakValue::from(-0.0f64), PakValue::from(-0.0f32), PakValue::decimal(0, 3),
+        PakValue::from(1i64), PakValue::from(1u8), PakValue::from(1.0f64), PakValue::from(1.0f32), PakValue::decimal(100, 2), PakValue::from(1i128),
+        PakValue::from(-1i64), PakValue::from(-1.0f64), PakValue::decimal(-10, 1),
+        PakValue::from(0.5f64), PakValue::from(0.5f32), PakValue::decimal(5, 1), PakValue::from(0.1f64), PakValue::from(0.1f32), PakValue::decimal(1, 1),
+        PakValue::from(i64::MIN), PakValue::from(i64::MAX), PakValue::from(u64::MAX), PakValue::from(u64::MAX as f64), PakValue::from(i128::MIN),
+        PakValue::from(u128::MAX), PakValue::from(2f64.powi(127)), PakValue::from(1u128 << 127), PakValue::from(2f64.powi(128)), PakValue::from(1e300f64),
+        PakValue::from(f64::INFINITY), PakValue::from(f32::NEG_INFINITY), PakValue::from(f64::NAN), PakValue::from(f32::NAN), PakValue::from(f64::MIN_POSITIVE / 4.0),
+        PakValue::decimal(1, 60), PakValue::decimal(-1, 200), PakValue::decimal(i128::MAX, 0), PakValue::decimal(i128::MIN, 38),
+    ];
+    
+    let hasher = RandomState::new();
+    for a in &values {
+        assert_eq!(a.cmp(a), Ordering::Equal, "{a:?} isn't equal to itself");
+        for b in &values {
+            assert_eq!(a.cmp(b), b.cmp(a).reverse(), "{a:?} and {b:?} compare asymmetrically");
+            assert_eq!(a == b, a.cmp(b) == Ordering::Equal);
+            if a == b { assert_eq!(hasher.hash_one(a), hasher.hash_one(b), "{a:?} and {b:?} are equal but hash differently") }
+            for c in &values {
+                if a <= b && b <= c { assert!(a <= c, "{a:?} <= {b:?} <= {c:?} isn't transitive") }
+            }
+        }
+    }
+    
+    assert_eq!(PakValue::from(0.1f32), PakValue::decimal(100000001490116119384765625i128, 27));
+    assert!(PakValue::from(0.1f64) > PakValue::decimal(1, 1));
+    assert!(PakValue::from(u64::MAX) < PakValue::from(u64::MAX as f64));
+    assert!(PakValue::from(i64::MAX - 1) < PakValue::from(i64::MAX as f64));
+    assert!(PakValue::from(f64::NAN) > PakValue::from(f64::INFINITY));
+}
+
+#[test]
+fn compact_postings() {
+    let build = |indexed : bool| {
+        let mut builder = PakBuilder::new().with_duplicate_keys(PakDuplicateKeys::Inline);
+        for number in 0..2000u32 {
+            let indices = if indexed { vec![PakIndex::new("even", number % 2 == 0)] } else { vec![] };
+            builder.pak(PakIndexedValue(indices)).unwrap();
+        }
+        builder.build_in_memory().unwrap()
+    };
+    let plain = build(false).size();
+    let pak = build(true);
+    // Each item's own copy of its index takes 17 bytes, which leaves only a few bytes per item for the tree's postings.
+    assert!(pak.size() - plain < 2000 * (17 + 6), "the index takes {} bytes", pak.size() - plain);
+    assert_eq!(pak.query::<(PakIndexedValue,)>("even".equals(true)).unwrap().len(), 1000);
+}
+
+#[test]
+fn compact_header_encoding() {
+    use crate::meta::PakEncoding;
+    
+    let builder = |encoding : PakEncoding| {
+        let mut builder = PakBuilder::new().with_header_encoding(encoding);
+        for number in 0..200u32 {
+            builder.pak(PakIndexedValue(vec![PakIndex::new(format!("key_{number}").as_str(), number), PakIndex::new("shared", number)])).unwrap();
+        }
+        builder
+    };
+    let path = std::env::temp_dir().join(format!("pak-compact-{}.pak", std::process::id()));
+    let fixed = builder(PakEncoding::Fixed).build_in_memory().unwrap();
+    let compact = builder(PakEncoding::Compact).build_file(&path).unwrap();
+    assert!(compact.size() < fixed.size());
+    assert_eq!(compact.query::<(PakIndexedValue,)>("shared".less_than(50u32)).unwrap().len(), 50);
+    assert_eq!(compact.query::<(PakIndexedValue,)>("key_7".equals(7u32)).unwrap().len(), 1);
+    
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes[..48].fill(0);
+    std::fs::write(&path, &bytes).unwrap();
+    let pak = Pak::open_recover(&path).unwrap().into_pak().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(pak.query::<(PakIndexedValue,)>("shared".greater_than_or_equal(150u32)).unwrap().len(), 50);
+}
+
+#[test]
+fn stale_pointers() {
+    use crate::{error::PakError, query::PakQueryExpression};
+    
+    let build = |generation : u64, age : u32| {
+        let mut builder = PakBuilder::new().with_generation(generation);
+        builder.pak(Person { first_name: "Jeff".to_string(), last_name: "Doe".to_string(), age }).unwrap();
+        builder.pak(Person { first_name: "Joe".to_string(), last_name: "Doe".to_string(), age }).unwrap();
+        builder.build_in_memory().unwrap()
+    };
+    let old = build(1, 30);
+    let pointer = old.pointer_of(1).unwrap().unwrap();
+    assert_eq!(pointer.generation(), Some(1));
+    assert_eq!(old.get::<Person>(&pointer).unwrap().first_name, "Joe");
+    
+    let reloaded = build(2, 31);
+    assert_eq!(reloaded.generation(), 2);
+    assert!(matches!(reloaded.get::<Person>(&pointer), Err(PakError::StalePointer(1, 2))));
+    let fresh = "first_name".equals("Joe").execute(&reloaded).unwrap().into_iter().next().unwrap().into_pointer();
+    assert_eq!(reloaded.get::<Person>(&fresh).unwrap().age, 31);
+    
+    let untagged = PakPointer::new_typed::<Person>(pointer.offset(), pointer.size());
+    assert_eq!(reloaded.get::<Person>(&untagged).unwrap().age, 31);
+}
+
+#[test]
+fn pinned_snapshot() {
+    use crate::error::PakError;
+    
+    let build = |generation : u64| {
+        let mut builder = PakBuilder::new().with_generation(generation);
+        builder.pak_with_id(7u64, Person { first_name: "Jeff".to_string(), last_name: "Doe".to_string(), age: 30 }).unwrap();
+        builder.pak(Person { first_name: "Joe".to_string(), last_name: "Doe".to_string(), age: 31 }).unwrap();
+        builder.build_in_memory().unwrap()
+    };
+    let old = build(1);
+    let reloaded = build(2);
+    let snapshot = reloaded.snapshot().unwrap();
+    assert_eq!(snapshot.generation(), 2);
+    assert_eq!(snapshot.query::<(Person,)>("last_name".equals("Doe")).unwrap().len(), 2);
+    assert!(snapshot.exists("age".equals(31u32)).unwrap());
+    assert_eq!(snapshot.by_id::<Person>(7u64).unwrap().unwrap().first_name, "Jeff");
+    let pointer = reloaded.pointer_of(1).unwrap().unwrap();
+    assert_eq!(snapshot.get::<Person>(&pointer).unwrap().first_name, "Joe");
+    let stale = old.pointer_of(1).unwrap().unwrap();
+    assert!(matches!(snapshot.read_bytes(&stale), Err(PakError::StalePointer(1, 2))));
+    
+    // Rewriting the file underneath an open pak, even with a build of the same generation, fails the snapshot instead of reading the new bytes.
+    let path = std::env::temp_dir().join("pak_pinned_snapshot.pak");
+    let builder = |last_name : &str| {
+        let mut builder = PakBuilder::new().with_generation(3);
+        builder.pak(Person { first_name: "Jane".to_string(), last_name: last_name.to_string(), age: 30 }).unwrap();
+        builder
+    };
+    builder("Doe").build_file(&path).unwrap();
+    let pak = Pak::new_from_file(&path).unwrap();
+    let snapshot = pak.snapshot().unwrap();
+    assert_eq!(snapshot.query::<(Person,)>("last_name".equals("Doe")).unwrap().len(), 1);
+    let rebuilt = std::env::temp_dir().join("pak_pinned_snapshot_rebuilt.pak");
+    builder("Smithson").build_file(&rebuilt).unwrap();
+    std::fs::copy(&rebuilt, &path).unwrap();
+    assert!(matches!(snapshot.query::<(Person,)>("last_name".equals("Doe")), Err(PakError::SourceChanged)));
+    assert!(matches!(snapshot.get::<Person>(&pak.pointer_of(0).unwrap().unwrap()), Err(PakError::SourceChanged)));
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&rebuilt).unwrap();
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn derived_references() {
+    use derived::{Companion, Monster, Tamer};
+    
+    let mut builder = PakBuilder::new();
+    let ash = builder.pak(Tamer { name: "Ash".to_string() }).unwrap();
+    let gary = builder.pak(Tamer { name: "Gary".to_string() }).unwrap();
+    let dragon = builder.pak(Monster { name: "Dragon".to_string(), health: 90, boss: true }).unwrap();
+    builder.pak(Companion { name: "Sparky".to_string(), tamer: ash.clone().into(), rival: dragon.clone() }).unwrap();
+    builder.pak(Companion { name: "Squirt".to_string(), tamer: ash.clone().into(), rival: dragon.clone() }).unwrap();
+    builder.pak(Companion { name: "Eevee".to_string(), tamer: gary.into(), rival: dragon.clone() }).unwrap();
+    let pak = builder.build_in_memory().unwrap();
+    
+    let sparky = pak.query::<(Companion,)>("name".equals("Sparky")).unwrap();
+    assert_eq!(sparky[0].tamer(&pak).unwrap().name, "Ash");
+    assert_eq!(sparky[0].rival(&pak).unwrap().health, 90);
+    assert_eq!(pak.query::<(Companion,)>("tamer".equals(&ash)).unwrap().len(), 2);
+    assert_eq!(pak.query::<(Companion,)>("rival_of".equals(&dragon)).unwrap().len(), 3);
+    
+    // Reordering moves the tamers behind their companions, and the companions follow them.
+    let path = std::env::temp_dir().join(format!("pak-derived-references-{}.pak", std::process::id()));
+    pak.repack_reordered(&path, |pointer| u64::MAX - pointer.offset()).unwrap();
+    let repacked = Pak::new_from_file(&path).unwrap();
+    let ash = repacked.pointer_of(0).unwrap().unwrap();
+    assert_ne!(ash.offset(), 0);
+    let squirt = repacked.query::<(Companion,)>("name".equals("Squirt")).unwrap();
+    assert_eq!(squirt[0].tamer.pointer().offset(), ash.offset());
+    assert_eq!(squirt[0].tamer(&repacked).unwrap().name, "Ash");
+    assert_eq!(squirt[0].rival(&repacked).unwrap().name, "Dragon");
+    assert_eq!(repacked.query::<(Companion,)>("tamer".equals(&ash)).unwrap().len(), 2);
+    assert_eq!(repacked.query::<(Companion,)>("rival_of".equals(&repacked.pointer_of(2).unwrap().unwrap())).unwrap().len(), 3);
+    
+    #[cfg(feature = "zstd")]
+    {
+        let mut builder = PakBuilder::new().with_compression(crate::meta::PakCompression::Zstd(0));
+        let tamers = (0..20).map(|i| builder.pak(Tamer { name: format!("Tamer {i}").repeat(i + 1) }).unwrap()).collect::<Vec<_>>();
+        let dragon = builder.pak(Monster { name: "Dragon".to_string(), health: 90, boss: true }).unwrap();
+        for (i, tamer) in tamers.iter().enumerate() {
+            builder.pak(Companion { name: format!("Companion {i}"), tamer: tamer.clone().into(), rival: dragon.clone() }).unwrap();
+        }
+        builder.build_in_memory().unwrap().repack_reordered(&path, |pointer| u64::MAX - pointer.offset()).unwrap();
+        let repacked = Pak::new_from_file(&path).unwrap();
+        for i in [0, 7, 19] {
+            let companion = repacked.query::<(Companion,)>("name".equals(format!("Companion {i}"))).unwrap();
+            assert_eq!(companion[0].tamer(&repacked).unwrap().name, format!("Tamer {i}").repeat(i + 1));
+        }
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn erased_items() {
+    use crate::item::ErasedPakItem;
+    
+    let plugin_items : Vec<Box<dyn ErasedPakItem>> = vec![
+        Box::new(Person { first_name: "Jeff".to_string(), last_name: "Doe".to_string(), age: 30 }),
+        Box::new(PakIndexedValue(vec![PakIndex::new("first_name", "Jeff")])),
+    ];
+    let mut builder = PakBuilder::new();
+    let pointers = plugin_items.into_iter().map(|item| builder.pak_dyn(item).unwrap()).collect::<Vec<_>>();
+    let pak = builder.build_in_memory().unwrap();
+    
+    assert_eq!(pak.get::<Person>(&pointers[0]).unwrap().age, 30);
+    assert!(pak.get::<Person>(&pointers[1]).is_err());
+    let (people, values) = pak.query::<(Person, PakIndexedValue)>("first_name".equals("Jeff")).unwrap();
+    assert_eq!((people.len(), values.len()), (1, 1));
+}
+
+/// A custom index kind that keeps a sorted list of string values and can answer prefix searches.
+struct PrefixIndex(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+impl crate::kind::PakIndexKind for PrefixIndex {
+    fn name(&self) -> &str {
+        "test_prefix"
+    }
+    
+    fn build(&self, entries : &[(PakValue, u32)]) -> PakResult<Vec<u8>> {
+        let mut entries = entries.to_vec();
+        entries.sort();
+        Ok(bincode::serialize(&entries)?)
+    }
+    
+    fn execute(&self, data : &[u8], query : &crate::kind::PakKindQuery) -> PakResult<Option<Vec<u32>>> {
+        self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let entries : Vec<(PakValue, u32)> = bincode::deserialize(data)?;
+        Ok(Some(entries.into_iter().filter(|(value, _)| self.matches(query, value)).map(|(_, ordinal)| ordinal).collect()))
+    }
+    
+    fn matches(&self, query : &crate::kind::PakKindQuery, value : &PakValue) -> bool {
+        use crate::kind::PakKindQuery;
+        match query {
+            PakKindQuery::Range(lower, upper) => crate::btree::range_contains(&(lower.as_ref(), upper.as_ref()), value),
+            PakKindQuery::Operation(_, PakValue::String(prefix)) => value.as_string().is_some_and(|value| value.starts_with(prefix.as_str())),
+            PakKindQuery::Operation(_, _) => false,
+        }
+    }
+}
+
+#[test]
+fn custom_index_kind() {
+    use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+    use crate::{kind::{PakCustomQuery, PakKindQuery}, testing::{assert_agrees, NaiveStore}};
+    
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut builder = PakBuilder::new().with_index_kind("first_name", PrefixIndex(calls.clone()));
+    let mut store = NaiveStore::new();
+    for name in ["Jeff", "Jenny", "Joe", "Bob"] {
+        store.pak(&mut builder, Person { first_name: name.to_string(), last_name: "Doe".to_string(), age: 20 }).unwrap();
+    }
+    let pak = builder.build_in_memory().unwrap();
+    
+    let prefix = PakCustomQuery::new("first_name", Arc::new(PrefixIndex(calls.clone())), PakKindQuery::Operation("prefix".to_string(), PakValue::from("Je")));
+    assert_eq!(pak.query::<(Person,)>(prefix).unwrap().len(), 2);
+    assert_eq!(pak.query::<(Person,)>("first_name".less_than("Jeff")).unwrap().len(), 1);
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+    
+    let prefix = PakCustomQuery::new("first_name", Arc::new(PrefixIndex(calls.clone())), PakKindQuery::Operation("prefix".to_string(), PakValue::from("J")));
+    assert_agrees(&pak, &store, &prefix);
+    let unbuilt = PakCustomQuery::new("last_name", Arc::new(PrefixIndex(calls)), PakKindQuery::Operation("prefix".to_string(), PakValue::from("D")));
+    assert!(pak.query::<(Person,)>(unbuilt).is_err());
+    
+    // Expressions written before the store existed only have to know how to run against a pak.
+    struct Everything;
+    impl crate::query::PakQueryExpression for Everything {
+        fn execute(&self, pak : &Pak) -> PakResult<HashSet<crate::pointer::PakTypedPointer>> {
+            Ok(pak.ordinals()?.iter().cloned().collect())
+        }
+    }
+    assert_eq!(pak.query::<(Person,)>(Everything).unwrap().len(), 4);
+    assert!(store.query(&Everything).is_empty());
+}
+
+#[test]
+fn substring_search() {
+    use crate::{query::PakQueryExpression, suffix::{contains, PakSuffixIndex}, testing::{assert_agrees, NaiveStore}};
+    
+    let descriptions = ["A dragon scale shield", "Sword of the Dragonborn", "Rusty dagger", "dragonfruit", "Élan potion of the dragon"];
+    let path = std::env::temp_dir().join(format!("pak-suffix-{}.pak", std::process::id()));
+    let mut builder = PakBuilder::new().with_index_kind("description", PakSuffixIndex);
+    let mut store = NaiveStore::new();
+    for description in descriptions {
+        store.pak(&mut builder, PakIndexedValue(vec![PakIndex::new("description", description), PakIndex::new("length", description.len() as u64)])).unwrap();
+    }
+    builder.build_file(&path).unwrap();
+    let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+    let pak = Pak::new(CountingSource { data : std::fs::read(&path).unwrap(), reads : reads.clone() }).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    
+    // The suffix array is decoded by the first query, and later ones only read the tree's meta to find it.
+    assert_eq!(contains("description", "dragon").execute(&pak).unwrap().len(), 3);
+    let first = reads.get();
+    assert_eq!(contains("description", "shield").execute(&pak).unwrap().len(), 1);
+    assert_eq!(reads.get(), first + 1);
+    
+    assert_eq!(pak.query::<(PakIndexedValue,)>(contains("description", "dragon")).unwrap().len(), 3);
+    assert_eq!(pak.query::<(PakIndexedValue,)>(contains("description", "Dragon")).unwrap().len(), 1);
+    assert_eq!(pak.query::<(PakIndexedValue,)>(contains("description", "lan")).unwrap().len(), 1);
+    assert!(pak.query::<(PakIndexedValue,)>(contains("description", "wizard")).unwrap().is_empty());
+    for pattern in ["d", "ag", "of the", "", "shield"] {
+        assert_agrees(&pak, &store, &contains("description", pattern));
+    }
+    assert!(pak.query::<(PakIndexedValue,)>(contains("length", "1")).is_err());
+}
+
+#[test]
+fn like_search() {
+    use crate::{ngram::{like, PakTrigramIndex}, testing::{assert_agrees, NaiveStore}};
+    
+    let names = ["Jello", "Shell", "Bell", "Hello there", "Ye", "hellish", "Nell"];
+    let mut builder = PakBuilder::new().with_index_kind("name", PakTrigramIndex);
+    let mut store = NaiveStore::new();
+    for name in names {
+        store.pak(&mut builder, PakIndexedValue(vec![PakIndex::new("name", name)])).unwrap();
+    }
+    let pak = builder.build_in_memory().unwrap();
+    
+    assert_eq!(pak.query::<(PakIndexedValue,)>(like("name", "%ell%")).unwrap().len(), 6);
+    assert_eq!(pak.query::<(PakIndexedValue,)>(like("name", "_ell")).unwrap().len(), 2);
+    assert_eq!(pak.query::<(PakIndexedValue,)>(like("name", "%ell")).unwrap().len(), 3);
+    for pattern in ["%", "", "Y_", "%o%e%", "H%e", "%ll_sh", "J%%o", "__", "%zzz%"] {
+        assert_agrees(&pak, &store, &like("name", pattern));
+    }
+}
+
+#[test]
+fn ranked_text_search() {
+    use crate::text::{matches_text, PakTextIndex};
+    
+    let descriptions = [
+        "A fire sword forged in dragon fire",
+        "A rusty sword",
+        "Fire resistant cloak",
+        "An ordinary wooden shield",
+        "The legendary fire sword of the north, a sword like no other",
+    ];
+    let mut builder = PakBuilder::new().with_index_kind("description", PakTextIndex::default());
+    for description in descriptions {
+        builder.pak(PakIndexedValue(vec![PakIndex::new("description", description)])).unwrap();
+    }
+    builder.pak(Person { first_name: "Fire".to_string(), last_name: "Sword".to_string(), age: 1 }).unwrap();
+    let pak = builder.build_in_memory().unwrap();
+    
+    let hits = pak.search::<PakIndexedValue>("description", "Fire SWORD", 3).unwrap();
+    assert_eq!(hits.len(), 3);
+    assert!(hits.windows(2).all(|pair| pair[0].0 >= pair[1].0));
+    let first = hits[0].1.0[0].value.as_string().unwrap();
+    assert!(first.contains("fire sword"), "{first}");
+    assert!(pak.search::<PakIndexedValue>("description", "dragon", 10).unwrap().len() == 1);
+    assert!(pak.search::<PakIndexedValue>("description", "wizard", 10).unwrap().is_empty());
+    assert!(pak.search::<PakIndexedValue>("first_name", "fire", 10).is_err());
+    assert_eq!(pak.query::<(PakIndexedValue,)>(matches_text("description", "cloak shield")).unwrap().len(), 2);
+}
+
+#[test]
+fn text_search_operators() {
+    use crate::text::PakTextIndex;
+    
+    let items = [
+        ("A rusty sword", "weapon"),
+        ("A sword that is rusty", "weapon"),
+        ("A rusty sword rack", "furniture"),
+        ("A shiny sword", "weapon"),
+    ];
+    let mut builder = PakBuilder::new().with_index_kind("description", PakTextIndex::default());
+    for (description, kind) in items {
+        builder.pak(PakIndexedValue(vec![PakIndex::new("description", description), PakIndex::new("kind", kind)])).unwrap();
+    }
+    let pak = builder.build_in_memory().unwrap();
+    
+    let describe = |query : &str| {
+        let mut hits = pak.search::<PakIndexedValue>("description", query, 10).unwrap().into_iter()
+            .map(|(_, item)| item.0[0].value.as_string().unwrap().to_string())
+            .collect::<Vec<_>>();
+        hits.sort();
+        hits
+    };
+    assert_eq!(describe("\"rusty sword\""), vec!["A rusty sword", "A rusty sword rack"]);
+    assert_eq!(describe("\"rusty sword\" AND kind:weapon"), vec!["A rusty sword"]);
+    assert_eq!(describe("sword AND NOT rusty"), vec!["A shiny sword"]);
+    assert_eq!(describe("(shiny OR rack) AND kind:weapon"), vec!["A shiny sword"]);
+    assert_eq!(describe("shiny rack").len(), 2);
+    assert!(describe("kind:armor").is_empty());
+    assert!(pak.search::<PakIndexedValue>("description", "\"rusty sword", 10).is_err());
+    assert!(pak.search::<PakIndexedValue>("description", "rusty AND", 10).is_err());
+    assert!(pak.search::<PakIndexedValue>("description", "(rusty", 10).is_err());
+}
+
+#[test]
+fn text_analyzers() {
+    use crate::text::{PakTextAnalyzer, PakTextIndex, PakTokenizer};
+    
+    let whitespace = PakTextAnalyzer::new(PakTokenizer::Whitespace);
+    assert_eq!(whitespace.analyze("A Rusty-Sword").unwrap(), vec!["a", "rusty-sword"]);
+    assert_eq!(PakTextAnalyzer::default().analyze("A Rusty-Sword").unwrap(), vec!["a", "rusty", "sword"]);
+    #[cfg(feature = "unicode")]
+    assert_eq!(PakTextAnalyzer::new(PakTokenizer::Unicode).analyze("The sword's edge").unwrap(), vec!["the", "sword's", "edge"]);
+    #[cfg(not(feature = "unicode"))]
+    assert!(PakTextAnalyzer::new(PakTokenizer::Unicode).analyze("The sword's edge").is_err());
+    
+    let mut builder = PakBuilder::new().with_index_kind("description", PakTextIndex::with_analyzer(whitespace));
+    builder.pak(PakIndexedValue(vec![PakIndex::new("description", "A rusty-sword")])).unwrap();
+    builder.pak(PakIndexedValue(vec![PakIndex::new("description", "A rusty shield")])).unwrap();
+    let pak = builder.build_in_memory().unwrap();
+    assert_eq!(pak.search::<PakIndexedValue>("description", "Rusty-Sword", 10).unwrap().len(), 1);
+    assert_eq!(pak.search::<PakIndexedValue>("description", "rusty", 10).unwrap().len(), 1);
+    
+    #[cfg(all(feature = "stemming", feature = "stopwords"))]
+    {
+        use crate::text::PakLanguage;
+        
+        let analyzer = PakTextAnalyzer::default().with_stemming(PakLanguage::English).with_stopwords(PakLanguage::English);
+        assert_eq!(analyzer.analyze("The swords of the north").unwrap(), vec!["sword", "north"]);
+        
+        let mut builder = PakBuilder::new().with_index_kind("description", PakTextIndex::with_analyzer(analyzer));
+        builder.pak(PakIndexedValue(vec![PakIndex::new("description", "Two swords of the north")])).unwrap();
+        builder.pak(PakIndexedValue(vec![PakIndex::new("description", "A shield")])).unwrap();
+        let pak = builder.build_in_memory().unwrap();
+        assert_eq!(pak.search::<PakIndexedValue>("description", "\"sword north\"", 10).unwrap().len(), 1);
+        assert_eq!(pak.search::<PakIndexedValue>("description", "the shields", 10).unwrap().len(), 1);
+    }
+}
+
+#[test]
+fn text_highlights() {
+    use crate::text::PakTextIndex;
+    
+    let item = PakIndexedValue(vec![PakIndex::new("description", "The legendary Fire sword of the north"), PakIndex::new("kind", "weapon")]);
+    let mut builder = PakBuilder::new().with_index_kind("description", PakTextIndex::default());
+    builder.pak(item.clone()).unwrap();
+    let pak = builder.build_in_memory().unwrap();
+    
+    let highlights = pak.highlight(&item, "description", "\"fire sword\" AND NOT ice AND kind:weapon").unwrap();
+    assert_eq!(highlights.len(), 1);
+    let highlight = &highlights[0];
+    assert_eq!(highlight.ranges, vec![14..18, 19..24]);
+    assert_eq!(&highlight.value[highlight.ranges[0].clone()], "Fire");
+    assert_eq!(highlight.snippet(4).unwrap(), "…ary Fire sword of …");
+    assert_eq!(highlight.snippet(100).unwrap(), "The legendary Fire sword of the north");
+    assert!(pak.highlight(&item, "description", "ice").unwrap()[0].snippet(4).is_none());
+    assert!(pak.highlight(&item, "kind", "weapon").is_err());
+}
+
+#[test]
+fn external_index_build() {
+    use crate::index::PakIndexBuild;
+    
+    let dir = std::env::temp_dir().join(format!("pak-runs-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let build = |index_build| {
+        let mut builder = PakBuilder::new().with_index_build(index_build).with_duplicate_keys(PakDuplicateKeys::Overflow(16));
+        for i in 0..2000u32 {
+            builder.pak(Person { first_name: format!("{}", (i * 7919) % 2000), last_name: format!("{}", i % 40), age: i % 3 }).unwrap();
+        }
+        builder.build_in_memory().unwrap()
+    };
+    let memory = build(PakIndexBuild::InMemory);
+    let external = build(PakIndexBuild::External { run_entries: 37, directory: dir.clone() });
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    
+    for key in ["first_name", "last_name", "age"] {
+        let walk = |pak : &Pak| {
+            let mut entries = Vec::new();
+            pak.get_tree(key).unwrap().walk(|value, postings| {
+                entries.push((value.clone(), postings.load()?.len()));
+                Ok(true)
+            }).unwrap();
+            entries
+        };
+        assert_eq!(walk(&memory), walk(&external));
+    }
+    for pak in [&memory, &external] {
+        assert_eq!(pak.query::<(Person,)>("first_name".equals("1234")).unwrap().len(), 1);
+        assert_eq!(pak.query::<(Person,)>("last_name".equals("7")).unwrap().len(), 50);
+        assert_eq!(pak.query::<(Person,)>("age".less_than(2) & "last_name".equals("7")).unwrap().len(), 33);
+        assert_eq!(pak.group_by("age", Aggregate::Count).unwrap().len(), 3);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// A source that corrupts the first few reads from the vault, like a flaky network drive would.
+struct FlakySource {
+    data : Vec<u8>,
+    failures : u32,
+}
+
+impl crate::PakSource for FlakySource {
+    fn read(&mut self, pointer : &PakPointer, offset : u64) -> PakResult<Vec<u8>> {
+        let start = (pointer.offset() + offset) as usize;
+        let mut bytes = self.data[start..start + pointer.size() as usize].to_vec();
+        if offset != 0 && self.failures > 0 && !bytes.is_empty() {
+            self.failures -= 1;
+            bytes[0] ^= 0xff;
+        }
+        Ok(bytes)
+    }
+}
+
+#[test]
+fn block_checksums() {
+    use crate::error::PakError;
+    
+    let mut builder = PakBuilder::new().with_block_checksums(64);
+    for i in 0..50u32 {
+        builder.pak(Person { first_name: format!("First {i}"), last_name: "Checked".to_string(), age: i }).unwrap();
+    }
+    let path = std::env::temp_dir().join(format!("pak-checksums-{}.pak", std::process::id()));
+    builder.build_file(&path).unwrap();
+    let data = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    
+    let pak = Pak::new(FlakySource { data : data.clone(), failures : 0 }).unwrap();
+    assert_eq!(pak.query::<(Person,)>("last_name".equals("Checked")).unwrap().len(), 50);
+    
+    let pak = Pak::new(FlakySource { data : data.clone(), failures : 2 }).unwrap();
+    assert_eq!(pak.query::<(Person,)>("age".equals(7u32)).unwrap()[0].first_name, "First 7");
+    
+    let pak = Pak::new(FlakySource { data, failures : 10 }).unwrap().with_checksum_retries(1);
+    assert!(matches!(pak.query::<(Person,)>("age".equals(7u32)), Err(PakError::ChecksumMismatch(_, 2))));
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn password_encryption() {
+    use std::io::Read;
+    use crate::{crypto::PakKey, error::PakError, meta::PakKdf};
+    
+    let path = std::env::temp_dir().join(format!("pak-password-{}.pak", std::process::id()));
+    let kdf = PakKdf::with_cost(64, 1, 1).unwrap();
+    let mut builder = PakBuilder::new().with_password_kdf("hunter2", kdf).unwrap().with_block_checksums(128);
+    for i in 0..20u32 {
+        builder.pak(Person { first_name: format!("Secret {i}"), last_name: "Agent".to_string(), age: i }).unwrap();
+    }
+    let built = builder.build_file(&path).unwrap();
+    assert_eq!(built.query::<(Person,)>("age".equals(3u32)).unwrap()[0].first_name, "Secret 3");
+    assert!(!std::fs::read(&path).unwrap().windows(6).any(|window| window == b"Secret"));
+    
+    assert!(matches!(Pak::new_from_file(&path).unwrap().query::<(Person,)>("age".equals(3u32)), Err(PakError::MissingKey)));
+    assert!(matches!(Pak::open_with_password(&path, "hunter3"), Err(PakError::WrongKey)));
+    let pak = Pak::open_with_password(&path, "hunter2").unwrap();
+    assert!(pak.is_encrypted());
+    let people = pak.query::<(Person,)>("last_name".equals("Agent")).unwrap();
+    assert_eq!(people.len(), 20);
+    
+    let pointer = pak.pointer_of(0).unwrap().unwrap();
+    let mut window = pak.window(&pointer);
+    let mut bytes = Vec::new();
+    window.read_to_end(&mut bytes).unwrap();
+    assert_eq!(window.len(), bytes.len() as u64);
+    assert_eq!(bincode::deserialize::<Person>(&bytes).unwrap().first_name, "Secret 0");
+    
+    let key = PakKey::new([7; 32]);
+    let mut builder = PakBuilder::new().with_encryption(&key).unwrap();
+    builder.pak(Person { first_name: "Keyed".to_string(), last_name: "Agent".to_string(), age: 1 }).unwrap();
+    builder.build_file(&path).unwrap();
+    assert!(matches!(Pak::open_with_key(&path, &PakKey::new([8; 32])), Err(PakError::WrongKey)));
+    assert_eq!(Pak::open_with_key(&path, &key).unwrap().query::<(Person,)>("age".equals(1u32)).unwrap().len(), 1);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn aes_gcm_encryption() {
+    use std::io::Cursor;
+    use crate::{crypto::PakKey, error::PakError, meta::PakCipherAlgorithm};
+    
+    let (key, unreleased) = (PakKey::new([5; 32]), PakKey::new([6; 32]));
+    let path = std::env::temp_dir().join(format!("pak-aes-{}.pak", std::process::id()));
+    let mut builder = PakBuilder::new().with_encryption(&key).unwrap().with_item_key("unreleased", &unreleased).unwrap().with_cipher_algorithm(PakCipherAlgorithm::Aes256Gcm).unwrap();
+    for i in 0..20u32 {
+        builder.pak(Person { first_name: format!("Secret {i}"), last_name: "Agent".to_string(), age: i }).unwrap();
+    }
+    builder.pak_encrypted(Person { first_name: "Hidden".to_string(), last_name: "Agent".to_string(), age: 20 }, "unreleased").unwrap();
+    builder.build_file(&path).unwrap();
+    let data = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(!data.windows(6).any(|window| window == b"Secret" || window == b"Hidden"));
+    
+    assert!(matches!(Pak::new_encrypted(Cursor::new(data.clone()), &PakKey::new([8; 32])), Err(PakError::WrongKey)));
+    let pak = Pak::new_encrypted(Cursor::new(data), &key).unwrap();
+    assert_eq!(pak.cipher_algorithm(), Some(PakCipherAlgorithm::Aes256Gcm));
+    assert_eq!(pak.query::<(Person,)>("age".equals(3u32)).unwrap()[0].first_name, "Secret 3");
+    let hidden = pak.pointer_of(20).unwrap().unwrap();
+    assert!(matches!(pak.get::<Person>(&hidden), Err(PakError::KeyRequired(_))));
+    let pak = pak.with_item_key("unreleased", &unreleased).unwrap();
+    assert_eq!(pak.query::<(Person,)>("last_name".equals("Agent")).unwrap().len(), 21);
+    
+    // The key can also be given once everything is paked, which seals the whole pak again.
+    let mut builder = PakBuilder::new().with_cipher_algorithm(PakCipherAlgorithm::Aes256Gcm).unwrap().with_item_key("unreleased", &unreleased).unwrap();
+    let late = builder.pak(Person { first_name: "Late".to_string(), last_name: "Agent".to_string(), age: 1 }).unwrap();
+    builder.pak_encrypted(Person { first_name: "Hidden".to_string(), last_name: "Agent".to_string(), age: 2 }, "unreleased").unwrap();
+    let sealed = builder.build_file_encrypted(&path, &key).unwrap();
+    assert!(matches!(sealed.get::<Person>(&late), Err(PakError::StalePointer(0, 1))));
+    assert_eq!(sealed.query::<(Person,)>("age".equals(2u32)).unwrap()[0].first_name, "Hidden");
+    let data = std::fs::read(&path).unwrap();
+    assert!(!data.windows(4).any(|window| window == b"Late"));
+    let pak = Pak::open_with_key(&path, &key).unwrap().with_item_key("unreleased", &unreleased).unwrap();
+    assert_eq!(pak.cipher_algorithm(), Some(PakCipherAlgorithm::Aes256Gcm));
+    assert_eq!(pak.query::<(Person,)>("last_name".equals("Agent")).unwrap().len(), 2);
+    std::fs::remove_file(&path).unwrap();
+    
+    let mut builder = PakBuilder::new().with_encryption(&key).unwrap();
+    builder.pak(Person { first_name: "Sealed".to_string(), last_name: "Agent".to_string(), age: 1 }).unwrap();
+    assert!(matches!(builder.with_cipher_algorithm(PakCipherAlgorithm::Aes256Gcm), Err(PakError::EncryptionAfterPak(1))));
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn protected_items() {
+    use std::io::Read;
+    use crate::{crypto::PakKey, error::PakError};
+    
+    let unreleased = PakKey::new([3; 32]);
+    let path = std::env::temp_dir().join(format!("pak-protected-{}.pak", std::process::id()));
+    let mut builder = PakBuilder::new().with_item_key("unreleased", &unreleased).unwrap();
+    builder.pak(Person { first_name: "Public".to_string(), last_name: "Hero".to_string(), age: 1 }).unwrap();
+    builder.pak_encrypted(Person { first_name: "Hidden".to_string(), last_name: "Hero".to_string(), age: 2 }, "unreleased").unwrap();
+    assert!(matches!(builder.pak_encrypted(Person { first_name: "Lost".to_string(), last_name: "Hero".to_string(), age: 3 }, "dlc"), Err(PakError::KeyRequired(id)) if id == "dlc"));
+    assert!(matches!(PakBuilder::new().with_item_key("unreleased", &unreleased).unwrap().with_item_key("unreleased", &PakKey::new([4; 32])), Err(PakError::DuplicateKeyId(id)) if id == "unreleased"));
+    builder.build_file(&path).unwrap();
+    
+    let pak = Pak::new_from_file(&path).unwrap();
+    assert!(!pak.is_encrypted());
+    assert_eq!(pak.query::<(Person,)>("age".equals(1u32)).unwrap()[0].first_name, "Public");
+    let hidden = pak.pointer_of(1).unwrap().unwrap();
+    assert!(matches!(pak.get::<Person>(&hidden), Err(PakError::KeyRequired(id)) if id == "unreleased"));
+    assert!(matches!(Pak::new_from_file(&path).unwrap().with_item_key("unreleased", &PakKey::new([4; 32])), Err(PakError::WrongKey)));
+    
+    let pak = pak.with_item_key("unreleased", &unreleased).unwrap();
+    assert_eq!(pak.get::<Person>(&hidden).unwrap().first_name, "Hidden");
+    assert_eq!(pak.query::<(Person,)>("last_name".equals("Hero")).unwrap().len(), 2);
+    let mut bytes = Vec::new();
+    pak.window(&hidden).read_to_end(&mut bytes).unwrap();
+    assert_eq!(bincode::deserialize::<Person>(&bytes).unwrap().first_name, "Hidden");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn key_rotation() {
+    use crate::{crypto::PakKey, error::PakError};
+    
+    let (old, new, unreleased) = (PakKey::new([1; 32]), PakKey::new([2; 32]), PakKey::new([3; 32]));
+    let path = std::env::temp_dir().join(format!("pak-rotation-{}.pak", std::process::id()));
+    let rotated = std::env::temp_dir().join(format!("pak-rotated-{}.pak", std::process::id()));
+    let mut builder = PakBuilder::new().with_encryption(&old).unwrap().with_item_key("unreleased", &unreleased).unwrap().with_block_checksums(256).with_item_checksums().with_vault_hash().with_manifest();
+    for i in 0..300u32 {
+        builder.pak(Person { first_name: format!("Person {i}"), last_name: format!("Family {}", i % 3), age: i % 50 }).unwrap();
+    }
+    builder.pak_encrypted(Person { first_name: "Hidden".to_string(), last_name: "Family 0".to_string(), age: 99 }, "unreleased").unwrap();
+    builder.build_file(&path).unwrap();
+    
+    assert!(matches!(Pak::new_from_file(&path).unwrap().repack_with_key(&rotated, &new), Err(PakError::MissingKey)));
+    let pak = Pak::open_with_key(&path, &old).unwrap();
+    pak.repack_with_key(&rotated, &new).unwrap();
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), std::fs::metadata(&rotated).unwrap().len());
+    
+    assert!(matches!(Pak::open_with_key(&rotated, &old), Err(PakError::WrongKey)));
+    let repacked = Pak::open_with_key(&rotated, &new).unwrap().with_item_key("unreleased", &unreleased).unwrap();
+    assert_eq!(repacked.fetch_indices().unwrap(), pak.fetch_indices().unwrap());
+    repacked.verify().unwrap();
+    assert_eq!(repacked.manifest_hashes().unwrap(), pak.manifest_hashes().unwrap());
+    assert_eq!(repacked.query::<(Person,)>("age".equals(7u32)).unwrap().len(), 6);
+    assert_eq!(repacked.query::<(Person,)>("last_name".equals("Family 0")).unwrap().len(), 101);
+    assert_eq!(repacked.query::<(Person,)>("age".equals(99u32)).unwrap()[0].first_name, "Hidden");
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&rotated).unwrap();
+}
+
+#[test]
+fn manifest_hashes() {
+    let mut builder = PakBuilder::new().with_manifest();
+    for i in 0..5u32 {
+        builder.pak(Person { first_name: format!("Person {i}"), last_name: "Smith".to_string(), age: i }).unwrap();
+    }
+    builder.pak_no_search("plain".to_string()).unwrap();
+    let pak = builder.build_in_memory().unwrap();
+    
+    let manifest = pak.manifest_hashes().unwrap().unwrap();
+    assert_eq!(manifest.len(), 6);
+    for (ordinal, entry) in manifest.iter().enumerate() {
+        let pointer = pak.pointer_of(ordinal as u32).unwrap().unwrap();
+        let bytes = pak.read_bytes(&pointer).unwrap();
+        assert_eq!(entry.size, bytes.len() as u64);
+        assert_eq!(entry.type_name, pointer.type_name());
+        assert_eq!(entry.hash_hex().len(), 64);
+    }
+    assert_eq!(manifest[5].hash_hex(), "ed7ad21bbe2b6d930ae7cfe31b0b56bb3310b65f814f5c625923fb4559046d26");
+    assert_ne!(manifest[0].hash, manifest[1].hash);
+    
+    let mut builder = PakBuilder::new();
+    builder.pak(Person { first_name: "Person 0".to_string(), last_name: "Smith".to_string(), age: 0 }).unwrap();
+    assert_eq!(builder.build_in_memory().unwrap().manifest_hashes().unwrap(), None);
+}
+
+#[test]
+fn diff_queries() {
+    use crate::{diff::compare_queries, meta::PakEncoding};
+    
+    let people = (0..20u32).map(|i| Person { first_name: format!("Person {i}"), last_name: format!("Family {}", i % 4), age: i }).collect::<Vec<_>>();
+    let mut builder_a = PakBuilder::new();
+    for person in &people {
+        builder_a.pak(person.clone()).unwrap();
+    }
+    let mut builder_b = PakBuilder::new().with_header_encoding(PakEncoding::Compact);
+    for person in people.iter().rev() {
+        let mut person = person.clone();
+        if person.age == 5 { person.first_name = "Renamed".to_string() }
+        builder_b.pak(person).unwrap();
+    }
+    let (pak_a, pak_b) = (builder_a.build_in_memory().unwrap(), builder_b.build_in_memory().unwrap());
+    
+    let report = compare_queries(&pak_a, &pak_b, &[&"last_name".equals("Family 0"), &"age".greater_than(3u32), &"age".less_than(2u32)]).unwrap();
+    assert_eq!(report.queries, 3);
+    assert!(!report.is_identical());
+    assert_eq!(report.differences.len(), 1);
+    let difference = &report.differences[0];
+    assert_eq!(difference.query, 1);
+    assert_eq!(pak_a.get::<Person>(&difference.only_in_a[0]).unwrap().first_name, "Person 5");
+    assert_eq!(pak_b.get::<Person>(&difference.only_in_b[0]).unwrap().first_name, "Renamed");
+    
+    assert!(compare_queries(&pak_a, &pak_a, &[&"age".greater_than(3u32)]).unwrap().is_identical());
+}
+
+#[test]
+fn build_plan() {
+    use crate::format::PakFormat;
+    
+    let path = std::env::temp_dir().join(format!("pak-plan-{}.pak", std::process::id()));
+    for format in [PakFormat::V1, PakFormat::V2] {
+        let plan = data_base_builder().with_format(format).plan().unwrap();
+        let pak = data_base_builder().with_format(format).build_file(&path).unwrap();
+        assert_eq!(plan.size, std::fs::metadata(&path).unwrap().len());
+        assert_eq!(plan.vault_start, pak.get_vault_start());
+        assert_eq!(plan.items.len() as u32, pak.item_count().unwrap());
+        let item = pak.pointer_of(3).unwrap().unwrap();
+        assert_eq!((plan.items[3].offset(), plan.items[3].size()), (item.offset(), item.size()));
+        assert_eq!(plan.item_size, plan.items.iter().map(|item| item.size()).sum::<u64>());
+        assert!(plan.indices.values().all(|index| index.size > 0) && plan.overhead() > 0);
+        assert_eq!(plan.indices.keys().map(String::as_str).collect::<Vec<_>>(), ["age", "first_name", "kind", "last_name", "name"]);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn format_versions() {
+    use std::io::Cursor;
+    use crate::{error::PakError, format::PakFormat};
+    
+    let path = std::env::temp_dir().join(format!("pak-format-v2-{}.pak", std::process::id()));
+    let mut builder = PakBuilder::new().with_format(PakFormat::V2).with_block_checksums(64).with_manifest();
+    for i in 0..50u32 {
+        builder.pak(Person { first_name: format!("Person {i}"), last_name: format!("Family {}", i % 5), age: i }).unwrap();
+    }
+    let built = builder.build_file(&path).unwrap();
+    assert_eq!(built.size(), std::fs::metadata(&path).unwrap().len());
+    
+    let pak = Pak::new_from_file(&path).unwrap();
+    assert_eq!(pak.format(), PakFormat::V2);
+    assert_eq!(pak.query::<(Person,)>("last_name".equals("Family 3")).unwrap().len(), 10);
+    assert_eq!(pak.query::<(Person,)>("age".equals(42u32)).unwrap()[0].first_name, "Person 42");
+    assert_eq!(pak.manifest_hashes().unwrap().unwrap().len(), 50);
+    
+    let mut builder = PakBuilder::new();
+    builder.pak(Person { first_name: "Old".to_string(), last_name: "Format".to_string(), age: 1 }).unwrap();
+    let old = builder.build_in_memory().unwrap();
+    assert_eq!(old.format(), PakFormat::V1);
+    
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes[4..8].copy_from_slice(&9u32.to_le_bytes());
+    assert!(matches!(Pak::new(Cursor::new(bytes.clone())), Err(PakError::UnsupportedFormat(9))));
+    bytes[4..8].copy_from_slice(&2u32.to_le_bytes());
+    bytes.pop();
+    assert!(matches!(Pak::new(Cursor::new(bytes)), Err(PakError::InvalidHeader(field, _)) if field == "footer"));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn legacy_pak() {
+    use crate::{format::PakFormat, query::PakQuery};
+
+    let pak = Pak::new_from_file(baseline_fixture()).unwrap();
+    assert_eq!(pak.format(), PakFormat::V1);
+    assert_eq!((pak.meta.revision, pak.name(), pak.author(), pak.version()), (0, "baseline", "pak-db 0.1", "1.0"));
+    
+    let paris = pak.query::<(LegacyCitizen,)>(PakQuery::equals("city", "Paris")).unwrap();
+    assert_eq!(paris.len(), 30);
+    assert!(paris.iter().all(|citizen| citizen.city == "Paris"));
+    let mut young = pak.query::<(LegacyCitizen,)>(PakQuery::less_than("age", 3u32)).unwrap().into_iter().map(|citizen| citizen.name).collect::<Vec<_>>();
+    young.sort();
+    assert_eq!(young, ["Citizen 000", "Citizen 001", "Citizen 002", "Citizen 090", "Citizen 091", "Citizen 092"]);
+    let older = pak.query::<(LegacyCitizen,)>(PakQuery::greater_than_or_equal("age", 80u32) & PakQuery::equals("city", "Rome")).unwrap();
+    assert_eq!(older.len(), 2);
+    assert_eq!(pak.query::<(LegacyCitizen,)>(PakQuery::equals("name", "Citizen 123")).unwrap()[0].age, 33);
+    assert_eq!(pak.distinct("city").unwrap().count(), 5);
+    
+    // Only the items the indices point at get ordinals, in the order they were paked.
+    assert_eq!(pak.item_count().unwrap(), 150);
+    assert_eq!(pak.get::<LegacyCitizen>(&pak.pointer_of(7).unwrap().unwrap()).unwrap().name, "Citizen 007");
+    assert_eq!(pak.get::<String>(&PakPointer::new_typed::<String>(0, 22)).unwrap(), "hello from 0.1");
+}
+
+#[test]
+fn meta_revision() {
+    use crate::{error::PakError, meta::{PakMeta, PAK_META_REVISION}};
+
+    let pak = data_base_builder().build_in_memory().unwrap();
+    assert_eq!(pak.meta.revision, PAK_META_REVISION);
+    let bytes = bincode::serialize(&pak.meta).unwrap();
+    assert_eq!(PakMeta::decode(&bytes).unwrap().ordinals, pak.meta.ordinals);
+
+    // The meta of pak-db 0.1 ends right before the revision.
+    let header = bincode::serialize(&("old", "1.0", "a pak from 0.1", "someone")).unwrap();
+    let old = PakMeta::decode(&header).unwrap();
+    assert_eq!((old.revision, old.name.as_str(), old.author.as_str()), (0, "old", "someone"));
+
+    let mut future = header.clone();
+    future.extend_from_slice(&(PAK_META_REVISION + 1).to_le_bytes());
+    future.extend_from_slice(&[0; 16]);
+    assert!(matches!(PakMeta::decode(&future), Err(PakError::UnsupportedRevision(revision)) if revision == PAK_META_REVISION + 1));
+    assert!(matches!(PakMeta::decode(&future[..header.len() + 2]), Err(PakError::InvalidHeader(..))));
+}
+
+#[test]
+fn upgrade_to_v2() {
+    use crate::{convert::{upgrade, PakUpgradeOptions}, format::PakFormat};
+    
+    let old_path = std::env::temp_dir().join(format!("pak-upgrade-v1-{}.pak", std::process::id()));
+    let new_path = std::env::temp_dir().join(format!("pak-upgrade-v2-{}.pak", std::process::id()));
+    let mut builder = PakBuilder::new().with_name("upgrade").with_block_checksums(32).with_manifest();
+    for i in 0..30u32 {
+        builder.pak(Person { first_name: format!("Person {i}"), last_name: format!("Family {}", i % 3), age: i }).unwrap();
+    }
+    let old = builder.build_file(&old_path).unwrap();
+    
+    assert_eq!(upgrade(&old_path, &new_path, PakUpgradeOptions::default()).unwrap(), PakFormat::V1);
+    let new = Pak::new_from_file(&new_path).unwrap();
+    assert_eq!(new.format(), PakFormat::V2);
+    assert_eq!(new.name(), "upgrade");
+    assert_eq!(new.manifest_hashes().unwrap(), old.manifest_hashes().unwrap());
+    assert!(crate::diff::compare_queries(&old, &new, &[&"last_name".equals("Family 1"), &"age".less_than(10u32)]).unwrap().is_identical());
+    let pointer = old.pointer_of(7).unwrap().unwrap();
+    assert_eq!(new.get::<Person>(&pointer).unwrap().first_name, "Person 7");
+    
+    assert_eq!(upgrade(&new_path, &new_path, PakUpgradeOptions::new().with_format(PakFormat::V1)).unwrap(), PakFormat::V2);
+    assert_eq!(Pak::new_from_file(&new_path).unwrap().format(), PakFormat::V1);
+    std::fs::remove_file(&old_path).unwrap();
+    std::fs::remove_file(&new_path).unwrap();
+}
+
+#[test]
+fn upgrade_legacy() {
+    use crate::{convert::{upgrade, PakUpgradeOptions}, format::PakFormat, meta::PAK_META_REVISION, query::PakQuery};
+    
+    let old = Pak::new_from_file(baseline_fixture()).unwrap();
+    for format in [PakFormat::V2, PakFormat::V1] {
+        let new_path = std::env::temp_dir().join(format!("pak-upgrade-legacy-{format:?}-{}.pak", std::process::id()));
+        assert_eq!(upgrade(baseline_fixture(), &new_path, PakUpgradeOptions::new().with_format(format)).unwrap(), PakFormat::V1);
+        let new = Pak::new_from_file(&new_path).unwrap();
+        assert_eq!((new.format(), new.meta.revision, new.name(), new.author()), (format, PAK_META_REVISION, "baseline", "pak-db 0.1"));
+        assert!(crate::diff::compare_queries(&old, &new, &[&PakQuery::equals("city", "Lima"), &PakQuery::less_than("age", 10u32)]).unwrap().is_identical());
+        assert_eq!(new.query::<(LegacyCitizen,)>(PakQuery::equals("name", "Citizen 123")).unwrap()[0].age, 33);
+        assert_eq!(new.item_count().unwrap(), 150);
+        
+        // Pointers handed out by the old pak still read the same items, even the ones without indices.
+        assert_eq!(new.get::<LegacyCitizen>(&old.pointer_of(7).unwrap().unwrap()).unwrap().name, "Citizen 007");
+        assert_eq!(new.get::<String>(&PakPointer::new_typed::<String>(0, 22)).unwrap(), "hello from 0.1");
+        std::fs::remove_file(&new_path).unwrap();
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn extract_stream() {
+    use futures::{executor::block_on, StreamExt};
+    
+    let blob = (0..200_000u32).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+    let mut builder = PakBuilder::new();
+    let pointer = builder.pak_no_search(blob.clone()).unwrap();
+    let pak = builder.build_in_memory().unwrap();
+    
+    let stream = pak.extract_stream(&pointer).unwrap().with_chunk_size(30_000);
+    assert_eq!(stream.len(), pointer.size());
+    let chunks = block_on(stream.collect::<Vec<_>>()).into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(chunks.len(), 7);
+    assert!(chunks[..6].iter().all(|chunk| chunk.len() == 30_000));
+    let bytes = pak.read_bytes(&pointer).unwrap();
+    assert_eq!(chunks.concat(), bytes);
+    
+    let mut stream = pak.extract_stream(&pointer).unwrap();
+    assert_eq!(block_on(stream.next()).unwrap().unwrap().len(), 64 * 1024);
+    
+    // A pak opened from a file streams on a thread of the stream's own, so the stream can outlive the pak and move to another thread.
+    let path = std::env::temp_dir().join(format!("pak-extract-stream-{}.pak", std::process::id()));
+    let mut builder = PakBuilder::new();
+    let pointer = builder.pak_no_search(blob).unwrap();
+    builder.build_file(&path).unwrap();
+    let pak = Pak::new_from_file(&path).unwrap();
+    let stream = pak.extract_stream(&pointer).unwrap().with_chunk_size(30_000);
+    drop(pak);
+    let chunks = std::thread::spawn(move || block_on(stream.collect::<Vec<_>>())).join().unwrap();
+    let chunks = chunks.into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(chunks.len(), 7);
+    assert_eq!(chunks.concat(), bytes);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn foreground_reads_overtake_background_reads() {
+    use std::sync::{mpsc, Arc, Mutex};
+    use futures::{executor::block_on, StreamExt};
+    use crate::{cache::PakPriority, stream::PakExtractStream};
+    
+    // Reads of the held item wait until the test lets them go, which keeps a foreground read in flight.
+    struct HeldSource {
+        data : Arc<Vec<u8>>,
+        held : u64,
+        entered : mpsc::Sender<()>,
+        release : Arc<Mutex<mpsc::Receiver<()>>>,
+        log : Arc<Mutex<Vec<u64>>>,
+    }
+    
+    impl crate::PakSource for HeldSource {
+        fn read(&mut self, pointer : &PakPointer, offset : u64) -> PakResult<Vec<u8>> {
+            if offset > 0 && pointer.offset() == self.held {
+                self.entered.send(()).unwrap();
+                self.release.lock().unwrap().recv().unwrap();
+            }
+            if offset > 0 { self.log.lock().unwrap().push(pointer.offset()) }
+            let start = (pointer.offset() + offset) as usize;
+            Ok(self.data[start..start + pointer.size() as usize].to_vec())
+        }
+    }
+    
+    let mut builder = PakBuilder::new();
+    let held = builder.pak_no_search(vec![0u8; 1000]).unwrap();
+    let overtaking = builder.pak_no_search(vec![1u8; 1000]).unwrap();
+    let queued = (2..5u8).map(|byte| builder.pak_no_search(vec![byte; 1000]).unwrap()).collect::<Vec<_>>();
+    let path = std::env::temp_dir().join(format!("pak-priority-{}.pak", std::process::id()));
+    builder.build_file(&path).unwrap();
+    let data = Arc::new(std::fs::read(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+    
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let (entered, was_entered) = mpsc::channel();
+    let (release, released) = mpsc::channel();
+    let released = Arc::new(Mutex::new(released));
+    let pak = Pak::new(std::io::Cursor::new(data.to_vec())).unwrap().with_parallel_sources({
+        let (data, log, held) = (data.clone(), log.clone(), held.offset());
+        move || Ok(Box::new(HeldSource { data : data.clone(), held, entered : entered.clone(), release : released.clone(), log : log.clone() }))
+    });
+    let collect = |stream : PakExtractStream| std::thread::spawn(move || block_on(stream.collect::<Vec<_>>()));
+    
+    let first = collect(pak.extract_stream(&held).unwrap());
+    was_entered.recv().unwrap();
+    let background = queued.iter().map(|pointer| collect(pak.with_priority(PakPriority::Background, |pak| pak.extract_stream(pointer)).unwrap())).collect::<Vec<_>>();
+    // The background streams asked first, but a foreground read asked for after them is made while they wait.
+    let chunks = block_on(pak.extract_stream(&overtaking).unwrap().collect::<Vec<_>>());
+    assert_eq!(chunks.len(), 1);
+    assert_eq!(log.lock().unwrap().as_slice(), &[overtaking.offset()]);
+    
+    release.send(()).unwrap();
+    assert!(first.join().unwrap().into_iter().all(|chunk| chunk.is_ok()));
+    for stream in background {
+        assert!(stream.join().unwrap().into_iter().all(|chunk| chunk.is_ok()));
+    }
+    let log = log.lock().unwrap();
+    assert_eq!(log[..2], [overtaking.offset(), held.offset()]);
+    assert_eq!(log[2..].iter().copied().collect::<HashSet<_>>(), queued.iter().map(PakPointer::offset).collect());
+}
+
+struct CountingSource {
+    data : Vec<u8>,
+    reads : std::rc::Rc<std::cell::Cell<u32>>,
+}
+
+impl crate::PakSource for CountingSource {
+    fn read(&mut self, pointer : &PakPointer, offset : u64) -> PakResult<Vec<u8>> {
+        self.reads.set(self.reads.get() + 1);
+        let start = (pointer.offset() + offset) as usize;
+        Ok(self.data[start..start + pointer.size() as usize].to_vec())
+    }
+}
+
+#[test]
+fn warm_cache_state() {
+    let mut builder = PakBuilder::new();
+    for i in 0..200u32 {
+        builder.pak(Person { first_name: format!("Person {i}"), last_name: format!("Family {}", i % 10), age: i }).unwrap();
+    }
+    let path = std::env::temp_dir().join(format!("pak-cache-{}.pak", std::process::id()));
+    let state = std::env::temp_dir().join(format!("pak-cache-{}.state", std::process::id()));
+    builder.build_file(&path).unwrap();
+    let data = std::fs::read(&path).unwrap();
+    
+    let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+    let pak = Pak::new(CountingSource { data : data.clone(), reads : reads.clone() }).unwrap().with_cache(1024 * 1024);
+    assert_eq!(pak.query::<(Person,)>("last_name".equals("Family 4")).unwrap().len(), 20);
+    let cold = reads.get();
+    assert_eq!(pak.query::<(Person,)>("last_name".equals("Family 4")).unwrap().len(), 20);
+    assert_eq!(reads.get(), cold);
+    pak.save_cache_state(&state).unwrap();
+    
+    let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+    let pak = Pak::new(CountingSource { data, reads : reads.clone() }).unwrap().with_cache(1024 * 1024);
+    assert!(pak.load_cache_state(&state).unwrap() > 20);
+    let prefetched = reads.get();
+    assert_eq!(pak.query::<(Person,)>("last_name".equals("Family 4")).unwrap().len(), 20);
+    // Only the index map is read, since it isn't a chunk of the vault.
+    assert!(reads.get() - prefetched <= 1);
+    
+    let mut builder = PakBuilder::new().with_generation(1);
+    builder.pak(Person { first_name: "Other".to_string(), last_name: "Build".to_string(), age: 1 }).unwrap();
+    assert_eq!(builder.build_in_memory().unwrap().with_cache(1024).load_cache_state(&state).unwrap(), 0);
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&state).unwrap();
+}
+
+#[test]
+fn background_priority() {
+    use crate::{access::PakPreloadList, cache::PakPriority};
+    
+    let mut builder = PakBuilder::new();
+    for i in 0..10u32 {
+        builder.pak(Person { first_name: format!("Person {i}"), last_name: "Family".to_string(), age: i }).unwrap();
+    }
+    let path = std::env::temp_dir().join(format!("pak-priority-{}.pak", std::process::id()));
+    builder.build_file(&path).unwrap();
+    let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+    let pak = Pak::new(CountingSource { data : std::fs::read(&path).unwrap(), reads : reads.clone() }).unwrap();
+    let pointers = (0..10).map(|ordinal| pak.pointer_of(ordinal).unwrap().unwrap()).collect::<Vec<_>>();
+    let pak = pak.with_cache(pointers[0].size() + pointers[1].size() + 8).record_access();
+    
+    pak.get::<Person>(&pointers[0]).unwrap();
+    pak.get::<Person>(&pointers[1]).unwrap();
+    assert_eq!(pak.preload(&PakPreloadList::new(vec![2, 3, 4])).unwrap(), 3);
+    pak.with_priority(PakPriority::Background, |pak| pak.get::<Person>(&pointers[5])).unwrap();
+    let cached = reads.get();
+    pak.get::<Person>(&pointers[0]).unwrap();
+    pak.get::<Person>(&pointers[1]).unwrap();
+    assert_eq!(reads.get(), cached);
+    assert_eq!(pak.preload_list().unwrap().ordinals, [0, 1]);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn direct_source() {
+    use crate::direct::PakDirectSource;
+    
+    let path = std::env::temp_dir().join(format!("pak-direct-{}.pak", std::process::id()));
+    data_base_builder().build_file(&path).unwrap();
+    let pak = Pak::open_direct(&path).unwrap();
+    assert_eq!(pak.query::<(Person,)>("first_name".equals("John")).unwrap().len(), 2);
+    
+    let source = PakDirectSource::open_with_alignment(&path, 512).unwrap();
+    assert_eq!(source.alignment(), 512);
+    let pak = Pak::new(source).unwrap();
+    let (people, pets) = pak.query::<(Person, Pet)>("age".less_than(30)).unwrap();
+    assert_eq!((people.len(), pets.len()), (2, 3));
+    assert!(PakDirectSource::open_with_alignment(&path, 1000).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn aligned_items() {
+    use crate::direct::PakDirectSource;
+    
+    let mut builder = PakBuilder::new().with_alignment(512).unwrap();
+    let pointers = (0..20u32).map(|i| builder.pak(Person { first_name: format!("Person {i}"), last_name: "Aligned".to_string(), age: i }).unwrap()).collect::<Vec<_>>();
+    // Items start on a block of the file, and the vault starts 8 bytes in.
+    assert!(pointers.iter().all(|pointer| (pointer.offset() + 8) % 512 == 0));
+    let path = std::env::temp_dir().join(format!("pak-aligned-{}.pak", std::process::id()));
+    builder.build_file(&path).unwrap();
+    
+    let pak = Pak::new(PakDirectSource::open_with_alignment(&path, 512).unwrap()).unwrap();
+    assert_eq!(pak.query::<(Person,)>("last_name".equals("Aligned")).unwrap().len(), 20);
+    let person : Person = pak.get(&pointers[7]).unwrap();
+    assert_eq!(person.age, 7);
+    assert!(PakBuilder::new().with_alignment(1000).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn access_recording() {
+    use crate::{access::PakPreloadList, error::PakError};
+    
+    let path = std::env::temp_dir().join(format!("pak-access-{}.pak", std::process::id()));
+    let ordered = std::env::temp_dir().join(format!("pak-access-ordered-{}.pak", std::process::id()));
+    let mut builder = PakBuilder::new().with_block_checksums(128).with_manifest();
+    for i in 0..40u32 {
+        builder.pak(Person { first_name: format!("Person {i}"), last_name: format!("Family {}", i % 8), age: i }).unwrap();
+    }
+    builder.build_file(&path).unwrap();
+    
+    let pak = Pak::new_from_file(&path).unwrap().record_access();
+    let late = pak.pointer_of(35).unwrap().unwrap();
+    assert_eq!(pak.get::<Person>(&late).unwrap().age, 35);
+    assert_eq!(pak.query::<(Person,)>("last_name".equals("Family 2")).unwrap().len(), 5);
+    let list = pak.preload_list().unwrap();
+    assert_eq!(list.ordinals[0], 35);
+    assert_eq!(list.ordinals.iter().skip(1).copied().collect::<std::collections::BTreeSet<_>>(), [2, 10, 18, 26, 34].into());
+    assert_eq!(Pak::new_from_file(&path).unwrap().preload_list().unwrap(), PakPreloadList::default());
+    
+    pak.repack_ordered(&ordered, &list).unwrap();
+    let repacked = Pak::new_from_file(&ordered).unwrap().with_cache(64 * 1024);
+    assert_eq!(repacked.generation(), pak.generation() + 1);
+    assert_eq!(repacked.pointer_of(35).unwrap().unwrap().offset(), 0);
+    let offsets = list.ordinals.iter().map(|ordinal| repacked.pointer_of(*ordinal).unwrap().unwrap().offset()).collect::<Vec<_>>();
+    assert!(offsets.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(matches!(repacked.get::<Person>(&late), Err(PakError::StalePointer(..))));
+    assert_eq!(repacked.query::<(Person,)>("last_name".equals("Family 2")).unwrap().len(), 5);
+    assert_eq!(repacked.query::<(Person,)>("age".equals(17u32)).unwrap()[0].first_name, "Person 17");
+    assert_eq!(repacked.manifest_hashes().unwrap(), pak.manifest_hashes().unwrap());
+    assert_eq!(repacked.preload(&list).unwrap(), 6);
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&ordered).unwrap();
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn encrypted_reorder() {
+    use crate::{access::PakPreloadList, crypto::PakKey};
+    
+    let (key, unreleased) = (PakKey::new([5; 32]), PakKey::new([6; 32]));
+    let path = std::env::temp_dir().join(format!("pak-reorder-{}.pak", std::process::id()));
+    let mut builder = PakBuilder::new().with_encryption(&key).unwrap().with_item_key("unreleased", &unreleased).unwrap();
+    builder.pak(Person { first_name: "First".to_string(), last_name: "Sealed".to_string(), age: 1 }).unwrap();
+    builder.pak_encrypted(Person { first_name: "Second".to_string(), last_name: "Sealed".to_string(), age: 2 }, "unreleased").unwrap();
+    builder.pak(Person { first_name: "Third".to_string(), last_name: "Sealed".to_string(), age: 3 }).unwrap();
+    let pak = builder.build_in_memory().unwrap();
+    
+    pak.repack_ordered(&path, &PakPreloadList::new(vec![2, 1])).unwrap();
+    let repacked = Pak::open_with_key(&path, &key).unwrap().with_item_key("unreleased", &unreleased).unwrap();
+    assert_eq!(repacked.pointer_of(2).unwrap().unwrap().offset(), 0);
+    // The items are sealed at offsets other chunks were sealed at before, so every key gets a new salt.
+    assert_ne!(repacked.meta.encryption.as_ref().unwrap().salt, pak.meta.encryption.as_ref().unwrap().salt);
+    assert_ne!(repacked.meta.protection.as_ref().unwrap().keys[0].1.salt, pak.meta.protection.as_ref().unwrap().keys[0].1.salt);
+    let people = repacked.query::<(Person,)>("last_name".equals("Sealed")).unwrap();
+    assert_eq!(people.iter().map(|person| person.age).collect::<std::collections::BTreeSet<_>>(), [1, 2, 3].into());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn reordered_repack() {
+    let path = std::env::temp_dir().join(format!("pak-reordered-{}.pak", std::process::id()));
+    let mut builder = PakBuilder::new().with_manifest();
+    for i in 0..10u32 {
+        builder.pak(Person { first_name: format!("Person {i}"), last_name: "Ranked".to_string(), age: i }).unwrap();
+        builder.pak_no_search(format!("note {i}")).unwrap();
+    }
+    let pak = builder.build_in_memory().unwrap();
+    
+    pak.repack_reordered(&path, |pointer| match pointer.type_is_match::<String>() {
+        true => 0,
+        false => 100 - pak.get::<Person>(pointer).unwrap().age as u64,
+    }).unwrap();
+    let repacked = Pak::new_from_file(&path).unwrap();
+    let mut by_offset = (0..20u32).map(|ordinal| repacked.pointer_of(ordinal).unwrap().unwrap()).collect::<Vec<_>>();
+    by_offset.sort_by_key(|pointer| pointer.offset());
+    assert!(by_offset[..10].iter().all(|pointer| pointer.type_is_match::<String>()));
+    assert_eq!(repacked.get::<String>(&by_offset[0]).unwrap(), "note 0");
+    assert_eq!(by_offset[10..].iter().map(|pointer| repacked.get::<Person>(pointer).unwrap().age).collect::<Vec<_>>(), (0..10).rev().collect::<Vec<_>>());
+    assert_eq!(repacked.query::<(Person,)>("age".equals(4u32)).unwrap()[0].first_name, "Person 4");
+    assert_eq!(repacked.manifest_hashes().unwrap(), pak.manifest_hashes().unwrap());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn path_addressing() {
+    use crate::{error::PakError, path::PakBlob};
+    
+    let mut builder = PakBuilder::new();
+    builder.pak_blob("textures/hero.png", vec![1, 2, 3, 4]).unwrap();
+    builder.pak_with_path("./people\\bob.person", Person { first_name: "Bob".to_string(), last_name: "Smith".to_string(), age: 30 }).unwrap();
+    assert!(matches!(builder.pak_blob("textures//hero.png", vec![5]), Err(PakError::DuplicatePath(path)) if path == "textures/hero.png"));
+    assert!(matches!(builder.pak_blob("../outside", vec![5]), Err(PakError::InvalidPath(_))));
+    let pak = builder.build_in_memory().unwrap();
+    
+    assert_eq!(pak.by_path::<PakBlob>("/textures/hero.png").unwrap(), Some(PakBlob(vec![1, 2, 3, 4])));
+    assert_eq!(pak.pointer_by_path("textures/hero.png").unwrap().unwrap().size(), 4);
+    assert_eq!(pak.by_path::<Person>("people/bob.person").unwrap().unwrap().first_name, "Bob");
+    assert_eq!(pak.query::<(Person,)>("age".equals(30u32)).unwrap().len(), 1);
+    assert_eq!(pak.by_path::<PakBlob>("textures/missing.png").unwrap(), None);
+    assert_eq!(pak.paths().unwrap().into_iter().map(|(path, _)| path).collect::<Vec<_>>(), ["people/bob.person", "textures/hero.png"]);
+}
+
+#[cfg(feature = "fuse")]
+#[test]
+fn mounted_filesystem() {
+    use crate::vfs::{PakFileKind, PakFileSystem, PAK_ROOT_INODE};
+    
+    let mut builder = PakBuilder::new();
+    builder.pak_blob("textures/hero.png", b"hero pixels".to_vec()).unwrap();
+    builder.pak_blob("textures/ui/button.png", b"button".to_vec()).unwrap();
+    builder.pak_blob("readme.txt", b"hello".to_vec()).unwrap();
+    let pak = builder.build_in_memory().unwrap();
+    let fs = PakFileSystem::new(&pak).unwrap();
+    
+    let root = fs.readdir(PAK_ROOT_INODE).unwrap();
+    assert_eq!(root.iter().map(|(_, _, name)| name.as_str()).collect::<Vec<_>>(), [".", "..", "readme.txt", "textures"]);
+    let textures = fs.lookup(PAK_ROOT_INODE, "textures").unwrap();
+    assert_eq!(textures.kind, PakFileKind::Directory);
+    let hero = fs.lookup(textures.inode, "hero.png").unwrap();
+    assert_eq!((hero.kind, hero.size), (PakFileKind::File, 11));
+    assert_eq!(fs.read(hero.inode, 5, 100).unwrap().unwrap(), b"pixels");
+    assert_eq!(fs.read(hero.inode, 20, 4).unwrap().unwrap(), b"");
+    assert_eq!(fs.read(textures.inode, 0, 4).unwrap(), None);
+    let ui = fs.lookup(textures.inode, "ui").unwrap();
+    assert_eq!(fs.path(fs.lookup(ui.inode, "button.png").unwrap().inode).unwrap(), "textures/ui/button.png");
+    assert_eq!(fs.lookup(PAK_ROOT_INODE, "missing"), None);
+    
+    // A file can't also be the directory of another path.
+    let mut builder = PakBuilder::new();
+    builder.pak_blob("textures", b"not a directory".to_vec()).unwrap();
+    builder.pak_blob("textures/hero.png", b"hero pixels".to_vec()).unwrap();
+    let pak = builder.build_in_memory().unwrap();
+    assert!(matches!(PakFileSystem::new(&pak), Err(crate::error::PakError::PathConflict(path)) if path == "textures"));
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn import_zip() {
+    use std::io::{Cursor, Write};
+    use crate::path::{PakBlob, PAK_MTIME_KEY, PAK_SIZE_KEY};
+    
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default().last_modified_time(zip::DateTime::from_date_and_time(2024, 3, 1, 12, 30, 0).unwrap());
+    writer.add_directory("textures/", options).unwrap();
+    writer.start_file("textures/hero.png", options).unwrap();
+    writer.write_all(&[7; 300]).unwrap();
+    writer.start_file("readme.txt", options.compression_method(zip::CompressionMethod::Stored)).unwrap();
+    writer.write_all(b"hello").unwrap();
+    let archive = writer.finish().unwrap().into_inner();
+    
+    let mut builder = PakBuilder::new();
+    assert_eq!(builder.import_zip(Cursor::new(archive)).unwrap(), 2);
+    let pak = builder.build_in_memory().unwrap();
+    assert_eq!(pak.by_path::<PakBlob>("textures/hero.png").unwrap(), Some(PakBlob(vec![7; 300])));
+    assert_eq!(pak.by_path::<PakBlob>("readme.txt").unwrap(), Some(PakBlob(b"hello".to_vec())));
+    assert_eq!(pak.query::<(PakBlob,)>(PAK_SIZE_KEY.greater_than(100u64)).unwrap().len(), 1);
+    assert_eq!(pak.query::<(PakBlob,)>(PAK_MTIME_KEY.equals(1_709_296_200u64)).unwrap().len(), 2);
+}
+
+#[cfg(feature = "tar")]
+#[test]
+fn import_tar() {
+    use crate::path::{PakBlob, PAK_MTIME_KEY, PAK_SIZE_KEY};
+    
+    let mut builder = tar::Builder::new(Vec::new());
+    for (path, contents, mtime) in [("sounds/step.wav", &b"step"[..], 100u64), ("sounds/jump.wav", &b"jumping"[..], 200)] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mtime(mtime);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, path, contents).unwrap();
+    }
+    let archive = builder.into_inner().unwrap();
+    
+    let mut builder = PakBuilder::new();
+    assert_eq!(builder.import_tar(archive.as_slice()).unwrap(), 2);
+    let pak = builder.build_in_memory().unwrap();
+    assert_eq!(pak.by_path::<PakBlob>("sounds/jump.wav").unwrap(), Some(PakBlob(b"jumping".to_vec())));
+    assert_eq!(pak.query::<(PakBlob,)>(PAK_MTIME_KEY.greater_than(150u64)).unwrap()[0], PakBlob(b"jumping".to_vec()));
+    assert_eq!(pak.query::<(PakBlob,)>(PAK_SIZE_KEY.equals(4u64)).unwrap().len(), 1);
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn export_zip() {
+    use std::io::{Cursor, Read};
+    use crate::path::PakBlob;
+    
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let time = zip::DateTime::from_date_and_time(2023, 12, 31, 23, 59, 58).unwrap();
+    writer.start_file("maps/level1.map", zip::write::SimpleFileOptions::default().last_modified_time(time)).unwrap();
+    std::io::Write::write_all(&mut writer, b"level one").unwrap();
+    let archive = writer.finish().unwrap().into_inner();
+    
+    let mut builder = PakBuilder::new();
+    builder.import_zip(Cursor::new(archive)).unwrap();
+    builder.pak(Person { first_name: "Exported".to_string(), last_name: "Person".to_string(), age: 9 }).unwrap();
+    builder.pak_blob("skipped.txt", b"skip".to_vec()).unwrap();
+    let pak = builder.build_in_memory().unwrap();
+    
+    let mut out = Cursor::new(Vec::new());
+    assert_eq!(pak.export_zip(&mut out, |pointer| pak.get::<PakBlob>(pointer).map_or(true, |blob| blob.0 != b"skip")).unwrap(), 2);
+    let mut archive = zip::ZipArchive::new(out).unwrap();
+    assert_eq!(archive.len(), 2);
+    let mut level = archive.by_name("maps/level1.map").unwrap();
+    assert_eq!(level.last_modified(), Some(time));
+    let mut contents = String::new();
+    level.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "level one");
+    drop(level);
+    let mut person = Vec::new();
+    archive.by_name("1.bin").unwrap().read_to_end(&mut person).unwrap();
+    assert_eq!(bincode::deserialize::<Person>(&person).unwrap().first_name, "Exported");
+}
+
+#[cfg(feature = "python")]
+#[test]
+fn python_module() {
+    use pyo3::{ffi::c_str, prelude::*, types::PyDict};
+    
+    let path = std::env::temp_dir().join(format!("pak-python-{}.pak", std::process::id()));
+    data_base_builder().build_file(&path).unwrap();
+    Python::initialize();
+    Python::attach(|py| {
+        let module = PyModule::new(py, "pak_db").unwrap();
+        crate::python::pak_db_module(&module).unwrap();
+        let locals = PyDict::new(py);
+        locals.set_item("pak_db", module).unwrap();
+        locals.set_item("path", path.to_str().unwrap()).unwrap();
+        py.run(c_str!(r#"
+pak = pak_db.open(path)
+johns = pak.query("first_name == 'John'")
+assert len(johns) == 2, johns
+assert johns[0].type_name.endswith("Person")
+assert len(pak.get_bytes(johns[0])) == johns[0].size
+assert pak.metadata()["item_count"] == len(pak)
+try:
+    pak.query("age >")
+    raise AssertionError("the query should have failed")
+except pak_db.PakException:
+    pass
+"#), None, Some(&locals)).unwrap();
+    });
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "serve")]
+#[test]
+fn remote_queries() {
+    use std::net::TcpListener;
+    use crate::{error::PakError, query::Query, serve::{self, RemotePak}};
+    
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+        let pak = build_data_base();
+        let (stream, _) = listener.accept().unwrap();
+        serve::serve_connection(&pak, stream).unwrap();
+    });
+    
+    let mut remote = RemotePak::connect(address).unwrap();
+    let people = remote.query::<Person>(Query::where_("first_name").eq("John")).unwrap();
+    assert_eq!(people.len(), 2);
+    let pointers = remote.query_pointers(Query::where_("age").lt(30u32)).unwrap();
+    assert_eq!(pointers.len(), 5);
+    assert!(pointers.windows(2).all(|pair| pair[0].offset() < pair[1].offset()));
+    let pet = pointers.iter().find(|pointer| pointer.type_is_match::<Pet>()).unwrap();
+    assert!(remote.get::<Pet>(pet).is_ok());
+    assert!(remote.get::<Person>(pet).is_err());
+    assert!(matches!(remote.query_pointers(Query::where_("age").eq("thirty")), Err(PakError::RemoteError(_))));
+    // Only the items the pak holds can be read, not the index structures after them or anything past the vault.
+    assert!(matches!(remote.read_bytes(&PakPointer::new_untyped(pet.offset(), pet.size() + 1)), Err(PakError::RemoteError(_))));
+    assert!(matches!(remote.read_bytes(&PakPointer::new_untyped(1 << 40, 1 << 40)), Err(PakError::RemoteError(_))));
+    assert_eq!(remote.read_bytes(&PakPointer::new_untyped(pet.offset(), pet.size())).unwrap().len() as u64, pet.size());
+    drop(remote);
+    server.join().unwrap();
+}
+
+#[test]
+fn result_sets() {
+    use crate::query::Query;
+    
+    let pak = build_data_base();
+    let does = pak.results(Query::where_("last_name").eq("Doe")).unwrap();
+    assert_eq!(does.len(), 2);
+    let narrowed = does.clone().and(Query::where_("age").lt(28u32)).unwrap();
+    assert_eq!(narrowed.items::<Person>().unwrap().iter().map(|person| person.first_name.as_str()).collect::<Vec<_>>(), ["Jane"]);
+    assert_eq!(does.len(), 2);
+    
+    let older = pak.results(Query::where_("age").gt(26u32)).unwrap().and(Query::where_("first_name").ge("")).unwrap();
+    let ages = |set : &crate::results::PakResultSet| set.items::<Person>().unwrap().into_iter().map(|person| person.age).collect::<Vec<_>>();
+    assert_eq!(ages(&older.clone().order_by("age")), [28, 30, 35, 40, 45]);
+    assert_eq!(ages(&older.clone().order_by_descending("age")), [45, 40, 35, 30, 28]);
+    assert_eq!(ages(&older.clone().order_by("age").page(1, 2)), [35, 40]);
+    assert_eq!(ages(&older.clone().order_by("age").page(2, 2)), [45]);
+    assert!(older.order_by("age").page(3, 2).pointers().unwrap().is_empty());
+}
+
+#[test]
+fn ordered_results() {
+    let mut builder = PakBuilder::new();
+    for i in 0..50u32 {
+        builder.pak(Person { first_name: format!("Person {i}"), last_name: "Family".to_string(), age: 49 - i }).unwrap();
+    }
+    let pak = builder.build_in_memory().unwrap().with_ordered_results();
+    let ages = pak.query::<(Person,)>("last_name".equals("Family")).unwrap().into_iter().map(|person| person.age).collect::<Vec<_>>();
+    assert_eq!(ages, (0..50u32).rev().collect::<Vec<_>>());
+    let (people, pets) = pak.query::<(Person, Pet)>("age".less_than(10u32)).unwrap();
+    assert_eq!(people.iter().map(|person| person.age).collect::<Vec<_>>(), (0..10u32).rev().collect::<Vec<_>>());
+    assert!(pets.is_empty());
+}
+
+#[test]
+fn type_mismatch_pointer() {
+    use crate::error::PakError;
+    
+    let pak = build_data_base();
+    let pointer = pak.pointer_of(0).unwrap().unwrap();
+    assert!(pointer.type_is_match::<Person>());
+    match pak.get::<Pet>(&pointer) {
+        Err(PakError::TypeMismatchError { pointer : found, expected, key : None }) => {
+            assert_eq!(found.offset(), pointer.offset());
+            assert_eq!(expected, std::any::type_name::<Pet>());
+        },
+        other => panic!("expected a type mismatch, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn error_categories() {
+    use crate::{budget::PakBudget, error::{PakError, PakErrorCategory}};
+    
+    let pak = build_data_base();
+    let error = pak.get::<Pet>(&pak.pointer_of(0).unwrap().unwrap()).unwrap_err();
+    assert!(error.is_type() && !error.is_corruption());
+    let error = pak.query_with_budget::<(Person,)>("age".less_than(30u32), PakBudget::new().with_max_reads(1)).unwrap_err();
+    assert_eq!(error.category(), PakErrorCategory::Limit);
+    assert!(crate::query::parse("age >").unwrap_err().is_query());
+    assert!(Pak::new(std::io::Cursor::new(vec![0u8; 64])).err().unwrap().is_corruption());
+    assert!(PakError::from(std::io::Error::from(std::io::ErrorKind::NotFound)).is_io());
+    assert!(PakError::ChecksumMismatch(0, 3).is_corruption());
+}
+
+#[test]
+fn large_offsets() {
+    use std::io::Cursor;
+    use crate::{error::PakError, item::PakItemDeserialize, pointer::{byte_range, to_usize}, PakSource};
+    
+    let beyond_4gb = 5u64 << 30;
+    assert_eq!(to_usize(beyond_4gb).is_ok(), usize::BITS > 32);
+    assert!(matches!(byte_range(u64::MAX - 4, 16), Err(PakError::OffsetOverflow(_))));
+    
+    let bytes = bincode::serialize(&42u32).unwrap();
+    assert_eq!(u32::from_pak(&bytes, &PakPointer::new_untyped(0, 4)).unwrap(), 42);
+    assert!(u32::from_pak(&bytes, &PakPointer::new_untyped(beyond_4gb, 4)).is_err());
+    assert!(u32::from_pak(&bytes, &PakPointer::new_untyped(u64::MAX - 1, 4)).is_err());
+    
+    let pak = build_data_base();
+    let mut source = Cursor::new(vec![0u8; 64]);
+    assert!(source.read(&PakPointer::new_untyped(beyond_4gb, 16), 0).is_err());
+    assert!(matches!(source.read(&PakPointer::new_untyped(u64::MAX - 4, 16), 8), Err(PakError::OffsetOverflow(_))));
+    assert!(matches!(pak.read_bytes(&PakPointer::new_untyped(beyond_4gb, 16)), Err(PakError::PointerOutOfBounds(..))));
+    assert!(matches!(pak.read_bytes(&PakPointer::new_untyped(8, u64::MAX)), Err(PakError::PointerOutOfBounds(..))));
+    
+    // The same vault with 5 GiB of zeros in front of it, so its items sit past 4 GiB. Offsets stay u64 all the way to the source, so they
+    // read back even where usize only has 32 bits.
+    let mut builder = PakBuilder::new();
+    builder.pak(Person { first_name: "Far".to_string(), last_name: "Away".to_string(), age: 5 }).unwrap();
+    let data = builder.build_internal().unwrap().0;
+    let pak = Pak::new(Cursor::new(data.clone())).unwrap();
+    let mut layout = pak.layout.clone();
+    layout.vault_len += beyond_4gb;
+    let source = GapSource { data, vault_start : pak.get_vault_start(), gap : beyond_4gb };
+    let far = Pak::from_parts(layout, pak.meta.clone(), source);
+    let near = pak.pointer_of(0).unwrap().unwrap();
+    let pointer = PakPointer::new_untyped(near.offset() + beyond_4gb, near.size());
+    let mut bytes = Vec::new();
+    std::io::Read::read_to_end(&mut far.window(&pointer), &mut bytes).unwrap();
+    assert_eq!(bytes, pak.read_bytes(&near).unwrap());
+    assert_eq!(far.read_bytes(&pointer).unwrap(), bytes);
+    assert!(matches!(far.read_bytes(&PakPointer::new_untyped(far.layout.vault_len, 1)), Err(PakError::PointerOutOfBounds(..))));
+}
+
+/// A source whose vault starts with `gap` bytes of zeros, which are never held in memory.
+struct GapSource {
+    data : Vec<u8>,
+    vault_start : u64,
+    gap : u64,
+}
+
+impl crate::PakSource for GapSource {
+    fn read(&mut self, pointer : &PakPointer, offset : u64) -> PakResult<Vec<u8>> {
+        let start = offset + pointer.offset();
+        if start < self.vault_start + self.gap { return Ok(vec![0; crate::pointer::to_usize(pointer.size())?]) }
+        Ok(self.data[crate::pointer::byte_range(start - self.gap, pointer.size())?].to_vec())
+    }
+}
+
+#[test]
+fn inline_items() {
+    let mut builder = PakBuilder::new().with_inline_items(64);
+    for i in 0..30u32 {
+        builder.pak(Person { first_name: format!("P{i}"), last_name: "F".to_string(), age: i }).unwrap();
+    }
+    builder.pak_blob("large.bin", vec![7u8; 5000]).unwrap();
+    let classes = builder.size_classes();
+    assert_eq!(classes.iter().map(|class| class.items).sum::<u64>(), 31);
+    assert_eq!(classes.iter().find(|class| class.max_size == 64).unwrap().items, 30);
+    let path = std::env::temp_dir().join(format!("pak-inline-{}.pak", std::process::id()));
+    builder.build_file(&path).unwrap();
+    
+    let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+    let pak = Pak::new(CountingSource { data : std::fs::read(&path).unwrap(), reads : reads.clone() }).unwrap();
+    assert_eq!(pak.size_classes().unwrap(), classes);
+    let pointers = (0..31).map(|ordinal| pak.pointer_of(ordinal).unwrap().unwrap()).collect::<Vec<_>>();
+    
+    // Items only come inlined with the tree pages that hold them, so nothing is inlined before a page is read.
+    let loaded = reads.get();
+    assert_eq!(pak.get::<Person>(&pointers[0]).unwrap().age, 0);
+    assert_eq!(reads.get(), loaded + 1);
+    
+    // The query reads the index map, the tree meta and its single page, and every item it matches comes with the page.
+    let loaded = reads.get();
+    assert_eq!(pak.query::<(Person, )>("last_name".equals("F")).unwrap().len(), 30);
+    assert_eq!(reads.get(), loaded + 3);
+    for (age, pointer) in pointers[..30].iter().enumerate() {
+        assert_eq!(pak.get::<Person>(pointer).unwrap().age, age as u32);
+    }
+    assert_eq!(reads.get(), loaded + 3);
+    assert_eq!(pak.read_bytes(&pointers[30]).unwrap().len(), 5000);
+    assert_eq!(reads.get(), loaded + 4);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn localization() {
+    use crate::error::PakError;
+    
+    let mut builder = PakBuilder::new();
+    builder.pak_l10n("menu.start", "en", "Start").unwrap();
+    builder.pak_l10n("menu.start", "fr", "Commencer").unwrap();
+    builder.pak_l10n("menu.quit", "en", "Quit").unwrap();
+    builder.pak_l10n("menu.quit", "fr-CA", "Quitter").unwrap();
+    assert!(matches!(builder.pak_l10n("menu.start", "fr", "Démarrer"), Err(PakError::DuplicateLocalization(_, _))));
+    let pak = builder.build_in_memory().unwrap();
+    
+    let chain = ["fr-CA", "fr", "en"];
+    assert_eq!(pak.localize("menu.start", &chain).unwrap().as_deref(), Some("Commencer"));
+    assert_eq!(pak.localize("menu.quit", &chain).unwrap().as_deref(), Some("Quitter"));
+    assert_eq!(pak.localize("menu.quit", &["fr", "en"]).unwrap().as_deref(), Some("Quit"));
+    assert_eq!(pak.localize("menu.options", &chain).unwrap(), None);
+    assert_eq!(pak.languages().unwrap(), ["en", "fr", "fr-CA"].map(str::to_string).into());
+    assert_eq!(build_data_base().localize("menu.start", &chain).unwrap(), None);
+}
+
+#[test]
+fn blob_sidecars() {
+    use crate::path::PakBlob;
+    
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct TextureMeta {
+        width : u32,
+        height : u32,
+        format : String,
+    }
+    
+    let mut builder = PakBuilder::new();
+    let meta = TextureMeta { width : 64, height : 32, format : "rgba8".to_string() };
+    builder.pak_blob_with_meta("textures/grass.png", vec![1u8; 300], meta).unwrap();
+    builder.pak_blob("textures/plain.png", vec![2u8; 10]).unwrap();
+    assert!(builder.pak_blob_with_meta("textures/grass.png", vec![0u8; 1], 0u32).is_err());
+    let path = std::env::temp_dir().join(format!("pak-sidecar-{}.pak", std::process::id()));
+    builder.build_file(&path).unwrap();
+    
+    let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+    let pak = Pak::new(CountingSource { data : std::fs::read(&path).unwrap(), reads : reads.clone() }).unwrap();
+    let (blob, meta) = pak.blob_with_meta::<TextureMeta>("textures//grass.png").unwrap().unwrap();
+    assert_eq!(blob, PakBlob(vec![1u8; 300]));
+    assert_eq!((meta.width, meta.height, meta.format.as_str()), (64, 32, "rgba8"));
+    
+    assert!(reads.get() > 0);
+    
+    assert!(pak.blob_with_meta::<TextureMeta>("textures/plain.png").unwrap().is_none());
+    assert!(pak.blob_meta::<TextureMeta>("textures/plain.png").unwrap().is_none());
+    assert!(pak.blob_with_meta::<u64>("textures/grass.png").is_err());
+    std::fs::remove_file(&path).unwrap();
+    
+    // The blob and its metadata are each checked against their own checksum.
+    let mut builder = PakBuilder::new().with_item_checksums();
+    builder.pak_blob_with_meta("textures/grass.png", b"green pixels".to_vec(), TextureMeta { width : 1, height : 1, format : "r8".to_string() }).unwrap();
+    let (mut data, _, _) = builder.build_internal().unwrap();
+    let at = data.windows(12).position(|window| window == b"green pixels").unwrap();
+    data[at] ^= 0x20;
+    let pak = Pak::new(std::io::Cursor::new(data)).unwrap();
+    assert!(matches!(pak.blob_with_meta::<TextureMeta>("textures/grass.png"), Err(crate::error::PakError::ItemChecksumMismatch(_))));
+    assert_eq!(pak.blob_meta::<TextureMeta>("textures/grass.png").unwrap().unwrap().format, "r8");
+}
+
+#[test]
+fn content_addressing() {
+    use crate::{content::content_hash, item::PakItemSerialize, path::PakBlob};
+    
+    let mut builder = PakBuilder::new().with_content_addressing();
+    let john = Person { first_name: "John".to_string(), last_name: "Doe".to_string(), age: 30 };
+    let first = builder.pak(john.clone()).unwrap();
+    let second = builder.pak_with_id(7u64, john.clone()).unwrap();
+    assert_eq!(first.offset(), second.offset());
+    builder.pak_blob("a.txt", vec![1u8, 2, 3]).unwrap();
+    builder.pak_blob("b.txt", vec![1u8, 2, 3]).unwrap();
+    builder.pak(Person { first_name: "Jane".to_string(), last_name: "Doe".to_string(), age: 25 }).unwrap();
+    assert_eq!(builder.len(), 3);
+    let pak = builder.build_in_memory().unwrap();
+    
+    let hash = content_hash(&john.into_bytes().unwrap());
+    let pointer = pak.by_hash(&hash).unwrap().unwrap();
+    assert_eq!(pak.get::<Person>(&pointer).unwrap().first_name, "John");
+    assert_eq!(pak.by_id::<Person>(7u64).unwrap().unwrap().first_name, "John");
+    assert_eq!(pak.query::<(Person,)>("last_name".equals("Doe")).unwrap().len(), 2);
+    assert_eq!(pak.pointer_by_path("a.txt").unwrap().unwrap().offset(), pak.pointer_by_path("b.txt").unwrap().unwrap().offset());
+    assert!(pak.by_hash(&content_hash(&[1u8, 2, 3])).unwrap().unwrap().type_is_match::<PakBlob>());
+    assert!(pak.by_hash(&content_hash(b"missing")).unwrap().is_none());
+    
+    let hashes = pak.content_hashes().unwrap();
+    assert_eq!(hashes.len(), 3);
+    assert!(hashes.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    assert!(build_data_base().content_hashes().unwrap().is_empty());
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel_queries() {
+    use std::sync::{atomic::{AtomicU32, Ordering}, Arc};
+    use crate::query::Query;
+    
+    let path = std::env::temp_dir().join(format!("pak-parallel-{}.pak", std::process::id()));
+    data_base_builder().build_file(&path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    let serial = Pak::new(std::io::Cursor::new(bytes.clone())).unwrap().with_ordered_results();
+    let pak = Pak::open_parallel(&path).unwrap().with_ordered_results();
+    
+    let queries = [
+        Query::where_("age").lt(26u32).or().where_("first_name").eq("John").and().where_("last_name").eq("Doe").build(),
+        Query::where_("last_name").eq("Doe").and().group(Query::where_("first_name").eq("Jane").or().where_("age").ge(30u32)).build(),
+        Query::where_("last_name").eq("Smith").and().where_("age").gt(100u32).build(),
+    ];
+    for query in queries {
+        assert_eq!(pak.query::<(Person,)>(query.clone()).unwrap(), serial.query::<(Person,)>(query).unwrap());
+    }
+    assert_eq!(pak.query::<(Person,)>("age".less_than(30u32) & "last_name".equals("Doe")).unwrap().len(), 1);
+    std::fs::remove_file(&path).unwrap();
+    
+    let forks = Arc::new(AtomicU32::new(0));
+    let pak = Pak::new(std::io::Cursor::new(bytes.clone())).unwrap().with_parallel_sources({
+        let forks = forks.clone();
+        move || {
+            forks.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(std::io::Cursor::new(bytes.clone())))
+        }
+    });
+    assert_eq!(pak.query::<(Person,)>("first_name".equals("Jane") | "first_name".equals("Bob")).unwrap().len(), 2);
+    assert_eq!(forks.load(Ordering::SeqCst), 1);
+    pak.with_budget(crate::budget::PakBudget::default(), |pak| pak.query::<(Person,)>("first_name".equals("Jane") | "age".equals(30u32))).unwrap();
+    assert_eq!(forks.load(Ordering::SeqCst), 1);
+    
+    // However many branches a union has, the workers are capped, and each one opens a single source for all the branches it runs.
+    let names = ["Jane", "Bob", "John", "Alice", "Jeff", "Joe"];
+    let any = crate::query::PakQueryNode::Any(names.iter().map(|name| crate::query::PakQuery::equals("first_name", *name).into()).collect());
+    let pak = pak.with_ordered_results().with_parallel_threads(2);
+    forks.store(0, Ordering::SeqCst);
+    assert_eq!(pak.query::<(Person,)>(any.clone()).unwrap(), serial.query::<(Person,)>(any.clone()).unwrap());
+    assert_eq!(forks.load(Ordering::SeqCst), 2);
+    let pak = pak.with_parallel_threads(0);
+    pak.query::<(Person,)>(any).unwrap();
+    assert_eq!(forks.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn probing_intersections() {
+    use crate::{btree::PakDuplicateKeys, query::{PakQueryExpression, Query}, testing::NaiveStore};
+    
+    let mut builder = PakBuilder::new().with_duplicate_keys(PakDuplicateKeys::Overflow(8));
+    let mut store = NaiveStore::new();
+    for i in 0..400u32 {
+        let person = Person { first_name: format!("Person {i}"), last_name: if i % 100 == 0 { "Rare".to_string() } else { "Common".to_string() }, age: i % 40 };
+        store.pak(&mut builder, person).unwrap();
+    }
+    let path = std::env::temp_dir().join(format!("pak-probe-{}.pak", std::process::id()));
+    builder.build_file(&path).unwrap();
+    let data = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+    let pak = Pak::new(CountingSource { data, reads : reads.clone() }).unwrap();
+    
+    let count = |query : &dyn PakQueryExpression| {
+        let before = reads.get();
+        let found = query.execute(&pak).unwrap().len();
+        (found, reads.get() - before)
+    };
+    let (common, full) = count(&"last_name".equals("Common"));
+    assert_eq!(common, 396);
+    let (found, probed) = count(&("last_name".equals("Common") & "first_name".equals("Person 7")));
+    assert_eq!(found, 1);
+    assert!(probed < full, "probing read {probed} chunks, reading the whole list read {full}");
+    assert_eq!(count(&("first_name".equals("Nobody") & "last_name".equals("Common"))).0, 0);
+    
+    let queries = [
+        Query::where_("last_name").eq("Common").and().where_("age").lt(3u32).build(),
+        Query::where_("last_name").eq("Rare").and().group(Query::where_("age").eq(0u32).or().where_("first_name").eq("Person 7")).build(),
+        Query::where_("age").ge(38u32).and().where_("last_name").eq("Common").and().where_("first_name").eq("Person 39").build(),
+    ];
+    for query in queries {
+        let found = query.execute(&pak).unwrap().into_iter().map(|pointer| pointer.offset()).collect::<HashSet<_>>();
+        let expected = store.query(&query).into_iter().map(|ordinal| pak.pointer_of(ordinal).unwrap().unwrap().offset()).collect::<HashSet<_>>();
+        assert_eq!(found, expected, "{query}");
+    }
+    
+    let index = pak.index("last_name").unwrap();
+    let rare = pak.pointer_of(100).unwrap().unwrap();
+    assert!(index.contains("Rare", &rare).unwrap());
+    assert!(!index.contains("Common", &rare).unwrap());
+    assert!(index.contains("Common", &pak.pointer_of(399).unwrap().unwrap()).unwrap());
+}
+
+#[test]
+fn posting_skips() {
+    use crate::btree::PakDuplicateKeys;
+    
+    let mut builder = PakBuilder::new().with_duplicate_keys(PakDuplicateKeys::Overflow(8));
+    for i in 0..400u32 {
+        builder.pak(Person { first_name: format!("Person {i}"), last_name: "Doe".to_string(), age: i % 2 }).unwrap();
+    }
+    let path = std::env::temp_dir().join(format!("pak-skips-{}.pak", std::process::id()));
+    builder.build_file(&path).unwrap();
+    let data = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+    let pak = Pak::new(CountingSource { data, reads : reads.clone() }).unwrap();
+    
+    pak.index("age").unwrap().scan(|value, postings| {
+        assert_eq!(postings.len(), 200);
+        let even = *value == PakValue::from(0u32);
+        let before = reads.get();
+        assert!(postings.contains_ordinal(if even { 398 } else { 399 }).unwrap());
+        assert!(!postings.contains_ordinal(if even { 399 } else { 398 }).unwrap());
+        assert!(reads.get() - before <= 2);
+        
+        let before = reads.get();
+        let found = postings.intersect_ordinals(&[0, 1, 2, 3, 200, 201, 399]).unwrap();
+        assert_eq!(found, if even { vec![0, 2, 200] } else { vec![1, 3, 201, 399] });
+        assert!(reads.get() - before <= 3, "{} chunks were read", reads.get() - before);
+        Ok(true)
+    }).unwrap();
+}
+
+#[test]
+fn filtered_scans() {
+    use crate::query::PakQueryExpression;
+    
+    let pak = build_data_base().with_ordered_results();
+    let even = pak.scan(|person : &Person| person.age.is_multiple_of(2)).unwrap();
+    assert!(!even.is_empty() && even.iter().all(|person| person.age.is_multiple_of(2)));
+    assert_eq!(even.len(), pak.query::<(Person,)>("age".greater_than_or_equal(0u32)).unwrap().iter().filter(|person| person.age.is_multiple_of(2)).count());
+    
+    let does = pak.query::<(Person,)>("last_name".equals("Doe").filter(|person : &Person| person.first_name.starts_with('J'))).unwrap();
+    assert_eq!(does.len(), 2);
+    let john = pak.query::<(Person,)>("last_name".equals("Doe").filter(|person : &Person| person.age > 26) & "first_name".equals("John")).unwrap();
+    assert_eq!(john.len(), 1);
+    let either = pak.query::<(Person,)>("last_name".equals("Smith") | "last_name".equals("Doe").filter(|person : &Person| person.age < 26)).unwrap();
+    assert_eq!(either.iter().map(|person| person.first_name.as_str()).collect::<Vec<_>>(), vec!["Jane", "Alice"]);
+    assert!("last_name".equals("Doe").filter(|person : &Person| person.age > 100).to_node().is_none());
+    
+    // The naive store checks the predicate against the items it was given, so it agrees with the pak on filtered queries.
+    let mut store = crate::testing::NaiveStore::new();
+    let mut builder = PakBuilder::new();
+    for i in 0..40u32 {
+        store.pak(&mut builder, Person { first_name: format!("P{i}"), last_name: ["Doe", "Smith"][i as usize % 2].to_string(), age: i }).unwrap();
+    }
+    store.push_indices(vec![PakIndex::new("last_name", "Doe")]);
+    let pak = builder.build_in_memory().unwrap();
+    let filtered = "last_name".equals("Doe").filter(|person : &Person| person.age.is_multiple_of(3));
+    assert_eq!(store.query(&filtered).len(), 7);
+    crate::testing::assert_agrees(&pak, &store, &filtered);
+    crate::testing::assert_agrees(&pak, &store, &("age".less_than(20u32).filter(|person : &Person| person.first_name.ends_with('1')) | "last_name".equals("Smith")));
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn index_audit() {
+    use crate::{audit::PakIndexAudit, error::PakError, schema::PakSchema};
+    use derived::Monster;
+    
+    #[derive(PakSchema)]
+    #[pak_schema(Person(first_name, last_name, age))]
+    struct AuditSchema;
+    
+    assert_eq!(Monster::index_keys(), &["name", "hp"]);
+    let monster = || Monster { name: "Slime".to_string(), health: 5, boss: false };
+    let john = || Person { first_name: "John".to_string(), last_name: "Doe".to_string(), age: 30 };
+    
+    let mut builder = PakBuilder::new().with_schema::<AuditSchema>().with_index_audit(PakIndexAudit::Warn).audit::<Monster>();
+    builder.pak(monster()).unwrap();
+    builder.pak(john()).unwrap();
+    builder.pak_blob_with_meta("slime.png", vec![0u8; 4], john()).unwrap();
+    assert!(builder.index_gaps().is_empty());
+    let forgotten = builder.pak_no_search(monster()).unwrap();
+    builder.pak_no_search(john()).unwrap();
+    builder.pak_no_search(42u32).unwrap();
+    let gaps = builder.index_gaps();
+    assert_eq!(gaps.len(), 2);
+    assert_eq!(gaps[0].pointer, forgotten);
+    assert_eq!(gaps[0].missing, vec!["name", "hp"]);
+    assert_eq!(gaps[1].missing, vec!["age", "first_name", "last_name"]);
+    
+    let mut builder = PakBuilder::new().audit::<Monster>().with_index_audit(PakIndexAudit::Deny);
+    builder.pak(monster()).unwrap();
+    let error = builder.pak_no_search(monster()).err().unwrap();
+    assert!(error.is_build() && matches!(error, PakError::MissingIndices(_, keys) if keys == "name, hp"));
+    assert_eq!(builder.len(), 1);
+    assert!(PakBuilder::new().index_gaps().is_empty());
+}
+
+#[test]
+fn index_coverage() {
+    use crate::{query::{self, PakQuery, PakQueryNode, Query}, value::PakValueKind};
+    
+    let queries : Vec<PakQueryNode> = vec![
+        PakQuery::equals("last_name", "Doe").into(),
+        query::parse(r#"first_name == "John" and (nickname == "JD" or nickname == "J")"#).unwrap(),
+        Query::where_("age").eq("thirty").and().where_("hometown").eq("Springfield").build(),
+    ];
+    let report = build_data_base().index_coverage(queries.clone()).unwrap();
+    assert!(!report.is_covered());
+    assert!(report.queries[0].is_covered());
+    assert_eq!(report.queries[1].missing, vec!["nickname"]);
+    assert_eq!(report.queries[2].missing, vec!["hometown"]);
+    assert_eq!(report.queries[2].mismatched, vec![("age".to_string(), PakValueKind::Int, PakValueKind::String)]);
+    assert_eq!(report.uncovered().count(), 2);
+    assert_eq!(report.missing_keys().into_iter().collect::<Vec<_>>(), vec!["hometown", "nickname"]);
+    
+    assert_eq!(data_base_builder().plan().unwrap().index_coverage(queries), report);
+}
+
+#[test]
+fn unknown_keys() {
+    use crate::{error::PakError, query::PakUnknownKeys};
+    
+    let pak = build_data_base();
+    let people = pak.query::<(Person, )>("nickname".equals("JD") | "first_name".equals("John")).unwrap();
+    assert_eq!(people.len(), 2);
+    assert!(pak.query::<(Person, )>("nickname".equals("JD") & "first_name".equals("John")).unwrap().is_empty());
+    assert!(!pak.exists("nickname".equals("JD")).unwrap());
+    assert!(matches!(pak.index("nickname"), Err(PakError::UnknownIndex(key)) if key == "nickname"));
+    
+    let pak = build_data_base().with_unknown_keys(PakUnknownKeys::Error);
+    let error = pak.query::<(Person, )>("nickname".equals("JD") | "first_name".equals("John")).unwrap_err();
+    assert!(error.is_query());
+    assert!(matches!(error, PakError::UnknownIndex(key) if key == "nickname"));
+    assert!(pak.exists("nickname".equals("JD")).is_err());
+    assert_eq!(pak.query::<(Person, )>("first_name".equals("John")).unwrap().len(), 2);
+}
+
+#[test]
+#[cfg(feature = "zstd")]
+fn compression() {
+    use std::io::Read;
+    use crate::meta::PakCompression;
+    
+    let build = |compression : Option<PakCompression>| {
+        let mut builder = PakBuilder::new().with_manifest().with_inline_items(16);
+        if let Some(compression) = compression { builder = builder.with_compression(compression) }
+        for i in 0..20u32 {
+            builder.pak(Person { first_name: format!("{{\"name\": \"P{i}\", \"tags\": [{}]}}", "\"npc\", ".repeat(50)), last_name: "Doe".to_string(), age: i }).unwrap();
+        }
+        builder.pak_blob_with_meta("readme.txt", "all work and no play ".repeat(100).into_bytes(), Person { first_name: "Jack".to_string(), last_name: "Torrance".to_string(), age: 40 }).unwrap();
+        builder.pak_l10n("menu.start", "en", "Start").unwrap();
+        builder.build_in_memory().unwrap()
+    };
+    let plain = build(None);
+    let pak = build(Some(PakCompression::Zstd(3)));
+    assert_eq!(pak.compression(), Some(PakCompression::Zstd(3)));
+    let item_bytes = |pak : &Pak| pak.size_classes().unwrap().iter().map(|class| class.bytes).sum::<u64>();
+    assert!(item_bytes(&pak) * 4 < item_bytes(&plain));
+    assert!(pak.size() < plain.size());
+    
+    let people = pak.query::<(Person, )>("age".less_than(5) & "last_name".equals("Doe")).unwrap();
+    assert_eq!(people.len(), 5);
+    assert!(people.iter().all(|person| person.first_name.len() > 350));
+    assert_eq!(pak.manifest_hashes().unwrap(), plain.manifest_hashes().unwrap());
+    assert_eq!(pak.localize("menu.start", &["en"]).unwrap().as_deref(), Some("Start"));
+    let (blob, author) = pak.blob_with_meta::<Person>("readme.txt").unwrap().unwrap();
+    assert_eq!((blob.0.len(), author.age), (2100, 40));
+    let mut window = pak.window(&pak.pointer_by_path("readme.txt").unwrap().unwrap());
+    assert_eq!(window.len(), 2100);
+    let mut text = String::new();
+    window.read_to_string(&mut text).unwrap();
+    assert_eq!(text, "all work and no play ".repeat(100));
+    
+    // A chunk can't decompress to more than the size it recorded, even when more frames follow it.
+    let zstd = PakCompression::Zstd(3);
+    let chunk = zstd.compress(&b"recorded ".repeat(20)).unwrap();
+    assert_eq!(zstd.decompressed_size(0, &chunk[..zstd.header_size() as usize]).unwrap(), 180);
+    assert_eq!(zstd.decompress(0, &chunk).unwrap(), b"recorded ".repeat(20));
+    let padded = [chunk, zstd.compress(&[0; 4096]).unwrap()].concat();
+    assert!(matches!(zstd.decompress(7, &padded), Err(crate::error::PakError::DecompressionFailed(7))));
+    
+    #[cfg(feature = "fuse")]
+    {
+        let fs = crate::vfs::PakFileSystem::new(&pak).unwrap();
+        let readme = fs.lookup(crate::vfs::PAK_ROOT_INODE, "readme.txt").unwrap();
+        assert_eq!(readme.size, 2100);
+        assert_eq!(fs.read(readme.inode, 21, 8).unwrap().unwrap(), b"all work");
+        assert_eq!(fs.read(readme.inode, 2095, 100).unwrap().unwrap(), b"play ");
+    }
+    
+    #[cfg(feature = "encryption")]
+    {
+        use crate::{access::PakPreloadList, crypto::PakKey};
+        
+        let (key, unreleased) = (PakKey::new([5; 32]), PakKey::new([6; 32]));
+        let path = std::env::temp_dir().join(format!("pak-compressed-{}.pak", std::process::id()));
+        let mut builder = PakBuilder::new().with_compression(PakCompression::Zstd(0)).with_encryption(&key).unwrap().with_item_key("unreleased", &unreleased).unwrap();
+        builder.pak(Person { first_name: "First".repeat(20), last_name: "Sealed".to_string(), age: 1 }).unwrap();
+        builder.pak_encrypted(Person { first_name: "Second".repeat(20), last_name: "Sealed".to_string(), age: 2 }, "unreleased").unwrap();
+        builder.pak(Person { first_name: "Third".repeat(20), last_name: "Sealed".to_string(), age: 3 }).unwrap();
+        builder.build_in_memory().unwrap().repack_ordered(&path, &PakPreloadList::new(vec![2, 1])).unwrap();
+        let repacked = Pak::open_with_key(&path, &key).unwrap().with_item_key("unreleased", &unreleased).unwrap();
+        let people = repacked.query::<(Person,)>("last_name".equals("Sealed")).unwrap();
+        assert_eq!(people.iter().map(|person| person.age).collect::<std::collections::BTreeSet<_>>(), [1, 2, 3].into());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
+
+#[test]
+fn pak_indices_macro() {
+    struct Tag { name : String, weight : u8 }
+    impl PakItemSearchable for Tag {
+        fn get_indices(&self) -> Vec<PakIndex> {
+            crate::pak_indices!(self => { name, "heavy" => self.weight > 10 })
+        }
+    }
+    assert_eq!(Tag { name : "sword".to_string(), weight : 12 }.get_indices(), vec![PakIndex::new("name", "sword"), PakIndex::new("heavy", true)]);
+    
+    let person = Person { first_name: "John".to_string(), last_name: "Doe".to_string(), age: 30 };
+    assert_eq!(crate::pak_indices!(person => { first_name, last_name, "age" => person.age, }), person.get_indices());
+    assert_eq!(crate::pak_indices!(person => { "years" => Some(person.age) }), vec![PakIndex::new("years", 30u32)]);
+    assert!(crate::pak_indices!(person => {}).is_empty());
+}
+
+#[test]
+#[cfg(feature = "zstd")]
+fn index_compression() {
+    use crate::{access::PakPreloadList, format::PakFormat, meta::PakCompression};
+    
+    let builder = |format : PakFormat, compression : Option<PakCompression>| {
+        let mut builder = PakBuilder::new().with_format(format).with_manifest();
+        if let Some(compression) = compression { builder = builder.with_index_compression(compression) }
+        for i in 0..2000u32 {
+            builder.pak(Person { first_name: format!("Person {}", i % 50), last_name: format!("Family {}", i % 7), age: i % 90 }).unwrap();
+        }
+        builder
+    };
+    let plain = builder(PakFormat::V1, None).plan().unwrap();
+    let plan = builder(PakFormat::V1, Some(PakCompression::Zstd(3))).plan().unwrap();
+    assert_eq!(plan.item_size, plain.item_size);
+    let index_size = |plan : &crate::plan::PakPlan| plan.indices.values().map(|index| index.size).sum::<u64>();
+    assert!(index_size(&plan) < index_size(&plain));
+    assert!(plan.overhead() < plain.overhead());
+    assert!(plan.size < plain.size);
+    assert!(plan.index_map.size() < plain.index_map.size());
+    
+    for format in [PakFormat::V1, PakFormat::V2] {
+        let path = std::env::temp_dir().join(format!("pak-index-compression-{}-{}.pak", format.version(), std::process::id()));
+        let pak = builder(format, Some(PakCompression::Zstd(3))).build_file(&path).unwrap();
+        assert_eq!(pak.index_compression(), Some(PakCompression::Zstd(3)));
+        assert_eq!(pak.compression(), None);
+        assert_eq!(pak.query::<(Person, )>("first_name".equals("Person 7") & "last_name".equals("Family 0")).unwrap().len(), 6);
+        assert_eq!(pak.distinct("age").unwrap().count(), 90);
+        assert_eq!(pak.manifest_hashes().unwrap().unwrap().len(), 2000);
+        
+        let reordered = std::env::temp_dir().join(format!("pak-index-compression-{}-{}-reordered.pak", format.version(), std::process::id()));
+        pak.repack_ordered(&reordered, &PakPreloadList::new(vec![1999])).unwrap();
+        let repacked = Pak::new_from_file(&reordered).unwrap();
+        assert_eq!(repacked.pointer_of(1999).unwrap().unwrap().offset(), 0);
+        assert_eq!(repacked.query::<(Person, )>("age".equals(19)).unwrap().len(), 23);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&reordered).unwrap();
+    }
+}
+
+#[test]
+fn foreign_types() {
+    use crate::{error::PakError, foreign::{PakForeign, PakForeignCodec}};
+    
+    // Stands in for a type from another crate, which implements none of the item traits or serde.
+    #[derive(Debug, PartialEq)]
+    struct Date { year : u32, month : u32, day : u32 }
+    
+    assert!(matches!(PakBuilder::new().pak(PakForeign(Date { year : 2024, month : 1, day : 1 })), Err(PakError::UnregisteredForeignType(_))));
+    PakForeign::<Date>::register(PakForeignCodec::via(|date : &Date| (date.year, date.month, date.day), |(year, month, day)| Date { year, month, day })
+        .with_indices(|date| crate::pak_indices!(date => { year, "month" => date.month })));
+    assert!(PakForeign::<Date>::is_registered());
+    
+    let mut builder = PakBuilder::new();
+    for month in 1..=12 {
+        builder.pak(PakForeign(Date { year : 2024, month, day : 1 })).unwrap();
+    }
+    builder.pak(PakForeign(Date { year : 2025, month : 3, day : 14 })).unwrap();
+    let pak = builder.build_in_memory().unwrap();
+    
+    assert_eq!(pak.query::<(PakForeign<Date>,)>("year".equals(2024u32)).unwrap().len(), 12);
+    let march = pak.query::<(PakForeign<Date>,)>("month".equals(3u32) & "year".equals(2025u32)).unwrap();
+    assert_eq!(march.into_iter().map(PakForeign::into_inner).collect::<Vec<_>>(), vec![Date { year : 2025, month : 3, day : 14 }]);
+}
+
+#[test]
+fn validity_windows() {
+    use std::time::{Duration, SystemTime};
+    use crate::{query::PakQueryExpression, validity::{PakActiveAt, PakValidity, PAK_VALID_FROM_KEY}};
+    
+    let day = |n : u64| SystemTime::UNIX_EPOCH + Duration::from_secs(86_400 * n);
+    let person = |name : &str| Person { first_name: name.to_string(), last_name: "Event".to_string(), age: 1 };
+    let mut builder = PakBuilder::new();
+    builder.pak_valid(person("Weekend"), PakValidity::between(day(5), day(7))).unwrap();
+    builder.pak_valid(person("Launch"), PakValidity::starting(day(6))).unwrap();
+    builder.pak_valid(person("Beta"), PakValidity::ending(day(6))).unwrap();
+    builder.pak(person("Always")).unwrap();
+    let pak = builder.build_in_memory().unwrap();
+    
+    let active = |instant : SystemTime| {
+        let mut names = pak.query_active_at::<(Person,)>(instant, "last_name".equals("Event")).unwrap().into_iter().map(|person| person.first_name).collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+    assert_eq!(active(day(1)), vec!["Always", "Beta"]);
+    assert_eq!(active(day(5)), vec!["Always", "Beta", "Weekend"]);
+    assert_eq!(active(day(6)), vec!["Always", "Launch", "Weekend"]);
+    assert_eq!(active(day(7)), vec!["Always", "Launch"]);
+    assert_eq!(pak.query::<(Person,)>("last_name".equals("Event")).unwrap().len(), 4);
+    assert!(PakValidity::between(day(5), day(7)).contains(day(5)) && !PakValidity::between(day(5), day(7)).contains(day(7)));
+    
+    let indices = person("Weekend").get_indices().into_iter().chain([PakIndex::new(PAK_VALID_FROM_KEY, day(5))]).collect::<Vec<_>>();
+    assert!(!PakActiveAt::new(day(4), "last_name".equals("Event")).matches(&indices));
+    assert!(PakActiveAt::new(day(5), "last_name".equals("Event")).matches(&indices));
+}
+
+#[test]
+fn item_checksums() {
+    use std::io::{Cursor, Read};
+    use crate::error::PakError;
+    
+    let mut builder = PakBuilder::new().with_item_checksums();
+    for i in 0..20u32 {
+        builder.pak(Person { first_name: format!("Checked {i:02}"), last_name: "Rot".to_string(), age: i }).unwrap();
+    }
+    let (mut data, _, _) = builder.build_internal().unwrap();
+    let path = std::env::temp_dir().join(format!("pak-item-checksums-{}.pak", std::process::id()));
+    Pak::new(Cursor::new(data.clone())).unwrap().repack_reordered(&path, |pointer| u64::MAX - pointer.offset()).unwrap();
+    let reordered = Pak::new_from_file(&path).unwrap();
+    assert_eq!(reordered.query::<(Person,)>("last_name".equals("Rot")).unwrap().len(), 20);
+    std::fs::remove_file(&path).unwrap();
+    
+    let at = data.windows(10).position(|window| window == b"Checked 07").unwrap();
+    data[at] ^= 0x20;
+    
+    let pak = Pak::new(Cursor::new(data)).unwrap();
+    assert_eq!(pak.query::<(Person,)>("age".equals(6u32)).unwrap()[0].first_name, "Checked 06");
+    let rotted = pak.pointer_of(7).unwrap().unwrap();
+    assert!(matches!(pak.get::<Person>(&rotted), Err(PakError::ItemChecksumMismatch(offset)) if offset == rotted.offset()));
+    assert!(pak.get::<Person>(&rotted).unwrap_err().is_corruption());
+    
+    // Reads of part of an item have no checksum to be checked against.
+    let mut bytes = [0u8; 4];
+    pak.window(&rotted).read_exact(&mut bytes).unwrap();
+}
+
+#[test]
+fn vault_hash() {
+    use std::io::Cursor;
+    use crate::error::PakError;
+    
+    let mut builder = PakBuilder::new().with_vault_hash();
+    for i in 0..50u32 {
+        builder.pak(Person { first_name: format!("Download {i}"), last_name: "Cdn".to_string(), age: i }).unwrap();
+    }
+    let (mut data, _, _) = builder.build_internal().unwrap();
+    Pak::new(Cursor::new(data.clone())).unwrap().verify().unwrap();
+    
+    let at = data.windows(11).position(|window| window == b"Download 42").unwrap();
+    data[at + 9] = b'7';
+    assert!(matches!(Pak::new(Cursor::new(data)).unwrap().verify(), Err(PakError::VaultHashMismatch)));
+    
+    let mut builder = PakBuilder::new();
+    builder.pak(Person { first_name: "Unhashed".to_string(), last_name: "Cdn".to_string(), age: 1 }).unwrap();
+    assert!(matches!(builder.build_in_memory().unwrap().verify(), Err(PakError::MissingVaultHash)));
+}
+
+#[test]
+fn record_and_replay() {
+    use std::{fs::File, io::BufReader};
+    use crate::replay::{PakRecordingSource, PakReplaySource};
+    
+    let path = std::env::temp_dir().join(format!("pak-recorded-{}.pak", std::process::id()));
+    let capture = std::env::temp_dir().join(format!("pak-capture-{}.bin", std::process::id()));
+    let mut builder = PakBuilder::new();
+    for i in 0..200u32 {
+        builder.pak(Person { first_name: format!("Person {i}"), last_name: format!("Family {}", i % 10), age: i % 40 }).unwrap();
+    }
+    builder.build_file(&path).unwrap();
+    
+    let recorded = Pak::new(PakRecordingSource::new(BufReader::new(File::open(&path).unwrap()), &capture).unwrap()).unwrap();
+    let mut expected = recorded.query::<(Person,)>("age".equals(7u32) & "last_name".equals("Family 7")).unwrap();
+    expected.sort_by(|a, b| a.first_name.cmp(&b.first_name));
+    assert_eq!(expected.len(), 5);
+    drop(recorded);
+    
+    let replay = PakReplaySource::open(&capture).unwrap();
+    assert!(!replay.is_empty());
+    let replayed = Pak::new(replay).unwrap();
+    let mut people = replayed.query::<(Person,)>("age".equals(7u32) & "last_name".equals("Family 7")).unwrap();
+    people.sort_by(|a, b| a.first_name.cmp(&b.first_name));
+    assert_eq!(people, expected);
+    assert!(replayed.query::<(Person,)>("first_name".equals("Person 3")).unwrap_err().is_io());
+    assert!(std::fs::metadata(&capture).unwrap().len() < std::fs::metadata(&path).unwrap().len());
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&capture).unwrap();
+}
+
+/// Checks the golden paks in tests/golden. Run with `PAK_WRITE_GOLDEN=1` and every feature to write the golden paks that don't exist yet.
+#[test]
+fn golden_paks() {
+    use crate::golden::{verify_goldens, PakGolden};
+    
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden");
+    if std::env::var_os("PAK_WRITE_GOLDEN").is_some() {
+        for golden in PakGolden::all().iter().filter(|golden| !golden.path(&dir).exists()) {
+            golden.write(&dir).unwrap();
+        }
+    }
+    verify_goldens(&dir).unwrap();
+}
+
+#[test]
+#[cfg(any(feature = "ahash", feature = "xxhash"))]
+fn deterministic_builds() {
+    use crate::hash::PAK_DETERMINISTIC_HASHER;
+    
+    const { assert!(PAK_DETERMINISTIC_HASHER) };
+    let build = || {
+        let mut builder = PakBuilder::new().with_content_addressing().with_manifest();
+        for i in 0..100u32 {
+            builder.pak(Person { first_name: format!("Person {}", i % 60), last_name: format!("Family {}", i % 7), age: i % 30 }).unwrap();
+        }
+        builder.build_internal().unwrap().0
+    };
+    assert_eq!(build(), build());
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn http_range_source() {
+    use std::{io::{BufRead, BufReader, Write}, net::TcpListener, sync::{atomic::{AtomicU64, Ordering}, Arc}};
+    use crate::{error::PakError, http::PakHttpSource, PakSource};
+    
+    let mut builder = PakBuilder::new();
+    for i in 0..500u32 {
+        builder.pak(Person { first_name: format!("Person {i}"), last_name: format!("Family {}", i % 25), age: i % 60 }).unwrap();
+    }
+    let (data, _, _) = builder.build_internal().unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let served = Arc::new(AtomicU64::new(0));
+    let counter = served.clone();
+    let file = data.clone();
+    // Answers HEAD requests with the size and GET requests with the range they ask for, or the whole file for `/norange`. The file at
+    // `/changed` is replaced after it is opened, so its ETag no longer matches, and `/shifted` sends its ranges a byte late.
+    std::thread::spawn(move || for stream in listener.incoming() {
+        let mut stream = stream.unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request = String::new();
+        reader.read_line(&mut request).unwrap();
+        let (mut range, mut if_range) = (None, None);
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() { break }
+            if let Some(bytes) = line.trim().strip_prefix("Range: bytes=") {
+                let (start, end) = bytes.split_once('-').unwrap();
+                range = Some((start.parse::<usize>().unwrap(), end.parse::<usize>().unwrap() + 1));
+            }
+            if let Some(etag) = line.trim().strip_prefix("If-Range: ") { if_range = Some(etag.to_string()) }
+        }
+        let head = request.starts_with("HEAD");
+        let etag = if request.contains("/changed") && !head { "\"v2\"" } else { "\"v1\"" };
+        let shift = usize::from(request.contains("/shifted"));
+        let (status, body, content_range) = match (head, request.contains("/norange"), range) {
+            (true, _, _) => ("200 OK", &file[..0], None),
+            (false, false, Some((start, end))) if if_range.as_deref().is_none_or(|if_range| if_range == etag) => {
+                ("206 Partial Content", &file[start + shift..end + shift], Some((start + shift, end + shift - 1)))
+            },
+            _ => ("200 OK", &file[..], None),
+        };
+        let len = if head { file.len() } else { body.len() };
+        counter.fetch_add(body.len() as u64, Ordering::SeqCst);
+        let content_range = content_range.map(|(start, end)| format!("Content-Range: bytes {start}-{end}/{}\r\n", file.len())).unwrap_or_default();
+        write!(stream, "HTTP/1.1 {status}\r\nContent-Length: {len}\r\nETag: {etag}\r\n{content_range}Connection: close\r\n\r\n").unwrap();
+        stream.write_all(body).unwrap();
+    });
+    
+    let mut source = PakHttpSource::new(format!("http://{address}/people.pak"));
+    assert_eq!(source.size().unwrap(), Some(data.len() as u64));
+    let pak = Pak::new(source).unwrap().with_cache(1024 * 1024);
+    let mut people = pak.query::<(Person,)>("last_name".equals("Family 3") & "age".equals(3u32)).unwrap();
+    people.sort_by(|a, b| a.first_name.cmp(&b.first_name));
+    assert_eq!(people.len(), 2);
+    assert_eq!((people[0].first_name.as_str(), people[1].first_name.as_str()), ("Person 3", "Person 303"));
+    assert!(served.load(Ordering::SeqCst) < data.len() as u64 / 2);
+    
+    assert!(matches!(Pak::open_url(format!("http://{address}/norange")), Err(PakError::RemoteError(_))));
+    
+    // Ranges that don't match the request, or that come from a file with another ETag, are never read as the pak.
+    let mut shifted = PakHttpSource::new(format!("http://{address}/shifted"));
+    assert!(matches!(shifted.read(&PakPointer::new_untyped(0, 16), 0), Err(PakError::RemoteError(reason)) if reason.contains("were asked for")));
+    let mut changed = PakHttpSource::new(format!("http://{address}/changed"));
+    assert_eq!(changed.size().unwrap(), Some(data.len() as u64));
+    assert!(matches!(changed.read(&PakPointer::new_untyped(0, 16), 0), Err(PakError::RemoteError(reason)) if reason.contains("changed")));
+}
@@ -0,0 +1,83 @@
+use std::collections::{BTreeSet, HashMap};
+use crate::{error::PakResult, index::PakIndex, item::{PakItemSearchable, PakItemSerialize}, pointer::PakPointer, query::PakQueryExpression, Pak, PakBuilder};
+
+//==============================================================================================
+//        NaiveStore
+//==============================================================================================
+
+/// A reference model that answers queries by scanning every item's indices, with no trees involved. Feed it the same items as a
+/// [PakBuilder](crate::PakBuilder) and then check that the built pak agrees with it using [assert_agrees](crate::testing::assert_agrees).
+/// Items are identified by their ordinal, which is the order they were added in.
+#[derive(Debug, Clone, Default)]
+pub struct NaiveStore {
+    items : Vec<Vec<PakIndex>>,
+}
+
+impl NaiveStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self { items : Vec::new() }
+    }
+
+    /// Adds an item to the store, returning its ordinal. The item is kept along with its indices, so [filters](crate::scan::PakQueryFilter)
+    /// can check their predicate against it.
+    pub fn push<T>(&mut self, item : &T) -> u32 where T : PakItemSerialize + PakItemSearchable {
+        let mut indices = item.get_indices();
+        indices.extend(crate::scan::item_indices(item));
+        self.push_indices(indices)
+    }
+
+    /// Adds an item to the store by its indices, returning its ordinal. The item itself isn't kept, so it never passes a filter.
+    pub fn push_indices(&mut self, indices : Vec<PakIndex>) -> u32 {
+        self.items.push(indices);
+        (self.items.len() - 1) as u32
+    }
+
+    /// Paks the item into the builder and adds it to the store, so both see the same items in the same order.
+    pub fn pak<T>(&mut self, builder : &mut PakBuilder, item : T) -> PakResult<PakPointer> where T : PakItemSerialize + PakItemSearchable {
+        self.push(&item);
+        builder.pak(item)
+    }
+
+    /// The ordinals of every item that matches the query.
+    pub fn query(&self, query : &dyn PakQueryExpression) -> BTreeSet<u32> {
+        self.items.iter().enumerate().filter(|(_, indices)| query.matches(indices)).map(|(ordinal, _)| ordinal as u32).collect()
+    }
+
+    /// Returns true if any item matches the query.
+    pub fn exists(&self, query : &dyn PakQueryExpression) -> bool {
+        self.items.iter().any(|indices| query.matches(indices))
+    }
+
+    /// The number of items in the store.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns true if the store has no items.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+//==============================================================================================
+//        Assertions
+//==============================================================================================
+
+/// Runs the query against the pak and resolves the results into ordinals, so they can be compared with a [NaiveStore](crate::testing::NaiveStore).
+pub fn query_ordinals(pak : &Pak, query : &dyn PakQueryExpression) -> PakResult<BTreeSet<u32>> {
+    let ordinals = pak.ordinals()?.iter().enumerate()
+        .map(|(ordinal, pointer)| (pointer.clone().into_pointer().offset(), ordinal as u32))
+        .collect::<HashMap<_, _>>();
+    let results = query.execute(pak)?;
+    Ok(results.into_iter().filter_map(|pointer| ordinals.get(&pointer.into_pointer().offset()).copied()).collect())
+}
+
+/// Panics if the pak and the store disagree on which items match the query, or on whether any item matches it.
+pub fn assert_agrees(pak : &Pak, store : &NaiveStore, query : &dyn PakQueryExpression) {
+    let expected = store.query(query);
+    let found = query_ordinals(pak, query).expect("the pak failed to answer the query");
+    assert_eq!(found, expected, "the pak and the naive store disagree on the query results");
+    let exists = query.exists(pak).expect("the pak failed to answer the exists query");
+    assert_eq!(exists, !expected.is_empty(), "the pak and the naive store disagree on whether the query has results");
+}
@@ -0,0 +1,613 @@
+use std::{collections::{BTreeMap, BTreeSet, HashMap, HashSet}, iter::Peekable, ops::{Bound, Range}, str::Chars, sync::Arc};
+use serde::{Deserialize, Serialize};
+use crate::{error::{PakError, PakResult}, kind::{PakCustomQuery, PakIndexKind, PakKindQuery}, value::PakValue, Pak};
+
+//==============================================================================================
+//        PakTextIndex
+//==============================================================================================
+
+/// The name of the operation that matches items containing any of the query's terms.
+pub const MATCH_OPERATION : &str = "match";
+
+const TEXT_KIND_NAME : &str = "pak_text";
+
+/// A built-in [PakIndexKind](crate::kind::PakIndexKind) for full-text search. Every string value is split into terms by a
+/// [PakTextAnalyzer](crate::text::PakTextAnalyzer), and an inverted index records which items contain each term and where. Search it with
+/// [Pak::search](crate::Pak::search) to get items ranked by relevance, or use [matches_text](crate::text::matches_text) inside of regular queries.
+/// An item with several values under the key is searched as one document.
+#[derive(Debug, Clone, Default)]
+pub struct PakTextIndex {
+    analyzer : PakTextAnalyzer,
+}
+
+impl PakTextIndex {
+    /// A text index that splits its values into terms with the analyzer. The analyzer is stored with the index, so queries are analyzed the same way.
+    pub fn with_analyzer(analyzer : PakTextAnalyzer) -> Self {
+        Self { analyzer }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PakTextData {
+    /// The analyzer the documents were split into terms with.
+    analyzer : PakTextAnalyzer,
+    /// The ordinal and number of terms of every document.
+    documents : Vec<(u32, u32)>,
+    /// The documents that contain each term along with the positions of the term in them, in ascending order.
+    terms : BTreeMap<String, Vec<(u32, Vec<u32>)>>,
+}
+
+//==============================================================================================
+//        PakTextAnalyzer
+//==============================================================================================
+
+/// How a [PakTextAnalyzer](crate::text::PakTextAnalyzer) splits text into terms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PakTokenizer {
+    /// Splits on whitespace only, so `rusty-sword` stays a single term.
+    Whitespace,
+    /// Splits on anything that isn't a letter or a number.
+    #[default]
+    Alphanumeric,
+    /// Splits on the word boundaries of Unicode Standard Annex #29. Requires the `unicode` feature.
+    Unicode,
+}
+
+/// The languages that stemming and stop words are available in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PakLanguage {
+    Arabic,
+    Danish,
+    Dutch,
+    English,
+    Finnish,
+    French,
+    German,
+    Greek,
+    Hungarian,
+    Italian,
+    Norwegian,
+    Portuguese,
+    Romanian,
+    Russian,
+    Spanish,
+    Swedish,
+    Turkish,
+}
+
+impl PakLanguage {
+    #[cfg(feature = "stopwords")]
+    fn code(&self) -> &'static str {
+        match self {
+            PakLanguage::Arabic => "ar",
+            PakLanguage::Danish => "da",
+            PakLanguage::Dutch => "nl",
+            PakLanguage::English => "en",
+            PakLanguage::Finnish => "fi",
+            PakLanguage::French => "fr",
+            PakLanguage::German => "de",
+            PakLanguage::Greek => "el",
+            PakLanguage::Hungarian => "hu",
+            PakLanguage::Italian => "it",
+            PakLanguage::Norwegian => "no",
+            PakLanguage::Portuguese => "pt",
+            PakLanguage::Romanian => "ro",
+            PakLanguage::Russian => "ru",
+            PakLanguage::Spanish => "es",
+            PakLanguage::Swedish => "sv",
+            PakLanguage::Turkish => "tr",
+        }
+    }
+    
+    #[cfg(feature = "stemming")]
+    fn algorithm(&self) -> rust_stemmers::Algorithm {
+        use rust_stemmers::Algorithm;
+        match self {
+            PakLanguage::Arabic => Algorithm::Arabic,
+            PakLanguage::Danish => Algorithm::Danish,
+            PakLanguage::Dutch => Algorithm::Dutch,
+            PakLanguage::English => Algorithm::English,
+            PakLanguage::Finnish => Algorithm::Finnish,
+            PakLanguage::French => Algorithm::French,
+            PakLanguage::German => Algorithm::German,
+            PakLanguage::Greek => Algorithm::Greek,
+            PakLanguage::Hungarian => Algorithm::Hungarian,
+            PakLanguage::Italian => Algorithm::Italian,
+            PakLanguage::Norwegian => Algorithm::Norwegian,
+            PakLanguage::Portuguese => Algorithm::Portuguese,
+            PakLanguage::Romanian => Algorithm::Romanian,
+            PakLanguage::Russian => Algorithm::Russian,
+            PakLanguage::Spanish => Algorithm::Spanish,
+            PakLanguage::Swedish => Algorithm::Swedish,
+            PakLanguage::Turkish => Algorithm::Turkish,
+        }
+    }
+}
+
+/// Decides how text is split into the terms of a [PakTextIndex](crate::text::PakTextIndex). Terms are always lowercase. Stop words are dropped
+/// before stemming, and the positions of the remaining terms are counted without them.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct PakTextAnalyzer {
+    tokenizer : PakTokenizer,
+    stemming : Option<PakLanguage>,
+    stopwords : Option<PakLanguage>,
+}
+
+impl PakTextAnalyzer {
+    pub fn new(tokenizer : PakTokenizer) -> Self {
+        Self { tokenizer, stemming : None, stopwords : None }
+    }
+    
+    /// Reduces every term to its stem, so `swords` matches `sword`.
+    #[cfg(feature = "stemming")]
+    pub fn with_stemming(mut self, language : PakLanguage) -> Self {
+        self.stemming = Some(language);
+        self
+    }
+    
+    /// Drops the common words of the language, like `the` and `of`.
+    #[cfg(feature = "stopwords")]
+    pub fn with_stopwords(mut self, language : PakLanguage) -> Self {
+        self.stopwords = Some(language);
+        self
+    }
+    
+    /// Splits the text into terms. Fails if the analyzer needs a feature that isn't enabled, which happens when reading a pak built elsewhere.
+    pub fn analyze(&self, text : &str) -> PakResult<Vec<String>> {
+        Ok(self.prepare()?.terms(text))
+    }
+    
+    fn prepare(&self) -> PakResult<PakPreparedAnalyzer> {
+        if self.tokenizer == PakTokenizer::Unicode && cfg!(not(feature = "unicode")) { return Err(PakError::AnalyzerUnavailable("unicode".to_string())) }
+        
+        #[cfg(feature = "stopwords")]
+        let stopwords = self.stopwords.map(|language| stop_words::get(language.code()).iter().copied().collect()).unwrap_or_default();
+        #[cfg(not(feature = "stopwords"))]
+        let stopwords = match self.stopwords {
+            Some(_) => return Err(PakError::AnalyzerUnavailable("stopwords".to_string())),
+            None => HashSet::new(),
+        };
+        
+        #[cfg(feature = "stemming")]
+        let stemmer = self.stemming.map(|language| rust_stemmers::Stemmer::create(language.algorithm()));
+        #[cfg(not(feature = "stemming"))]
+        if self.stemming.is_some() { return Err(PakError::AnalyzerUnavailable("stemming".to_string())) }
+        
+        Ok(PakPreparedAnalyzer {
+            tokenizer : self.tokenizer,
+            stopwords,
+            #[cfg(feature = "stemming")]
+            stemmer,
+        })
+    }
+}
+
+/// A [PakTextAnalyzer](crate::text::PakTextAnalyzer) with its stop words and stemmer loaded, so many texts can be analyzed without loading them again.
+struct PakPreparedAnalyzer {
+    tokenizer : PakTokenizer,
+    stopwords : HashSet<&'static str>,
+    #[cfg(feature = "stemming")]
+    stemmer : Option<rust_stemmers::Stemmer>,
+}
+
+impl PakPreparedAnalyzer {
+    fn terms(&self, text : &str) -> Vec<String> {
+        self.spans(text).into_iter().map(|(_, term)| term).collect()
+    }
+    
+    /// The terms of the text along with the byte range of the word each of them came from.
+    fn spans(&self, text : &str) -> Vec<(Range<usize>, String)> {
+        let words : Box<dyn Iterator<Item = &str>> = match self.tokenizer {
+            PakTokenizer::Whitespace => Box::new(text.split_whitespace()),
+            #[cfg(feature = "unicode")]
+            PakTokenizer::Unicode => Box::new(unicode_segmentation::UnicodeSegmentation::unicode_words(text)),
+            _ => Box::new(text.split(|c : char| !c.is_alphanumeric()).filter(|term| !term.is_empty())),
+        };
+        words
+            .map(|word| {
+                // Every word is a slice of the text, so its offset is the distance between the two.
+                let start = word.as_ptr() as usize - text.as_ptr() as usize;
+                (start..start + word.len(), word.to_lowercase())
+            })
+            .filter(|(_, term)| !self.stopwords.contains(term.as_str()))
+            .map(|(range, term)| (range, self.stem(term)))
+            .collect()
+    }
+    
+    #[cfg(feature = "stemming")]
+    fn stem(&self, term : String) -> String {
+        match &self.stemmer {
+            Some(stemmer) => stemmer.stem(&term).into_owned(),
+            None => term,
+        }
+    }
+    
+    #[cfg(not(feature = "stemming"))]
+    fn stem(&self, term : String) -> String {
+        term
+    }
+}
+
+impl PakTextData {
+    fn build(analyzer : &PakTextAnalyzer, entries : &[(PakValue, u32)]) -> PakResult<Self> {
+        let prepared = analyzer.prepare()?;
+        let mut documents = BTreeMap::<u32, Vec<String>>::new();
+        for (value, ordinal) in entries {
+            let Some(text) = value.as_string() else { continue };
+            documents.entry(*ordinal).or_default().extend(prepared.terms(&text));
+        }
+        
+        let mut data = PakTextData { analyzer : analyzer.clone(), documents : Vec::with_capacity(documents.len()), terms : BTreeMap::new() };
+        for (document, (ordinal, terms)) in documents.into_iter().enumerate() {
+            data.documents.push((ordinal, terms.len() as u32));
+            for (position, term) in terms.into_iter().enumerate() {
+                let postings = data.terms.entry(term).or_default();
+                match postings.last_mut() {
+                    Some((last, positions)) if *last == document as u32 => positions.push(position as u32),
+                    _ => postings.push((document as u32, vec![position as u32])),
+                }
+            }
+        }
+        Ok(data)
+    }
+    
+    /// Scores every document that contains at least one of the terms with BM25, returning the ordinals and scores in no particular order.
+    pub(crate) fn score(&self, terms : &[String]) -> Vec<(u32, f32)> {
+        const K1 : f32 = 1.2;
+        const B : f32 = 0.75;
+        let count = self.documents.len() as f32;
+        let average = self.documents.iter().map(|(_, length)| *length as f32).sum::<f32>() / count.max(1.0);
+        
+        let mut scores = BTreeMap::<u32, f32>::new();
+        for term in terms.iter().collect::<BTreeSet<_>>() {
+            let Some(postings) = self.terms.get(term) else { continue };
+            let idf = (1.0 + (count - postings.len() as f32 + 0.5) / (postings.len() as f32 + 0.5)).ln();
+            for (document, positions) in postings {
+                let frequency = positions.len() as f32;
+                let length = self.documents[*document as usize].1 as f32;
+                let score = idf * frequency * (K1 + 1.0) / (frequency + K1 * (1.0 - B + B * length / average.max(1.0)));
+                *scores.entry(*document).or_default() += score;
+            }
+        }
+        scores.into_iter().map(|(document, score)| (self.documents[document as usize].0, score)).collect()
+    }
+    
+    /// The ordinals of the documents that contain the terms next to each other, in order.
+    fn phrase(&self, terms : &[String]) -> BTreeSet<u32> {
+        let Some((first, rest)) = terms.split_first() else { return BTreeSet::new() };
+        let Some(postings) = self.terms.get(first) else { return BTreeSet::new() };
+        let rest = rest.iter().map(|term| self.terms.get(term)).collect::<Option<Vec<_>>>().unwrap_or_default();
+        if rest.len() + 1 != terms.len() { return BTreeSet::new() }
+        
+        let positions_in = |postings : &Vec<(u32, Vec<u32>)>, document : u32| {
+            postings.binary_search_by_key(&document, |(document, _)| *document).ok().map(|index| postings[index].1.clone())
+        };
+        postings.iter().filter(|(document, starts)| {
+            let following = rest.iter().map(|postings| positions_in(postings, *document)).collect::<Option<Vec<_>>>();
+            let Some(following) = following else { return false };
+            starts.iter().any(|start| following.iter().enumerate().all(|(offset, positions)| positions.binary_search(&(start + offset as u32 + 1)).is_ok()))
+        }).map(|(document, _)| self.documents[*document as usize].0).collect()
+    }
+}
+
+impl PakIndexKind for PakTextIndex {
+    fn name(&self) -> &str {
+        TEXT_KIND_NAME
+    }
+    
+    fn build(&self, entries : &[(PakValue, u32)]) -> PakResult<Vec<u8>> {
+        Ok(bincode::serialize(&PakTextData::build(&self.analyzer, entries)?)?)
+    }
+    
+    fn execute(&self, data : &[u8], query : &PakKindQuery) -> PakResult<Option<Vec<u32>>> {
+        let PakKindQuery::Operation(operation, PakValue::String(text)) = query else { return Ok(None) };
+        if operation != MATCH_OPERATION { return Ok(None) }
+        let data : PakTextData = bincode::deserialize(data)?;
+        let terms = data.analyzer.analyze(text)?;
+        let mut ordinals = data.score(&terms).into_iter().map(|(ordinal, _)| ordinal).collect::<Vec<_>>();
+        ordinals.sort();
+        Ok(Some(ordinals))
+    }
+    
+    fn matches(&self, query : &PakKindQuery, value : &PakValue) -> bool {
+        match (query, value) {
+            (PakKindQuery::Operation(operation, PakValue::String(text)), PakValue::String(value)) => {
+                let Ok(analyzer) = self.analyzer.prepare() else { return false };
+                let terms = analyzer.terms(value).into_iter().collect::<BTreeSet<_>>();
+                operation == MATCH_OPERATION && analyzer.terms(text).iter().any(|term| terms.contains(term))
+            },
+            (PakKindQuery::Range(lower, upper), value) => crate::btree::range_contains(&(lower.as_ref(), upper.as_ref()), value),
+            _ => false,
+        }
+    }
+}
+
+/// Finds every item whose text under the key contains at least one of the terms of the text. The key must have a [PakTextIndex](crate::text::PakTextIndex).
+pub fn matches_text(key : &str, text : &str) -> PakCustomQuery {
+    PakCustomQuery::new(key, Arc::new(PakTextIndex::default()), PakKindQuery::Operation(MATCH_OPERATION.to_string(), PakValue::from(text)))
+}
+
+
+//==============================================================================================
+//        PakTextQuery
+//==============================================================================================
+
+/// A parsed search query. Words and quoted phrases can be combined with `AND`, `OR`, `NOT` and parentheses, and words next to each other are
+/// joined with `OR`. A word or phrase can be scoped to another index with `key:word` or `key:"a phrase"`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum PakTextQuery {
+    /// Text whose terms have to appear next to each other, in the default index or the scoped one. It is analyzed by the index it is searched in.
+    Phrase(Option<String>, String),
+    And(Box<PakTextQuery>, Box<PakTextQuery>),
+    Or(Box<PakTextQuery>, Box<PakTextQuery>),
+    Not(Box<PakTextQuery>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PakTextToken {
+    Word(Option<String>, String),
+    Quoted(Option<String>, String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+fn lex(text : &str) -> PakResult<Vec<PakTextToken>> {
+    fn quoted(chars : &mut Peekable<Chars>) -> PakResult<String> {
+        chars.next();
+        let mut phrase = String::new();
+        loop {
+            match chars.next() {
+                Some('"') => return Ok(phrase),
+                Some(c) => phrase.push(c),
+                None => return Err(PakError::InvalidSearch("a quoted phrase is never closed".to_string())),
+            }
+        }
+    }
+    
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => { chars.next(); },
+            '(' => { chars.next(); tokens.push(PakTextToken::Open) },
+            ')' => { chars.next(); tokens.push(PakTextToken::Close) },
+            '"' => tokens.push(PakTextToken::Quoted(None, quoted(&mut chars)?)),
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' { break }
+                    word.push(c);
+                    chars.next();
+                }
+                let token = match word.as_str() {
+                    "AND" => PakTextToken::And,
+                    "OR" => PakTextToken::Or,
+                    "NOT" => PakTextToken::Not,
+                    _ => match word.split_once(':') {
+                        Some((field, "")) if !field.is_empty() && chars.peek() == Some(&'"') => PakTextToken::Quoted(Some(field.to_string()), quoted(&mut chars)?),
+                        Some((field, term)) if !field.is_empty() && !term.is_empty() => PakTextToken::Word(Some(field.to_string()), term.to_string()),
+                        _ => PakTextToken::Word(None, word),
+                    },
+                };
+                tokens.push(token);
+            },
+        }
+    }
+    Ok(tokens)
+}
+
+struct PakTextParser {
+    tokens : Vec<PakTextToken>,
+    position : usize,
+}
+
+impl PakTextParser {
+    fn peek(&self) -> Option<&PakTextToken> {
+        self.tokens.get(self.position)
+    }
+    
+    fn next(&mut self) -> Option<PakTextToken> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+    
+    fn or(&mut self) -> PakResult<PakTextQuery> {
+        let mut query = self.and()?;
+        loop {
+            match self.peek() {
+                Some(PakTextToken::Or) => { self.next(); },
+                Some(PakTextToken::Close) | None => return Ok(query),
+                // Words next to each other are joined with OR, so search boxes behave the way players expect.
+                Some(_) => {},
+            }
+            query = PakTextQuery::Or(Box::new(query), Box::new(self.and()?));
+        }
+    }
+    
+    fn and(&mut self) -> PakResult<PakTextQuery> {
+        let mut query = self.unary()?;
+        while self.peek() == Some(&PakTextToken::And) {
+            self.next();
+            query = PakTextQuery::And(Box::new(query), Box::new(self.unary()?));
+        }
+        Ok(query)
+    }
+    
+    fn unary(&mut self) -> PakResult<PakTextQuery> {
+        match self.next() {
+            Some(PakTextToken::Not) => Ok(PakTextQuery::Not(Box::new(self.unary()?))),
+            Some(PakTextToken::Open) => {
+                let query = self.or()?;
+                match self.next() {
+                    Some(PakTextToken::Close) => Ok(query),
+                    _ => Err(PakError::InvalidSearch("a parenthesis is never closed".to_string())),
+                }
+            },
+            Some(PakTextToken::Word(field, text)) | Some(PakTextToken::Quoted(field, text)) => Ok(PakTextQuery::Phrase(field, text)),
+            Some(token) => Err(PakError::InvalidSearch(format!("expected a word or a phrase but found {token:?}"))),
+            None => Err(PakError::InvalidSearch("the query ends where a word or a phrase was expected".to_string())),
+        }
+    }
+}
+
+impl PakTextQuery {
+    pub(crate) fn parse(text : &str) -> PakResult<Self> {
+        let mut parser = PakTextParser { tokens : lex(text)?, position : 0 };
+        if parser.peek().is_none() { return Ok(PakTextQuery::Phrase(None, String::new())) }
+        let query = parser.or()?;
+        match parser.next() {
+            None => Ok(query),
+            Some(token) => Err(PakError::InvalidSearch(format!("unexpected {token:?}"))),
+        }
+    }
+    
+    /// Collects the text that adds to an item's score, grouped by the index it is searched in. Text under a `NOT` never scores.
+    fn scoring_terms(&self, terms : &mut BTreeMap<Option<String>, Vec<String>>) {
+        match self {
+            PakTextQuery::Phrase(field, phrase) => terms.entry(field.clone()).or_default().push(phrase.clone()),
+            PakTextQuery::And(a, b) | PakTextQuery::Or(a, b) => {
+                a.scoring_terms(terms);
+                b.scoring_terms(terms);
+            },
+            PakTextQuery::Not(_) => {},
+        }
+    }
+}
+
+/// Evaluates a [PakTextQuery](crate::text::PakTextQuery) against a pak, loading the text index of every key it touches once.
+struct PakTextSearch<'p> {
+    pak : &'p Pak,
+    key : String,
+    texts : HashMap<String, Option<PakTextData>>,
+}
+
+impl PakTextSearch<'_> {
+    fn text(&mut self, key : &str) -> PakResult<Option<&PakTextData>> {
+        if !self.texts.contains_key(key) {
+            let mut data = None;
+            if self.pak.fetch_indices()?.contains_key(key) && let Some((name, pointer)) = self.pak.get_tree(key)?.custom() && name == TEXT_KIND_NAME {
+                data = Some(bincode::deserialize(&self.pak.read_bytes(&pointer.as_pointer())?)?);
+            }
+            self.texts.insert(key.to_string(), data);
+        }
+        Ok(self.texts[key].as_ref())
+    }
+    
+    /// The ordinals of every item that matches the query.
+    fn evaluate(&mut self, query : &PakTextQuery) -> PakResult<BTreeSet<u32>> {
+        Ok(match query {
+            PakTextQuery::Phrase(field, phrase) => {
+                let key = field.clone().unwrap_or_else(|| self.key.clone());
+                match self.text(&key)? {
+                    Some(text) => text.phrase(&text.analyzer.analyze(phrase)?),
+                    // Keys without a text index are matched by their exact value instead.
+                    None => self.exact(&key, phrase)?,
+                }
+            },
+            PakTextQuery::And(a, b) => self.evaluate(a)?.intersection(&self.evaluate(b)?).copied().collect(),
+            PakTextQuery::Or(a, b) => self.evaluate(a)?.union(&self.evaluate(b)?).copied().collect(),
+            PakTextQuery::Not(query) => {
+                let excluded = self.evaluate(query)?;
+                (0..self.pak.item_count()?).filter(|ordinal| !excluded.contains(ordinal)).collect()
+            },
+        })
+    }
+    
+    fn exact(&self, key : &str, value : &str) -> PakResult<BTreeSet<u32>> {
+        let mut ordinals = BTreeSet::new();
+        let Some(tree) = self.pak.query_tree(key)? else { return Ok(ordinals) };
+        let value = PakValue::from(value);
+        tree.range((Bound::Included(&value), Bound::Included(&value)), |_, postings| {
+            postings.for_each_ordinal_chunk(|chunk| {
+                ordinals.extend(chunk);
+                true
+            })?;
+            Ok(true)
+        })?;
+        Ok(ordinals)
+    }
+}
+
+/// Runs the search and returns the ordinals of the matching items with their scores, from the best match to the worst.
+pub(crate) fn search(pak : &Pak, key : &str, text : &str) -> PakResult<Vec<(u32, f32)>> {
+    let query = PakTextQuery::parse(text)?;
+    let mut search = PakTextSearch { pak, key : key.to_string(), texts : HashMap::new() };
+    if search.text(key)?.is_none() {
+        return Err(PakError::UnsupportedIndexOperation(key.to_string(), TEXT_KIND_NAME.to_string(), "search".to_string()))
+    }
+    let matched = search.evaluate(&query)?;
+    
+    let mut terms = BTreeMap::new();
+    query.scoring_terms(&mut terms);
+    let mut scores = matched.iter().map(|ordinal| (*ordinal, 0.0)).collect::<BTreeMap<u32, f32>>();
+    for (field, phrases) in terms {
+        let Some(text) = search.text(field.as_deref().unwrap_or(key))? else { continue };
+        let terms = text.analyzer.analyze(&phrases.join(" "))?;
+        for (ordinal, score) in text.score(&terms) {
+            if let Some(total) = scores.get_mut(&ordinal) { *total += score }
+        }
+    }
+    let mut scores = scores.into_iter().collect::<Vec<_>>();
+    scores.sort_by(|(a_ordinal, a_score), (b_ordinal, b_score)| b_score.total_cmp(a_score).then(a_ordinal.cmp(b_ordinal)));
+    Ok(scores)
+}
+
+//==============================================================================================
+//        PakHighlight
+//==============================================================================================
+
+/// A value of a text index along with the byte ranges of the words in it that match a search, in ascending order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PakHighlight {
+    pub value : String,
+    pub ranges : Vec<Range<usize>>,
+}
+
+impl PakHighlight {
+    /// A piece of the value around the first match, with up to `context` characters on either side and `…` where the value was cut. Matches that
+    /// follow within `context` characters are kept in the snippet too. Returns `None` if nothing in the value matched.
+    pub fn snippet(&self, context : usize) -> Option<String> {
+        let first = self.ranges.first()?;
+        let mut last = first.end;
+        for range in &self.ranges[1..] {
+            if self.value[last..range.start].chars().count() > context { break }
+            last = range.end;
+        }
+        let start = self.value[..first.start].char_indices().rev().take(context).last().map(|(index, _)| index).unwrap_or(first.start);
+        let end = self.value[last..].char_indices().nth(context).map(|(index, _)| last + index).unwrap_or(self.value.len());
+        
+        let mut snippet = String::new();
+        if start > 0 { snippet.push('…') }
+        snippet.push_str(&self.value[start..end]);
+        if end < self.value.len() { snippet.push('…') }
+        Some(snippet)
+    }
+}
+
+/// Finds the words of every value under the key that match the query's terms. Terms are matched one by one, so the words of a phrase are highlighted
+/// wherever they appear, and terms under a `NOT` are never highlighted.
+pub(crate) fn highlight(pak : &Pak, key : &str, text : &str, values : Vec<String>) -> PakResult<Vec<PakHighlight>> {
+    let query = PakTextQuery::parse(text)?;
+    let mut search = PakTextSearch { pak, key : key.to_string(), texts : HashMap::new() };
+    let Some(data) = search.text(key)? else {
+        return Err(PakError::UnsupportedIndexOperation(key.to_string(), TEXT_KIND_NAME.to_string(), "highlight".to_string()))
+    };
+    let analyzer = data.analyzer.prepare()?;
+    
+    let mut phrases = BTreeMap::new();
+    query.scoring_terms(&mut phrases);
+    let terms = phrases.into_iter()
+        .filter(|(field, _)| field.as_deref().is_none_or(|field| field == key))
+        .flat_map(|(_, phrases)| phrases)
+        .flat_map(|phrase| analyzer.terms(&phrase))
+        .collect::<HashSet<_>>();
+    
+    Ok(values.into_iter().map(|value| {
+        let ranges = analyzer.spans(&value).into_iter().filter(|(_, term)| terms.contains(term)).map(|(range, _)| range).collect();
+        PakHighlight { value, ranges }
+    }).collect())
+}
@@ -0,0 +1,112 @@
+use std::{collections::HashSet, time::SystemTime};
+use crate::{error::PakResult, index::PakIndex, item::{PakItemDeserializeGroup, PakItemSearchable, PakItemSerialize}, pointer::{PakPointer, PakTypedPointer}, query::{PakQuery, PakQueryExpression}, Pak, PakBuilder};
+
+/// The reserved index key that the start of each item's validity window is stored under.
+pub const PAK_VALID_FROM_KEY : &str = "__pak_valid_from";
+
+/// The reserved index key that the end of each item's validity window is stored under.
+pub const PAK_VALID_UNTIL_KEY : &str = "__pak_valid_until";
+
+//==============================================================================================
+//        PakValidity
+//==============================================================================================
+
+/// The window of time an item is active in, like an event that runs for a weekend. The window includes its start and ends right before
+/// its end, and a window without one of them is open on that side. Add items with [PakBuilder::pak_valid](crate::PakBuilder::pak_valid)
+/// and find the active ones with [Pak::query_active_at](crate::Pak::query_active_at).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PakValidity {
+    pub from : Option<SystemTime>,
+    pub until : Option<SystemTime>,
+}
+
+impl PakValidity {
+    pub fn between(from : SystemTime, until : SystemTime) -> Self {
+        Self { from : Some(from), until : Some(until) }
+    }
+
+    /// A window that starts at `from` and never ends.
+    pub fn starting(from : SystemTime) -> Self {
+        Self { from : Some(from), until : None }
+    }
+
+    /// A window that has always been open and ends at `until`.
+    pub fn ending(until : SystemTime) -> Self {
+        Self { from : None, until : Some(until) }
+    }
+
+    /// Returns true if the instant falls inside the window.
+    pub fn contains(&self, instant : SystemTime) -> bool {
+        self.from.is_none_or(|from| from <= instant) && self.until.is_none_or(|until| instant < until)
+    }
+
+    fn indices(&self) -> Vec<PakIndex> {
+        let from = self.from.map(|from| PakIndex::new(PAK_VALID_FROM_KEY, from));
+        let until = self.until.map(|until| PakIndex::new(PAK_VALID_UNTIL_KEY, until));
+        from.into_iter().chain(until).collect()
+    }
+}
+
+//==============================================================================================
+//        PakActiveAt
+//==============================================================================================
+
+/// An expression narrowed down to the items that are active at an instant, made by [Pak::query_active_at](crate::Pak::query_active_at).
+/// Items that were paked without a window are always active. Only the items whose windows have not started or have already ended are
+/// looked up, so the cost of the filter grows with the inactive items rather than with the whole pak.
+pub struct PakActiveAt<Q> {
+    query : Q,
+    instant : SystemTime,
+}
+
+impl<Q> PakActiveAt<Q> {
+    pub fn new(instant : SystemTime, query : Q) -> Self {
+        Self { query, instant }
+    }
+
+    fn not_started(&self) -> PakQuery {
+        PakQuery::greater_than(PAK_VALID_FROM_KEY, self.instant)
+    }
+
+    fn ended(&self) -> PakQuery {
+        PakQuery::less_than_or_equal(PAK_VALID_UNTIL_KEY, self.instant)
+    }
+}
+
+impl<Q> PakQueryExpression for PakActiveAt<Q> where Q : PakQueryExpression {
+    fn execute(&self, pak : &Pak) -> PakResult<HashSet<PakTypedPointer>> {
+        let mut results = self.query.execute(pak)?;
+        if results.is_empty() { return Ok(results) }
+        let indices = pak.fetch_indices()?;
+        if indices.contains_key(PAK_VALID_FROM_KEY) {
+            for pointer in self.not_started().execute(pak)? { results.remove(&pointer); }
+        }
+        if indices.contains_key(PAK_VALID_UNTIL_KEY) {
+            for pointer in self.ended().execute(pak)? { results.remove(&pointer); }
+        }
+        Ok(results)
+    }
+
+    fn matches(&self, indices : &[PakIndex]) -> bool {
+        self.query.matches(indices) && !self.not_started().matches(indices) && !self.ended().matches(indices)
+    }
+}
+
+impl PakBuilder {
+    /// Adds a searchable item that is only active within the window, so [Pak::query_active_at](crate::Pak::query_active_at) leaves it out
+    /// at any other time. Regular queries still find it.
+    pub fn pak_valid<T : PakItemSerialize + PakItemSearchable>(&mut self, item : T, validity : PakValidity) -> PakResult<PakPointer> {
+        let mut indices = item.get_indices();
+        indices.extend(validity.indices());
+        let bytes = item.into_bytes()?;
+        self.pak_internal::<T>(bytes, indices)
+    }
+}
+
+impl Pak {
+    /// Runs a query like [query](crate::Pak::query), keeping only the items that are active at the instant. Items paked with
+    /// [PakBuilder::pak_valid](crate::PakBuilder::pak_valid) are active within their window, and every other item is always active.
+    pub fn query_active_at<T>(&self, instant : SystemTime, query : impl PakQueryExpression) -> PakResult<T::ReturnType> where T : PakItemDeserializeGroup {
+        self.query::<T>(PakActiveAt::new(instant, query))
+    }
+}
@@ -4,8 +4,10 @@
 
 use std::fmt::Debug;
 use serde::{Deserialize, Serialize};
+use crate::error::PakValueConversionError;
 
-#[derive(Deserialize, Serialize, Clone, Hash, Default)]
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum PakValue {
     String(String),
     Float(u64),
@@ -13,26 +15,18 @@ pub enum PakValue {
     Uint(u64),
     Boolean(bool),
     #[default]
-    Void
+    Void,
+    /// A single precision float, stored as its bits like [Float](crate::value::PakValue::Float).
+    Float32(u32),
+    Int128(i128),
+    Uint128(u128),
+    /// An exact fixed point number, `mantissa / 10^scale`. Use this instead of floats for things like prices, where range queries need exact boundaries.
+    Decimal(i128, u8),
 }
 
 impl PartialEq for PakValue {
     fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (PakValue::String(a), PakValue::String(b)) => a == b,
-            (PakValue::Float(a), PakValue::Float(b)) => a == b,
-            (PakValue::Float(a), PakValue::Int(b)) => f64::from_bits(*a) == *b as f64,
-            (PakValue::Float(a), PakValue::Uint(b)) => f64::from_bits(*a) == *b as f64,
-            (PakValue::Int(a), PakValue::Float(b)) => *a as f64 == f64::from_bits(*b),
-            (PakValue::Int(a), PakValue::Int(b)) => a == b,
-            (PakValue::Int(a), PakValue::Uint(b)) => *a == *b as i64,
-            (PakValue::Uint(a), PakValue::Float(b)) => *a as f64 == f64::from_bits(*b),
-            (PakValue::Uint(a), PakValue::Int(b)) => *a as i64 == *b,
-            (PakValue::Uint(a), PakValue::Uint(b)) => a == b,
-            (PakValue::Boolean(a), PakValue::Boolean(b)) => a == b,
-            (PakValue::Void, PakValue::Void) => true,
-            _ => false,
-        }
+        self.compare(other) == Some(std::cmp::Ordering::Equal)
     }
 }
 
@@ -45,38 +39,182 @@ impl Debug for PakValue {
             PakValue::Uint(uint) => uint.fmt(f),
             PakValue::Boolean(boolean) => boolean.fmt(f),
             PakValue::Void => f.write_str("Void"),
+            PakValue::Float32(float) => float.fmt(f),
+            PakValue::Int128(int) => int.fmt(f),
+            PakValue::Uint128(uint) => uint.fmt(f),
+            PakValue::Decimal(mantissa, scale) => {
+                let digits = mantissa.unsigned_abs().to_string();
+                let scale = *scale as usize;
+                let sign = if *mantissa < 0 { "-" } else { "" };
+                if scale == 0 { return write!(f, "{sign}{digits}") }
+                let digits = format!("{digits:0>width$}", width = scale + 1);
+                let (whole, fraction) = digits.split_at(digits.len() - scale);
+                write!(f, "{sign}{whole}.{fraction}")
+            },
         }
     }
 }
 
 impl PartialOrd for PakValue {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Eq for PakValue {}
+
+impl Ord for PakValue {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // Values of different kinds can't be compared directly, so they are ordered by kind instead. Without this they would all compare as
+        // equal, and the tree would merge them into a single entry.
+        self.compare(other).unwrap_or_else(|| self.kind_rank().cmp(&other.kind_rank()))
+    }
+}
+
+impl std::hash::Hash for PakValue {
+    /// Hashes the value the same way as every other value it is equal to, so `Int(1)`, `Float(1.0)` and `Decimal(10, 1)` all hash the same.
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.kind_rank().hash(state);
+        match self {
+            PakValue::String(string) => string.hash(state),
+            PakValue::Boolean(boolean) => boolean.hash(state),
+            PakValue::Void => {},
+            numeric => if let Some(number) = numeric.numeric() { number.exact().hash(state) },
+        }
+    }
+}
+
+impl PakValue {
+    fn kind_rank(&self) -> u8 {
+        match self {
+            PakValue::Void => 0,
+            PakValue::Boolean(_) => 1,
+            PakValue::Float(_) | PakValue::Int(_) | PakValue::Uint(_) | PakValue::Float32(_) | PakValue::Int128(_) | PakValue::Uint128(_) | PakValue::Decimal(_, _) => 2,
+            PakValue::String(_) => 3,
+        }
+    }
+    
+    /// Compares two values of the same kind. Numbers are compared by their exact mathematical value no matter which variant holds them, with
+    /// `-0.0` equal to `0.0` and every NaN equal to each other and greater than every other number. This gives a total order, which the trees rely on.
+    fn compare(&self, other: &Self) -> Option<std::cmp::Ordering> {
         match (self, other) {
             (PakValue::String(a), PakValue::String(b)) => a.partial_cmp(b),
-            (PakValue::Float(a), PakValue::Float(b)) => a.partial_cmp(b),
-            (PakValue::Float(a), PakValue::Int(b)) => f64::from_bits(*a).partial_cmp(&(*b as f64)),
-            (PakValue::Float(a), PakValue::Uint(b)) => f64::from_bits(*a).partial_cmp(&(*b as f64)),
-            (PakValue::Int(a), PakValue::Float(b)) => (*a as f64).partial_cmp(&f64::from_bits(*b)),
-            (PakValue::Int(a), PakValue::Int(b)) => a.partial_cmp(b),
-            (PakValue::Int(a), PakValue::Uint(b)) => (*a as i64).partial_cmp(&(*b as i64)),
-            (PakValue::Uint(a), PakValue::Float(b)) => (*a as f64).partial_cmp(&f64::from_bits(*b)),
-            (PakValue::Uint(a), PakValue::Int(b)) => (*a as i64).partial_cmp(&(*b as i64)),
-            (PakValue::Uint(a), PakValue::Uint(b)) => a.partial_cmp(b),
             (PakValue::Boolean(a), PakValue::Boolean(b)) => a.partial_cmp(b),
             (PakValue::Void, PakValue::Void) => Some(std::cmp::Ordering::Equal),
+            (a, b) => Some(a.numeric()?.compare(&b.numeric()?)),
+        }
+    }
+    
+    fn numeric(&self) -> Option<PakNumeric> {
+        match self {
+            PakValue::Float(bits) => Some(PakNumeric::Float(f64::from_bits(*bits))),
+            PakValue::Float32(bits) => Some(PakNumeric::Float(f32::from_bits(*bits) as f64)),
+            PakValue::Int(value) => Some(PakNumeric::Int(*value as i128)),
+            PakValue::Int128(value) => Some(PakNumeric::Int(*value)),
+            PakValue::Uint(value) => Some(PakNumeric::Uint(*value as u128)),
+            PakValue::Uint128(value) => Some(PakNumeric::Uint(*value)),
+            PakValue::Decimal(mantissa, scale) => Some(PakNumeric::Decimal(*mantissa, *scale)),
             _ => None,
         }
     }
 }
 
-impl Eq for PakValue {}
+/// Every numeric variant widened to the largest type of its family, so integers of different widths and signs compare exactly.
+enum PakNumeric {
+    Int(i128),
+    Uint(u128),
+    Float(f64),
+    Decimal(i128, u8),
+}
 
-impl Ord for PakValue {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other).unwrap_or(std::cmp::Ordering::Equal)
+impl PakNumeric {
+    fn compare(&self, other : &Self) -> std::cmp::Ordering {
+        use std::cmp::Ordering;
+        match (self, other) {
+            (PakNumeric::Int(a), PakNumeric::Int(b)) => a.cmp(b),
+            (PakNumeric::Uint(a), PakNumeric::Uint(b)) => a.cmp(b),
+            (PakNumeric::Int(a), PakNumeric::Uint(b)) => if *a < 0 { Ordering::Less } else { (*a as u128).cmp(b) },
+            (PakNumeric::Uint(_), PakNumeric::Int(_)) => other.compare(self).reverse(),
+            (PakNumeric::Float(a), PakNumeric::Float(b)) => match (a.is_nan(), b.is_nan()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+            },
+            _ => self.exact().cmp(&other.exact()),
+        }
+    }
+    
+    /// The exact value of the number. This is slower than comparing the variants directly, so it is only used when the families differ.
+    fn exact(&self) -> PakExact {
+        match self {
+            PakNumeric::Int(int) if *int < 0 => PakExact::Negative(std::cmp::Reverse(PakMagnitude::Finite(int.unsigned_abs(), String::new()))),
+            PakNumeric::Int(int) => PakExact::Positive(PakMagnitude::Finite(*int as u128, String::new())),
+            PakNumeric::Uint(uint) => PakExact::Positive(PakMagnitude::Finite(*uint, String::new())),
+            PakNumeric::Decimal(mantissa, scale) => {
+                let digits = mantissa.unsigned_abs();
+                let (whole, fraction) = match 10u128.checked_pow(*scale as u32) {
+                    Some(power) => (digits / power, digits % power),
+                    None => (0, digits),
+                };
+                let fraction = format!("{fraction:0>width$}", width = *scale as usize).trim_end_matches('0').to_string();
+                PakExact::signed(*mantissa < 0, PakMagnitude::Finite(whole, fraction))
+            },
+            PakNumeric::Float(float) if float.is_nan() => PakExact::NaN,
+            PakNumeric::Float(float) => {
+                let magnitude = float.abs();
+                let magnitude = if magnitude.is_infinite() {
+                    PakMagnitude::Infinite
+                } else if magnitude >= 2f64.powi(128) {
+                    PakMagnitude::Huge(magnitude.to_bits())
+                } else {
+                    let whole = magnitude.trunc();
+                    let fraction = magnitude - whole;
+                    let fraction = if fraction == 0.0 { String::new() } else {
+                        // A float's fraction has exactly as many decimal digits as it has binary digits, so this prints it without rounding.
+                        let printed = format!("{fraction:.precision$}", precision = fraction_bits(fraction));
+                        printed.trim_start_matches("0.").trim_end_matches('0').to_string()
+                    };
+                    PakMagnitude::Finite(whole as u128, fraction)
+                };
+                PakExact::signed(float.is_sign_negative(), magnitude)
+            },
+        }
+    }
+}
+
+/// The number of binary digits after the point that it takes to write out a float between 0 and 1.
+fn fraction_bits(fraction : f64) -> usize {
+    let bits = fraction.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as i64;
+    let mantissa = bits & ((1 << 52) - 1);
+    let (mantissa, exponent) = if exponent == 0 { (mantissa, -1074) } else { (mantissa | (1 << 52), exponent - 1075) };
+    (-(exponent + mantissa.trailing_zeros() as i64)).max(0) as usize
+}
+
+/// A number in a form where equal values are always identical, no matter which variant they came from. Deriving the order and hash from it
+/// keeps [PakValue](crate::value::PakValue)'s `Eq`, `Ord` and `Hash` consistent with each other.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum PakExact {
+    Negative(std::cmp::Reverse<PakMagnitude>),
+    Positive(PakMagnitude),
+    NaN,
+}
+
+impl PakExact {
+    fn signed(negative : bool, magnitude : PakMagnitude) -> Self {
+        if negative && magnitude != PakMagnitude::Finite(0, String::new()) { PakExact::Negative(std::cmp::Reverse(magnitude)) } else { PakExact::Positive(magnitude) }
     }
 }
 
+/// The size of a number. Finite numbers are split into their whole part and the decimal digits of their fraction, without trailing zeros,
+/// which compare correctly as strings. Floats too large for a u128 are kept as their bits, which order the same as their values.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum PakMagnitude {
+    Finite(u128, String),
+    Huge(u64),
+    Infinite,
+}
 
 impl PakValue {
     pub fn as_string(&self) -> Option<String> {
@@ -89,6 +227,7 @@ impl PakValue {
     pub fn as_f64(&self) -> Option<f64> {
         match self {
             PakValue::Float(bits) => Some(f64::from_bits(*bits)),
+            PakValue::Float32(bits) => Some(f32::from_bits(*bits) as f64),
             _ => None,
         }
     }
@@ -96,6 +235,23 @@ impl PakValue {
     pub fn as_f32(&self) -> Option<f32> {
         match self {
             PakValue::Float(bits) => Some(f64::from_bits(*bits) as f32),
+            PakValue::Float32(bits) => Some(f32::from_bits(*bits)),
+            _ => None,
+        }
+    }
+    
+    pub fn as_u128(&self) -> Option<u128> {
+        match self {
+            PakValue::Uint(value) => Some(*value as u128),
+            PakValue::Uint128(value) => Some(*value),
+            _ => None,
+        }
+    }
+    
+    pub fn as_i128(&self) -> Option<i128> {
+        match self {
+            PakValue::Int(value) => Some(*value as i128),
+            PakValue::Int128(value) => Some(*value),
             _ => None,
         }
     }
@@ -156,6 +312,22 @@ impl PakValue {
         }
     }
 
+    /// Returns the mantissa and scale of a decimal value.
+    pub fn as_decimal_parts(&self) -> Option<(i128, u8)> {
+        match self {
+            PakValue::Decimal(mantissa, scale) => Some((*mantissa, *scale)),
+            _ => None,
+        }
+    }
+    
+    #[cfg(feature = "rust_decimal")]
+    pub fn as_decimal(&self) -> Option<rust_decimal::Decimal> {
+        match self {
+            PakValue::Decimal(mantissa, scale) => rust_decimal::Decimal::try_from_i128_with_scale(*mantissa, *scale as u32).ok(),
+            _ => None,
+        }
+    }
+
     pub fn as_bool(&self) -> Option<bool> {
         match self {
             PakValue::Boolean(value) => Some(*value),
@@ -163,6 +335,23 @@ impl PakValue {
         }
     }
     
+    /// Returns true if this value is a float, int or uint of any width.
+    pub fn is_numeric(&self) -> bool {
+        self.numeric().is_some()
+    }
+    
+    /// Returns the [PakValueKind](crate::value::PakValueKind) of this value.
+    pub fn kind(&self) -> PakValueKind {
+        match self {
+            PakValue::String(_) => PakValueKind::String,
+            PakValue::Float(_) | PakValue::Float32(_) => PakValueKind::Float,
+            PakValue::Decimal(_, _) => PakValueKind::Decimal,
+            PakValue::Int(_) | PakValue::Uint(_) | PakValue::Int128(_) | PakValue::Uint128(_) => PakValueKind::Int,
+            PakValue::Boolean(_) => PakValueKind::Boolean,
+            PakValue::Void => PakValueKind::Void,
+        }
+    }
+    
     pub fn float(float : impl Into<f64>) -> Self {
         let f : f64 = float.into();
         Self::Float(f.to_bits())
@@ -173,12 +362,79 @@ impl PakValue {
         Self::Int(i)
     }
     
+    /// Creates a decimal value equal to `mantissa / 10^scale`.
+    pub fn decimal(mantissa : impl Into<i128>, scale : u8) -> Self {
+        Self::Decimal(mantissa.into(), scale)
+    }
+    
     pub fn uint(integer : impl Into<u64>) -> Self {
         let i : u64 = integer.into();
         Self::Uint(i)
     }
 }
 
+//==============================================================================================
+//        PakValueKind
+//==============================================================================================
+
+/// The kind of values that an index holds. Every index records its kind in the pak's header, so queries that compare against the wrong
+/// kind of value can fail instead of quietly matching nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PakValueKind {
+    /// Only void values, or no values at all.
+    Void,
+    Boolean,
+    /// Signed or unsigned integers.
+    Int,
+    /// Floats, or a mix of different kinds of numbers.
+    Float,
+    /// Fixed point decimals.
+    Decimal,
+    String,
+    /// Values of kinds that can't be compared with each other.
+    Mixed,
+}
+
+impl PakValueKind {
+    /// Combines the kinds of two values stored in the same index. Void values don't change the kind, since they stand in for missing values.
+    pub fn merge(self, other : PakValueKind) -> PakValueKind {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (PakValueKind::Void, kind) | (kind, PakValueKind::Void) => kind,
+            (a, b) if a.is_numeric() && b.is_numeric() => PakValueKind::Float,
+            _ => PakValueKind::Mixed,
+        }
+    }
+    
+    /// Returns true if an index of this kind can be queried with a value of the other kind.
+    pub fn accepts(self, other : PakValueKind) -> bool {
+        self == other
+            || self == PakValueKind::Mixed
+            || self == PakValueKind::Void
+            || other == PakValueKind::Void
+            || (self.is_numeric() && other.is_numeric())
+    }
+    
+    fn is_numeric(self) -> bool {
+        matches!(self, PakValueKind::Int | PakValueKind::Float | PakValueKind::Decimal)
+    }
+}
+
+impl std::fmt::Display for PakValueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            PakValueKind::Void => "void",
+            PakValueKind::Boolean => "boolean",
+            PakValueKind::Int => "int",
+            PakValueKind::Float => "float",
+            PakValueKind::Decimal => "decimal",
+            PakValueKind::String => "string",
+            PakValueKind::Mixed => "mixed",
+        };
+        f.write_str(name)
+    }
+}
+
 //==============================================================================================
 //        Easy of use Traits
 //==============================================================================================
@@ -222,7 +478,26 @@ impl From<f64> for PakValue {
 
 impl From<f32> for PakValue {
     fn from(value: f32) -> Self {
-        PakValue::Float((value as f64).to_bits())
+        PakValue::Float32(value.to_bits())
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+impl From<rust_decimal::Decimal> for PakValue {
+    fn from(value: rust_decimal::Decimal) -> Self {
+        PakValue::Decimal(value.mantissa(), value.scale() as u8)
+    }
+}
+
+impl From<i128> for PakValue {
+    fn from(value: i128) -> Self {
+        PakValue::Int128(value)
+    }
+}
+
+impl From<u128> for PakValue {
+    fn from(value: u128) -> Self {
+        PakValue::Uint128(value)
     }
 }
 
@@ -252,7 +527,7 @@ impl From<i8> for PakValue {
 
 impl From<u64> for PakValue {
     fn from(value: u64) -> Self {
-        PakValue::Uint(value as u64)
+        PakValue::Uint(value)
     }
 }
 
@@ -278,4 +553,303 @@ impl From<bool> for PakValue {
     fn from(value: bool) -> Self {
         PakValue::Boolean(value)
     }
-}
\ No newline at end of file
+}
+
+impl<'s> From<&'s String> for PakValue {
+    fn from(value: &'s String) -> Self {
+        PakValue::String(value.clone())
+    }
+}
+
+impl<'s> From<std::borrow::Cow<'s, str>> for PakValue {
+    fn from(value: std::borrow::Cow<'s, str>) -> Self {
+        PakValue::String(value.into_owned())
+    }
+}
+
+impl From<char> for PakValue {
+    fn from(value: char) -> Self {
+        PakValue::String(value.to_string())
+    }
+}
+
+macro_rules! impl_from_non_zero {
+    ($($ty:ty),*) => {$(
+        impl From<$ty> for PakValue {
+            fn from(value: $ty) -> Self {
+                PakValue::from(value.get())
+            }
+        }
+    )*};
+}
+
+impl_from_non_zero!(
+    std::num::NonZeroU8, std::num::NonZeroU16, std::num::NonZeroU32, std::num::NonZeroU64, std::num::NonZeroU128,
+    std::num::NonZeroI8, std::num::NonZeroI16, std::num::NonZeroI32, std::num::NonZeroI64, std::num::NonZeroI128
+);
+
+/// Durations are stored as a whole number of nanoseconds.
+impl From<std::time::Duration> for PakValue {
+    fn from(value: std::time::Duration) -> Self {
+        PakValue::Uint128(value.as_nanos())
+    }
+}
+
+/// Times are stored as the number of nanoseconds since the unix epoch, which is negative for times before it.
+impl From<std::time::SystemTime> for PakValue {
+    fn from(value: std::time::SystemTime) -> Self {
+        match value.duration_since(std::time::UNIX_EPOCH) {
+            Ok(since) => PakValue::Int128(since.as_nanos() as i128),
+            Err(before) => PakValue::Int128(-(before.duration().as_nanos() as i128)),
+        }
+    }
+}
+
+/// Paths are stored as strings. Parts of the path that aren't valid unicode are replaced.
+impl<'p> From<&'p std::path::Path> for PakValue {
+    fn from(value: &'p std::path::Path) -> Self {
+        PakValue::String(value.to_string_lossy().into_owned())
+    }
+}
+
+impl From<std::path::PathBuf> for PakValue {
+    fn from(value: std::path::PathBuf) -> Self {
+        PakValue::from(value.as_path())
+    }
+}
+
+/// Pointers are stored as the offset of the item they point to, so items can be indexed by the items they reference.
+impl<'p> From<&'p crate::pointer::PakPointer> for PakValue {
+    fn from(value: &'p crate::pointer::PakPointer) -> Self {
+        PakValue::Uint(value.offset())
+    }
+}
+
+impl<'p, T> From<&'p crate::pointer::PakRef<T>> for PakValue {
+    fn from(value: &'p crate::pointer::PakRef<T>) -> Self {
+        PakValue::from(value.pointer())
+    }
+}
+
+/// Byte arrays, like hashes, are stored as lowercase hex strings. Arrays of the same length sort in the same order as their bytes.
+impl<const N : usize> From<[u8; N]> for PakValue {
+    fn from(value: [u8; N]) -> Self {
+        PakValue::String(value.iter().map(|byte| format!("{byte:02x}")).collect())
+    }
+}
+
+//==============================================================================================
+//        TryFrom<PakValue>
+//==============================================================================================
+
+impl PakValue {
+    fn kind_mismatch<T>(&self) -> PakValueConversionError {
+        PakValueConversionError::KindMismatch(self.kind().to_string(), std::any::type_name::<T>().to_string())
+    }
+    
+    fn out_of_range<T>(&self) -> PakValueConversionError {
+        PakValueConversionError::OutOfRange(format!("{self:?}"), std::any::type_name::<T>().to_string())
+    }
+}
+
+/// Integers convert from any integer variant as long as the value fits, and never from floats or decimals.
+macro_rules! impl_try_from_integer {
+    ($($ty:ty),*) => {$(
+        impl TryFrom<&PakValue> for $ty {
+            type Error = PakValueConversionError;
+            
+            fn try_from(value: &PakValue) -> Result<Self, Self::Error> {
+                let converted = match value {
+                    PakValue::Int(int) => <$ty>::try_from(*int).ok(),
+                    PakValue::Int128(int) => <$ty>::try_from(*int).ok(),
+                    PakValue::Uint(uint) => <$ty>::try_from(*uint).ok(),
+                    PakValue::Uint128(uint) => <$ty>::try_from(*uint).ok(),
+                    _ => return Err(value.kind_mismatch::<$ty>()),
+                };
+                converted.ok_or_else(|| value.out_of_range::<$ty>())
+            }
+        }
+        
+        impl TryFrom<PakValue> for $ty {
+            type Error = PakValueConversionError;
+            
+            fn try_from(value: PakValue) -> Result<Self, Self::Error> {
+                <$ty>::try_from(&value)
+            }
+        }
+    )*};
+}
+
+impl_try_from_integer!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+impl TryFrom<&PakValue> for f64 {
+    type Error = PakValueConversionError;
+    
+    fn try_from(value: &PakValue) -> Result<Self, Self::Error> {
+        match value {
+            PakValue::Float(bits) => Ok(f64::from_bits(*bits)),
+            PakValue::Float32(bits) => Ok(f32::from_bits(*bits) as f64),
+            _ => Err(value.kind_mismatch::<f64>()),
+        }
+    }
+}
+
+impl TryFrom<&PakValue> for f32 {
+    type Error = PakValueConversionError;
+    
+    /// Doubles only convert if they can be represented exactly as a single precision float.
+    fn try_from(value: &PakValue) -> Result<Self, Self::Error> {
+        match value {
+            PakValue::Float32(bits) => Ok(f32::from_bits(*bits)),
+            PakValue::Float(bits) => {
+                let float = f64::from_bits(*bits);
+                let narrowed = float as f32;
+                if narrowed as f64 == float || float.is_nan() { Ok(narrowed) } else { Err(value.out_of_range::<f32>()) }
+            },
+            _ => Err(value.kind_mismatch::<f32>()),
+        }
+    }
+}
+
+impl TryFrom<&PakValue> for bool {
+    type Error = PakValueConversionError;
+    
+    fn try_from(value: &PakValue) -> Result<Self, Self::Error> {
+        match value {
+            PakValue::Boolean(boolean) => Ok(*boolean),
+            _ => Err(value.kind_mismatch::<bool>()),
+        }
+    }
+}
+
+impl TryFrom<&PakValue> for String {
+    type Error = PakValueConversionError;
+    
+    fn try_from(value: &PakValue) -> Result<Self, Self::Error> {
+        match value {
+            PakValue::String(string) => Ok(string.clone()),
+            _ => Err(value.kind_mismatch::<String>()),
+        }
+    }
+}
+
+impl TryFrom<PakValue> for String {
+    type Error = PakValueConversionError;
+    
+    fn try_from(value: PakValue) -> Result<Self, Self::Error> {
+        match value {
+            PakValue::String(string) => Ok(string),
+            _ => Err(value.kind_mismatch::<String>()),
+        }
+    }
+}
+
+macro_rules! impl_try_from_owned {
+    ($($ty:ty),*) => {$(
+        impl TryFrom<PakValue> for $ty {
+            type Error = PakValueConversionError;
+            
+            fn try_from(value: PakValue) -> Result<Self, Self::Error> {
+                <$ty>::try_from(&value)
+            }
+        }
+    )*};
+}
+
+impl_try_from_owned!(f64, f32, bool);
+
+//==============================================================================================
+//        JSON
+//==============================================================================================
+
+#[cfg(feature = "json")]
+impl PakValue {
+    /// Converts the value into JSON. Integers that don't fit in 64 bits and decimals become strings so no precision is lost, and void becomes
+    /// null. Floats that are NaN or infinite can't be represented in JSON and fail to convert.
+    pub fn to_json(&self) -> Result<serde_json::Value, PakValueConversionError> {
+        use serde_json::Value;
+        Ok(match self {
+            PakValue::String(string) => Value::String(string.clone()),
+            PakValue::Float(bits) => {
+                let number = serde_json::Number::from_f64(f64::from_bits(*bits)).ok_or_else(|| self.out_of_range::<serde_json::Number>())?;
+                Value::Number(number)
+            },
+            PakValue::Float32(bits) => {
+                let number = serde_json::Number::from_f64(f32::from_bits(*bits) as f64).ok_or_else(|| self.out_of_range::<serde_json::Number>())?;
+                Value::Number(number)
+            },
+            PakValue::Int(int) => Value::from(*int),
+            PakValue::Uint(uint) => Value::from(*uint),
+            PakValue::Int128(int) => match (i64::try_from(*int), u64::try_from(*int)) {
+                (Ok(int), _) => Value::from(int),
+                (_, Ok(uint)) => Value::from(uint),
+                _ => Value::String(int.to_string()),
+            },
+            PakValue::Uint128(uint) => match u64::try_from(*uint) {
+                Ok(uint) => Value::from(uint),
+                Err(_) => Value::String(uint.to_string()),
+            },
+            PakValue::Decimal(_, _) => Value::String(format!("{self:?}")),
+            PakValue::Boolean(boolean) => Value::Bool(*boolean),
+            PakValue::Void => Value::Null,
+        })
+    }
+    
+    /// Converts a JSON value into a pak value. Whole numbers become [Int](crate::value::PakValue::Int) when they fit, or
+    /// [Uint](crate::value::PakValue::Uint) when they are too large, and every other number becomes a [Float](crate::value::PakValue::Float).
+    /// Arrays and objects have no pak value and fail to convert.
+    pub fn from_json(value : &serde_json::Value) -> Result<PakValue, PakValueConversionError> {
+        use serde_json::Value;
+        match value {
+            Value::Null => Ok(PakValue::Void),
+            Value::Bool(boolean) => Ok(PakValue::Boolean(*boolean)),
+            Value::String(string) => Ok(PakValue::String(string.clone())),
+            Value::Number(number) => {
+                if let Some(int) = number.as_i64() { return Ok(PakValue::Int(int)) }
+                if let Some(uint) = number.as_u64() { return Ok(PakValue::Uint(uint)) }
+                match number.as_f64() {
+                    Some(float) => Ok(PakValue::from(float)),
+                    None => Err(PakValueConversionError::OutOfRange(number.to_string(), "PakValue".to_string())),
+                }
+            },
+            Value::Array(_) => Err(PakValueConversionError::KindMismatch("array".to_string(), "PakValue".to_string())),
+            Value::Object(_) => Err(PakValueConversionError::KindMismatch("object".to_string(), "PakValue".to_string())),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl TryFrom<&PakValue> for serde_json::Value {
+    type Error = PakValueConversionError;
+    
+    fn try_from(value: &PakValue) -> Result<Self, Self::Error> {
+        value.to_json()
+    }
+}
+
+#[cfg(feature = "json")]
+impl TryFrom<PakValue> for serde_json::Value {
+    type Error = PakValueConversionError;
+    
+    fn try_from(value: PakValue) -> Result<Self, Self::Error> {
+        value.to_json()
+    }
+}
+
+#[cfg(feature = "json")]
+impl TryFrom<&serde_json::Value> for PakValue {
+    type Error = PakValueConversionError;
+    
+    fn try_from(value: &serde_json::Value) -> Result<Self, Self::Error> {
+        PakValue::from_json(value)
+    }
+}
+
+#[cfg(feature = "json")]
+impl TryFrom<serde_json::Value> for PakValue {
+    type Error = PakValueConversionError;
+    
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        PakValue::from_json(&value)
+    }
+}
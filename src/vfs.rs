@@ -0,0 +1,237 @@
+use std::{cell::RefCell, collections::BTreeMap, ffi::OsStr, path::Path, time::{Duration, UNIX_EPOCH}};
+use fuser::{FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry, Request};
+use crate::{error::{PakError, PakResult}, pointer::{to_usize, PakPointer}, Pak};
+
+/// The inode of the root directory, which is 1 in FUSE.
+pub const PAK_ROOT_INODE : u64 = 1;
+
+/// How long the kernel may cache the entries and attributes it was given. A mounted pak never changes, so they stay valid.
+const PAK_FUSE_TTL : Duration = Duration::from_secs(60);
+
+//==============================================================================================
+//        PakFileSystem
+//==============================================================================================
+
+/// A read-only filesystem over the items of a pak that have a path, for mounting a pak so other tools can browse it without extracting it.
+/// Every path becomes a file, and the segments before the last one become directories. The operations match the ones a read-only FUSE
+/// filesystem has to answer, lookup, getattr, readdir and read, and inodes are numbered the same way, starting from
+/// [PAK_ROOT_INODE](crate::vfs::PAK_ROOT_INODE). Files read the bytes of their item, which for a [PakBlob](crate::path::PakBlob) are
+/// the bytes of the original file. [mount](crate::vfs::PakFileSystem::mount) serves it through FUSE.
+pub struct PakFileSystem<'p> {
+    pak : &'p Pak,
+    nodes : Vec<PakNode>,
+    /// The inode and the bytes of the last file read that is encrypted or compressed. FUSE reads a file a piece at a time, and those files
+    /// can only be decoded as a whole, so each piece is cut from the bytes decoded for the first one.
+    decoded : RefCell<Option<(u64, Vec<u8>)>>,
+}
+
+struct PakNode {
+    parent : u64,
+    name : String,
+    kind : PakNodeKind,
+}
+
+enum PakNodeKind {
+    Directory(BTreeMap<String, u64>),
+    File(PakPointer, u64),
+}
+
+/// Whether an inode is a directory or a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PakFileKind {
+    Directory,
+    File,
+}
+
+/// What [getattr](crate::vfs::PakFileSystem::getattr) knows about an inode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PakFileAttr {
+    pub inode : u64,
+    pub kind : PakFileKind,
+    pub size : u64,
+}
+
+impl<'p> PakFileSystem<'p> {
+    /// Builds the directory tree from every path in the pak. A path that is also the directory of another path, like `a` next to `a/b`,
+    /// fails with [PakError::PathConflict](crate::error::PakError::PathConflict), since a filesystem can't show both.
+    pub fn new(pak : &'p Pak) -> PakResult<Self> {
+        let mut system = Self { pak, nodes : vec![PakNode { parent : PAK_ROOT_INODE, name : String::new(), kind : PakNodeKind::Directory(BTreeMap::new()) }], decoded : RefCell::new(None) };
+        for (path, pointer) in pak.paths()? {
+            let segments = path.split('/').collect::<Vec<_>>();
+            let mut parent = PAK_ROOT_INODE;
+            for (depth, segment) in segments[..segments.len() - 1].iter().enumerate() {
+                parent = match system.entry(parent, segment) {
+                    Some((inode, PakFileKind::Directory)) => inode,
+                    Some((_, PakFileKind::File)) => return Err(PakError::PathConflict(segments[..=depth].join("/"))),
+                    None => system.insert(parent, segment, PakNodeKind::Directory(BTreeMap::new())),
+                };
+            }
+            if system.entry(parent, segments[segments.len() - 1]).is_some() { return Err(PakError::PathConflict(path)) }
+            let size = pak.item_len(&pointer)?;
+            system.insert(parent, segments[segments.len() - 1], PakNodeKind::File(pointer, size));
+        }
+        Ok(system)
+    }
+    
+    fn node(&self, inode : u64) -> Option<&PakNode> {
+        self.nodes.get(to_usize(inode.checked_sub(PAK_ROOT_INODE)?).ok()?)
+    }
+    
+    fn child(&self, parent : u64, name : &str) -> Option<u64> {
+        match &self.node(parent)?.kind {
+            PakNodeKind::Directory(children) => children.get(name).copied(),
+            PakNodeKind::File(..) => None,
+        }
+    }
+    
+    fn entry(&self, parent : u64, name : &str) -> Option<(u64, PakFileKind)> {
+        let inode = self.child(parent, name)?;
+        Some((inode, self.getattr(inode)?.kind))
+    }
+    
+    fn insert(&mut self, parent : u64, name : &str, kind : PakNodeKind) -> u64 {
+        let inode = self.nodes.len() as u64 + PAK_ROOT_INODE;
+        self.nodes.push(PakNode { parent, name : name.to_string(), kind });
+        if let Some(PakNode { kind : PakNodeKind::Directory(children), .. }) = to_usize(parent - PAK_ROOT_INODE).ok().and_then(|index| self.nodes.get_mut(index)) {
+            children.insert(name.to_string(), inode);
+        }
+        inode
+    }
+    
+    /// Finds the entry with the name in the directory.
+    pub fn lookup(&self, parent : u64, name : &str) -> Option<PakFileAttr> {
+        self.getattr(self.child(parent, name)?)
+    }
+    
+    /// The kind and size of the inode. Directories have a size of 0.
+    pub fn getattr(&self, inode : u64) -> Option<PakFileAttr> {
+        let (kind, size) = match &self.node(inode)?.kind {
+            PakNodeKind::Directory(_) => (PakFileKind::Directory, 0),
+            PakNodeKind::File(_, size) => (PakFileKind::File, *size),
+        };
+        Some(PakFileAttr { inode, kind, size })
+    }
+    
+    /// The entries of the directory, including `.` and `..`, ordered by name after those two.
+    pub fn readdir(&self, inode : u64) -> Option<Vec<(u64, PakFileKind, String)>> {
+        let node = self.node(inode)?;
+        let PakNodeKind::Directory(children) = &node.kind else { return None };
+        let mut entries = vec![(inode, PakFileKind::Directory, ".".to_string()), (node.parent, PakFileKind::Directory, "..".to_string())];
+        for (name, child) in children {
+            entries.push((*child, self.getattr(*child)?.kind, name.clone()));
+        }
+        Some(entries)
+    }
+    
+    /// Reads up to `size` bytes of the file from `offset`. Reads past the end of the file return fewer bytes, or none.
+    pub fn read(&self, inode : u64, offset : u64, size : u64) -> PakResult<Option<Vec<u8>>> {
+        use std::io::{Read, Seek, SeekFrom};
+        let Some(PakNode { kind : PakNodeKind::File(pointer, len), .. }) = self.node(inode) else { return Ok(None) };
+        let count = size.min(len.saturating_sub(offset));
+        if self.pak.decodes_whole(pointer.offset()) {
+            let mut decoded = self.decoded.borrow_mut();
+            let bytes = match decoded.take() {
+                Some((cached, bytes)) if cached == inode => bytes,
+                _ => self.pak.read_bytes(pointer)?,
+            };
+            let start = to_usize(offset.min(*len))?;
+            let piece = bytes.get(start..start + to_usize(count)?).map(<[u8]>::to_vec);
+            *decoded = Some((inode, bytes));
+            return piece.map(Some).ok_or(PakError::PointerOutOfBounds(pointer.offset() + offset, count))
+        }
+        let mut window = self.pak.window(pointer);
+        window.seek(SeekFrom::Start(offset))?;
+        let mut bytes = vec![0u8; to_usize(count)?];
+        window.read_exact(&mut bytes)?;
+        Ok(Some(bytes))
+    }
+    
+    /// The full path of the inode, which is empty for the root.
+    pub fn path(&self, inode : u64) -> Option<String> {
+        let mut segments = Vec::new();
+        let mut current = inode;
+        while current != PAK_ROOT_INODE {
+            let node = self.node(current)?;
+            segments.push(node.name.as_str());
+            current = node.parent;
+        }
+        segments.reverse();
+        Some(segments.join("/"))
+    }
+}
+
+//==============================================================================================
+//        FUSE
+//==============================================================================================
+
+impl PakFileSystem<'_> {
+    /// Mounts the filesystem read-only at the mountpoint and serves it until it is unmounted, blocking the calling thread. Mounting goes
+    /// through the `fusermount3` helper, so it needs FUSE installed, on Linux or on macOS with macFUSE.
+    pub fn mount(self, mountpoint : impl AsRef<Path>) -> PakResult<()> {
+        let options = [MountOption::RO, MountOption::FSName("pak".to_string()), MountOption::Subtype("pak-db".to_string())];
+        fuser::mount2(self, mountpoint, &options)?;
+        Ok(())
+    }
+    
+    fn fuse_attr(attr : PakFileAttr, request : &Request<'_>) -> FileAttr {
+        let (kind, perm, nlink) = match attr.kind {
+            PakFileKind::Directory => (FileType::Directory, 0o555, 2),
+            PakFileKind::File => (FileType::RegularFile, 0o444, 1),
+        };
+        FileAttr {
+            ino : attr.inode,
+            size : attr.size,
+            blocks : attr.size.div_ceil(512),
+            atime : UNIX_EPOCH,
+            mtime : UNIX_EPOCH,
+            ctime : UNIX_EPOCH,
+            crtime : UNIX_EPOCH,
+            kind,
+            perm,
+            nlink,
+            uid : request.uid(),
+            gid : request.gid(),
+            rdev : 0,
+            blksize : 512,
+            flags : 0,
+        }
+    }
+}
+
+impl Filesystem for PakFileSystem<'_> {
+    fn lookup(&mut self, request : &Request<'_>, parent : u64, name : &OsStr, reply : ReplyEntry) {
+        match name.to_str().and_then(|name| PakFileSystem::lookup(self, parent, name)) {
+            Some(attr) => reply.entry(&PAK_FUSE_TTL, &Self::fuse_attr(attr, request), 0),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+    
+    fn getattr(&mut self, request : &Request<'_>, inode : u64, _handle : Option<u64>, reply : ReplyAttr) {
+        match PakFileSystem::getattr(self, inode) {
+            Some(attr) => reply.attr(&PAK_FUSE_TTL, &Self::fuse_attr(attr, request)),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+    
+    fn read(&mut self, _request : &Request<'_>, inode : u64, _handle : u64, offset : i64, size : u32, _flags : i32, _lock : Option<u64>, reply : ReplyData) {
+        let Ok(offset) = u64::try_from(offset) else { return reply.error(libc::EINVAL) };
+        match PakFileSystem::read(self, inode, offset, size as u64) {
+            Ok(Some(bytes)) => reply.data(&bytes),
+            Ok(None) => reply.error(libc::EISDIR),
+            Err(_) => reply.error(libc::EIO),
+        }
+    }
+    
+    fn readdir(&mut self, _request : &Request<'_>, inode : u64, _handle : u64, offset : i64, mut reply : ReplyDirectory) {
+        let Some(entries) = PakFileSystem::readdir(self, inode) else { return reply.error(libc::ENOTDIR) };
+        // Each entry carries the offset of the one after it, which the kernel hands back to continue the listing.
+        for (index, (child, kind, name)) in entries.into_iter().enumerate().skip(usize::try_from(offset).unwrap_or(0)) {
+            let kind = match kind {
+                PakFileKind::Directory => FileType::Directory,
+                PakFileKind::File => FileType::RegularFile,
+            };
+            if reply.add(child, index as i64 + 1, kind, name) { break }
+        }
+        reply.ok()
+    }
+}
@@ -0,0 +1,157 @@
+//! A JavaScript API for reading paks, compiled to WebAssembly with [wasm-bindgen](https://rustwasm.github.io/docs/wasm-bindgen/), so
+//! content browsers on the web can open the same paks the game ships. Build it with `wasm-pack build --features wasm`:
+//!
+//! ```js
+//! import { Pak } from "pak-db";
+//!
+//! const pak = await Pak.fromUrl("/items.pak");
+//! for (const pointer of pak.query('kind == "sword" and damage > 10')) {
+//!     const bytes = pak.getBytes(pointer);
+//! }
+//! ```
+//!
+//! Items come back as their raw bytes, or parsed as JSON for items that hold JSON text, since their Rust types aren't known to JavaScript.
+
+use std::io::Cursor;
+use js_sys::{Array, Object, Promise, Reflect, Uint8Array, JSON};
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::JsFuture;
+use crate::{error::PakError, pointer::PakPointer, query::{self, PakQueryExpression}};
+
+#[wasm_bindgen]
+extern "C" {
+    /// The global `fetch`, which browsers, workers and Node all have.
+    #[wasm_bindgen(js_name = fetch)]
+    fn fetch(url : &str) -> Promise;
+
+    type Response;
+
+    #[wasm_bindgen(method, getter)]
+    fn ok(this : &Response) -> bool;
+
+    #[wasm_bindgen(method, getter)]
+    fn status(this : &Response) -> u16;
+
+    #[wasm_bindgen(method, js_name = arrayBuffer)]
+    fn array_buffer(this : &Response) -> Promise;
+}
+
+impl From<PakError> for JsValue {
+    fn from(error : PakError) -> Self {
+        js_sys::Error::new(&error.to_string()).into()
+    }
+}
+
+//==============================================================================================
+//        Pak
+//==============================================================================================
+
+/// An opened pak, read from bytes that are held in memory.
+#[wasm_bindgen(js_name = Pak)]
+pub struct JsPak(crate::Pak);
+
+#[wasm_bindgen(js_class = Pak)]
+impl JsPak {
+    /// Opens a pak from an `ArrayBuffer` or a `Uint8Array`.
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes : &JsValue) -> Result<JsPak, JsValue> {
+        let bytes = Uint8Array::new(bytes).to_vec();
+        Ok(Self(crate::Pak::new(Cursor::new(bytes))?))
+    }
+
+    /// Downloads the whole pak at the url and opens it.
+    #[wasm_bindgen(js_name = fromUrl)]
+    pub async fn from_url(url : String) -> Result<JsPak, JsValue> {
+        let response : Response = JsFuture::from(fetch(&url)).await?.unchecked_into();
+        if !response.ok() {
+            return Err(js_sys::Error::new(&format!("{url} couldn't be downloaded: status {}", response.status())).into())
+        }
+        let bytes = JsFuture::from(response.array_buffer()).await?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// The items matching the query text, in the order they sit in the pak. See [parse](crate::query::parse) for the syntax.
+    pub fn query(&self, text : &str) -> Result<Vec<JsPakPointer>, JsValue> {
+        let mut pointers = query::parse(text)?.execute(&self.0)?.into_iter().map(|pointer| pointer.into_pointer()).collect::<Vec<_>>();
+        pointers.sort_by_key(|pointer| pointer.offset());
+        Ok(pointers.into_iter().map(JsPakPointer).collect())
+    }
+
+    #[wasm_bindgen(js_name = getBytes)]
+    pub fn get_bytes(&self, pointer : &JsPakPointer) -> Result<Uint8Array, JsValue> {
+        Ok(self.0.read_bytes(&pointer.0)?.as_slice().into())
+    }
+
+    /// Parses the bytes of the item as JSON text, for items like JSON files that were paked as blobs.
+    #[wasm_bindgen(js_name = getJson)]
+    pub fn get_json(&self, pointer : &JsPakPointer) -> Result<JsValue, JsValue> {
+        let bytes = self.0.read_bytes(&pointer.0)?;
+        let text = std::str::from_utf8(&bytes).map_err(|error| js_sys::Error::new(&format!("the item isn't text: {error}")))?;
+        JSON::parse(text)
+    }
+
+    /// The bytes of the item paked at the path, or `undefined` if there isn't one.
+    #[wasm_bindgen(js_name = getPath)]
+    pub fn get_path(&self, path : &str) -> Result<Option<Uint8Array>, JsValue> {
+        let Some(pointer) = self.0.pointer_by_path(path)? else { return Ok(None) };
+        Ok(Some(self.0.read_bytes(&pointer)?.as_slice().into()))
+    }
+
+    pub fn paths(&self) -> Result<Vec<String>, JsValue> {
+        Ok(self.0.paths()?.into_iter().map(|(path, _)| path).collect())
+    }
+
+    /// The name, version, author, description, generation, format, size and item count of the pak, as a plain object.
+    pub fn metadata(&self) -> Result<Object, JsValue> {
+        let metadata = Object::new();
+        let entries : [(&str, JsValue); 8] = [
+            ("name", self.0.name().into()),
+            ("version", self.0.version().into()),
+            ("author", self.0.author().into()),
+            ("description", self.0.description().into()),
+            ("generation", (self.0.generation() as f64).into()),
+            ("format", self.0.format().version().into()),
+            ("size", (self.0.size() as f64).into()),
+            ("itemCount", self.0.item_count()?.into()),
+        ];
+        for (key, value) in entries {
+            Reflect::set(&metadata, &key.into(), &value)?;
+        }
+        Ok(metadata)
+    }
+
+    /// The metadata and the paths of the pak, as a JSON string.
+    #[wasm_bindgen(js_name = toJson)]
+    pub fn to_json(&self) -> Result<String, JsValue> {
+        let metadata = self.metadata()?;
+        let paths = self.paths()?.into_iter().map(JsValue::from).collect::<Array>();
+        Reflect::set(&metadata, &"paths".into(), &paths)?;
+        Ok(JSON::stringify(&metadata)?.into())
+    }
+}
+
+//==============================================================================================
+//        Pointer
+//==============================================================================================
+
+/// Where an item is in a pak, as returned by a query. Offsets and sizes are plain numbers, which are exact for paks below 8 PiB.
+#[wasm_bindgen(js_name = Pointer)]
+pub struct JsPakPointer(PakPointer);
+
+#[wasm_bindgen(js_class = Pointer)]
+impl JsPakPointer {
+    #[wasm_bindgen(getter)]
+    pub fn offset(&self) -> f64 {
+        self.0.offset() as f64
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> f64 {
+        self.0.size() as f64
+    }
+
+    #[wasm_bindgen(getter, js_name = typeName)]
+    pub fn type_name(&self) -> String {
+        self.0.type_name().to_string()
+    }
+}
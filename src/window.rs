@@ -0,0 +1,78 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use crate::{pointer::{byte_range, PakPointer}, Pak};
+
+//==============================================================================================
+//        PakWindow
+//==============================================================================================
+
+/// A bounded reader over the bytes of a single item, created with [Pak::window](crate::Pak::window). It implements [Read](std::io::Read)
+/// and [Seek](std::io::Seek), so decoders that expect a stream can consume an item in place. Seeking is relative to the start of the item, and
+/// reads never go past its end.
+pub struct PakWindow<'p> {
+    pak : &'p Pak,
+    pointer : PakPointer,
+    size : u64,
+    position : u64,
+    /// The whole item, for encrypted and compressed items, which can only be decoded as a whole.
+    decrypted : Option<Vec<u8>>,
+}
+
+impl<'p> PakWindow<'p> {
+    pub(crate) fn new(pak : &'p Pak, pointer : &PakPointer) -> Self {
+        // A compressed item records its size, so it isn't decompressed until it is read. If the size can't be read, the first read fails
+        // the same way.
+        let size = pak.item_len(pointer).unwrap_or(pointer.size());
+        Self { pak, pointer : pointer.clone(), size, position : 0, decrypted : None }
+    }
+    
+    /// The size of the item in bytes.
+    pub fn len(&self) -> u64 {
+        self.size
+    }
+    
+    /// Returns true if the item has no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+}
+
+impl Read for PakWindow<'_> {
+    fn read(&mut self, buf : &mut [u8]) -> io::Result<usize> {
+        let remaining = self.size.saturating_sub(self.position);
+        let count = remaining.min(buf.len() as u64);
+        if count == 0 { return Ok(0) }
+        
+        if self.pak.decodes_whole(self.pointer.offset()) {
+            if self.decrypted.is_none() { self.decrypted = Some(self.pak.read_bytes(&self.pointer).map_err(io::Error::other)?) }
+            let decrypted = self.decrypted.as_deref().unwrap_or_default();
+            let range = byte_range(self.position, count).map_err(io::Error::other)?;
+            let Some(bytes) = decrypted.get(range) else { return Err(io::ErrorKind::UnexpectedEof.into()) };
+            buf[..bytes.len()].copy_from_slice(bytes);
+            self.position += count;
+            return Ok(bytes.len())
+        }
+        
+        let pointer = PakPointer::new_untyped(self.pointer.offset() + self.position, count);
+        let bytes = self.pak.read_uncached(&pointer).map_err(io::Error::other)?;
+        buf[..bytes.len()].copy_from_slice(&bytes);
+        self.position += bytes.len() as u64;
+        Ok(bytes.len())
+    }
+}
+
+impl Seek for PakWindow<'_> {
+    fn seek(&mut self, pos : SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            },
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative position")),
+        }
+    }
+}